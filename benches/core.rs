@@ -0,0 +1,80 @@
+//! Time taken by the core commands with 1k, 10k and 100k tasks, generated
+//! with `Lab3 --bench-data`. Each command runs the app as a one-shot
+//! command, so every timing includes loading the data files; `save` is the
+//! time adding a task (`quick`) takes beyond that.
+//!
+//! Run with `cargo bench --bench core`, or `cargo bench --bench core --
+//! 10000` for only some sizes.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const DEFAULT_SIZES: &[u32] = &[1_000, 10_000, 100_000];
+
+/// Runs of each command; the median is shown.
+const RUNS: usize = 5;
+
+/// Runs the app in `dir` with `args` and returns how long it took.
+fn run(dir: &Path, args: &[&str]) -> Duration {
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_Lab3"))
+        .args(args)
+        .current_dir(dir)
+        .env("TODO_PASSWORD", "bench")
+        .output()
+        .unwrap();
+    let elapsed = start.elapsed();
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    elapsed
+}
+
+/// The median time of `RUNS` runs of a command as the bench user.
+fn measure(dir: &Path, command: &[&str]) -> Duration {
+    let args: Vec<&str> = ["--user", "bench"].iter().chain(command).copied().collect();
+    let mut times: Vec<Duration> = (0..RUNS).map(|_| run(dir, &args)).collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    // Cargo passes `--bench` along with any sizes given.
+    let mut sizes: Vec<u32> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    if sizes.is_empty() {
+        sizes = DEFAULT_SIZES.to_vec();
+    }
+    println!(
+        "{:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "tasks", "load", "add", "save", "list", "grep"
+    );
+    for size in sizes {
+        let dir = std::env::temp_dir().join(format!("todo-bench-core-{}", size));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        run(&dir, &["--bench-data", &size.to_string()]);
+
+        let load = measure(&dir, &["stats"]);
+        let add = measure(&dir, &["quick", "Benchmark task"]);
+        let list = measure(&dir, &["list"]);
+        let grep = measure(&dir, &["grep", "-i", "deploy.*backup"]);
+        println!(
+            "{:>8} {:>8} ms {:>8} ms {:>8} ms {:>8} ms {:>8} ms",
+            size,
+            load.as_millis(),
+            add.as_millis(),
+            add.saturating_sub(load).as_millis(),
+            list.as_millis(),
+            grep.as_millis()
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
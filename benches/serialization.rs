@@ -0,0 +1,123 @@
+//! Peak memory of saving and loading 100k tasks, building the whole JSON
+//! document in memory versus streaming it to and from the file.
+//!
+//! Run with `cargo bench --bench serialization`.
+
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const TASKS: u32 = 100_000;
+
+/// Counts live heap bytes and the peak since the last reset.
+struct CountingAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+/// The shape of a stored task, with the fields that take up most space.
+#[derive(Serialize, Deserialize)]
+struct Task {
+    id: u32,
+    uuid: String,
+    title: String,
+    description: String,
+    completed: bool,
+    created_at: i64,
+    user_id: String,
+    due_date: Option<i64>,
+    custom: HashMap<String, String>,
+    project: Option<String>,
+    version: u32,
+}
+
+fn tasks() -> HashMap<u32, Task> {
+    (1..=TASKS)
+        .map(|id| {
+            let task = Task {
+                id,
+                uuid: format!("{:08x}-0000-4000-8000-{:012x}", id, id),
+                title: format!("Task number {}", id),
+                description: "Some notes about what needs to be done. ".repeat(4),
+                completed: id % 3 == 0,
+                created_at: 1_700_000_000 + i64::from(id),
+                user_id: format!("user{}", id % 50),
+                due_date: (id % 2 == 0).then_some(1_800_000_000),
+                custom: HashMap::from([("client".to_string(), "ACME".to_string())]),
+                project: Some("Project".to_string()),
+                version: 1,
+            };
+            (id, task)
+        })
+        .collect()
+}
+
+/// Runs `f` and prints its duration and the heap it used on top of what was
+/// already allocated.
+fn measure<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+    println!(
+        "{:<24} {:>8.1} MiB peak {:>8} ms",
+        name,
+        peak as f64 / (1024.0 * 1024.0),
+        elapsed.as_millis()
+    );
+    result
+}
+
+fn main() {
+    let path = std::env::temp_dir().join("todo-bench-tasks.json");
+    let tasks = tasks();
+    println!("{} tasks", TASKS);
+
+    measure("save: to_string", || {
+        let json = serde_json::to_string(&tasks).unwrap();
+        fs::write(&path, json).unwrap();
+    });
+    measure("save: to_writer", || {
+        let mut writer = BufWriter::new(File::create(&path).unwrap());
+        serde_json::to_writer(&mut writer, &tasks).unwrap();
+        writer.flush().unwrap();
+    });
+    drop(tasks);
+    println!(
+        "file size {:.1} MiB",
+        fs::metadata(&path).unwrap().len() as f64 / (1024.0 * 1024.0)
+    );
+
+    let loaded: HashMap<u32, Task> = measure("load: from_str", || {
+        let json = fs::read_to_string(&path).unwrap();
+        serde_json::from_str(&json).unwrap()
+    });
+    drop(loaded);
+    let loaded: HashMap<u32, Task> = measure("load: from_reader", || {
+        serde_json::from_reader(BufReader::new(File::open(&path).unwrap())).unwrap()
+    });
+    assert_eq!(loaded.len(), TASKS as usize);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,57 @@
+//! Output modes for users who can't rely on colours or on the layout of the
+//! screen, set with `accessibility` in `config.json`:
+//!
+//! - `symbols` marks statuses, priorities and busy days with symbols instead
+//!   of colours, for colour-blind users;
+//! - `screen-reader` writes plain labelled lines in reading order: no
+//!   colours, symbols or markdown, and lists instead of bars, grids and
+//!   charts. The arrow-key task picker gives way to a typed ID.
+//!
+//! `--accessible` turns on the screen reader mode for one run, and
+//! `--accessible=symbols` the symbols mode.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Accessibility {
+    #[default]
+    Off,
+    Symbols,
+    ScreenReader,
+}
+
+impl Accessibility {
+    /// Parses the value of `--accessible=`; a bare `--accessible` is the
+    /// screen reader mode.
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "--accessible" | "--accessible=screen-reader" => Some(Self::ScreenReader),
+            "--accessible=symbols" => Some(Self::Symbols),
+            "--accessible=off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Sets the mode for the rest of the process.
+pub fn set(mode: Accessibility) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+/// The mode in use.
+pub fn mode() -> Accessibility {
+    match MODE.load(Ordering::Relaxed) {
+        1 => Accessibility::Symbols,
+        2 => Accessibility::ScreenReader,
+        _ => Accessibility::Off,
+    }
+}
+
+/// Whether output is meant for a screen reader.
+pub fn screen_reader() -> bool {
+    mode() == Accessibility::ScreenReader
+}
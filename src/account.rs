@@ -0,0 +1,444 @@
+//! Export of a user's account to a single archive, and import of such an
+//! archive on another machine.
+//!
+//! The archive is a tar file holding `manifest.json`, which lists the
+//! SHA-256 checksum of every other file, and the data files themselves.
+//! Attachments are exported as a manifest of their locations; the files
+//! they point to aren't copied. Given a password, the whole archive is
+//! encrypted (see `encryption`).
+//!
+//! `delete-account` removes an account along with its personal tasks.
+
+use crate::attachments::Attachment;
+use crate::crypto;
+use crate::encryption;
+use crate::importing::{Decision, Resolution};
+use crate::log;
+use crate::settings::UserSettings;
+use crate::tar;
+use crate::validation;
+use crate::{Task, TaskEvent, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+
+/// Version of the archive layout, bumped on incompatible changes.
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const ACCOUNT: &str = "account.json";
+const TASKS: &str = "tasks.json";
+const SETTINGS: &str = "settings.json";
+const ATTACHMENTS: &str = "attachments.json";
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    format_version: u32,
+    username: String,
+    #[serde(with = "ts_seconds")]
+    exported_at: DateTime<Utc>,
+    /// SHA-256 checksum of each file, as hex.
+    checksums: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Account {
+    username: String,
+    email: Option<String>,
+}
+
+/// An attachment and the task it belongs to.
+#[derive(Serialize, Deserialize)]
+struct AttachmentEntry {
+    task_uuid: String,
+    #[serde(flatten)]
+    attachment: Attachment,
+}
+
+impl TodoApp {
+    /// Writes the current user's tasks, projects, settings and attachment
+    /// manifest to a tar archive at `path`, encrypted with `password` if
+    /// given.
+    pub fn export_account(&self, path: &str, password: Option<&str>) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let user = self.users.get(user_id);
+        let tasks: Vec<&Task> = self.own_tasks(user_id).collect();
+        let attachments: Vec<AttachmentEntry> = tasks
+            .iter()
+            .flat_map(|task| {
+                task.attachments.iter().map(|attachment| AttachmentEntry {
+                    task_uuid: task.uuid.clone(),
+                    attachment: attachment.clone(),
+                })
+            })
+            .collect();
+        let account = Account {
+            username: user_id.clone(),
+            email: user.and_then(|user| user.email.clone()),
+        };
+
+        let files = [
+            (ACCOUNT, serde_json::to_vec_pretty(&account)),
+            (TASKS, serde_json::to_vec_pretty(&tasks)),
+            (SETTINGS, serde_json::to_vec_pretty(&self.settings()?)),
+            (ATTACHMENTS, serde_json::to_vec_pretty(&attachments)),
+        ];
+        let mut entries = Vec::new();
+        for (name, contents) in files {
+            let contents = contents.map_err(|_| "Failed to export the account")?;
+            entries.push((name.to_string(), contents));
+        }
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            username: user_id.clone(),
+            exported_at: Utc::now(),
+            checksums: entries
+                .iter()
+                .map(|(name, contents)| (name.clone(), crypto::to_hex(&crypto::sha256(contents))))
+                .collect(),
+        };
+        let manifest =
+            serde_json::to_vec_pretty(&manifest).map_err(|_| "Failed to export the account")?;
+        entries.insert(0, (MANIFEST.to_string(), manifest));
+
+        let archive = tar::write(&entries, Utc::now().timestamp())
+            .map_err(|_| "Failed to export the account")?;
+        let archive = match password {
+            Some(password) => encryption::encrypt(&archive, password)?,
+            None => archive,
+        };
+        fs::write(path, archive)
+            .inspect_err(|e| log::error(&format!("Failed to write {}: {}", path, e)))
+            .map_err(|_| "Failed to write the archive")?;
+        log::info(&format!("{} exported their account to {}", user_id, path));
+        Ok(())
+    }
+
+    /// Restores an archive made by `export_account` into the current
+    /// user's account, which may have a different name. `resolve` decides
+    /// what happens to tasks that already exist here; the archived settings
+    /// replace the current ones. An encrypted archive needs its `password`.
+    pub fn import_account(
+        &mut self,
+        path: &str,
+        password: Option<&str>,
+        resolve: &mut dyn FnMut(&Task, &Task) -> Resolution,
+    ) -> Result<Vec<Decision>, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let mut archive = fs::read(path).map_err(|_| "Failed to read the archive")?;
+        if encryption::is_encrypted(&archive) {
+            let password = password.ok_or("The archive is encrypted, a password is needed")?;
+            archive = encryption::decrypt(&archive, password)?;
+        }
+        let files: HashMap<String, Vec<u8>> = tar::read(&archive)
+            .map_err(|_| "Not a valid account archive")?
+            .into_iter()
+            .collect();
+
+        let manifest: Manifest = files
+            .get(MANIFEST)
+            .and_then(|contents| serde_json::from_slice(contents).ok())
+            .ok_or("Not a valid account archive")?;
+        if manifest.format_version > FORMAT_VERSION {
+            return Err("The archive was made by a newer version");
+        }
+        for name in [ACCOUNT, TASKS, SETTINGS, ATTACHMENTS] {
+            let contents = files.get(name).ok_or("The archive is incomplete")?;
+            if manifest.checksums.get(name) != Some(&crypto::to_hex(&crypto::sha256(contents))) {
+                return Err("The archive is damaged: checksum mismatch");
+            }
+        }
+        let account: Account =
+            serde_json::from_slice(&files[ACCOUNT]).map_err(|_| "Not a valid account archive")?;
+        let tasks: Vec<Task> =
+            serde_json::from_slice(&files[TASKS]).map_err(|_| "Not a valid account archive")?;
+        let settings: UserSettings =
+            serde_json::from_slice(&files[SETTINGS]).map_err(|_| "Not a valid account archive")?;
+        for task in &tasks {
+            validation::title(&task.title)?;
+        }
+
+        let tasks = tasks
+            .into_iter()
+            .map(|mut task| {
+                task.user_id = user_id.clone();
+                // Workspaces and other users may not exist on this machine,
+                // and the user may not be allowed to add tasks to one.
+                if !self.workspaces.can_add(task.workspace.as_deref(), &user_id) {
+                    task.workspace = None;
+                }
+                task.assignee = task
+                    .assignee
+                    .filter(|assignee| *assignee == account.username)
+                    .map(|_| user_id.clone());
+                task
+            })
+            .collect();
+        let message = format!("Import account {} into {}", account.username, user_id);
+        let mut transaction = self.begin();
+        let decisions = transaction.merge_imported(tasks, resolve)?;
+        transaction.commit(&message)?;
+        self.settings.insert(user_id.clone(), settings);
+        if let Some(user) = self.users.get_mut(&user_id) {
+            user.email = user.email.take().or(account.email);
+        }
+
+        self.save_settings(&message)
+            .map_err(|_| "Failed to save settings")?;
+        self.save_users(&message)
+            .map_err(|_| "Failed to save users")?;
+        self.apply_settings();
+        log::info(&format!("{}: {} from {}", user_id, message, path));
+        Ok(decisions)
+    }
+
+    /// Deletes the current user's account and logs them out. Their personal
+    /// tasks are deleted for good, trash included; tasks they added to
+    /// workspaces stay there. Their tokens and chat links stop working and
+    /// they leave every workspace and group. Fails while they own a
+    /// workspace or group. Returns how many tasks were deleted.
+    pub fn delete_account(&mut self) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if self.workspaces.owns_any(&user_id) {
+            return Err("Delete or hand over your workspaces and groups first");
+        }
+        if !self.users.contains_key(&user_id) {
+            return Err("User not found");
+        }
+        let message = format!("Delete account {}", user_id);
+
+        // Tasks go first: if the account can't be removed after that, it's
+        // still there without them, rather than gone with its tasks left.
+        let task_ids: Vec<u32> = self
+            .own_tasks(&user_id)
+            .filter(|task| task.workspace.is_none())
+            .map(|task| task.id)
+            .collect();
+        let mut transaction = self.begin();
+        let mut deleted = Vec::new();
+        for task_id in &task_ids {
+            deleted.extend(transaction.remove_task(*task_id));
+            transaction.remove_links_to(*task_id);
+        }
+        transaction.commit(&message)?;
+        let user = self.users.remove(&user_id).ok_or("User not found")?;
+        if self.save_users(&message).is_err() {
+            self.users.insert(user_id, user);
+            return Err("Failed to save users");
+        }
+        let trashed = self.trash.len();
+        self.trash
+            .retain(|_, trashed| trashed.task.user_id != user_id);
+        if self.trash.len() != trashed {
+            self.save_trash(&message)
+                .map_err(|_| "Failed to save the trash")?;
+        }
+        let tokens = self.tokens.len();
+        self.tokens.retain(|_, token| token.username != user_id);
+        if self.tokens.len() != tokens {
+            self.save_tokens(&message)
+                .map_err(|_| "Failed to save tokens")?;
+        }
+        let (workspaces, groups) = self.workspaces.remove_everywhere(&user_id);
+        if workspaces {
+            self.save_workspaces(&message)
+                .map_err(|_| "Failed to save workspaces")?;
+        }
+        if groups {
+            self.save_groups(&message)
+                .map_err(|_| "Failed to save groups")?;
+        }
+        #[cfg(feature = "bot")]
+        self.unlink_chat_accounts()?;
+
+        for task in &deleted {
+            self.emit(TaskEvent::Deleted, task);
+        }
+        self.logout();
+        log::info(&format!("Deleted account {}", user_id));
+        Ok(deleted.len())
+    }
+}
+
+/// Whether the archive at `path` is encrypted. `false` if it can't be read.
+pub fn is_encrypted_archive(path: &str) -> bool {
+    let mut magic = [0u8; 8];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| encryption::is_encrypted(&magic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MemoryStorage};
+    use std::path::Path;
+
+    /// Alice, logged in with one personal task, on `storage`.
+    fn alice_with_a_task(storage: &MemoryStorage) -> TodoApp {
+        let mut app = testing::app_on(storage);
+        testing::register(&mut app, &["alice"]);
+        testing::login(&mut app, "alice");
+        let task = app.new_task("Cancel the gym", "", None).unwrap();
+        app.insert_task(task);
+        app
+    }
+
+    #[test]
+    fn a_failed_task_deletion_keeps_the_account() {
+        let storage = MemoryStorage::default();
+        let mut app = alice_with_a_task(&storage);
+        storage.fail_writes("tasks.json");
+
+        assert!(app.delete_account().is_err());
+        assert!(app.users.contains_key("alice"));
+        assert!(storage.get("users.json").unwrap().contains("alice"));
+        assert_eq!(app.tasks.len(), 1);
+    }
+
+    #[test]
+    fn a_failed_account_removal_leaves_no_orphaned_tasks() {
+        let storage = MemoryStorage::default();
+        let mut app = alice_with_a_task(&storage);
+        storage.fail_writes("users.json");
+
+        assert!(app.delete_account().is_err());
+        assert!(app.users.contains_key("alice"));
+        assert!(app.tasks.is_empty());
+        assert_eq!(storage.get("tasks.json").unwrap(), "{}");
+    }
+
+    fn bob() -> TodoApp {
+        let mut app = testing::dry_run_app();
+        testing::register(&mut app, &["bob"]);
+        testing::login(&mut app, "bob");
+        app
+    }
+
+    fn import(app: &mut TodoApp, path: &Path, password: Option<&str>) -> Result<(), &'static str> {
+        let path = path.to_str().unwrap();
+        app.import_account(path, password, &mut |_, _| Resolution::Skip)
+            .map(|_| ())
+    }
+
+    /// Rewrites the plain archive at `path` with `change` applied to its files.
+    fn tamper(path: &Path, change: impl FnOnce(&mut Vec<(String, Vec<u8>)>)) {
+        let mut files = tar::read(&fs::read(path).unwrap()).unwrap();
+        change(&mut files);
+        fs::write(path, tar::write(&files, 0).unwrap()).unwrap();
+    }
+
+    fn contents<'a>(files: &'a mut [(String, Vec<u8>)], name: &str) -> &'a mut Vec<u8> {
+        &mut files.iter_mut().find(|(file, _)| file == name).unwrap().1
+    }
+
+    /// Alice's account exported without a password to `dir/name`.
+    fn exported(dir: &Path, name: &str) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let app = alice_with_a_task(&MemoryStorage::default());
+        app.export_account(path.to_str().unwrap(), None).unwrap();
+        path
+    }
+
+    #[test]
+    fn an_archive_imports_into_another_account() {
+        let dir = testing::temp_dir("account-plain");
+        let path = exported(&dir, "alice.tar");
+        let mut app = bob();
+        import(&mut app, &path, None).unwrap();
+
+        let task = app.tasks.values().next().unwrap();
+        assert_eq!(task.title, "Cancel the gym");
+        assert_eq!(task.user_id, "bob");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn an_encrypted_archive_imports_only_with_its_password() {
+        let dir = testing::temp_dir("account-encrypted");
+        let path = dir.join("alice.tar.enc");
+        let alice = alice_with_a_task(&MemoryStorage::default());
+        alice
+            .export_account(path.to_str().unwrap(), Some("open sesame"))
+            .unwrap();
+
+        let archive = fs::read(&path).unwrap();
+        assert!(is_encrypted_archive(path.to_str().unwrap()));
+        assert!(!archive
+            .windows(14)
+            .any(|window| window == b"Cancel the gym"));
+        let mut app = bob();
+        assert_eq!(
+            import(&mut app, &path, None),
+            Err("The archive is encrypted, a password is needed")
+        );
+        assert!(import(&mut app, &path, Some("open sesame!")).is_err());
+        assert!(app.tasks.is_empty());
+        import(&mut app, &path, Some("open sesame")).unwrap();
+        assert_eq!(app.tasks.values().next().unwrap().title, "Cancel the gym");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_changed_after_export_are_refused() {
+        let dir = testing::temp_dir("account-tampered");
+        let path = exported(&dir, "tasks.tar");
+        tamper(&path, |files| {
+            let tasks = contents(files, TASKS);
+            *tasks = String::from_utf8(tasks.clone())
+                .unwrap()
+                .replace("Cancel the gym", "Transfer the savings")
+                .into_bytes();
+        });
+        let mut app = bob();
+        assert_eq!(
+            import(&mut app, &path, None),
+            Err("The archive is damaged: checksum mismatch")
+        );
+
+        let path = exported(&dir, "manifest.tar");
+        tamper(&path, |files| {
+            let mut manifest: Manifest = serde_json::from_slice(contents(files, MANIFEST)).unwrap();
+            manifest
+                .checksums
+                .insert(SETTINGS.to_string(), crypto::to_hex(&crypto::sha256(b"{}")));
+            *contents(files, MANIFEST) = serde_json::to_vec(&manifest).unwrap();
+        });
+        assert_eq!(
+            import(&mut app, &path, None),
+            Err("The archive is damaged: checksum mismatch")
+        );
+
+        let path = exported(&dir, "missing.tar");
+        tamper(&path, |files| files.retain(|(name, _)| name != ATTACHMENTS));
+        assert_eq!(
+            import(&mut app, &path, None),
+            Err("The archive is incomplete")
+        );
+        assert!(app.tasks.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entries_leading_out_of_the_archive_are_refused() {
+        let dir = testing::temp_dir("account-paths");
+        for name in ["../tasks.json", "/tmp/tasks.json", "data/../../tasks.json"] {
+            let path = exported(&dir, "alice.tar");
+            tamper(&path, |files| {
+                let tasks = contents(files, TASKS).clone();
+                files.push((name.to_string(), tasks));
+            });
+            let mut app = bob();
+            assert_eq!(
+                import(&mut app, &path, None),
+                Err("Not a valid account archive"),
+                "{}",
+                name
+            );
+            assert!(app.tasks.is_empty());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
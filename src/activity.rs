@@ -0,0 +1,165 @@
+//! Audit log of task events, shown to workspace members as an activity feed.
+//!
+//! Each event is appended to `audit.jsonl` as one JSON object per line, so
+//! recording never rewrites earlier entries.
+
+use crate::i18n::{self, trf};
+use crate::log;
+use crate::{Task, TaskEvent, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+pub const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Number of entries on each page of the feed.
+pub const PAGE_SIZE: usize = 20;
+
+/// An entry of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activity {
+    #[serde(with = "ts_seconds")]
+    pub at: DateTime<Utc>,
+    /// User who made the change.
+    pub user: String,
+    pub event: TaskEvent,
+    pub task_id: u32,
+    /// Title of the task at the time of the event.
+    pub title: String,
+    pub workspace: Option<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
+}
+
+impl TodoApp {
+    /// Appends an event to the audit log. Like the diagnostic log, the audit
+    /// log never fails the change being recorded.
+    pub fn record_activity(&self, event: TaskEvent, task: &Task) {
+        if let Some(user_id) = &self.current_user {
+            self.record_activity_by(user_id, event, task);
+        }
+    }
+
+    /// `record_activity` for a change made by `user` rather than the
+    /// current user, such as the app itself.
+    pub fn record_activity_by(&self, user: &str, event: TaskEvent, task: &Task) {
+        if self.dry_run {
+            return;
+        }
+        if event == TaskEvent::Created {
+            self.count_task_created(user);
+        }
+        let activity = Activity {
+            at: Utc::now(),
+            user: user.to_string(),
+            event,
+            task_id: task.id,
+            title: task.title.clone(),
+            workspace: task.workspace.clone(),
+            assignee: task.assignee.clone(),
+        };
+        if let Err(e) = append(&activity) {
+            log::warn(&format!("Failed to record activity: {}", e));
+        }
+    }
+
+    /// One page of a workspace's activity, newest first, optionally only the
+    /// changes made by `user`. Pages are numbered from 1.
+    pub fn workspace_activity(
+        &self,
+        workspace: &str,
+        user: Option<&str>,
+        page: usize,
+    ) -> Result<Vec<Activity>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        if self.workspaces.role(workspace, user_id).is_none() {
+            return Err("Not a member of this workspace");
+        }
+        if page == 0 {
+            return Err("Invalid page number");
+        }
+
+        let entries = read().map_err(|_| "Failed to read the activity log")?;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter(|activity| activity.workspace.as_deref() == Some(workspace))
+            .filter(|activity| user.is_none_or(|user| activity.user == user))
+            .skip((page - 1) * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .collect())
+    }
+}
+
+/// A line of the feed, e.g. `2024-05-01 10:00 alice completed #3 Buy milk`.
+pub fn describe(activity: &Activity) -> String {
+    let task = format!("#{} {}", activity.task_id, activity.title);
+    let text = match (activity.event, &activity.assignee) {
+        (TaskEvent::Created, _) => trf("{} created {}", &[&activity.user, &task]),
+        (TaskEvent::Completed, _) => trf("{} completed {}", &[&activity.user, &task]),
+        (TaskEvent::Deleted, _) => trf("{} deleted {}", &[&activity.user, &task]),
+        (TaskEvent::Assigned, Some(assignee)) => {
+            trf("{} assigned {} to {}", &[&activity.user, &task, assignee])
+        }
+        (TaskEvent::Assigned, None) => trf("{} unassigned {}", &[&activity.user, &task]),
+        (TaskEvent::Escalated, _) => trf("{} raised the priority of {}", &[&activity.user, &task]),
+        (TaskEvent::Reminded, _) => trf("{} was reminded of {}", &[&activity.user, &task]),
+        (TaskEvent::Cancelled, _) => trf("{} cancelled {}", &[&activity.user, &task]),
+    };
+    format!("{} {}", i18n::format_datetime(activity.at), text)
+}
+
+fn append(activity: &Activity) -> io::Result<()> {
+    let line = serde_json::to_string(activity)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_FILE)?;
+    writeln!(file, "{}", line)
+}
+
+/// Reads the whole audit log, oldest first. Lines that can't be parsed,
+/// such as one cut short by a crash, are skipped.
+fn read() -> io::Result<Vec<Activity>> {
+    let contents = match fs::read_to_string(AUDIT_FILE) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Size of the audit log in bytes.
+pub fn size() -> io::Result<u64> {
+    match fs::metadata(AUDIT_FILE) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Drops the oldest entries of the audit log until it's at most
+/// `max_bytes` long. Returns how many were dropped.
+pub fn trim(max_bytes: u64) -> io::Result<usize> {
+    if size()? <= max_bytes {
+        return Ok(0);
+    }
+    let contents = fs::read_to_string(AUDIT_FILE)?;
+    let mut start = 0;
+    let mut dropped = 0;
+    while (contents.len() - start) as u64 > max_bytes {
+        start = contents[start..]
+            .find('\n')
+            .map_or(contents.len(), |end| start + end + 1);
+        dropped += 1;
+    }
+    // Written aside and renamed so a crash can't leave half a log.
+    let temp = format!("{}.tmp", AUDIT_FILE);
+    fs::write(&temp, &contents[start..])?;
+    fs::rename(temp, AUDIT_FILE)?;
+    Ok(dropped)
+}
@@ -0,0 +1,49 @@
+//! The week-at-a-glance agenda view. Pinned tasks come first within each day.
+
+use crate::schedule::{local_date, start_of_today};
+use crate::{Task, TodoApp};
+use chrono::{Duration, NaiveDate};
+
+/// Pending tasks grouped by the day they're due.
+pub struct Agenda<'a> {
+    /// Tasks that were due before today.
+    pub overdue: Vec<&'a Task>,
+    /// One entry per day starting today, in order.
+    pub days: Vec<(NaiveDate, Vec<&'a Task>)>,
+    /// Tasks without a due date.
+    pub undated: Vec<&'a Task>,
+}
+
+impl TodoApp {
+    /// Groups the current user's pending tasks by due day for the next `days` days.
+    pub fn agenda(&self, days: u32) -> Result<Agenda<'_>, &'static str> {
+        let today = local_date(start_of_today());
+        let mut agenda = Agenda {
+            overdue: Vec::new(),
+            days: (0..days)
+                .map(|offset| (today + Duration::days(i64::from(offset)), Vec::new()))
+                .collect(),
+            undated: Vec::new(),
+        };
+
+        let mut tasks: Vec<&Task> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| !task.completed)
+            .collect();
+        tasks.sort_by_key(|task| (!task.pinned, task.due_date, task.id));
+        for task in tasks {
+            let Some(due) = task.due_date else {
+                agenda.undated.push(task);
+                continue;
+            };
+            let date = local_date(due);
+            if date < today {
+                agenda.overdue.push(task);
+            } else if let Some((_, bucket)) = agenda.days.iter_mut().find(|(day, _)| *day == date) {
+                bucket.push(task);
+            }
+        }
+        Ok(agenda)
+    }
+}
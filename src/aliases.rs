@@ -0,0 +1,91 @@
+//! Short aliases for tasks, like git's short hashes: the start of a task's
+//! UUID, without dashes, long enough to match no other task. An alias can
+//! be given anywhere a task number can, and is shown instead of the number
+//! with the `ids` setting set to `short`.
+//!
+//! Aliases are at least `MIN_ALIAS_LENGTH` characters and grow as tasks with
+//! the same start are added. One made only of digits would read as a task
+//! number, so it's lengthened until it has a letter.
+
+use crate::i18n::{tr, trf};
+use crate::settings::IdDisplay;
+use crate::{Task, TodoApp};
+
+/// Fewest characters of a UUID taken as an alias.
+pub const MIN_ALIAS_LENGTH: usize = 4;
+
+/// The UUID's hex digits, without dashes.
+fn digits(uuid: &str) -> String {
+    uuid.chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Length of the common start of two strings of ASCII digits.
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+impl TodoApp {
+    /// The shortest start of `task`'s UUID that no other task shares.
+    pub fn short_id(&self, task: &Task) -> String {
+        let own = digits(&task.uuid);
+        let shared = self
+            .tasks
+            .values()
+            .filter(|other| other.id != task.id)
+            .map(|other| common_prefix(&own, &digits(&other.uuid)))
+            .max()
+            .unwrap_or(0);
+        let mut length = (shared + 1).max(MIN_ALIAS_LENGTH);
+        while own[..length.min(own.len())]
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            && length < own.len()
+        {
+            length += 1;
+        }
+        own[..length.min(own.len())].to_string()
+    }
+
+    /// How the current user sees `task` identified: its number or alias.
+    pub fn display_id(&self, task: &Task) -> String {
+        match self.settings().map(|settings| settings.ids) {
+            Ok(IdDisplay::Short) => self.short_id(task),
+            _ => task.id.to_string(),
+        }
+    }
+
+    /// The number of the task `text` refers to: a task number, or an alias
+    /// of at least `MIN_ALIAS_LENGTH` characters. Either may start with `#`.
+    pub fn resolve_task_id(&self, text: &str) -> Result<u32, String> {
+        let text = text.trim().trim_start_matches('#');
+        if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) {
+            return text.parse().map_err(|_| tr("Invalid task ID").to_string());
+        }
+        let prefix = digits(text);
+        if prefix.len() < MIN_ALIAS_LENGTH || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(tr("Invalid task ID").to_string());
+        }
+        let matches: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| digits(&task.uuid).starts_with(&prefix))
+            .collect();
+        match matches.as_slice() {
+            [] => Err(tr("Task not found").to_string()),
+            [task] => Ok(task.id),
+            _ => {
+                let candidates: Vec<String> = matches
+                    .iter()
+                    .map(|task| format!("{} (#{})", self.short_id(task), task.id))
+                    .collect();
+                Err(trf(
+                    "Ambiguous task ID {}, it could be {}",
+                    &[&text, &candidates.join(", ")],
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Assigning tasks to the person responsible for them, separately from
+//! the user who created them.
+
+use crate::log;
+use crate::{Task, TaskEvent, TodoApp};
+
+impl TodoApp {
+    /// Assigns a task to `assignee`, or unassigns it with `None`. The
+    /// assignee must be able to see the task, so tasks outside a workspace
+    /// can only be assigned to their creator.
+    pub fn assign_task(
+        &mut self,
+        task_id: u32,
+        assignee: Option<&str>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let assignee = assignee
+            .map(|assignee| {
+                self.find_user(assignee)
+                    .map(str::to_string)
+                    .ok_or("User not found")
+            })
+            .transpose()?;
+        let assignee = assignee.as_deref();
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if let Some(assignee) = assignee {
+            if !self.workspaces.can_edit(task, assignee) {
+                return Err("The user can't edit this task");
+            }
+        }
+
+        task.assignee = assignee.map(str::to_string);
+        task.touch();
+        let message = match assignee {
+            Some(assignee) => format!("Assign task #{} to {}", task_id, assignee),
+            None => format!("Unassign task #{}", task_id),
+        };
+        self.save_tasks(&message)
+            .map_err(|_| "Failed to save tasks")?;
+        log::info(&format!("{}: {}", user_id, message));
+        self.record_activity(TaskEvent::Assigned, &self.tasks[&task_id]);
+        // Only tell integrations about assignments made by someone else.
+        if assignee.is_some_and(|assignee| assignee != user_id) {
+            self.notify_integrations(TaskEvent::Assigned, &self.tasks[&task_id]);
+        }
+        Ok(())
+    }
+
+    /// Tasks assigned to the current user in any workspace, in their
+    /// preferred order.
+    pub fn my_assignments(&self) -> Result<Vec<&Task>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let sort = self.settings()?.sort;
+        let mut tasks: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| task.assignee.as_ref() == Some(user_id))
+            .filter(|task| self.workspaces.can_access(task, user_id))
+            .collect();
+        tasks.sort_by(|a, b| sort.compare(a, b));
+        Ok(tasks)
+    }
+}
@@ -0,0 +1,113 @@
+//! File paths and URLs attached to tasks.
+
+use crate::TodoApp;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// A reference to a file or URL related to a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    /// File path or URL.
+    pub location: String,
+    #[serde(with = "ts_seconds")]
+    pub added_at: DateTime<Utc>,
+}
+
+impl TodoApp {
+    /// Attaches a file path or URL to a task. Without a name, the last path
+    /// segment of the location is used.
+    pub fn add_attachment(
+        &mut self,
+        task_id: u32,
+        location: &str,
+        name: Option<&str>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        let name = name
+            .or_else(|| location.trim_end_matches('/').rsplit('/').next())
+            .filter(|name| !name.is_empty())
+            .ok_or("Attachment name cannot be empty")?;
+        if task.attachments.iter().any(|a| a.name == name) {
+            return Err("Task already has an attachment with this name");
+        }
+
+        task.attachments.push(Attachment {
+            name: name.to_string(),
+            location: location.to_string(),
+            added_at: Utc::now(),
+        });
+        task.touch();
+        self.save_tasks(&format!("Attach {} to task #{}", name, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Removes an attachment from a task.
+    pub fn remove_attachment(&mut self, task_id: u32, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        let index = task
+            .attachments
+            .iter()
+            .position(|a| a.name == name)
+            .ok_or("Attachment not found")?;
+        task.attachments.remove(index);
+        task.touch();
+        self.save_tasks(&format!("Detach {} from task #{}", name, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Finds an attachment on one of the current user's tasks.
+    pub fn attachment(&self, task_id: u32, name: &str) -> Result<&Attachment, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_access(task, user_id) {
+            return Err("Not authorized to view this task");
+        }
+        task.attachments
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or("Attachment not found")
+    }
+}
+
+/// Opens an attachment with the platform's default application.
+pub fn open(attachment: &Attachment) -> io::Result<()> {
+    let location = &attachment.location;
+    let is_url = location.contains("://");
+    if !is_url && !Path::new(location).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", location),
+        ));
+    }
+
+    // Not `cmd /C start`: cmd would run anything after a `&` in the location.
+    let status = if cfg!(target_os = "windows") {
+        Command::new("rundll32")
+            .args(["url.dll,FileProtocolHandler", location])
+            .status()?
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(location).status()?
+    } else {
+        Command::new("xdg-open").arg(location).status()?
+    };
+    if !status.success() {
+        return Err(io::Error::other(format!("opener exited with {}", status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Synthetic data for benchmarks: `Lab3 --bench-data <count>` adds `count`
+//! made-up tasks for the user `bench` (password `bench`), registering it if
+//! needed. The same count always gives the same tasks, so timings taken on
+//! different builds can be compared.
+
+use crate::priority::Priority;
+use crate::TodoApp;
+use chrono::{Duration, Utc};
+
+pub const BENCH_USER: &str = "bench";
+
+const WORDS: &[&str] = &[
+    "write", "review", "report", "call", "plan", "budget", "fix", "bug", "order", "meeting",
+    "update", "draft", "invoice", "deploy", "backup", "email", "design", "test", "release",
+    "notes",
+];
+const PROJECTS: &[&str] = &["work", "home", "garden", "finance", "travel"];
+const TAGS: &[&str] = &["urgent", "waiting", "errand", "phone", "computer", "bench"];
+
+/// A small linear congruential generator; good enough for made-up data and
+/// the same on every platform.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    /// A number below `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+impl TodoApp {
+    /// Adds `count` synthetic tasks for the bench user and saves them at
+    /// once. Rules, quotas and integrations are skipped.
+    pub fn generate_bench_data(&mut self, count: u32) -> Result<(), &'static str> {
+        if self.find_user(BENCH_USER).is_none() {
+            self.register(BENCH_USER, BENCH_USER)?;
+        }
+        let previous_user = self.current_user.replace(BENCH_USER.to_string());
+        let mut rng = Lcg(u64::from(count));
+        let now = Utc::now();
+        for _ in 0..count {
+            let title: Vec<&str> = (0..2 + rng.below(4)).map(|_| rng.pick(WORDS)).collect();
+            let description: Vec<&str> = (0..rng.below(30)).map(|_| rng.pick(WORDS)).collect();
+            let due_date = match rng.below(3) {
+                0 => None,
+                _ => Some(now + Duration::days(rng.below(120) as i64 - 30)),
+            };
+            let mut task = self.new_task(&title.join(" "), &description.join(" "), due_date)?;
+            task.id = self.next_task_id;
+            task.created_at = now - Duration::days(rng.below(365) as i64);
+            if rng.below(10) < 3 {
+                task.completed = true;
+                task.completed_at = Some(now);
+            }
+            if rng.below(2) == 0 {
+                task.project = Some(rng.pick(PROJECTS).to_string());
+            }
+            for _ in 0..rng.below(3) {
+                task.tags.insert(rng.pick(TAGS).to_string());
+            }
+            task.priority = match rng.below(8) {
+                0 => Priority::Low,
+                1 => Priority::High,
+                2 => Priority::Urgent,
+                _ => Priority::Normal,
+            };
+            self.insert_task(task);
+            self.next_task_id += 1;
+        }
+        self.current_user = previous_user;
+        self.save_tasks(&format!("Generate {} benchmark tasks", count))
+            .map_err(|_| "Failed to save tasks")
+    }
+}
@@ -0,0 +1,447 @@
+//! Chat bot mode (`--bot`, built with the `bot` feature): watches a Slack or
+//! Discord channel for messages starting with the command prefix, such as
+//! `!todo add Pay rent due:friday`, and answers them in the channel.
+//!
+//! Chat users act as the app user their chat account is linked to. To link
+//! one, they send `!todo link` and run `bot link <code>` with the code the
+//! bot replies with while logged in to the app.
+//!
+//! The HTTP client only speaks plain HTTP, so `api_url` points at a local
+//! proxy to `https://slack.com/api` or `https://discord.com/api/v10`. The
+//! bot token is sent in the clear, so a config with any other `api_url`
+//! fails to load. A Discord bot needs the message content intent to read
+//! commands.
+
+use crate::crypto;
+use crate::http;
+use crate::i18n::{tr, trf};
+use crate::log;
+use crate::TodoApp;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::thread;
+
+const BOT_STATE_FILE: &str = "bot.json";
+
+/// How long a link code can be used for.
+const LINK_CODE_MINUTES: i64 = 10;
+
+/// Messages fetched per check.
+const PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    #[default]
+    Slack,
+    Discord,
+}
+
+impl Platform {
+    fn name(self) -> &'static str {
+        match self {
+            Platform::Slack => "slack",
+            Platform::Discord => "discord",
+        }
+    }
+}
+
+/// Bot settings, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotConfig {
+    pub platform: Platform,
+    /// Base URL of the platform's API.
+    pub api_url: String,
+    /// The bot's token.
+    pub token: String,
+    /// ID of the channel the bot listens in.
+    pub channel: String,
+    /// Text commands start with.
+    pub prefix: String,
+    /// Seconds between checks for new messages.
+    pub poll_secs: u64,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            platform: Platform::Slack,
+            api_url: "http://localhost:8081/api".to_string(),
+            token: String::new(),
+            channel: String::new(),
+            prefix: "!todo".to_string(),
+            poll_secs: 5,
+        }
+    }
+}
+
+impl BotConfig {
+    /// Fails unless `api_url` is on this machine, as the token is sent in
+    /// the clear.
+    pub fn validate(&self) -> io::Result<()> {
+        if http::is_loopback_url(&self.api_url) {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr("The bot api_url must be an http:// URL on localhost, e.g. a local TLS proxy"),
+        ))
+    }
+}
+
+/// A link code waiting to be confirmed in the app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingLink {
+    /// The chat account, as `platform:user id`.
+    account: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// The last message seen and the linked accounts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    /// ID of the newest message seen, per platform and channel.
+    cursors: BTreeMap<String, String>,
+    /// App usernames by chat account (`platform:user id`).
+    links: BTreeMap<String, String>,
+    /// Link codes not confirmed yet.
+    pending: BTreeMap<String, PendingLink>,
+}
+
+fn load_state() -> io::Result<State> {
+    match fs::read_to_string(BOT_STATE_FILE) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_state(state: &State) -> io::Result<()> {
+    let json = serde_json::to_string(state)?;
+    fs::write(BOT_STATE_FILE, json)?;
+    Ok(())
+}
+
+impl TodoApp {
+    /// Links the chat account a `!todo link` code was given to to the
+    /// current user. Returns the chat account.
+    pub fn link_chat_account(&self, code: &str) -> Result<String, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let mut state = load_state().map_err(|_| "Failed to load chat accounts")?;
+        let pending = state
+            .pending
+            .remove(code)
+            .filter(|pending| pending.expires_at > Utc::now())
+            .ok_or("Invalid or expired link code")?;
+        state.links.insert(pending.account.clone(), user_id.clone());
+        if !self.skip_save(&format!("Link {} to user {}", pending.account, user_id)) {
+            save_state(&state).map_err(|_| "Failed to save chat accounts")?;
+        }
+        Ok(pending.account)
+    }
+
+    /// Unlinks every chat account linked to the current user. Returns how
+    /// many were linked.
+    pub fn unlink_chat_accounts(&self) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let mut state = load_state().map_err(|_| "Failed to load chat accounts")?;
+        let before = state.links.len();
+        state.links.retain(|_, linked| *linked != user_id);
+        let removed = before - state.links.len();
+        if removed > 0 && !self.skip_save(&format!("Unlink chat accounts of {}", user_id)) {
+            save_state(&state).map_err(|_| "Failed to save chat accounts")?;
+        }
+        Ok(removed)
+    }
+}
+
+/// A message from a person in the channel.
+struct ChatMessage {
+    user: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct SlackHistory {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    messages: Vec<SlackMessage>,
+}
+
+#[derive(Deserialize)]
+struct SlackMessage {
+    ts: String,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    bot_id: Option<String>,
+    #[serde(default)]
+    subtype: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SlackReply {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiscordMessage {
+    id: String,
+    #[serde(default)]
+    content: String,
+    author: DiscordUser,
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    id: String,
+    #[serde(default)]
+    bot: bool,
+}
+
+struct Client<'a> {
+    config: &'a BotConfig,
+}
+
+impl Client<'_> {
+    fn request(&self, method: &str, path: &str, body: &str) -> io::Result<String> {
+        let url = format!("{}/{}", self.config.api_url.trim_end_matches('/'), path);
+        let authorization = match self.config.platform {
+            Platform::Slack => format!("Bearer {}", self.config.token),
+            Platform::Discord => format!("Bot {}", self.config.token),
+        };
+        let response = http::send(
+            method,
+            &url,
+            &[
+                ("Authorization", &authorization),
+                ("Content-Type", "application/json; charset=utf-8"),
+            ],
+            body,
+        )?;
+        if !response.is_success() {
+            return Err(io::Error::other(format!(
+                "{} {} returned {}",
+                method, path, response.status
+            )));
+        }
+        Ok(response.body)
+    }
+
+    /// People's messages after the one with ID `after`, oldest first, and
+    /// the ID of the newest message fetched. Without `after`, only the
+    /// newest message's ID is returned, so history isn't replayed.
+    fn messages(&self, after: Option<&str>) -> io::Result<(Vec<ChatMessage>, Option<String>)> {
+        let limit = if after.is_some() { PAGE_SIZE } else { 1 };
+        let channel = &self.config.channel;
+        match self.config.platform {
+            Platform::Slack => {
+                let mut path = format!("conversations.history?channel={}&limit={}", channel, limit);
+                if let Some(after) = after {
+                    path.push_str(&format!("&oldest={}", after));
+                }
+                let history: SlackHistory = serde_json::from_str(&self.request("GET", &path, "")?)?;
+                if !history.ok {
+                    return Err(io::Error::other(history.error.unwrap_or_default()));
+                }
+                // Timestamps all have the same number of digits.
+                let newest = history.messages.iter().map(|m| &m.ts).max().cloned();
+                let mut messages: Vec<SlackMessage> = history
+                    .messages
+                    .into_iter()
+                    .filter(|m| m.bot_id.is_none() && m.subtype.is_none())
+                    .filter(|m| after.is_some_and(|after| m.ts.as_str() > after))
+                    .collect();
+                messages.sort_by(|a, b| a.ts.cmp(&b.ts));
+                let messages = messages
+                    .into_iter()
+                    .filter_map(|m| {
+                        Some(ChatMessage {
+                            user: m.user?,
+                            text: unescape_slack(&m.text),
+                        })
+                    })
+                    .collect();
+                Ok((messages, newest))
+            }
+            Platform::Discord => {
+                let mut path = format!("channels/{}/messages?limit={}", channel, limit);
+                if let Some(after) = after {
+                    path.push_str(&format!("&after={}", after));
+                }
+                let mut messages: Vec<DiscordMessage> =
+                    serde_json::from_str(&self.request("GET", &path, "")?)?;
+                messages.sort_by_key(|m| m.id.parse::<u64>().unwrap_or(0));
+                let newest = messages.last().map(|m| m.id.clone());
+                let messages = messages
+                    .into_iter()
+                    .filter(|m| !m.author.bot && after.is_some())
+                    .map(|m| ChatMessage {
+                        user: m.author.id,
+                        text: m.content,
+                    })
+                    .collect();
+                Ok((messages, newest))
+            }
+        }
+    }
+
+    /// Posts `text` to the channel.
+    fn send(&self, text: &str) -> io::Result<()> {
+        let channel = &self.config.channel;
+        match self.config.platform {
+            Platform::Slack => {
+                let body = serde_json::json!({ "channel": channel, "text": text }).to_string();
+                let reply: SlackReply =
+                    serde_json::from_str(&self.request("POST", "chat.postMessage", &body)?)?;
+                if !reply.ok {
+                    return Err(io::Error::other(reply.error.unwrap_or_default()));
+                }
+            }
+            Platform::Discord => {
+                let body = serde_json::json!({ "content": text }).to_string();
+                self.request("POST", &format!("channels/{}/messages", channel), &body)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Undoes the escaping Slack applies to `&`, `<` and `>` in message text.
+fn unescape_slack(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Answers one command from chat account `account`. `args` is the text
+/// after the prefix.
+fn answer(app: &mut TodoApp, state: &mut State, prefix: &str, account: &str, args: &str) -> String {
+    let (command, rest) = args.split_once(' ').unwrap_or((args, ""));
+    let rest = rest.trim();
+    match command {
+        "link" => {
+            let Ok(token) = crypto::random_token() else {
+                return tr(crypto::NO_RANDOM).to_string();
+            };
+            let code = token[..8].to_string();
+            state
+                .pending
+                .retain(|_, pending| pending.expires_at > Utc::now());
+            state.pending.insert(
+                code.clone(),
+                PendingLink {
+                    account: account.to_string(),
+                    expires_at: Utc::now() + Duration::minutes(LINK_CODE_MINUTES),
+                },
+            );
+            return trf(
+                "To link your account, run `bot link {}` in the app within {} minutes",
+                &[&code, &LINK_CODE_MINUTES],
+            );
+        }
+        "unlink" => {
+            return match state.links.remove(account) {
+                Some(_) => tr("Your account has been unlinked").to_string(),
+                None => tr("Your account isn't linked").to_string(),
+            };
+        }
+        "add" | "list" | "done" => {}
+        _ => {
+            return trf(
+                "Commands: {} add <task>, {} list, {} done <task id>, {} link, {} unlink",
+                &[&prefix, &prefix, &prefix, &prefix, &prefix],
+            )
+        }
+    }
+
+    let Some(linked) = state.links.get(account) else {
+        return trf(
+            "Your account isn't linked yet, send `{} link` to link it",
+            &[&prefix],
+        );
+    };
+    let Some(user_id) = app.find_user(linked).map(str::to_string) else {
+        return tr("The account yours is linked to no longer exists").to_string();
+    };
+    app.current_user = Some(user_id);
+    app.active_workspace = None;
+    let reply = match command {
+        "add" => app
+            .quick_add(rest)
+            .map(|task_id| trf("Task #{} added", &[&task_id]))
+            .map_err(|e| tr(e).to_string()),
+        "list" => app
+            .list_tasks()
+            .map(|tasks| {
+                let open: Vec<String> = tasks
+                    .iter()
+                    .filter(|task| !task.completed)
+                    .map(|task| format!("#{} {}", app.display_id(task), task.title))
+                    .collect();
+                match open.is_empty() {
+                    true => tr("No open tasks").to_string(),
+                    false => open.join("\n"),
+                }
+            })
+            .map_err(|e| tr(e).to_string()),
+        _ => app.resolve_task_id(rest).and_then(|task_id| {
+            app.complete_task(task_id).map_err(|e| tr(e).to_string())?;
+            Ok(trf("Task #{} completed", &[&task_id]))
+        }),
+    };
+    app.current_user = None;
+    reply.unwrap_or_else(|e| e)
+}
+
+/// Answers the commands sent since the last check.
+fn poll(app: &mut TodoApp, config: &BotConfig, client: &Client) -> io::Result<()> {
+    let mut state = load_state()?;
+    let cursor_key = format!("{}:{}", config.platform.name(), config.channel);
+    let (messages, newest) = client.messages(state.cursors.get(&cursor_key).map(String::as_str))?;
+    for message in messages {
+        let Some(args) = message.text.trim().strip_prefix(&config.prefix) else {
+            continue;
+        };
+        if !args.is_empty() && !args.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let account = format!("{}:{}", config.platform.name(), message.user);
+        log::info(&format!("Chat command from {}: {}", account, args.trim()));
+        let reply = answer(app, &mut state, &config.prefix, &account, args.trim());
+        if let Err(e) = client.send(&reply) {
+            log::warn(&format!("Failed to reply in chat: {}", e));
+        }
+    }
+    if let Some(newest) = newest {
+        state.cursors.insert(cursor_key, newest);
+    }
+    save_state(&state)
+}
+
+/// Checks the channel every `poll_secs` seconds and answers commands,
+/// forever.
+pub fn run(app: &mut TodoApp, config: &BotConfig) -> io::Result<()> {
+    let client = Client { config };
+    println!("{}", trf("Listening for {} in chat", &[&config.prefix]));
+    loop {
+        // Pick up changes made by other sessions since the last check.
+        app.load_users()?;
+        app.load_tasks()?;
+        if let Err(e) = poll(app, config, &client) {
+            log::warn(&format!("Failed to check chat: {}", e));
+        }
+        thread::sleep(std::time::Duration::from_secs(config.poll_secs.max(1)));
+    }
+}
@@ -0,0 +1,253 @@
+//! Editing many tasks at once in an external editor (`edit-bulk`). The tasks
+//! are written to a YAML or JSON file; when it's saved, edited entries update
+//! their task, entries without an `id` become new tasks and tasks whose entry
+//! was removed are deleted. Either every change is saved or none is.
+
+use crate::contexts::parse_context;
+use crate::formats::DataFormat;
+use crate::priority::Priority;
+use crate::rules::{self, Trigger};
+use crate::schedule::{local_date, start_of_day};
+use crate::validation;
+use crate::yaml;
+use crate::{Task, TaskEvent, TodoApp};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A task as written to the file. Only the fields worth editing in bulk
+/// are included; the rest of the task is left as it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    /// Left out for tasks to be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub completed: bool,
+    /// `YYYY-MM-DD`.
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+impl Entry {
+    fn from_task(task: &Task) -> Self {
+        Self {
+            id: Some(task.id),
+            title: task.title.clone(),
+            description: task.description.clone(),
+            completed: task.completed,
+            due: task
+                .due_date
+                .map(|due| local_date(due).format("%Y-%m-%d").to_string()),
+            priority: task.priority,
+            project: task.project.clone(),
+            context: task.context.clone(),
+            tags: task.tags.clone(),
+        }
+    }
+
+    /// Applies this entry to `task`, checking each value.
+    fn apply_to(&self, task: &mut Task) -> Result<(), String> {
+        let invalid = |e: &str| match self.id {
+            Some(id) => format!("#{}: {}", id, e),
+            None => format!("{}: {}", self.title, e),
+        };
+        task.title = validation::title(&self.title).map_err(invalid)?;
+        task.description = self.description.clone();
+        if self.completed != task.completed {
+            task.completed = self.completed;
+            task.completed_at = self.completed.then(Utc::now);
+        }
+        task.due_date = match &self.due {
+            Some(due) => Some(start_of_day(
+                NaiveDate::parse_from_str(due, "%Y-%m-%d")
+                    .map_err(|_| invalid("Invalid date, expected YYYY-MM-DD"))?,
+            )),
+            None => None,
+        };
+        task.priority = self.priority;
+        task.project = self
+            .project
+            .as_deref()
+            .map(str::trim)
+            .filter(|project| !project.is_empty())
+            .map(str::to_string);
+        task.context = self
+            .context
+            .as_deref()
+            .map(parse_context)
+            .transpose()
+            .map_err(invalid)?;
+        task.tags = self.tags.iter().map(|tag| tag.to_lowercase()).collect();
+        Ok(())
+    }
+}
+
+/// The changes made in the file, checked and ready to be applied.
+#[derive(Default)]
+pub struct Plan {
+    pub create: Vec<Task>,
+    pub update: Vec<Task>,
+    pub delete: Vec<u32>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// `entries` as the text of a file in `format`.
+pub fn render(entries: &[Entry], format: DataFormat) -> Result<String, String> {
+    let value = serde_json::to_value(entries).map_err(|e| e.to_string())?;
+    match format {
+        DataFormat::Yaml => Ok(yaml::encode(&value)),
+        _ => serde_json::to_string_pretty(&value).map_err(|e| e.to_string()),
+    }
+}
+
+/// Reads the entries back from the edited file.
+fn parse(text: &str, format: DataFormat) -> Result<Vec<Entry>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let value = match format {
+        DataFormat::Yaml => yaml::decode(text).map_err(|e| e.to_string())?,
+        _ => serde_json::from_str(text).map_err(|e| e.to_string())?,
+    };
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+impl TodoApp {
+    /// The tasks of the current list, optionally only those in `project`,
+    /// as entries to edit.
+    pub fn bulk_entries(&self, project: Option<&str>) -> Result<Vec<Entry>, &'static str> {
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| project.is_none() || task.project.as_deref() == project)
+            .map(Entry::from_task)
+            .collect())
+    }
+
+    /// Works out the changes between the `original` entries and the edited
+    /// `text`. Entries may only refer to tasks among the originals.
+    pub fn plan_bulk_edit(
+        &self,
+        original: &[Entry],
+        text: &str,
+        format: DataFormat,
+    ) -> Result<Plan, String> {
+        let edited = parse(text, format)?;
+        let original: BTreeMap<u32, &Entry> = original
+            .iter()
+            .filter_map(|entry| Some((entry.id?, entry)))
+            .collect();
+
+        let mut plan = Plan::default();
+        let mut seen = BTreeSet::new();
+        for entry in &edited {
+            let Some(id) = entry.id else {
+                let mut task = self
+                    .new_task(&entry.title, "", None)
+                    .map_err(|e| format!("{}: {}", entry.title, e))?;
+                entry.apply_to(&mut task)?;
+                plan.create.push(task);
+                continue;
+            };
+            if !original.contains_key(&id) {
+                return Err(format!("#{}: not one of the tasks being edited", id));
+            }
+            if !seen.insert(id) {
+                return Err(format!("#{}: listed more than once", id));
+            }
+            if original[&id] == entry {
+                continue;
+            }
+            let mut task = self.tasks.get(&id).ok_or("Task not found")?.clone();
+            entry.apply_to(&mut task)?;
+            task.touch();
+            plan.update.push(task);
+        }
+        plan.delete = original
+            .keys()
+            .filter(|id| !seen.contains(id))
+            .copied()
+            .collect();
+        Ok(plan)
+    }
+
+    /// Applies a bulk edit and saves it. If saving fails, every task is
+    /// put back the way it was.
+    pub fn apply_bulk_edit(&mut self, plan: Plan) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let changed = plan
+            .update
+            .iter()
+            .map(|task| task.id)
+            .chain(plan.delete.iter().copied());
+        for task_id in changed {
+            if !self
+                .tasks
+                .get(&task_id)
+                .is_some_and(|task| self.workspaces.can_edit(task, &user_id))
+            {
+                return Err("Not authorized to modify this task");
+            }
+        }
+        self.check_quota(plan.create.len() as u32)?;
+        let mut transaction = self.begin();
+
+        let updated = plan.update.len();
+        for mut task in plan.update {
+            rules::apply(&transaction.rules, &mut task, Trigger::Updated);
+            transaction.insert_task(task);
+        }
+        let mut deleted = Vec::new();
+        for task_id in &plan.delete {
+            if let Some(task) = transaction.remove_task(*task_id) {
+                transaction.remove_links_to(*task_id);
+                deleted.push(task);
+            }
+        }
+        let mut created = Vec::new();
+        for mut task in plan.create {
+            task.id = transaction.next_task_id;
+            rules::apply(&transaction.rules, &mut task, Trigger::Created);
+            created.push(task.id);
+            transaction.insert_task(task);
+            transaction.next_task_id += 1;
+        }
+
+        let message = format!(
+            "Bulk edit by {}: {} created, {} updated, {} deleted",
+            user_id,
+            created.len(),
+            updated,
+            deleted.len()
+        );
+        transaction.commit(&message)?;
+        for task_id in created {
+            self.emit(TaskEvent::Created, &self.tasks[&task_id]);
+        }
+        if !deleted.is_empty() {
+            self.move_to_trash(&deleted, &user_id);
+        }
+        for task in deleted {
+            self.emit(TaskEvent::Deleted, &task);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! Optional CalDAV sync of tasks with due dates.
+//!
+//! Tasks are pushed as `VTODO` items into a CalDAV collection and remote
+//! completions are pulled back. Each user's tasks are mapped to their own
+//! UIDs, so one user's sync never touches another's tasks.
+//!
+//! Like all integrations this goes through the plain-HTTP client, which
+//! can't speak HTTPS. Providers such as Nextcloud or Fastmail only accept
+//! HTTPS, so they have to be reached through a TLS proxy on the same
+//! machine, e.g. `stunnel`. Only URLs on the loopback interface are
+//! accepted, so the password is never sent over the network in cleartext.
+
+use crate::http::{self, Response};
+use crate::log;
+use crate::{Task, TodoApp};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+const CALDAV_MAP_FILE: &str = "caldav.json";
+
+/// Connection settings for the CalDAV collection, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalDavConfig {
+    /// Collection URL behind a local TLS proxy, e.g.
+    /// `http://localhost:8080/remote.php/dav/calendars/me/tasks/`.
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// Summary of a single sync run.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub pushed: usize,
+    pub completed: usize,
+    /// Tasks that couldn't be pushed or completed; the rest are synced anyway.
+    pub failed: usize,
+}
+
+/// Each user's task IDs by CalDAV UID, as stored in `CALDAV_MAP_FILE`.
+type UidMaps = HashMap<String, HashMap<String, u32>>;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UidMapFile {
+    ByUser(UidMaps),
+    /// One map for everyone, as written by earlier versions.
+    Shared(HashMap<String, u32>),
+}
+
+struct CalDavClient {
+    base_url: String,
+    auth: String,
+}
+
+impl CalDavClient {
+    fn new(config: &CalDavConfig) -> Result<Self, &'static str> {
+        if !http::is_loopback_url(&config.url) {
+            return Err(
+                "The CalDAV URL must be an http:// URL on localhost, e.g. a local TLS proxy",
+            );
+        }
+        let mut base_url = config.url.clone();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let credentials = format!("{}:{}", config.username, config.password);
+
+        Ok(Self {
+            base_url,
+            auth: format!("Basic {}", http::base64_encode(credentials.as_bytes())),
+        })
+    }
+
+    /// Sends a request for a resource in the collection.
+    fn request(&self, method: &str, resource: &str, body: &str) -> io::Result<Response> {
+        let headers = [
+            ("Authorization", self.auth.as_str()),
+            ("Content-Type", "text/calendar; charset=utf-8"),
+        ];
+        http::send(
+            method,
+            &format!("{}{}", self.base_url, resource),
+            &headers,
+            body,
+        )
+    }
+}
+
+/// Pushes the current user's due-dated tasks and pulls back remote completions.
+/// A task the server rejects or that can't be completed is counted as failed
+/// and skipped; only losing the connection stops the sync.
+pub fn sync(app: &mut TodoApp, config: &CalDavConfig) -> Result<SyncReport, &'static str> {
+    let user_id = app.current_user.clone().ok_or("Not logged in")?;
+    let client = CalDavClient::new(config)?;
+    let mut maps = load_uid_maps(app).map_err(|_| "Failed to load CalDAV mapping")?;
+    let mut uids = maps.remove(&user_id).unwrap_or_default();
+    let mut report = SyncReport::default();
+
+    let tasks: Vec<(u32, String, String)> = app
+        .list_tasks()?
+        .into_iter()
+        .filter(|task| task.due_date.is_some())
+        .map(|task| {
+            let uid = task_uid(task, &uids);
+            let vtodo = to_vtodo(task, &uid);
+            (task.id, uid, vtodo)
+        })
+        .collect();
+    for (task_id, uid, vtodo) in tasks {
+        let response = client
+            .request("PUT", &format!("{}.ics", uid), &vtodo)
+            .map_err(|_| "Failed to reach CalDAV server")?;
+        if !response.is_success() {
+            log::warn(&format!(
+                "CalDAV server rejected task #{} with status {}",
+                task_id, response.status
+            ));
+            report.failed += 1;
+            continue;
+        }
+        uids.insert(uid, task_id);
+        report.pushed += 1;
+    }
+
+    let pending: Vec<(String, u32)> = uids
+        .iter()
+        .filter(|(_, id)| app.tasks.get(id).is_some_and(|task| !task.completed))
+        .map(|(uid, id)| (uid.clone(), *id))
+        .collect();
+    for (uid, task_id) in pending {
+        let response = client
+            .request("GET", &format!("{}.ics", uid), "")
+            .map_err(|_| "Failed to reach CalDAV server")?;
+        if response.status == 200
+            && response
+                .body
+                .lines()
+                .any(|line| line.trim() == "STATUS:COMPLETED")
+        {
+            match app.complete_task(task_id) {
+                Ok(()) => report.completed += 1,
+                Err(e) => {
+                    log::warn(&format!(
+                        "Failed to complete task #{} from CalDAV: {}",
+                        task_id, e
+                    ));
+                    report.failed += 1;
+                }
+            }
+        }
+    }
+
+    uids.retain(|_, id| app.tasks.contains_key(id));
+    maps.insert(user_id, uids);
+    save_uid_maps(&maps).map_err(|_| "Failed to save CalDAV mapping")?;
+    Ok(report)
+}
+
+/// Stable UID used for a task on the CalDAV server: the task's UUID, or the
+/// UID it was already pushed under.
+fn task_uid(task: &Task, uids: &HashMap<String, u32>) -> String {
+    uids.iter()
+        .find(|(_, id)| **id == task.id)
+        .map_or_else(|| task.uuid.clone(), |(uid, _)| uid.clone())
+}
+
+/// Renders a task as an iCalendar `VTODO` document.
+fn to_vtodo(task: &Task, uid: &str) -> String {
+    const STAMP: &str = "%Y%m%dT%H%M%SZ";
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Lab3//Todo App//EN".to_string(),
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", uid),
+        format!("DTSTAMP:{}", task.created_at.format(STAMP)),
+        format!("SUMMARY:{}", escape_text(&task.title)),
+        format!("DESCRIPTION:{}", escape_text(&task.description)),
+        format!(
+            "STATUS:{}",
+            if task.completed {
+                "COMPLETED"
+            } else {
+                "NEEDS-ACTION"
+            }
+        ),
+    ];
+    if let Some(due) = task.due_date {
+        lines.push(format!("DUE:{}", due.format(STAMP)));
+    }
+    lines.push("END:VTODO".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Loads every user's UID map. A shared map from earlier versions is split
+/// up by the owners of its tasks.
+fn load_uid_maps(app: &TodoApp) -> io::Result<UidMaps> {
+    let contents = match fs::read_to_string(CALDAV_MAP_FILE) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(UidMaps::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(match serde_json::from_str(&contents)? {
+        UidMapFile::ByUser(maps) => maps,
+        UidMapFile::Shared(uids) => {
+            let mut maps = UidMaps::new();
+            for (uid, task_id) in uids {
+                if let Some(task) = app.tasks.get(&task_id) {
+                    maps.entry(task.user_id.clone())
+                        .or_default()
+                        .insert(uid, task_id);
+                }
+            }
+            maps
+        }
+    })
+}
+
+fn save_uid_maps(maps: &UidMaps) -> io::Result<()> {
+    let json = serde_json::to_string(maps)?;
+    fs::write(CALDAV_MAP_FILE, json)?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! A month calendar showing how many pending tasks are due on each day.
+
+use crate::accessibility::{self, Accessibility};
+use crate::i18n::{self, tr, trf};
+use crate::schedule::local_date;
+use crate::settings::Role;
+use crate::theme;
+use crate::{Task, TodoApp};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+
+/// Short weekday names for the header, Monday first.
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// Width of a day's cell, including the space after it.
+const CELL: usize = 7;
+
+impl TodoApp {
+    /// The current user's pending tasks due in the month of `month`, by day.
+    pub fn due_by_day(
+        &self,
+        month: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, Vec<&Task>>, &'static str> {
+        let mut days: BTreeMap<NaiveDate, Vec<&Task>> = BTreeMap::new();
+        for task in self.list_tasks()? {
+            let Some(due) = task.due_date.filter(|_| !task.completed) else {
+                continue;
+            };
+            let date = local_date(due);
+            if (date.year(), date.month()) == (month.year(), month.month()) {
+                days.entry(date).or_default().push(task);
+            }
+        }
+        Ok(days)
+    }
+}
+
+/// The month of `month` as a grid, with the number of tasks due next to
+/// each day, coloured by how busy the day is. Today is marked with `*`. In
+/// the symbols accessibility mode busy days are marked with `+` and packed
+/// ones with `!` instead, and for screen readers the month is a list.
+pub fn render(month: NaiveDate, due: &BTreeMap<NaiveDate, Vec<&Task>>, today: NaiveDate) -> String {
+    let first = month.with_day(1).unwrap_or(month);
+    let symbols = match accessibility::mode() {
+        Accessibility::Off => false,
+        Accessibility::Symbols => true,
+        Accessibility::ScreenReader => return render_list(first, due, today),
+    };
+    let mut out = format!("{}\n", first.format("%Y-%m"));
+    for weekday in WEEKDAYS {
+        out.push_str(&format!("{:<width$}", tr(weekday), width = CELL));
+    }
+    out = out.trim_end().to_string();
+    out.push('\n');
+
+    let offset = first.weekday().num_days_from_monday() as usize;
+    let mut line = " ".repeat(offset * CELL);
+    for day in first
+        .iter_days()
+        .take_while(|day| day.month() == first.month())
+    {
+        let marker = if day == today { '*' } else { ' ' };
+        let count = due.get(&day).map_or(0, Vec::len);
+        let count_text = match count {
+            0 => "   ".to_string(),
+            1 if symbols => format!("{:<3}", count),
+            2..=3 if symbols => format!("{:<3}", format!("{}+", count)),
+            count if symbols => format!("{:<3}", format!("{}!", count)),
+            count => format!("{:<3}", count),
+        };
+        let count_text = match count {
+            _ if symbols => count_text,
+            0 => count_text,
+            1 => theme::paint(&count_text, Role::LightDay),
+            2..=3 => theme::paint(&count_text, Role::BusyDay),
+            _ => theme::paint(&count_text, Role::PackedDay),
+        };
+        line.push_str(&format!("{:>2}{}{} ", day.day(), marker, count_text));
+        if day.weekday().num_days_from_monday() == 6 {
+            out.push_str(line.trim_end());
+            out.push('\n');
+            line.clear();
+        }
+    }
+    if !line.trim().is_empty() {
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    if symbols {
+        out.push_str(tr("+ busy day, ! packed day"));
+        out.push('\n');
+    }
+    out
+}
+
+/// The month as a line for today and for each day with tasks due.
+fn render_list(
+    first: NaiveDate,
+    due: &BTreeMap<NaiveDate, Vec<&Task>>,
+    today: NaiveDate,
+) -> String {
+    let mut out = format!("{}\n", first.format("%Y-%m"));
+    let days = first
+        .iter_days()
+        .take_while(|day| day.month() == first.month())
+        .filter(|day| *day == today || due.contains_key(day));
+    for day in days {
+        let weekday = day.format("%A").to_string();
+        let count = due.get(&day).map_or(0, Vec::len);
+        let mut line = format!("{} {}", tr(&weekday), i18n::format_day(day));
+        if day == today {
+            line.push_str(&format!(", {}", tr("today")));
+        }
+        line.push_str(&format!(": {}\n", trf("{} task(s) due", &[&count])));
+        out.push_str(&line);
+    }
+    out
+}
@@ -0,0 +1,40 @@
+//! Command line parsing: `Lab3 [global flags] [command [args...]]`.
+
+/// Parsed command line arguments.
+#[derive(Debug, Default)]
+pub struct Cli {
+    /// Global flags before the command, e.g. `--single-user`.
+    pub flags: Vec<String>,
+    /// Account to run a one-shot command as, from `--user <name>`.
+    pub user: Option<String>,
+    /// Number of synthetic tasks to generate, from `--bench-data <count>`.
+    pub bench_data: Option<String>,
+    /// Command to run instead of the interactive menu, with its arguments.
+    pub command: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Self {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--user" {
+                cli.user = args.next();
+            } else if arg == "--bench-data" {
+                cli.bench_data = args.next();
+            } else if arg == "-v" {
+                cli.flags.push("--verbose".to_string());
+            } else if arg.starts_with("--") {
+                cli.flags.push(arg);
+            } else {
+                cli.command.push(arg);
+                cli.command.extend(args.by_ref());
+            }
+        }
+        cli
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|arg| arg == flag)
+    }
+}
@@ -0,0 +1,2525 @@
+//! Typed commands accepted at the logged-in menu prompt, e.g. `history 3`.
+
+use crate::accessibility;
+use crate::account;
+use crate::activity::{self, PAGE_SIZE};
+use crate::attachments;
+use crate::bulk;
+use crate::calendar;
+use crate::defaults::{Scope, DEFAULTS_FILE};
+use crate::digest;
+use crate::doctor;
+use crate::dot;
+use crate::editor;
+use crate::formats::DataFormat;
+use crate::fsck;
+use crate::grep::{GrepOptions, Pattern};
+use crate::groups::GROUPS_FILE;
+use crate::habits::{self, Frequency, HABITS_FILE};
+use crate::i18n::{self, tr, trf};
+use crate::icons;
+use crate::importing::{Decision, Outcome, Resolution};
+use crate::inbox::Triage;
+use crate::links;
+use crate::merge::{Conflict, Side};
+use crate::next;
+use crate::notifications;
+use crate::ordering::Move;
+use crate::planner;
+use crate::priority::Priority;
+use crate::progress;
+use crate::quickadd;
+use crate::quota::{self, Quota};
+use crate::report;
+use crate::schedule::{self, parse_duration, start_of_today};
+use crate::settings::{self, Role, SortOrder, UserSettings, SETTINGS_FILE};
+use crate::shares::SHARES_FILE;
+use crate::snapshots::{self, SNAPSHOTS_FILE};
+use crate::stale::{StaleTask, Suggestion};
+use crate::statsexport;
+use crate::storage;
+use crate::terminal;
+use crate::theme;
+use crate::timesheet;
+use crate::tokens::TOKENS_FILE;
+use crate::trash::TRASH_FILE;
+use crate::usagestats;
+use crate::workspaces::{self, WORKSPACES_FILE};
+use crate::{
+    confirm, parse_due_date, print_context_note, print_error, print_focus, print_task,
+    prompt_input, Task, TodoApp, TASKS_FILE, USERS_FILE,
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Tasks listed by `next` when no count is given.
+const DEFAULT_NEXT_COUNT: usize = 5;
+
+/// Days charted by `burndown` when no number is given, and the most it charts.
+const DEFAULT_BURNDOWN_DAYS: u32 = 30;
+const MAX_BURNDOWN_DAYS: u32 = 366;
+
+/// Name and usage of every command, in the order shown by `help`.
+const COMMANDS: &[(&str, &str)] = &[
+    ("help", "help"),
+    ("history", "history <task id>"),
+    ("sync", "sync [--prefer local|remote]"),
+    ("public", "public <task id>"),
+    ("private", "private <task id>"),
+    ("view-user", "view-user <username>"),
+    (
+        "remind",
+        "remind <task id> <time before due, e.g. 1d or 2h> | remind <task id> cron <minute hour day month weekday>",
+    ),
+    ("reminders", "reminders list | reminders clear <task id>"),
+    ("snooze", "snooze <task id> <duration, e.g. 2h or 3 days>"),
+    ("reschedule", "reschedule <task id> <YYYY-MM-DD>"),
+    ("rollover", "rollover [YYYY-MM-DD] [--dry-run]"),
+    ("expire", "expire <task id> <YYYY-MM-DD [HH:MM] | never>"),
+    ("calendar", "calendar [YYYY-MM | YYYY-MM-DD]"),
+    (
+        "focus",
+        "focus list | focus add <task id> | focus remove <task id> | focus clear",
+    ),
+    ("stale", "stale [days]"),
+    ("snapshot", "snapshot"),
+    ("burndown", "burndown [days]"),
+    ("stats", "stats [--format json]"),
+    ("usage", "usage"),
+    ("next", "next [count]"),
+    ("depend", "depend <task id> <task id it waits for>"),
+    ("undepend", "undepend <task id> <task id it waits for>"),
+    ("agenda", "agenda"),
+    ("planner", "planner [file.txt | file.pdf]"),
+    ("holidays", "holidays [YYYY]"),
+    ("report", "report html [file]"),
+    ("track", "track <task id> <duration> [notes]"),
+    ("timesheet", "timesheet <from YYYY-MM-DD> <to YYYY-MM-DD> [file.csv]"),
+    ("digest", "digest [--email]"),
+    ("email", "email <address>"),
+    (
+        "field",
+        "field set <task id> <name> <value> | field unset <task id> <name>",
+    ),
+    (
+        "quick",
+        "quick <title> [+project] [@tag] [!low|!high|!urgent] [due:<when>]",
+    ),
+    ("inbox", "inbox [title]"),
+    ("triage", "triage"),
+    (
+        "defaults",
+        "defaults list | defaults <project|tag> <name> <priority|remind|estimate|icon> <value|none> | defaults <project|tag> <name> --clear",
+    ),
+    ("estimate", "estimate <task id> <duration, e.g. 2h> | estimate <task id> --none"),
+    ("progress", "progress <task id> <0-100> | progress <task id> --none"),
+    (
+        "list",
+        "list [--all | --field <name>=<value> | --assigned | --query <query>]",
+    ),
+    ("grep", "grep [-i] [--all] [-C <lines>] <pattern>"),
+    ("filter", "filter <name> | filter save <name> <expression> | filter delete <name>"),
+    ("filters", "filters"),
+    (
+        "context",
+        "context list | context <task id> <@context> | context <task id> --none",
+    ),
+    ("icon", "icon <task id> <icon> | icon <task id> --none"),
+    ("comment", "comment <task id> <text>"),
+    ("assign", "assign <task id> <username>"),
+    ("unassign", "unassign <task id>"),
+    ("attach", "attach <task id> <path or URL> [name]"),
+    ("detach", "detach <task id> <name>"),
+    ("open", "open <task id> <attachment name>"),
+    (
+        "project",
+        "project <task id> <name> | project <task id> --none",
+    ),
+    ("link", "link <task id> <related task id>"),
+    ("unlink", "unlink <task id> <related task id>"),
+    (
+        "graph",
+        "graph [task id] | graph --format dot [--project <name>]",
+    ),
+    (
+        "share",
+        "share list | share create <project> [--expires <duration, e.g. 7d>] | share revoke <id>",
+    ),
+    ("trash", "trash | trash empty"),
+    ("restore", "restore <task id>"),
+    ("duplicate", "duplicate <task id> [YYYY-MM-DD]"),
+    ("clone-project", "clone-project <project> <new project>"),
+    ("pin", "pin <task id>"),
+    ("unpin", "unpin <task id>"),
+    (
+        "move",
+        "move <task id> up | move <task id> down | move <task id> to <position>",
+    ),
+    ("edit-bulk", "edit-bulk [--project <name>] [--json]"),
+    ("tag", "tag rename <old> <new> | tag merge <tag> <into tag>"),
+    ("settings", "settings | settings <name> <value>"),
+    ("rules", "rules test"),
+    #[cfg(feature = "bot")]
+    ("bot", "bot link <code> | bot unlink"),
+    ("theme", "theme check <file>"),
+    ("su", "su <username>"),
+    ("fsck", "fsck"),
+    ("doctor", "doctor"),
+    (
+        "orphans",
+        "orphans | orphans reassign <username> | orphans export <file.json> | orphans delete",
+    ),
+    ("retention", "retention status"),
+    (
+        "activity",
+        "activity [workspace] [--user <username>] [--page <n>]",
+    ),
+    ("convert-format", "convert-format <json|yaml|toml>"),
+    ("export-account", "export-account <file.tar> [--encrypt]"),
+    (
+        "import-account",
+        "import-account <file.tar> [--on-conflict skip|overwrite|duplicate|ask]",
+    ),
+    ("delete-account", "delete-account"),
+    ("export-todotxt", "export-todotxt <todo.txt>"),
+    (
+        "import-todotxt",
+        "import-todotxt <todo.txt> [--on-conflict skip|overwrite|duplicate|ask]",
+    ),
+    (
+        "workspace",
+        "workspace list | workspace create <name> | workspace delete <name> | workspace invite <name> <username> | workspace remove <name> <username> | workspace switch <name> | workspace switch --personal | workspace grant <name> <group> <view|comment|edit|admin> | workspace revoke <name> <group>",
+    ),
+    (
+        "group",
+        "group list | group create <name> | group delete <name> | group add <name> <username> | group remove <name> <username>",
+    ),
+    (
+        "permissions",
+        "permissions <workspace> | permissions <workspace> <username> <view|comment|edit|admin>",
+    ),
+    (
+        "habit",
+        "habit list | habit add <name> daily | habit add <name> weekly [<days a week>] | habit check <name> [YYYY-MM-DD] | habit uncheck <name> [YYYY-MM-DD] | habit remove <name> | habit grid [YYYY-MM]",
+    ),
+    (
+        "quota",
+        "quota | quota set <username> <max open|none> <max total|none> | quota reset <username>",
+    ),
+];
+
+/// Whether `line` starts with a known command name.
+pub fn is_command(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .is_some_and(|name| COMMANDS.iter().any(|(command, _)| *command == name))
+}
+
+/// Completions for the start of a command line: command names for the first
+/// word, then titles of the tasks in the current list.
+pub fn complete(app: &TodoApp, line: &str) -> Vec<String> {
+    let Some((command, rest)) = line.split_once(' ') else {
+        return COMMANDS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| name.starts_with(line))
+            .map(str::to_string)
+            .collect();
+    };
+    let rest = rest.trim_start().to_lowercase();
+    let mut titles: Vec<String> = app
+        .list_tasks()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|task| task.title.to_lowercase().starts_with(&rest))
+        .map(|task| format!("{} {}", command, task.title))
+        .collect();
+    titles.sort();
+    titles.dedup();
+    titles
+}
+
+/// Runs a typed command, printing its output or error.
+pub fn run(app: &mut TodoApp, line: &str) {
+    let args = split_args(line);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if let Err(e) = execute(app, &args) {
+        print_error(&e);
+    }
+}
+
+/// Splits a command line into words, keeping double-quoted text together.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    args
+}
+
+/// Runs a command given as separate arguments.
+pub fn execute(app: &mut TodoApp, args: &[&str]) -> Result<(), String> {
+    if let Some(command) = args.first().filter(|command| is_command(command)) {
+        app.count_command(command);
+    }
+    match args {
+        ["help"] => help(),
+        ["history", id] => history(app, id),
+        ["sync"] => sync(app, None),
+        ["sync", "--prefer", side] => {
+            let side = Side::parse(side).ok_or_else(|| trf("Usage: {}", &[&usage("sync")]))?;
+            sync(app, Some(side))
+        }
+        ["public", id] => set_public(app, id, true),
+        ["private", id] => set_public(app, id, false),
+        ["view-user", username] => view_user(app, username),
+        ["remind", id, "cron", schedule @ ..] if !schedule.is_empty() => {
+            let task_id = parse_task_id(app, id)?;
+            app.add_cron_reminder(task_id, &schedule.join(" "))?;
+            println!("{}", tr("Reminder added"));
+            Ok(())
+        }
+        ["remind", id, offset @ ..] if !offset.is_empty() => remind(app, id, &offset.join(" ")),
+        ["reminders", "list"] => list_reminders(app),
+        ["reminders", "clear", id] => clear_reminders(app, id),
+        ["snooze", id, duration @ ..] if !duration.is_empty() => {
+            snooze(app, id, &duration.join(" "))
+        }
+        ["reschedule", id, date] => reschedule(app, id, date),
+        ["rollover", options @ ..] => roll_over(app, options),
+        ["expire", id, "never"] => set_expiry(app, id, None),
+        ["expire", id, date] => set_expiry(app, id, Some((date, None))),
+        ["expire", id, date, time] => set_expiry(app, id, Some((date, Some(time)))),
+        ["agenda"] => agenda(app),
+        ["planner", path @ ..] if path.len() <= 1 => planner(app, path.first().copied()),
+        ["holidays"] => holidays(app, None),
+        ["holidays", year] => holidays(app, Some(year)),
+        ["digest"] => {
+            print!("{}", digest::render(&app.digest()?));
+            Ok(())
+        }
+        ["digest", "--email"] => send_digest(app),
+        ["email", address] => set_email(app, address),
+        ["field", "set", id, name, value @ ..] if !value.is_empty() => {
+            set_field(app, id, name, &value.join(" "))
+        }
+        ["field", "unset", id, name] => unset_field(app, id, name),
+        ["focus", "list"] => {
+            if app.focus_tasks()?.is_empty() {
+                println!("{}", tr("Nothing in focus today"));
+            }
+            print_focus(app);
+            Ok(())
+        }
+        ["focus", "add", id] => {
+            let task_id = parse_task_id(app, id)?;
+            app.add_focus(task_id)?;
+            println!("{}", trf("Task #{} added to today's focus", &[&task_id]));
+            Ok(())
+        }
+        ["focus", "remove", id] => {
+            let task_id = parse_task_id(app, id)?;
+            app.remove_focus(task_id)?;
+            println!(
+                "{}",
+                trf("Task #{} removed from today's focus", &[&task_id])
+            );
+            Ok(())
+        }
+        ["focus", "clear"] => {
+            app.clear_focus()?;
+            println!("{}", tr("Today's focus cleared"));
+            Ok(())
+        }
+        ["calendar"] => calendar(app, None),
+        ["calendar", when] => calendar(app, Some(when)),
+        ["stale"] => stale(app, None),
+        ["stale", days] => stale(app, Some(days)),
+        ["next"] => next_actions(app, None),
+        ["next", count] => next_actions(app, Some(count)),
+        ["depend", id, on] => set_dependency(app, id, on, true),
+        ["undepend", id, on] => set_dependency(app, id, on, false),
+        ["defaults", "list"] => list_defaults(app),
+        ["defaults", scope, name, "--clear"] => {
+            app.clear_defaults(parse_scope(scope)?, name)?;
+            println!("{}", trf("Defaults of {} cleared", &[name]));
+            Ok(())
+        }
+        ["defaults", scope, name, default, value @ ..] if !value.is_empty() => {
+            app.set_default(parse_scope(scope)?, name, default, &value.join(" "))?;
+            println!("{}", trf("Defaults of {} updated", &[name]));
+            Ok(())
+        }
+        ["estimate", id, "--none"] => set_estimate(app, id, None),
+        ["estimate", id, duration @ ..] if !duration.is_empty() => {
+            set_estimate(app, id, Some(&duration.join(" ")))
+        }
+        ["progress", id, "--none"] => set_progress(app, id, None),
+        ["progress", id, percent] => set_progress(app, id, Some(percent)),
+        ["report", "html"] => html_report(app, None),
+        ["report", "html", path] => html_report(app, Some(path)),
+        ["track", id, duration, note @ ..] => track(app, id, duration, &note.join(" ")),
+        ["timesheet", from, to] => timesheet(app, from, to, None),
+        ["timesheet", from, to, path] => timesheet(app, from, to, Some(path)),
+        ["quick", text @ ..] if !text.is_empty() => quick_add(app, &text.join(" ")),
+        ["inbox"] => print_tasks(app, app.inbox()?),
+        ["inbox", title @ ..] => capture(app, &title.join(" ")),
+        ["triage"] => triage(app),
+        ["list"] => list(app, None, false),
+        ["list", "--all"] => list(app, None, true),
+        ["context", "list"] => list_contexts(app),
+        ["context", id, "--none"] => set_context(app, id, None),
+        ["context", id, context] => set_context(app, id, Some(context)),
+        ["icon", id, "--none"] => set_icon(app, id, None),
+        ["icon", id, icon] => set_icon(app, id, Some(icon)),
+        ["comment", id, text @ ..] if !text.is_empty() => {
+            let task_id = parse_task_id(app, id)?;
+            app.comment_on_task(task_id, &text.join(" "))?;
+            println!("{}", tr("Comment added"));
+            Ok(())
+        }
+        ["list", "--field", filter] => list(app, Some(filter), true),
+        ["list", "--assigned"] => print_tasks(app, app.my_assignments()?),
+        ["list", "--query", query @ ..] if !query.is_empty() => {
+            print_tasks(app, app.query_tasks(&query.join(" "))?)
+        }
+        ["filter", "save", name, expression @ ..] if !expression.is_empty() => {
+            app.save_filter(name, &expression.join(" "))?;
+            println!("{}", trf("Filter {} saved", &[name]));
+            Ok(())
+        }
+        ["filter", "delete", name] => {
+            app.delete_filter(name)?;
+            println!("{}", trf("Filter {} deleted", &[name]));
+            Ok(())
+        }
+        ["filter", name] => print_tasks(app, app.filtered_tasks(name)?),
+        ["filters"] => list_filters(app),
+        ["grep", options @ ..] if !options.is_empty() => grep(app, options),
+        ["assign", id, username] => assign(app, id, Some(username)),
+        ["unassign", id] => assign(app, id, None),
+        ["attach", id, location] => attach(app, id, location, None),
+        ["attach", id, location, name] => attach(app, id, location, Some(name)),
+        ["detach", id, name] => detach(app, id, name),
+        ["open", id, name] => open_attachment(app, id, name),
+        ["project", id, "--none"] => set_project(app, id, None),
+        ["project", id, name] => set_project(app, id, Some(name)),
+        ["link", id, to] => set_link(app, id, to, true),
+        ["unlink", id, to] => set_link(app, id, to, false),
+        ["graph"] => graph(app, None),
+        ["graph", "--format", "dot"] => graph_dot(app, None),
+        ["graph", "--format", "dot", "--project", project] => graph_dot(app, Some(project)),
+        ["graph", id] => graph(app, Some(id)),
+        ["share", "list"] => list_shares(app),
+        ["share", "create", project] => create_share(app, project, None),
+        ["share", "create", project, "--expires", duration @ ..] if !duration.is_empty() => {
+            create_share(app, project, Some(&duration.join(" ")))
+        }
+        ["share", "revoke", id] => {
+            app.revoke_share(id)?;
+            println!("{}", tr("Share link revoked"));
+            Ok(())
+        }
+        ["duplicate", id] => duplicate(app, id, None),
+        ["duplicate", id, date] => duplicate(app, id, Some(date)),
+        ["clone-project", project, new_project] => clone_project(app, project, new_project),
+        ["pin", id] => set_pinned(app, id, true),
+        ["unpin", id] => set_pinned(app, id, false),
+        ["move", id, to @ ..] => move_task(app, id, to),
+        ["edit-bulk", options @ ..] => edit_bulk(app, options),
+        ["settings"] => settings_menu(app),
+        ["rules", "test"] => test_rules(app),
+        #[cfg(feature = "bot")]
+        ["bot", "link", code] => {
+            let account = app.link_chat_account(code)?;
+            println!("{}", trf("Linked chat account {}", &[&account]));
+            Ok(())
+        }
+        #[cfg(feature = "bot")]
+        ["bot", "unlink"] => {
+            let count = app.unlink_chat_accounts()?;
+            println!("{}", trf("Unlinked {} chat accounts", &[&count]));
+            Ok(())
+        }
+        ["theme", "check", path] => {
+            theme::load_file(path)?;
+            println!("{}", trf("{} is a valid theme", &[path]));
+            Ok(())
+        }
+        ["settings", name, value @ ..] => change_setting(app, name, &value.join(" ")),
+        ["su", username] => switch_user(app, username),
+        ["quota"] => show_quota(app),
+        ["quota", "set", username, max_open, max_total] => {
+            set_quota(app, username, max_open, max_total)
+        }
+        ["quota", "reset", username] => {
+            app.set_quota(username, None)?;
+            println!("{}", trf("{} now has the default quota", &[username]));
+            Ok(())
+        }
+        ["workspace", "list"] => list_workspaces(app),
+        ["stats"] => show_stats(app),
+        ["stats", "--format", "json"] => {
+            let tasks = app.list_tasks()?;
+            let stats = statsexport::build(&tasks, Utc::now());
+            let json = serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?;
+            println!("{}", json);
+            Ok(())
+        }
+        ["usage"] => show_usage(app),
+        ["snapshot"] => {
+            let snapshot = app.record_snapshot()?;
+            println!(
+                "{}",
+                trf(
+                    "Snapshot recorded: {} open, {} completed",
+                    &[&snapshot.open, &snapshot.completed]
+                )
+            );
+            Ok(())
+        }
+        ["burndown"] => burndown(app, None),
+        ["burndown", days] => burndown(app, Some(days)),
+        ["habit", "list"] => list_habits(app),
+        ["habit", "add", name, frequency, times @ ..] if times.len() <= 1 => {
+            app.add_habit(name, Frequency::parse(frequency, times.first().copied())?)?;
+            println!("{}", trf("Habit {} added", &[name]));
+            Ok(())
+        }
+        ["habit", "check", name, date @ ..] if date.len() <= 1 => {
+            check_habit(app, name, date.first().copied(), true)
+        }
+        ["habit", "uncheck", name, date @ ..] if date.len() <= 1 => {
+            check_habit(app, name, date.first().copied(), false)
+        }
+        ["habit", "remove", name] => {
+            app.remove_habit(name)?;
+            println!("{}", trf("Habit {} removed", &[name]));
+            Ok(())
+        }
+        ["habit", "grid", month @ ..] if month.len() <= 1 => {
+            habit_grid(app, month.first().copied())
+        }
+        ["activity", options @ ..] => activity(app, options),
+        ["tag", "rename", old, new] => {
+            let changed = app.rename_tag(old, new)?;
+            println!("{}", trf("Tag renamed on {} task(s)", &[&changed]));
+            Ok(())
+        }
+        ["tag", "merge", from, into] => {
+            let changed = app.merge_tags(from, into)?;
+            println!("{}", trf("Tags merged on {} task(s)", &[&changed]));
+            Ok(())
+        }
+        ["trash"] => list_trash(app),
+        ["trash", "empty"] => empty_trash(app),
+        ["restore", id] => {
+            let task_id = app.restore_task(parse_task_id(app, id)?)?;
+            println!("{}", trf("Task #{} restored", &[&task_id]));
+            Ok(())
+        }
+        ["retention", "status"] => retention_status(app),
+        ["convert-format", format] => convert_format(app, format),
+        ["export-account", path] => {
+            app.export_account(path, None)?;
+            println!("{}", trf("Account exported to {}", &[path]));
+            Ok(())
+        }
+        ["export-account", path, "--encrypt"] => {
+            let password = archive_password(true)?;
+            app.export_account(path, Some(&password))?;
+            println!("{}", trf("Account exported to {}, encrypted", &[path]));
+            Ok(())
+        }
+        ["import-account", path, options @ ..] => import_account(app, path, options),
+        ["delete-account"] => delete_account(app),
+        ["export-todotxt", path] => {
+            let count = app.export_todotxt(path)?;
+            println!("{}", trf("Exported {} tasks to {}", &[&count, path]));
+            Ok(())
+        }
+        ["import-todotxt", path, options @ ..] => {
+            let mut resolve = conflict_resolver(options, "import-todotxt", Resolution::Duplicate)?;
+            let decisions = app.import_todotxt(path, &mut resolve)?;
+            print_import_summary(&decisions);
+            Ok(())
+        }
+        ["workspace", "create", name] => {
+            app.create_workspace(name)?;
+            println!("{}", trf("Workspace {} created", &[name]));
+            Ok(())
+        }
+        ["workspace", "delete", name] => delete_workspace(app, name),
+        ["workspace", "invite", name, username] => {
+            app.invite_to_workspace(name, username)?;
+            println!("{}", trf("{} joined workspace {}", &[username, name]));
+            Ok(())
+        }
+        ["workspace", "remove", name, username] => {
+            app.remove_from_workspace(name, username)?;
+            println!("{}", trf("{} removed from workspace {}", &[username, name]));
+            Ok(())
+        }
+        ["workspace", "switch", "--personal"] => {
+            app.switch_workspace(None)?;
+            println!("{}", tr("Showing your personal tasks"));
+            Ok(())
+        }
+        ["permissions", name] => list_permissions(app, name),
+        ["permissions", name, username, level] => {
+            app.set_permission(name, username, workspaces::Role::parse(level)?)?;
+            println!(
+                "{}",
+                trf(
+                    "{} now has {} permission in workspace {}",
+                    &[username, level, name]
+                )
+            );
+            Ok(())
+        }
+        ["workspace", "grant", name, group, level] => {
+            app.grant_group(name, group, workspaces::Role::parse(level)?)?;
+            println!(
+                "{}",
+                trf(
+                    "Group {} now has {} permission in workspace {}",
+                    &[group, level, name]
+                )
+            );
+            Ok(())
+        }
+        ["workspace", "revoke", name, group] => {
+            app.revoke_group(name, group)?;
+            println!(
+                "{}",
+                trf("Group {} removed from workspace {}", &[group, name])
+            );
+            Ok(())
+        }
+        ["group", "list"] => list_groups(app),
+        ["group", "create", name] => {
+            app.create_group(name)?;
+            println!("{}", trf("Group {} created", &[name]));
+            Ok(())
+        }
+        ["group", "delete", name] => {
+            app.delete_group(name)?;
+            println!("{}", trf("Group {} deleted", &[name]));
+            Ok(())
+        }
+        ["group", "add", name, username] => {
+            app.add_to_group(name, username)?;
+            println!("{}", trf("{} added to group {}", &[username, name]));
+            Ok(())
+        }
+        ["group", "remove", name, username] => {
+            app.remove_from_group(name, username)?;
+            println!("{}", trf("{} removed from group {}", &[username, name]));
+            Ok(())
+        }
+        ["workspace", "switch", name] => {
+            app.switch_workspace(Some(name))?;
+            println!("{}", trf("Switched to workspace {}", &[name]));
+            Ok(())
+        }
+        ["orphans"] => list_orphans(app),
+        ["orphans", "reassign", username] => {
+            let count = app.reassign_orphans(username)?;
+            println!(
+                "{}",
+                trf("{} task(s) reassigned to {}", &[&count, username])
+            );
+            Ok(())
+        }
+        ["orphans", "export", path] => {
+            let count = app.export_orphans(path)?;
+            println!("{}", trf("{} task(s) exported to {}", &[&count, path]));
+            Ok(())
+        }
+        ["orphans", "delete"] => delete_orphans(app),
+        ["fsck"] => {
+            fsck::run(app.storage.as_ref(), app.dry_run).map_err(|e| e.to_string())?;
+            Ok(())
+        }
+        ["doctor"] => {
+            if doctor::run(app.storage.as_ref()).map_err(|e| e.to_string())? {
+                Ok(())
+            } else {
+                Err(tr("Problems found").to_string())
+            }
+        }
+        [] => Err(tr("Invalid choice").to_string()),
+        [command, ..] if !is_command(command) => Err(trf("Unknown command: {}", &[command])),
+        [command, ..] => Err(trf("Usage: {}", &[&usage(command)])),
+    }
+}
+
+fn usage(command: &str) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map_or("help", |(_, usage)| usage)
+}
+
+fn parse_task_id(app: &TodoApp, id: &str) -> Result<u32, String> {
+    app.resolve_task_id(id)
+}
+
+/// Lists the available commands.
+fn help() -> Result<(), String> {
+    println!("{}", tr("Commands:"));
+    for (_, usage) in COMMANDS {
+        println!("  {}", usage);
+    }
+    Ok(())
+}
+
+/// Shows the commits that touched a task.
+fn history(app: &TodoApp, id: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let uuid = &app.tasks.get(&task_id).ok_or(tr("Task not found"))?.uuid;
+    // Commits made before trailers were added only name the task in their
+    // subject.
+    let pattern = format!(
+        "^{}: {}$|task #{}$",
+        storage::TASK_ID_TRAILER,
+        uuid,
+        task_id
+    );
+    let entries = app.storage.history(&pattern).map_err(|e| e.to_string())?;
+    if entries.is_empty() {
+        println!("{}", trf("No history for task {}", &[&task_id]));
+    }
+    for entry in entries {
+        println!("{}", entry);
+    }
+    Ok(())
+}
+
+/// Pulls and pushes the data repository, then reloads the data files.
+/// Conflicting edits go to `prefer` if given; otherwise the user picks the
+/// winners in a terminal, and elsewhere both versions are kept.
+fn sync(app: &mut TodoApp, prefer: Option<Side>) -> Result<(), String> {
+    if app.dry_run {
+        return Err(tr("Not available in dry-run mode").to_string());
+    }
+    let interactive = terminal::is_interactive();
+    let mut resolve = |conflict: &Conflict| match prefer {
+        Some(side) => Some(side),
+        None if interactive => ask_conflict(conflict),
+        None => None,
+    };
+    app.storage.sync(&mut resolve).map_err(|e| e.to_string())?;
+    app.load_tasks().map_err(|e| e.to_string())?;
+    app.load_users().map_err(|e| e.to_string())?;
+    app.load_workspaces().map_err(|e| e.to_string())?;
+    app.load_groups().map_err(|e| e.to_string())?;
+    app.load_habits().map_err(|e| e.to_string())?;
+    app.load_snapshots().map_err(|e| e.to_string())?;
+    app.load_defaults().map_err(|e| e.to_string())?;
+    app.load_shares().map_err(|e| e.to_string())?;
+    app.load_settings().map_err(|e| e.to_string())?;
+    app.apply_settings();
+    println!("{}", tr("Synced with remote"));
+    Ok(())
+}
+
+/// Shows the base, local and remote values of a conflicting field and asks
+/// which wins. Keeps both when the answer can't be read.
+fn ask_conflict(conflict: &Conflict) -> Option<Side> {
+    match conflict.task {
+        Some((id, title)) => println!(
+            "{}",
+            trf(
+                "Conflict in task #{} {}, field {}:",
+                &[&id, &title, &conflict.field]
+            )
+        ),
+        None => println!(
+            "{}",
+            trf(
+                "Conflict in {}, field {}:",
+                &[&conflict.file, &conflict.field]
+            )
+        ),
+    }
+    for (label, value) in [
+        (tr("base"), conflict.base),
+        (tr("local"), conflict.local),
+        (tr("remote"), conflict.remote),
+    ] {
+        let value = match value {
+            Value::Null => tr("(none)").to_string(),
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        println!("  {:<8} {}", label, value);
+    }
+    let prompt = if conflict.task.is_some() {
+        tr("[l]ocal, [r]emote or keep [b]oth? ")
+    } else {
+        tr("[l]ocal or [r]emote? ")
+    };
+    loop {
+        let answer = prompt_input(prompt).ok()?;
+        match answer.to_lowercase().as_str() {
+            "l" | "local" => return Some(Side::Local),
+            "r" | "remote" => return Some(Side::Remote),
+            "b" | "both" if conflict.task.is_some() => return None,
+            _ => println!("{}", tr("Invalid choice")),
+        }
+    }
+}
+
+/// Shares a task with other users, or makes it private again.
+fn set_public(app: &mut TodoApp, id: &str, public: bool) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_task_public(task_id, public)
+        .map_err(|e| e.to_string())?;
+    if public {
+        println!("{}", tr("Task is now public"));
+    } else {
+        println!("{}", tr("Task is now private"));
+    }
+    Ok(())
+}
+
+/// Shows another user's public tasks.
+fn view_user(app: &TodoApp, username: &str) -> Result<(), String> {
+    let tasks = app.list_public_tasks(username).map_err(|e| e.to_string())?;
+    if tasks.is_empty() {
+        println!("{}", trf("{} has no public tasks", &[&username]));
+    }
+    for task in tasks {
+        print_task(app, task);
+    }
+    Ok(())
+}
+
+/// Adds a reminder to a task.
+fn remind(app: &mut TodoApp, id: &str, offset: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let offset = parse_duration(offset)?;
+    app.add_reminder(task_id, offset)?;
+    println!("{}", tr("Reminder added"));
+    Ok(())
+}
+
+/// Shows the reminders that are still to fire.
+fn list_reminders(app: &TodoApp) -> Result<(), String> {
+    let upcoming = app.upcoming_reminders()?;
+    if upcoming.is_empty() {
+        println!("{}", tr("No upcoming reminders"));
+    }
+    for (fire_at, task) in upcoming {
+        println!(
+            "{}  #{} {}",
+            i18n::format_datetime(fire_at),
+            task.id,
+            task.title
+        );
+    }
+    Ok(())
+}
+
+/// Removes all reminders from a task.
+fn clear_reminders(app: &mut TodoApp, id: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.clear_reminders(task_id)?;
+    println!("{}", tr("Reminders cleared"));
+    Ok(())
+}
+
+/// Pushes a task's due date back.
+fn snooze(app: &mut TodoApp, id: &str, duration: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.snooze_task(task_id, parse_duration(duration)?)?;
+    print_new_due_date(app, task_id);
+    Ok(())
+}
+
+/// Moves a task to a new due date.
+fn reschedule(app: &mut TodoApp, id: &str, date: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let due = parse_due_date(date)?.ok_or_else(|| tr("Invalid date, expected YYYY-MM-DD"))?;
+    app.reschedule_task(task_id, due)?;
+    print_new_due_date(app, task_id);
+    Ok(())
+}
+
+/// Sets when a task is cancelled if it isn't completed. A date without a
+/// time means the end of that day.
+fn set_expiry(
+    app: &mut TodoApp,
+    id: &str,
+    when: Option<(&str, Option<&str>)>,
+) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let expires_at = match when {
+        Some((date, time)) => {
+            let date =
+                parse_due_date(date)?.ok_or_else(|| tr("Invalid date, expected YYYY-MM-DD"))?;
+            Some(match time {
+                Some(time) => {
+                    let time = notifications::parse_time(time)?;
+                    date + (time - NaiveTime::MIN)
+                }
+                None => date + Duration::days(1),
+            })
+        }
+        None => None,
+    };
+    app.set_expiry(task_id, expires_at)?;
+    match expires_at {
+        Some(at) => println!("{}", trf("Task expires {}", &[&i18n::format_datetime(at)])),
+        None => println!("{}", tr("Task no longer expires")),
+    }
+    Ok(())
+}
+
+fn print_new_due_date(app: &TodoApp, task_id: u32) {
+    if let Some(due) = app.tasks[&task_id].due_date {
+        println!(
+            "{}",
+            trf("Task is now due {}", &[&i18n::format_datetime(due)])
+        );
+    }
+}
+
+/// Moves overdue tasks to today, or the next working day if today is a day
+/// off, or to a given date after confirmation.
+fn roll_over(app: &mut TodoApp, options: &[&str]) -> Result<(), String> {
+    let dry_run = app.dry_run || options.contains(&"--dry-run");
+    let date = match options.iter().find(|option| !option.starts_with("--")) {
+        Some(date) => {
+            parse_due_date(date)?.ok_or_else(|| tr("Invalid date, expected YYYY-MM-DD"))?
+        }
+        None => schedule::start_of_day(
+            app.work_calendar()
+                .next_working_day(schedule::local_date(start_of_today())),
+        ),
+    };
+
+    let overdue = app.overdue_tasks()?;
+    if overdue.is_empty() {
+        println!("{}", tr("No overdue tasks"));
+        return Ok(());
+    }
+    println!(
+        "{}",
+        trf("Tasks to move to {}:", &[&i18n::format_date(date)])
+    );
+    for task in &overdue {
+        let due = task.due_date.map(i18n::format_date).unwrap_or_default();
+        println!("  #{} {} ({})", task.id, task.title, due);
+    }
+    if dry_run {
+        println!("{}", tr("Dry run: nothing was changed"));
+        return Ok(());
+    }
+
+    if !confirm(tr("Move these tasks?")).map_err(|e| e.to_string())? {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let moved = app.roll_over(date)?;
+    println!("{}", trf("Moved {} task(s)", &[&moved.len()]));
+    Ok(())
+}
+
+/// Shows pending tasks grouped by day for the coming week, pointing out
+/// days off.
+fn agenda(app: &TodoApp) -> Result<(), String> {
+    let agenda = app.agenda(7)?;
+    let calendar = app.work_calendar();
+    let print_tasks = |tasks: &[&crate::Task]| {
+        if tasks.is_empty() {
+            println!("  -");
+        }
+        for task in tasks {
+            if task.pinned {
+                println!(
+                    "  #{} {} {}",
+                    app.display_id(task),
+                    app.title_with_icon(task),
+                    tr("(pinned)")
+                );
+            } else {
+                println!("  #{} {}", app.display_id(task), app.title_with_icon(task));
+            }
+        }
+    };
+
+    if !agenda.overdue.is_empty() {
+        println!("{}", tr("Overdue:"));
+        print_tasks(&agenda.overdue);
+    }
+    for (day, tasks) in &agenda.days {
+        let weekday = day.format("%A").to_string();
+        let day_off = match calendar.holiday(*day) {
+            Some(name) if !name.is_empty() => format!(" ({})", name),
+            _ if !calendar.is_working_day(*day) => format!(" ({})", tr("day off")),
+            _ => String::new(),
+        };
+        println!("{} {}{}:", tr(&weekday), i18n::format_day(*day), day_off);
+        print_tasks(tasks);
+    }
+    println!("{}", tr("No due date:"));
+    print_tasks(&agenda.undated);
+    Ok(())
+}
+
+/// Lists the current user's holidays in a year, this year by default.
+fn holidays(app: &TodoApp, year: Option<&str>) -> Result<(), String> {
+    let year = match year {
+        Some(year) => year
+            .parse()
+            .map_err(|_| tr("Invalid year, expected YYYY"))?,
+        None => schedule::local_date(Utc::now()).year(),
+    };
+    let holidays = app.work_calendar().holidays_in(year);
+    if holidays.is_empty() {
+        println!(
+            "{}",
+            tr("No holidays, set them with: settings holidays <country code or file>")
+        );
+    }
+    for (date, name) in holidays {
+        let weekday = date.format("%A").to_string();
+        println!("{} {}  {}", tr(&weekday), i18n::format_day(date), name);
+    }
+    Ok(())
+}
+
+/// Prints today's planner, or writes it to `path`, as PDF if the name ends
+/// in `.pdf`.
+fn planner(app: &TodoApp, path: Option<&str>) -> Result<(), String> {
+    let text = planner::render(&app.planner()?);
+    let Some(path) = path else {
+        print!("{}", text);
+        return Ok(());
+    };
+    let contents = if path.to_lowercase().ends_with(".pdf") {
+        #[cfg(feature = "pdf")]
+        {
+            crate::pdf::render(&text)
+        }
+        #[cfg(not(feature = "pdf"))]
+        return Err(tr("PDF output needs the pdf feature").to_string());
+    } else {
+        text.into_bytes()
+    };
+    std::fs::write(path, contents).map_err(|e| e.to_string())?;
+    println!("{}", trf("Planner written to {}", &[&path]));
+    Ok(())
+}
+
+/// Emails today's digest to the current user.
+fn send_digest(app: &TodoApp) -> Result<(), String> {
+    app.send_digest(app.mailer.as_ref())?;
+    println!("{}", tr("Digest sent"));
+    Ok(())
+}
+
+/// Sets the address digests are sent to.
+fn set_email(app: &mut TodoApp, address: &str) -> Result<(), String> {
+    app.set_email(address)?;
+    println!("{}", tr("Email address saved"));
+    Ok(())
+}
+
+/// Sets a custom field on a task.
+fn set_field(app: &mut TodoApp, id: &str, name: &str, value: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_custom_field(task_id, name, value)?;
+    println!("{}", tr("Field saved"));
+    Ok(())
+}
+
+/// Removes a custom field from a task.
+fn unset_field(app: &mut TodoApp, id: &str, name: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.unset_custom_field(task_id, name)?;
+    println!("{}", tr("Field removed"));
+    Ok(())
+}
+
+/// Lists tasks, optionally only those with a matching custom field.
+/// Lists tasks, only those doable in the current context unless `all` is set.
+fn list(app: &TodoApp, field: Option<&str>, all: bool) -> Result<(), String> {
+    let tasks = match field {
+        Some(filter) => {
+            let (name, value) = filter
+                .split_once('=')
+                .ok_or_else(|| trf("Usage: {}", &[&usage("list")]))?;
+            app.tasks_with_field(name, value)?
+        }
+        None if all => {
+            print_focus(app);
+            app.list_tasks()?
+        }
+        None => {
+            print_focus(app);
+            print_context_note(app);
+            app.context_tasks()?
+        }
+    };
+    print_tasks(app, tasks)
+}
+
+/// Lists the current user's saved filters.
+fn list_filters(app: &TodoApp) -> Result<(), String> {
+    let filters = app.saved_filters();
+    if filters.is_empty() {
+        println!("{}", tr("No saved filters"));
+    }
+    for (name, expression) in filters {
+        println!("{}: {}", name, expression);
+    }
+    Ok(())
+}
+
+/// Prints the lines of tasks matching a pattern, grep-style: matching
+/// lines as `#id field:line: text`, context lines with `-` instead.
+fn grep(app: &TodoApp, options: &[&str]) -> Result<(), String> {
+    let mut grep_options = GrepOptions::default();
+    let mut ignore_case = false;
+    let mut pattern = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "-i" => ignore_case = true,
+            "--all" => grep_options.include_completed = true,
+            "-C" => {
+                grep_options.context = options
+                    .next()
+                    .and_then(|lines| lines.parse().ok())
+                    .ok_or_else(|| tr("Invalid number of context lines").to_string())?;
+            }
+            text if pattern.is_none() => pattern = Some(text),
+            _ => return Err(trf("Usage: {}", &[&usage("grep")])),
+        }
+    }
+    let pattern = pattern.ok_or_else(|| trf("Usage: {}", &[&usage("grep")]))?;
+    let matches = app.grep(&Pattern::new(pattern, ignore_case)?, grep_options)?;
+    if matches.is_empty() {
+        println!("{}", tr("No matches"));
+    }
+    for (index, found) in matches.iter().enumerate() {
+        if index > 0 && grep_options.context > 0 {
+            println!("--");
+        }
+        let field = tr(found.field.name());
+        let first = found.line_number - found.before.len();
+        for (offset, line) in found.before.iter().enumerate() {
+            println!("#{} {}-{}- {}", found.task_id, field, first + offset, line);
+        }
+        let mut line = String::new();
+        let mut end = 0;
+        for range in &found.ranges {
+            line.push_str(&found.line[end..range.start]);
+            line.push_str(&theme::paint(&found.line[range.clone()], Role::Match));
+            end = range.end;
+        }
+        line.push_str(&found.line[end..]);
+        println!(
+            "#{} {}:{}: {}",
+            found.task_id, field, found.line_number, line
+        );
+        for (offset, line) in found.after.iter().enumerate() {
+            println!(
+                "#{} {}-{}- {}",
+                found.task_id,
+                field,
+                found.line_number + 1 + offset,
+                line
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Lists the contexts in use, marking the current one.
+fn list_contexts(app: &TodoApp) -> Result<(), String> {
+    let contexts = app.contexts()?;
+    let current = app.current_context();
+    if contexts.is_empty() {
+        println!("{}", tr("No tasks have a context"));
+    }
+    for (context, count) in contexts {
+        let marker = if current.as_ref() == Some(&context) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} @{} ({})", marker, context, trf("{} pending", &[&count]));
+    }
+    if let Some(current) = current {
+        println!("{}", trf("Current context: @{}", &[&current]));
+    }
+    Ok(())
+}
+
+fn set_context(app: &mut TodoApp, id: &str, context: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_task_context(task_id, context)?;
+    match &app.tasks[&task_id].context {
+        Some(context) => println!("{}", trf("Task can be done at @{}", &[context])),
+        None => println!("{}", tr("Task can be done anywhere")),
+    }
+    Ok(())
+}
+
+fn set_icon(app: &mut TodoApp, id: &str, icon: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_task_icon(task_id, icon)?;
+    match &app.tasks[&task_id].icon {
+        Some(icon) => println!("{}", trf("Task icon set to {}", &[&icons::render(icon)])),
+        None => println!("{}", tr("Task icon removed")),
+    }
+    Ok(())
+}
+
+/// Prints tasks, or a note when there are none.
+fn print_tasks(app: &TodoApp, tasks: Vec<&Task>) -> Result<(), String> {
+    if tasks.is_empty() {
+        println!("{}", tr("No tasks found"));
+    }
+    for task in tasks {
+        print_task(app, task);
+    }
+    Ok(())
+}
+
+/// Shows a month with the number of tasks due each day, or the tasks due on
+/// one day. In a terminal, the user may then pick days of the month to list.
+fn calendar(app: &TodoApp, when: Option<&str>) -> Result<(), String> {
+    let today = schedule::local_date(Utc::now());
+    let month = match when {
+        Some(when) => {
+            if let Ok(date) = NaiveDate::parse_from_str(when, "%Y-%m-%d") {
+                let mut due = app.due_by_day(date)?;
+                return print_tasks(app, due.remove(&date).unwrap_or_default());
+            }
+            NaiveDate::parse_from_str(&format!("{}-01", when), "%Y-%m-%d")
+                .map_err(|_| trf("Usage: {}", &[&usage("calendar")]))?
+        }
+        None => today,
+    };
+    let due = app.due_by_day(month)?;
+    print!("{}", calendar::render(month, &due, today));
+    if !terminal::is_interactive() {
+        return Ok(());
+    }
+    loop {
+        let input =
+            prompt_input(tr("Day to show (blank to finish): ")).map_err(|e| e.to_string())?;
+        if input.is_empty() {
+            return Ok(());
+        }
+        match input.parse().ok().and_then(|day| month.with_day(day)) {
+            Some(date) => {
+                println!("{}", i18n::format_day(date));
+                print_tasks(app, due.get(&date).cloned().unwrap_or_default())?;
+            }
+            None => println!("{}", tr("Invalid day")),
+        }
+    }
+}
+
+fn stale(app: &TodoApp, days: Option<&str>) -> Result<(), String> {
+    let days =
+        match days {
+            Some(days) => days.parse().ok().filter(|days| *days > 0).ok_or_else(|| {
+                tr("Invalid number of days, expected a positive number").to_string()
+            })?,
+            None => app.stale_days(),
+        };
+    let stale = app.stale_tasks(days)?;
+    if stale.is_empty() {
+        println!(
+            "{}",
+            trf(
+                "No tasks have gone unchanged for more than {} days",
+                &[&days]
+            )
+        );
+        return Ok(());
+    }
+    for StaleTask {
+        task,
+        days,
+        suggestion,
+    } in stale
+    {
+        let suggestion = match suggestion {
+            Suggestion::Reschedule => trf(
+                "overdue, reschedule it: reschedule {} <YYYY-MM-DD>",
+                &[&task.id],
+            ),
+            Suggestion::Delete => tr("probably no longer needed, consider deleting it").to_string(),
+            Suggestion::Snooze => trf("snooze it for now: snooze {} 1w", &[&task.id]),
+        };
+        println!("#{} {}", app.display_id(task), task.title);
+        println!(
+            "    {}",
+            trf("unchanged for {} days; {}", &[&days, &suggestion])
+        );
+    }
+    Ok(())
+}
+
+/// Lists the tasks best worth doing next, with what makes them urgent.
+fn next_actions(app: &TodoApp, count: Option<&str>) -> Result<(), String> {
+    let count = match count {
+        Some(count) => count
+            .parse()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| tr("Invalid count, expected a positive number").to_string())?,
+        None => DEFAULT_NEXT_COUNT,
+    };
+    let tasks = app.next_actions(count, next::default_score)?;
+    if tasks.is_empty() {
+        println!("{}", tr("Nothing to do right now"));
+    }
+    for task in tasks {
+        let mut details = Vec::new();
+        if task.priority != Priority::Normal {
+            details.push(tr(task.priority.name()).to_string());
+        }
+        if let Some(due) = task.due_date {
+            details.push(trf("due {}", &[&i18n::format_date(due)]));
+        }
+        if details.is_empty() {
+            println!("#{} {}", app.display_id(task), app.title_with_icon(task));
+        } else {
+            println!(
+                "#{} {} ({})",
+                app.display_id(task),
+                app.title_with_icon(task),
+                details.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn set_dependency(app: &mut TodoApp, id: &str, on: &str, depend: bool) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let on_id = parse_task_id(app, on)?;
+    if depend {
+        app.add_dependency(task_id, on_id)?;
+        println!(
+            "{}",
+            trf("Task #{} now waits for task #{}", &[&task_id, &on_id])
+        );
+    } else {
+        app.remove_dependency(task_id, on_id)?;
+        println!(
+            "{}",
+            trf("Task #{} no longer waits for task #{}", &[&task_id, &on_id])
+        );
+    }
+    Ok(())
+}
+
+fn parse_scope(scope: &str) -> Result<Scope, String> {
+    match scope {
+        "project" => Ok(Scope::Project),
+        "tag" => Ok(Scope::Tag),
+        _ => Err(trf("Usage: {}", &[&usage("defaults")])),
+    }
+}
+
+/// Shows the defaults of the current user's projects and tags.
+fn list_defaults(app: &TodoApp) -> Result<(), String> {
+    let defaults = app.defaults()?;
+    if defaults.projects.is_empty() && defaults.tags.is_empty() {
+        println!("{}", tr("No defaults set"));
+        return Ok(());
+    }
+    let projects = defaults
+        .projects
+        .iter()
+        .map(|(name, defaults)| (name.clone(), defaults));
+    let tags = defaults
+        .tags
+        .iter()
+        .map(|(name, defaults)| (format!("@{}", name), defaults));
+    for (name, defaults) in projects.chain(tags) {
+        let mut details = Vec::new();
+        if let Some(priority) = defaults.priority {
+            details.push(trf("priority {}", &[&tr(priority.name())]));
+        }
+        for offset in &defaults.reminders {
+            let offset = schedule::format_duration(Duration::seconds(*offset));
+            details.push(trf("reminder {} before", &[&offset]));
+        }
+        if let Some(estimate) = defaults.estimate_secs {
+            let estimate = schedule::format_duration(Duration::seconds(estimate));
+            details.push(trf("estimate {}", &[&estimate]));
+        }
+        if let Some(icon) = &defaults.icon {
+            details.push(trf("icon {}", &[&icons::render(icon)]));
+        }
+        println!("{}: {}", name, details.join(", "));
+    }
+    Ok(())
+}
+
+fn set_estimate(app: &mut TodoApp, id: &str, duration: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let estimate = duration.map(parse_duration).transpose()?;
+    app.set_task_estimate(task_id, estimate)?;
+    match estimate {
+        Some(estimate) => println!(
+            "{}",
+            trf(
+                "Task estimated at {}",
+                &[&schedule::format_duration(estimate)]
+            )
+        ),
+        None => println!("{}", tr("Estimate removed")),
+    }
+    Ok(())
+}
+
+fn set_link(app: &mut TodoApp, id: &str, to: &str, link: bool) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let to_id = parse_task_id(app, to)?;
+    if link {
+        app.link_tasks(task_id, to_id)?;
+        println!(
+            "{}",
+            trf("Task #{} linked to task #{}", &[&task_id, &to_id])
+        );
+    } else {
+        app.unlink_tasks(task_id, to_id)?;
+        println!(
+            "{}",
+            trf("Task #{} unlinked from task #{}", &[&task_id, &to_id])
+        );
+    }
+    Ok(())
+}
+
+/// Prints the links from one task as a tree, or the trees of every linked
+/// task in the current list, starting from those nothing links to.
+fn graph(app: &TodoApp, id: Option<&str>) -> Result<(), String> {
+    let mut seen = BTreeSet::new();
+    if let Some(id) = id {
+        let task_id = parse_task_id(app, id)?;
+        let task = app
+            .tasks
+            .get(&task_id)
+            .filter(|task| app.can_see(task))
+            .ok_or_else(|| tr("Task not found").to_string())?;
+        print!("{}", links::render_tree(app, task, &mut seen));
+        return Ok(());
+    }
+    let linked: Vec<&Task> = app
+        .list_tasks()?
+        .into_iter()
+        .filter(|task| !app.links(task).is_empty())
+        .collect();
+    if linked.is_empty() {
+        println!("{}", tr("No linked tasks"));
+    }
+    // Tasks in a loop of links all have backlinks, so they're shown last.
+    let (roots, rest): (Vec<&Task>, Vec<&Task>) = linked
+        .into_iter()
+        .partition(|task| app.backlinks(task).is_empty());
+    for task in roots.into_iter().chain(rest) {
+        if !seen.contains(&task.id) {
+            print!("{}", links::render_tree(app, task, &mut seen));
+        }
+    }
+    Ok(())
+}
+
+/// Prints the dependencies and links between the current list's tasks, or
+/// one project's, as Graphviz DOT.
+fn graph_dot(app: &TodoApp, project: Option<&str>) -> Result<(), String> {
+    let tasks: Vec<&Task> = app
+        .list_tasks()?
+        .into_iter()
+        .filter(|task| project.is_none() || task.project.as_deref() == project)
+        .collect();
+    print!("{}", dot::render(&tasks));
+    Ok(())
+}
+
+fn create_share(app: &mut TodoApp, project: &str, expires: Option<&str>) -> Result<(), String> {
+    let expires_in = expires.map(parse_duration).transpose()?;
+    let token = app.create_share(project, expires_in)?;
+    println!(
+        "{}",
+        trf(
+            "Anyone with this link can see the tasks of {}:",
+            &[&project]
+        )
+    );
+    println!("{}/share/{}", app.share_url, token);
+    println!(
+        "{}",
+        tr("The link works while the server is running (--serve).")
+    );
+    Ok(())
+}
+
+/// Lists the current user's share links; the links themselves can't be
+/// shown again, as only their hashes are kept.
+fn list_shares(app: &TodoApp) -> Result<(), String> {
+    let shares = app.shares()?;
+    if shares.is_empty() {
+        println!("{}", tr("You haven't shared any projects"));
+    }
+    for (id, share) in shares {
+        let status = match share.expires_at {
+            _ if share.is_expired() => tr("expired").to_string(),
+            Some(expires_at) => trf("expires {}", &[&i18n::format_datetime(expires_at)]),
+            None => tr("doesn't expire").to_string(),
+        };
+        println!(
+            "{}  {}  {} ({})",
+            id,
+            share.project,
+            i18n::format_datetime(share.created_at),
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Lists the deleted tasks the current user can see, newest first.
+fn list_trash(app: &TodoApp) -> Result<(), String> {
+    let trashed = app.trashed_tasks()?;
+    if trashed.is_empty() {
+        println!("{}", tr("The trash is empty"));
+    }
+    for trashed in trashed {
+        println!(
+            "#{}  {}  {}",
+            trashed.task.id,
+            trashed.task.title,
+            trf(
+                "deleted by {} {}",
+                &[
+                    &trashed.deleted_by,
+                    &i18n::format_datetime(trashed.deleted_at)
+                ]
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Shows how much old data is kept and the retention policy.
+fn retention_status(app: &TodoApp) -> Result<(), String> {
+    let status = app.retention_status().map_err(|e| e.to_string())?;
+    let policy = status.policy;
+
+    let purge = match policy.trash_days {
+        Some(days) => trf("purged after {} days", &[&days]),
+        None => tr("kept until restored").to_string(),
+    };
+    match status.oldest_trashed {
+        Some(oldest) => println!(
+            "{}",
+            trf(
+                "Trash: {} tasks, the oldest deleted {}; {}",
+                &[&status.trashed, &i18n::format_datetime(oldest), &purge]
+            )
+        ),
+        None => println!("{}", trf("Trash: empty; {}", &[&purge])),
+    }
+    let trim = match policy.audit_log_max_kb {
+        Some(max_kb) => trf("trimmed at {} KB", &[&max_kb]),
+        None => tr("never trimmed").to_string(),
+    };
+    println!(
+        "{}",
+        trf(
+            "Audit log: {} KB; {}",
+            &[&status.audit_log_bytes.div_ceil(1024), &trim]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Backups: {} files, {} KB; {} kept of each data file",
+            &[
+                &status.backups,
+                &status.backup_bytes.div_ceil(1024),
+                &policy.backups
+            ]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Snapshots: {}; one every {} days",
+            &[&status.snapshots, &policy.snapshot_every_days.max(1)]
+        )
+    );
+    Ok(())
+}
+
+/// Writes an HTML report of the current list to `path`, or prints it.
+fn html_report(app: &TodoApp, path: Option<&str>) -> Result<(), String> {
+    let title = match (&app.active_workspace, &app.current_user) {
+        (Some(workspace), _) => trf("Tasks of workspace {}", &[workspace]),
+        (None, Some(user_id)) => trf("Tasks of {}", &[user_id]),
+        (None, None) => return Err(tr("Not logged in").to_string()),
+    };
+    let html = report::render(&title, &app.list_tasks()?, Utc::now());
+    match path {
+        Some(path) => {
+            std::fs::write(path, html).map_err(|e| e.to_string())?;
+            println!("{}", trf("Report written to {}", &[&path]));
+        }
+        None => print!("{}", html),
+    }
+    Ok(())
+}
+
+/// Logs time spent on a task.
+fn track(app: &mut TodoApp, id: &str, duration: &str, note: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let duration = parse_duration(duration)?;
+    app.log_time(task_id, duration, note)?;
+    println!(
+        "{}",
+        trf(
+            "Logged {} on task #{}",
+            &[&schedule::format_duration(duration), &task_id]
+        )
+    );
+    Ok(())
+}
+
+/// Exports the time logged between two dates as CSV.
+fn timesheet(app: &TodoApp, from: &str, to: &str, path: Option<&str>) -> Result<(), String> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| tr("Invalid date, expected YYYY-MM-DD").to_string())
+    };
+    let rows = app.timesheet(parse(from)?, parse(to)?)?;
+    let csv = timesheet::render_csv(&rows);
+    match path {
+        Some(path) => {
+            std::fs::write(path, csv).map_err(|e| e.to_string())?;
+            println!("{}", trf("Timesheet written to {}", &[&path]));
+        }
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+fn quick_add(app: &mut TodoApp, text: &str) -> Result<(), String> {
+    let task_id = app.quick_add(text)?;
+    println!("{}", trf("Task #{} added", &[&task_id]));
+    Ok(())
+}
+
+fn capture(app: &mut TodoApp, title: &str) -> Result<(), String> {
+    let task_id = app.capture(title)?;
+    println!("{}", trf("Task #{} added to the inbox", &[&task_id]));
+    Ok(())
+}
+
+/// Goes through the inbox one task at a time, asking for each task's
+/// project, due date and priority.
+fn triage(app: &mut TodoApp) -> Result<(), String> {
+    if !terminal::is_interactive() {
+        return Err(tr("Triage needs an interactive terminal").to_string());
+    }
+    let task_ids: Vec<u32> = app.inbox()?.iter().map(|task| task.id).collect();
+    if task_ids.is_empty() {
+        println!("{}", tr("The inbox is empty"));
+        return Ok(());
+    }
+    let ask = |prompt: &str| prompt_input(prompt).map_err(|e| e.to_string());
+    let (mut triaged, mut deleted) = (0, 0);
+    for (i, task_id) in task_ids.iter().enumerate() {
+        let Some(task) = app.tasks.get(task_id) else {
+            continue;
+        };
+        println!(
+            "\n{}",
+            trf(
+                "[{}/{}] #{} {}",
+                &[
+                    &(i + 1),
+                    &task_ids.len(),
+                    &app.display_id(task),
+                    &app.title_with_icon(task)
+                ]
+            )
+        );
+        let action = loop {
+            let answer = ask(tr("[o]rganize, [s]kip, [d]elete or [q]uit? "))?;
+            match answer.to_lowercase().as_str() {
+                "" | "o" | "organize" => break 'o',
+                "s" | "skip" => break 's',
+                "d" | "delete" => break 'd',
+                "q" | "quit" => break 'q',
+                _ => println!("{}", tr("Invalid choice")),
+            }
+        };
+        match action {
+            's' => continue,
+            'q' => break,
+            'd' => {
+                app.delete_task(*task_id)?;
+                deleted += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let project = ask(tr("Project (blank for none): "))?;
+        let today = schedule::local_date(Utc::now());
+        let due_date = loop {
+            let when = ask(tr(
+                "Due (e.g. tomorrow, friday, +3d or YYYY-MM-DD; blank for none): ",
+            ))?;
+            if when.is_empty() {
+                break None;
+            }
+            match quickadd::parse_when(&when, today, &app.work_calendar()) {
+                Ok(date) => break Some(schedule::start_of_day(date)),
+                Err(e) => println!("{}", tr(e)),
+            }
+        };
+        let priority = loop {
+            let level = ask(tr(
+                "Priority (low, normal, high or urgent; blank to keep): ",
+            ))?;
+            if level.is_empty() {
+                break None;
+            }
+            match Priority::parse(&level) {
+                Some(priority) => break Some(priority),
+                None => println!(
+                    "{}",
+                    tr("Unknown priority, expected low, normal, high or urgent")
+                ),
+            }
+        };
+        app.triage_task(
+            *task_id,
+            Triage {
+                project: Some(project).filter(|project| !project.is_empty()),
+                due_date,
+                priority,
+            },
+        )?;
+        triaged += 1;
+    }
+    println!(
+        "{}",
+        trf(
+            "Triaged {} task(s), deleted {}, {} left in the inbox",
+            &[&triaged, &deleted, &app.inbox()?.len()]
+        )
+    );
+    Ok(())
+}
+
+/// Assigns a task to a user, or unassigns it.
+fn assign(app: &mut TodoApp, id: &str, assignee: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.assign_task(task_id, assignee)?;
+    match assignee {
+        Some(assignee) => println!("{}", trf("Task assigned to {}", &[&assignee])),
+        None => println!("{}", tr("Task unassigned")),
+    }
+    Ok(())
+}
+
+/// Attaches a file path or URL to a task.
+fn attach(app: &mut TodoApp, id: &str, location: &str, name: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.add_attachment(task_id, location, name)?;
+    println!("{}", tr("Attachment added"));
+    Ok(())
+}
+
+/// Removes an attachment from a task.
+fn detach(app: &mut TodoApp, id: &str, name: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.remove_attachment(task_id, name)?;
+    println!("{}", tr("Attachment removed"));
+    Ok(())
+}
+
+/// Opens an attachment with the platform's default application.
+fn open_attachment(app: &TodoApp, id: &str, name: &str) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let attachment = app.attachment(task_id, name)?;
+    attachments::open(attachment).map_err(|e| e.to_string())
+}
+
+/// Moves a task into a project or out of it.
+fn set_project(app: &mut TodoApp, id: &str, project: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_task_project(task_id, project)?;
+    println!("{}", tr("Task updated successfully!"));
+    Ok(())
+}
+
+/// Copies a task, optionally with a new due date.
+fn duplicate(app: &mut TodoApp, id: &str, date: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let due = match date {
+        Some(date) => parse_due_date(date)?,
+        None => None,
+    };
+    let copy_id = app.duplicate_task(task_id, due)?;
+    println!("{}", trf("Created task {}", &[&copy_id]));
+    Ok(())
+}
+
+/// Copies all tasks of a project into a new project.
+fn clone_project(app: &mut TodoApp, project: &str, new_project: &str) -> Result<(), String> {
+    let copies = app.clone_project(project, new_project)?;
+    println!(
+        "{}",
+        trf("Copied {} task(s) into {}", &[&copies.len(), &new_project])
+    );
+    Ok(())
+}
+
+/// Pins a task to the top of lists, or unpins it.
+fn set_pinned(app: &mut TodoApp, id: &str, pinned: bool) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    app.set_task_pinned(task_id, pinned)?;
+    if pinned {
+        println!("{}", tr("Task pinned"));
+    } else {
+        println!("{}", tr("Task unpinned"));
+    }
+    Ok(())
+}
+
+/// Sets or clears how far along a task is.
+fn set_progress(app: &mut TodoApp, id: &str, percent: Option<&str>) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let percent = percent.map(progress::parse_percent).transpose()?;
+    app.set_task_progress(task_id, percent)?;
+    match percent {
+        Some(percent) => println!(
+            "{}",
+            trf("Task #{} is {}", &[&task_id, &progress::bar(percent)])
+        ),
+        None => println!("{}", trf("Progress of task #{} cleared", &[&task_id])),
+    }
+    Ok(())
+}
+
+/// Moves a task within its project's manual order.
+fn move_task(app: &mut TodoApp, id: &str, to: &[&str]) -> Result<(), String> {
+    let task_id = parse_task_id(app, id)?;
+    let position = app.move_task(task_id, Move::parse(to)?)?;
+    println!(
+        "{}",
+        trf("Task #{} moved to position {}", &[&task_id, &position])
+    );
+    if app.settings()?.sort != SortOrder::Manual {
+        println!(
+            "{}",
+            tr("Lists aren't in manual order; `settings sort manual` shows it")
+        );
+    }
+    Ok(())
+}
+
+/// Opens the listed tasks, or those of one project, in the editor and
+/// applies the changes made there after confirmation.
+fn edit_bulk(app: &mut TodoApp, options: &[&str]) -> Result<(), String> {
+    let (project, format) = match options {
+        [] => (None, DataFormat::Yaml),
+        ["--json"] => (None, DataFormat::Json),
+        ["--project", project] => (Some(*project), DataFormat::Yaml),
+        ["--project", project, "--json"] | ["--json", "--project", project] => {
+            (Some(*project), DataFormat::Json)
+        }
+        _ => return Err(trf("Usage: {}", &[&usage("edit-bulk")])),
+    };
+    let entries = app.bulk_entries(project)?;
+    let extension = match format {
+        DataFormat::Yaml => "yaml",
+        _ => "json",
+    };
+    let mut text = bulk::render(&entries, format)?;
+    let plan = loop {
+        text = editor::edit_file(&text, app.editor.as_deref(), extension)
+            .map_err(|e| e.to_string())?;
+        match app.plan_bulk_edit(&entries, &text, format) {
+            Ok(plan) => break plan,
+            Err(e) => {
+                print_error(&e);
+                if !confirm(tr("Edit again?")).map_err(|e| e.to_string())? {
+                    println!("{}", tr("Cancelled"));
+                    return Ok(());
+                }
+            }
+        }
+    };
+    if plan.is_empty() {
+        println!("{}", tr("No changes"));
+        return Ok(());
+    }
+    println!(
+        "{}",
+        trf(
+            "{} to create, {} to update, {} to delete",
+            &[&plan.create.len(), &plan.update.len(), &plan.delete.len()]
+        )
+    );
+    if !confirm(tr("Apply these changes?")).map_err(|e| e.to_string())? {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    app.apply_bulk_edit(plan)?;
+    println!("{}", tr("Changes saved"));
+    Ok(())
+}
+
+/// Shows what the automation rules would change in the current list,
+/// without changing it.
+fn test_rules(app: &TodoApp) -> Result<(), String> {
+    if app.rules.is_empty() {
+        println!("{}", tr("No rules are configured"));
+        return Ok(());
+    }
+    let results = app.test_rules()?;
+    if results.is_empty() {
+        println!("{}", tr("The rules wouldn't change any task"));
+    }
+    for (task, applied) in results {
+        println!("#{} {}", app.display_id(task), task.title);
+        for applied in applied {
+            println!("  {}: {}", applied.rule, applied.changes.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Shows the current user's settings and lets them pick one to change.
+fn settings_menu(app: &mut TodoApp) -> Result<(), String> {
+    println!("{}", tr("Settings:"));
+    settings::print(&app.settings()?);
+    let choice =
+        prompt_input(tr("Setting to change (blank to go back): ")).map_err(|e| e.to_string())?;
+    if choice.is_empty() {
+        return Ok(());
+    }
+    let name = choice
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| UserSettings::NAMES.get(number.wrapping_sub(1)))
+        .ok_or_else(|| tr("Invalid choice").to_string())?;
+    let value = prompt_input(tr("New value (blank for default): ")).map_err(|e| e.to_string())?;
+    change_setting(app, name, &value)
+}
+
+/// Changes one of the current user's settings.
+fn change_setting(app: &mut TodoApp, name: &str, value: &str) -> Result<(), String> {
+    app.set_setting(name, value)?;
+    let value = app.settings()?.get(name)?;
+    println!(
+        "{}",
+        trf("{} set to {}", &[&name, &settings::describe(&value)])
+    );
+    Ok(())
+}
+
+/// Logs in as another user without going through the logout menu.
+fn switch_user(app: &mut TodoApp, username: &str) -> Result<(), String> {
+    let password = prompt_input(tr("Password: ")).map_err(|e| e.to_string())?;
+    app.switch_user(username, &password)?;
+    println!("{}", trf("Switched to {}", &[&username]));
+    Ok(())
+}
+
+/// Shows the current user's own usage counts.
+fn show_usage(app: &TodoApp) -> Result<(), String> {
+    let usage = app.usage_stats()?;
+    if !app.track_usage {
+        println!(
+            "{}",
+            tr("Usage isn't being counted: usage_stats is off in config.json")
+        );
+    }
+    if let Some(since) = usage.since {
+        println!("{}", trf("Counted since {}", &[&i18n::format_date(since)]));
+    }
+    println!("{}", tr("Commands run:"));
+    let commands = usage.top_commands();
+    if commands.is_empty() {
+        println!("  -");
+    }
+    for (name, count) in commands {
+        println!("  {:<16} {}", name, count);
+    }
+    println!("{}", tr("Tasks created per week:"));
+    let today = schedule::local_date(Utc::now());
+    for (week, count) in usage.weekly_tasks(today, usagestats::REPORT_WEEKS) {
+        if accessibility::screen_reader() {
+            println!("  {}: {}", week, count);
+        } else {
+            println!("  {}  {:>3} {}", week, count, "#".repeat(count as usize));
+        }
+    }
+    Ok(())
+}
+
+/// Lists the tasks whose owner isn't a registered user.
+fn list_orphans(app: &TodoApp) -> Result<(), String> {
+    let orphans = app.orphaned_tasks()?;
+    if orphans.is_empty() {
+        println!("{}", tr("No orphaned tasks"));
+        return Ok(());
+    }
+    for task in orphans {
+        println!(
+            "#{} {} ({})",
+            task.id,
+            task.title,
+            trf("owner {} doesn't exist", &[&task.user_id])
+        );
+    }
+    Ok(())
+}
+
+/// Moves the orphaned tasks to the trash after confirmation.
+fn delete_orphans(app: &mut TodoApp) -> Result<(), String> {
+    let count = app.orphaned_tasks()?.len();
+    if count == 0 {
+        println!("{}", tr("No orphaned tasks"));
+        return Ok(());
+    }
+    if !confirm(&trf("Delete {} orphaned task(s)?", &[&count])).map_err(|e| e.to_string())? {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let deleted = app.delete_orphans()?;
+    println!("{}", trf("{} task(s) moved to the trash", &[&deleted]));
+    Ok(())
+}
+
+/// Removes the current user's tasks from the trash for good.
+fn empty_trash(app: &mut TodoApp) -> Result<(), String> {
+    let user_id = app.current_user.clone().unwrap_or_default();
+    let count = app
+        .trashed_tasks()?
+        .iter()
+        .filter(|trashed| app.workspaces.can_edit(&trashed.task, &user_id))
+        .count();
+    if count == 0 {
+        println!("{}", tr("The trash is empty"));
+        return Ok(());
+    }
+    if !confirm(&trf(
+        "Permanently delete {} task(s) in the trash?",
+        &[&count],
+    ))
+    .map_err(|e| e.to_string())?
+    {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let removed = app.empty_trash()?;
+    println!("{}", trf("{} task(s) deleted for good", &[&removed]));
+    Ok(())
+}
+
+/// Deletes a workspace, moving its tasks to the trash.
+fn delete_workspace(app: &mut TodoApp, name: &str) -> Result<(), String> {
+    app.check_workspace_owner(name)?;
+    if !confirm(&trf(
+        "Delete workspace {} and move its tasks to the trash?",
+        &[&name],
+    ))
+    .map_err(|e| e.to_string())?
+    {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let deleted = app.delete_workspace(name)?;
+    println!(
+        "{}",
+        trf(
+            "Workspace {} deleted, {} task(s) moved to the trash",
+            &[&name, &deleted]
+        )
+    );
+    Ok(())
+}
+
+/// Shows how many tasks the current user has next to their limits.
+fn show_quota(app: &TodoApp) -> Result<(), String> {
+    let usage = app.usage()?;
+    println!(
+        "{}",
+        trf(
+            "Open tasks: {} of {}",
+            &[&usage.open, &quota::format_limit(usage.quota.max_open)]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Total tasks: {} of {}",
+            &[&usage.total, &quota::format_limit(usage.quota.max_total)]
+        )
+    );
+    Ok(())
+}
+
+/// Shows the task counts of all users and the counters kept since the app
+/// started, the same figures the server reports at `/metrics`.
+fn show_stats(app: &TodoApp) -> Result<(), String> {
+    let metrics = app.metrics();
+    println!(
+        "{}",
+        trf(
+            "Tasks: {} open ({} overdue), {} completed",
+            &[
+                &metrics.open_tasks,
+                &metrics.overdue_tasks,
+                &metrics.completed_tasks
+            ]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Saves: {} ({} failed), {} ms on average, {} ms at most",
+            &[
+                &metrics.saves,
+                &metrics.save_failures,
+                &metrics.average_save().as_millis(),
+                &metrics.slowest_save.as_millis()
+            ]
+        )
+    );
+    println!("{}", trf("Failed logins: {}", &[&metrics.auth_failures]));
+    if !metrics.requests.is_empty() {
+        println!("{}", tr("Requests answered:"));
+        for (status, count) in &metrics.requests {
+            println!("  {}: {}", status, count);
+        }
+    }
+    Ok(())
+}
+
+/// Gives a user their own task limits.
+fn set_quota(
+    app: &mut TodoApp,
+    username: &str,
+    max_open: &str,
+    max_total: &str,
+) -> Result<(), String> {
+    let quota = Quota {
+        max_open: quota::parse_limit(max_open)?,
+        max_total: quota::parse_limit(max_total)?,
+    };
+    app.set_quota(username, Some(quota))?;
+    println!("{}", trf("Quota of {} updated", &[&username]));
+    Ok(())
+}
+
+/// Lists the current user's workspaces, marking the active one.
+fn list_workspaces(app: &TodoApp) -> Result<(), String> {
+    let workspaces = app.my_workspaces()?;
+    if workspaces.is_empty() {
+        println!("{}", tr("You are not a member of any workspace"));
+    }
+    for (workspace, role) in workspaces {
+        let marker = if app.active_workspace.as_ref() == Some(&workspace.name) {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{} {} ({}, {})",
+            marker,
+            workspace.name,
+            tr(role.name()),
+            trf("{} members", &[&workspace.members.len()])
+        );
+    }
+    Ok(())
+}
+
+fn list_permissions(app: &TodoApp, name: &str) -> Result<(), String> {
+    for (username, role) in app.permissions(name)? {
+        println!("{}: {}", username, tr(role.name()));
+    }
+    for (group, role) in app.group_permissions(name)? {
+        println!("{}: {}", trf("group {}", &[&group]), tr(role.name()));
+    }
+    Ok(())
+}
+
+/// Lists the groups the current user is in, with their members.
+fn list_groups(app: &TodoApp) -> Result<(), String> {
+    let groups = app.my_groups()?;
+    if groups.is_empty() {
+        println!("{}", tr("You are not a member of any group"));
+    }
+    for group in groups {
+        let members: Vec<&str> = group.members.iter().map(String::as_str).collect();
+        println!(
+            "{} ({}): {}",
+            group.name,
+            trf("owner {}", &[&group.owner]),
+            members.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn list_habits(app: &TodoApp) -> Result<(), String> {
+    let habits = app.habits()?;
+    if habits.is_empty() {
+        println!("{}", tr("You have no habits yet"));
+    }
+    let today = schedule::local_date(Utc::now());
+    for (name, habit) in &habits {
+        let (frequency, streak) = match habit.frequency {
+            Frequency::Daily => (
+                tr("daily").to_string(),
+                trf("streak: {} day(s)", &[&habit.streak(today)]),
+            ),
+            Frequency::Weekly(times) => (
+                trf("{} day(s) a week", &[&times]),
+                trf("streak: {} week(s)", &[&habit.streak(today)]),
+            ),
+        };
+        let done = if habit.checks.contains(&today) {
+            format!(", {}", tr("done today"))
+        } else {
+            String::new()
+        };
+        println!("{} ({}): {}{}", name, frequency, streak, done);
+    }
+    Ok(())
+}
+
+fn check_habit(
+    app: &mut TodoApp,
+    name: &str,
+    date: Option<&str>,
+    done: bool,
+) -> Result<(), String> {
+    let date = match date {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| tr("Invalid date, expected YYYY-MM-DD").to_string())?,
+        None => schedule::local_date(Utc::now()),
+    };
+    let streak = app.check_habit(name, date, done)?;
+    let day = i18n::format_day(date);
+    if done {
+        println!(
+            "{}",
+            trf("{} done on {}, streak: {}", &[&name, &day, &streak])
+        );
+    } else {
+        println!(
+            "{}",
+            trf(
+                "{} no longer done on {}, streak: {}",
+                &[&name, &day, &streak]
+            )
+        );
+    }
+    Ok(())
+}
+
+fn habit_grid(app: &TodoApp, month: Option<&str>) -> Result<(), String> {
+    let today = schedule::local_date(Utc::now());
+    let month = match month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| tr("Invalid month, expected YYYY-MM").to_string())?,
+        None => today,
+    };
+    let habits = app.habits()?;
+    if habits.is_empty() {
+        println!("{}", tr("You have no habits yet"));
+        return Ok(());
+    }
+    print!("{}", habits::render_grid(&habits, month, today));
+    Ok(())
+}
+
+/// Charts the current user's open tasks over the last few weeks, or the
+/// given number of days.
+fn burndown(app: &TodoApp, days: Option<&str>) -> Result<(), String> {
+    let days = match days {
+        Some(days) => days
+            .parse()
+            .ok()
+            .filter(|days| (1..=MAX_BURNDOWN_DAYS).contains(days))
+            .ok_or_else(|| tr("Invalid number of days, expected 1 to 366").to_string())?,
+        None => DEFAULT_BURNDOWN_DAYS,
+    };
+    let history = app.snapshots()?;
+    if history.is_empty() {
+        println!("{}", tr("No snapshots yet, see `snapshot`"));
+        return Ok(());
+    }
+    let today = schedule::local_date(Utc::now());
+    println!("{}", tr("Open tasks:"));
+    print!("{}", snapshots::render_burndown(&history, today, days));
+    Ok(())
+}
+
+/// Shows a page of a workspace's activity feed, the active workspace by default.
+fn activity(app: &TodoApp, options: &[&str]) -> Result<(), String> {
+    let mut workspace = app.active_workspace.as_deref();
+    let mut user = None;
+    let mut page = 1;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--user" => {
+                user = Some(
+                    *options
+                        .next()
+                        .ok_or_else(|| trf("Usage: {}", &[&usage("activity")]))?,
+                )
+            }
+            "--page" => {
+                page = options
+                    .next()
+                    .and_then(|page| page.parse().ok())
+                    .ok_or_else(|| tr("Invalid page number").to_string())?;
+            }
+            name if !name.starts_with("--") => workspace = Some(name),
+            _ => return Err(trf("Usage: {}", &[&usage("activity")])),
+        }
+    }
+    let workspace = workspace.ok_or_else(|| tr("No workspace selected").to_string())?;
+
+    let entries = app.workspace_activity(workspace, user, page)?;
+    if entries.is_empty() {
+        println!("{}", tr("No activity"));
+    }
+    for entry in &entries {
+        println!("{}", activity::describe(entry));
+    }
+    if entries.len() == PAGE_SIZE {
+        println!(
+            "{}",
+            trf("More: activity {} --page {}", &[&workspace, &(page + 1)])
+        );
+    }
+    Ok(())
+}
+
+/// What to do with imported tasks that already exist, from the
+/// `--on-conflict` option; `default` without it. With `ask`, the user is
+/// asked about each task.
+fn conflict_resolver(
+    options: &[&str],
+    command: &str,
+    default: Resolution,
+) -> Result<impl FnMut(&Task, &Task) -> Resolution, String> {
+    let strategy = match options {
+        [] => None,
+        ["--on-conflict", "ask"] => Some(None),
+        ["--on-conflict", name] => Some(Some(
+            Resolution::parse(name).ok_or_else(|| trf("Usage: {}", &[&usage(command)]))?,
+        )),
+        _ => return Err(trf("Usage: {}", &[&usage(command)])),
+    };
+    let fixed = strategy.unwrap_or(Some(default));
+    Ok(move |existing: &Task, imported: &Task| match fixed {
+        Some(resolution) => resolution,
+        None => ask_resolution(existing, imported),
+    })
+}
+
+/// Asks what to do with an imported task that already exists. Skips it
+/// when the answer can't be read.
+fn ask_resolution(existing: &Task, imported: &Task) -> Resolution {
+    println!(
+        "{}",
+        trf("{} already exists as #{}", &[&imported.title, &existing.id])
+    );
+    loop {
+        let Ok(answer) = prompt_input(tr("[s]kip, [o]verwrite or [d]uplicate? ")) else {
+            return Resolution::Skip;
+        };
+        match answer.to_lowercase().as_str() {
+            "" | "s" | "skip" => return Resolution::Skip,
+            "o" | "overwrite" => return Resolution::Overwrite,
+            "d" | "duplicate" => return Resolution::Duplicate,
+            _ => println!("{}", tr("Invalid choice")),
+        }
+    }
+}
+
+/// Lists what an import did with each task, then the totals.
+fn print_import_summary(decisions: &[Decision]) {
+    let (mut added, mut skipped, mut overwritten, mut duplicated) = (0, 0, 0, 0);
+    for decision in decisions {
+        let line = match decision.outcome {
+            Outcome::Added(id) => {
+                added += 1;
+                trf("Added #{} {}", &[&id, &decision.title])
+            }
+            Outcome::Skipped { existing } => {
+                skipped += 1;
+                trf(
+                    "Skipped {}, it exists as #{}",
+                    &[&decision.title, &existing],
+                )
+            }
+            Outcome::Overwritten(id) => {
+                overwritten += 1;
+                trf("Overwrote #{} {}", &[&id, &decision.title])
+            }
+            Outcome::Duplicated { id, existing } => {
+                duplicated += 1;
+                trf(
+                    "Added #{} {} next to #{}",
+                    &[&id, &decision.title, &existing],
+                )
+            }
+        };
+        println!("  {}", line);
+    }
+    println!(
+        "{}",
+        trf(
+            "Import done: {} added, {} duplicated, {} overwritten, {} skipped",
+            &[&added, &duplicated, &overwritten, &skipped]
+        )
+    );
+}
+
+/// Restores an exported account into the current user's account.
+fn import_account(app: &mut TodoApp, path: &str, options: &[&str]) -> Result<(), String> {
+    let mut resolve = conflict_resolver(options, "import-account", Resolution::Skip)?;
+    if !confirm(tr(
+        "Your settings will be replaced by the archived ones. Continue?",
+    ))
+    .map_err(|e| e.to_string())?
+    {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let password = match account::is_encrypted_archive(path) {
+        true => Some(archive_password(false)?),
+        false => None,
+    };
+    let decisions = app.import_account(path, password.as_deref(), &mut resolve)?;
+    print_import_summary(&decisions);
+    Ok(())
+}
+
+/// Deletes the current user's account once they confirm.
+fn delete_account(app: &mut TodoApp) -> Result<(), String> {
+    let user_id = app.current_user.clone().ok_or(tr("Not logged in"))?;
+    if !confirm(&trf(
+        "Delete account {} and its personal tasks for good?",
+        &[&user_id],
+    ))
+    .map_err(|e| e.to_string())?
+    {
+        println!("{}", tr("Cancelled"));
+        return Ok(());
+    }
+    let deleted = app.delete_account()?;
+    println!(
+        "{}",
+        trf("Account {} deleted with {} task(s)", &[&user_id, &deleted])
+    );
+    Ok(())
+}
+
+/// The password of an encrypted archive, from `TODO_ARCHIVE_PASSWORD` or a
+/// prompt. A new password is asked for twice.
+fn archive_password(new: bool) -> Result<String, String> {
+    if let Ok(password) = std::env::var("TODO_ARCHIVE_PASSWORD") {
+        return Ok(password);
+    }
+    let password = prompt_input(tr("Archive password: ")).map_err(|e| e.to_string())?;
+    if password.is_empty() {
+        return Err(tr("The password can't be empty").to_string());
+    }
+    if new {
+        let repeated = prompt_input(tr("Repeat the password: ")).map_err(|e| e.to_string())?;
+        if repeated != password {
+            return Err(tr("The passwords don't match").to_string());
+        }
+    }
+    Ok(password)
+}
+
+/// Rewrites the data files in another format.
+fn convert_format(app: &TodoApp, format: &str) -> Result<(), String> {
+    let format =
+        DataFormat::parse(format).ok_or_else(|| trf("Usage: {}", &[&usage("convert-format")]))?;
+    if app.dry_run {
+        return Err(tr("Not available in dry-run mode").to_string());
+    }
+    app.storage
+        .convert(
+            &[
+                TASKS_FILE,
+                USERS_FILE,
+                SETTINGS_FILE,
+                TOKENS_FILE,
+                WORKSPACES_FILE,
+                GROUPS_FILE,
+                HABITS_FILE,
+                SNAPSHOTS_FILE,
+                DEFAULTS_FILE,
+                SHARES_FILE,
+                TRASH_FILE,
+            ],
+            format,
+        )
+        .map_err(|e| e.to_string())?;
+    println!(
+        "{}",
+        trf("Data files converted to {}", &[&format.extension()])
+    );
+    println!(
+        "{}",
+        trf(
+            "Set data_format to {} in config.json to create new files in it too",
+            &[&format.extension()]
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Comments on tasks. A comment is added to the end of the task's
+//! description with who wrote it and when, so it shows wherever the
+//! description does. Workspace members with the `comment` permission may
+//! comment on tasks they can't otherwise change.
+
+use crate::i18n::format_datetime;
+use crate::TodoApp;
+use chrono::Utc;
+
+impl TodoApp {
+    /// Adds a comment by the current user to a task.
+    pub fn comment_on_task(&mut self, task_id: u32, text: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Comment cannot be empty");
+        }
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_comment(task, &user_id) {
+            return Err("Not authorized to comment on this task");
+        }
+
+        if !task.description.is_empty() {
+            task.description.push_str("\n\n");
+        }
+        task.description.push_str(&format!(
+            "**{}**, {}: {}",
+            user_id,
+            format_datetime(Utc::now()),
+            text
+        ));
+        task.touch();
+        self.save_tasks(&format!("Comment on task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+}
@@ -0,0 +1,238 @@
+use crate::accessibility::Accessibility;
+#[cfg(feature = "bot")]
+use crate::bot::BotConfig;
+#[cfg(feature = "caldav")]
+use crate::caldav::CalDavConfig;
+use crate::formats::DataFormat;
+use crate::hooks::HooksConfig;
+use crate::i18n::Language;
+#[cfg(feature = "imap")]
+use crate::imap::ImapConfig;
+use crate::mail::MailConfig;
+use crate::quota::Quota;
+use crate::retention::Retention;
+use crate::rules::Rule;
+use crate::server::ServerConfig;
+use crate::storage::StorageKind;
+use crate::theme::Theme;
+use crate::webhooks::WebhookConfig;
+use crate::{TASKS_FILE, USERS_FILE};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "config.json";
+
+/// Directory under the user's config directory holding their config file.
+const APP_DIR: &str = "lab3";
+
+/// Name of the account used when the app runs in single-user mode.
+pub const LOCAL_USER: &str = "local";
+
+/// Application settings loaded from `config.json` and command line flags.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory the data files are kept in; the current directory when
+    /// unset.
+    pub data_dir: Option<String>,
+    /// Skip login entirely and keep all tasks under `LOCAL_USER`.
+    pub single_user: bool,
+    /// Interface language.
+    pub language: Language,
+    /// Timezone for dates: `local`, `UTC` or an offset like `+02:00`.
+    pub timezone: Option<String>,
+    /// Render Markdown in task descriptions. `--plain` turns it off.
+    pub markdown: bool,
+    /// Output for colour-blind users or screen readers (`--accessible`).
+    pub accessibility: Accessibility,
+    /// Editor command for descriptions; defaults to `$VISUAL` or `$EDITOR`.
+    pub editor: Option<String>,
+    /// Always edit descriptions in the editor instead of at the prompt (`--editor`).
+    pub use_editor: bool,
+    /// Answer yes to confirmation prompts, e.g. in scripts (`--yes`).
+    pub assume_yes: bool,
+    /// Backend used to persist tasks and users.
+    pub storage: StorageKind,
+    /// Write JSON data files indented, which is easier to read and diff.
+    pub pretty_json: bool,
+    /// Format of new data files with the file storage backend.
+    pub data_format: DataFormat,
+    /// Git remote that `sync` pulls from and pushes to when using git storage.
+    pub git_remote: Option<String>,
+    /// URLs notified when tasks are created, completed or deleted.
+    pub webhooks: Vec<WebhookConfig>,
+    /// Scripts run on task events.
+    pub hooks: HooksConfig,
+    /// How digests are sent by email.
+    pub mail: MailConfig,
+    /// Task limits of users without their own quota.
+    pub quota: Quota,
+    /// Users allowed to change other users' quotas.
+    pub admins: Vec<String>,
+    /// Settings for server mode (`--serve`).
+    pub server: ServerConfig,
+    /// Changes to the colours and symbols of the built-in themes.
+    pub theme: Theme,
+    /// JSON file with more changes to the built-in themes, applied before
+    /// those in `theme`.
+    pub theme_file: Option<String>,
+    /// Seconds between retries of data files that failed to save, in the
+    /// menu, server and daemon modes; 0 turns retrying off.
+    pub autosave_secs: u64,
+    /// Days a task may be overdue before its priority is raised a level on
+    /// startup; unset turns escalation off.
+    pub escalate_after_days: Option<u32>,
+    /// Minutes without input after which the menu locks and asks for the
+    /// password again; unset never locks. Single-user mode doesn't lock.
+    pub lock_after_mins: Option<u64>,
+    /// How long deleted tasks, the audit log, backups and snapshots are kept.
+    pub retention: Retention,
+    /// Whether to count commands run and tasks created in `usage.json`, for
+    /// the `usage` report. Nothing leaves the data directory.
+    pub usage_stats: bool,
+    /// Automation rules run when tasks are created or edited.
+    pub rules: Vec<Rule>,
+    /// CalDAV collection that due-dated tasks are synced with.
+    #[cfg(feature = "caldav")]
+    pub caldav: Option<CalDavConfig>,
+    /// Mailbox whose new messages are added as tasks by `--email-in`.
+    #[cfg(feature = "imap")]
+    pub imap: Option<ImapConfig>,
+    /// Slack or Discord channel answered by `--bot`.
+    #[cfg(feature = "bot")]
+    pub bot: Option<BotConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            single_user: false,
+            language: Language::default(),
+            timezone: None,
+            markdown: true,
+            accessibility: Accessibility::default(),
+            editor: None,
+            use_editor: false,
+            assume_yes: false,
+            storage: StorageKind::default(),
+            pretty_json: false,
+            data_format: DataFormat::default(),
+            git_remote: None,
+            webhooks: Vec::new(),
+            hooks: HooksConfig::default(),
+            mail: MailConfig::default(),
+            quota: Quota::default(),
+            admins: Vec::new(),
+            server: ServerConfig::default(),
+            autosave_secs: 60,
+            escalate_after_days: None,
+            lock_after_mins: None,
+            retention: Retention::default(),
+            usage_stats: true,
+            rules: Vec::new(),
+            theme: Theme::default(),
+            theme_file: None,
+            #[cfg(feature = "caldav")]
+            caldav: None,
+            #[cfg(feature = "imap")]
+            imap: None,
+            #[cfg(feature = "bot")]
+            bot: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file. If there is none, defaults are used.
+    pub fn load() -> io::Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the config file at `path`. If there is none, defaults are used.
+    pub fn load_from(path: &Path) -> io::Result<Self> {
+        let config: Self = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        for webhook in &config.webhooks {
+            webhook.validate()?;
+        }
+        #[cfg(feature = "bot")]
+        if let Some(bot) = &config.bot {
+            bot.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// The config file in use: `config.json` in the current directory or,
+    /// failing that, the one in the user's config directory. A current
+    /// directory with data but no config file keeps using the defaults, as
+    /// before there was a user config file.
+    pub fn path() -> Option<PathBuf> {
+        if Path::new(CONFIG_FILE).exists() {
+            return Some(PathBuf::from(CONFIG_FILE));
+        }
+        if has_local_data() {
+            return None;
+        }
+        user_path().filter(|path| path.exists())
+    }
+
+    /// Whether the app has never been set up: there's no config file and
+    /// no data in the current directory.
+    pub fn is_first_run() -> bool {
+        Self::path().is_none() && !has_local_data()
+    }
+
+    /// Writes the config to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Applies command line flags on top of the values from the config file.
+    pub fn apply_args(&mut self, flags: &[String]) {
+        for flag in flags {
+            match flag.as_str() {
+                "--single-user" => self.single_user = true,
+                "--plain" => self.markdown = false,
+                "--editor" => self.use_editor = true,
+                "--yes" => self.assume_yes = true,
+                flag => {
+                    if let Some(mode) = Accessibility::from_flag(flag) {
+                        self.accessibility = mode;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The config file in the user's config directory, e.g.
+/// `~/.config/lab3/config.json`.
+pub fn user_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join(APP_DIR).join(CONFIG_FILE))
+}
+
+/// Whether the current directory has tasks or users in any data format.
+fn has_local_data() -> bool {
+    DataFormat::ALL.iter().any(|format| {
+        [TASKS_FILE, USERS_FILE]
+            .iter()
+            .any(|name| Path::new(&format.path(name)).exists())
+    })
+}
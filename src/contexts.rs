@@ -0,0 +1,64 @@
+//! GTD-style contexts: where a task can be done, such as `@home` or
+//! `@errands`. While a current context is set, lists only show the tasks
+//! that can be done there and those that can be done anywhere.
+
+use crate::{Task, TodoApp};
+use std::collections::BTreeMap;
+
+/// Parses a context name, with or without the leading `@`, ignoring case.
+pub fn parse_context(name: &str) -> Result<String, &'static str> {
+    let name = name.trim();
+    let name = name.strip_prefix('@').unwrap_or(name).to_lowercase();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err("Invalid context, expected a single word such as @home");
+    }
+    Ok(name)
+}
+
+impl TodoApp {
+    /// Sets where a task can be done, or makes it doable anywhere with `None`.
+    pub fn set_task_context(
+        &mut self,
+        task_id: u32,
+        context: Option<&str>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let context = context.map(parse_context).transpose()?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.context = context;
+        task.touch();
+        self.save_tasks(&format!("Change context of task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// The context the current user is working in, if they set one.
+    pub fn current_context(&self) -> Option<String> {
+        self.settings().ok()?.context
+    }
+
+    /// The tasks of the current list that can be done in the current context.
+    pub fn context_tasks(&self) -> Result<Vec<&Task>, &'static str> {
+        let current = self.current_context();
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| current.is_none() || task.context.is_none() || task.context == current)
+            .collect())
+    }
+
+    /// The contexts used in the current list, with how many pending tasks
+    /// each has.
+    pub fn contexts(&self) -> Result<BTreeMap<String, usize>, &'static str> {
+        let mut contexts = BTreeMap::new();
+        for task in self.list_tasks()? {
+            if let Some(context) = task.context.as_ref().filter(|_| !task.completed) {
+                *contexts.entry(context.clone()).or_default() += 1;
+            }
+        }
+        Ok(contexts)
+    }
+}
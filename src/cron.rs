@@ -0,0 +1,215 @@
+//! Cron expressions for repeating reminders, e.g. `0 9 * * MON-FRI` for
+//! 9:00 on weekdays. The five fields are the minute, hour, day of the month,
+//! month and day of the week; each is `*`, a number or name, a range like
+//! `MON-FRI`, a list like `1,15` or any of those with a step like `*/15`.
+//! As in cron, when both day fields are restricted a day matching either
+//! one matches.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use std::fmt;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Days searched for the next match; long enough to reach a 29th of
+/// February.
+const SEARCH_DAYS: usize = 8 * 366;
+
+/// A parsed cron expression. Each field is a bit set of the values it
+/// matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Sunday is 0.
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// One of the five fields: its name, range and the names its values may go
+/// by.
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+    /// Whether names count from `min` rather than from 0.
+    names_from_min: bool,
+}
+
+const FIELDS: [Field; 5] = [
+    Field {
+        name: "minute",
+        min: 0,
+        max: 59,
+        names: &[],
+        names_from_min: false,
+    },
+    Field {
+        name: "hour",
+        min: 0,
+        max: 23,
+        names: &[],
+        names_from_min: false,
+    },
+    Field {
+        name: "day of month",
+        min: 1,
+        max: 31,
+        names: &[],
+        names_from_min: false,
+    },
+    Field {
+        name: "month",
+        min: 1,
+        max: 12,
+        names: &MONTHS,
+        names_from_min: true,
+    },
+    // 7 is Sunday too.
+    Field {
+        name: "day of week",
+        min: 0,
+        max: 7,
+        names: &WEEKDAYS,
+        names_from_min: false,
+    },
+];
+
+impl Cron {
+    /// Parses a five-field expression.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = expression.split_whitespace().collect();
+        if parts.len() != FIELDS.len() {
+            return Err(format!(
+                "Invalid schedule {:?}: expected 5 fields (minute hour day month weekday), found {}",
+                expression,
+                parts.len()
+            ));
+        }
+        let mut sets = [0u64; 5];
+        for ((set, part), field) in sets.iter_mut().zip(&parts).zip(&FIELDS) {
+            *set = field
+                .parse(part)
+                .map_err(|e| format!("Invalid schedule {:?}: {}: {}", expression, field.name, e))?;
+        }
+        let [minutes, hours, days, months, mut weekdays] = sets;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            source: parts.join(" "),
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            any_day: parts[2].starts_with('*'),
+            any_weekday: parts[4].starts_with('*'),
+        })
+    }
+
+    /// The first minute after `time` that matches, if there's one within
+    /// the next few years.
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = time.date();
+        for day in start.iter_days().take(SEARCH_DAYS) {
+            if !self.matches_day(day) {
+                continue;
+            }
+            for hour in (0..24).filter(|hour| has(self.hours, *hour)) {
+                for minute in (0..60).filter(|minute| has(self.minutes, *minute)) {
+                    let candidate = day.and_time(NaiveTime::from_hms_opt(hour, minute, 0)?);
+                    if candidate > time {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day: NaiveDate) -> bool {
+        if !has(self.months, day.month()) {
+            return false;
+        }
+        let by_day = has(self.days, day.day());
+        let by_weekday = has(self.weekdays, day.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => by_day,
+            (true, false) => by_weekday,
+            (false, false) => by_day || by_weekday,
+        }
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Field {
+    /// The set of values `part` matches.
+    fn parse(&self, part: &str) -> Result<u64, String> {
+        let mut set = 0;
+        for item in part.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step
+                        .parse()
+                        .ok()
+                        .filter(|step| *step > 0)
+                        .ok_or_else(|| format!("invalid step {:?}", step))?;
+                    (range, step)
+                }
+                None => (item, 1),
+            };
+            let (first, last) = if range == "*" {
+                (self.min, self.max)
+            } else if let Some((first, last)) = range.split_once('-') {
+                (self.value(first)?, self.value(last)?)
+            } else {
+                let value = self.value(range)?;
+                // `5/15` means from 5 to the end, every 15.
+                (value, if step > 1 { self.max } else { value })
+            };
+            if first > last {
+                return Err(format!("range {:?} goes backwards", range));
+            }
+            for value in (first..=last).step_by(step as usize) {
+                set |= 1 << value;
+            }
+        }
+        Ok(set)
+    }
+
+    fn value(&self, text: &str) -> Result<u32, String> {
+        let upper = text.to_uppercase();
+        let value = match self.names.iter().position(|name| *name == upper) {
+            Some(index) if self.names_from_min => index as u32 + self.min,
+            Some(index) => index as u32,
+            None => text
+                .parse()
+                .map_err(|_| format!("invalid value {:?}", text))?,
+        };
+        if !(self.min..=self.max).contains(&value) {
+            return Err(format!(
+                "{} is out of range {}-{}",
+                value, self.min, self.max
+            ));
+        }
+        Ok(value)
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
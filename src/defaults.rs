@@ -0,0 +1,206 @@
+//! Defaults a project or tag gives to tasks created in it, such as a
+//! priority or reminders, and the icon its tasks are shown with. Values
+//! given for the task itself take precedence.
+
+use crate::icons::parse_icon;
+use crate::log;
+use crate::priority::Priority;
+use crate::reminders::Reminder;
+use crate::schedule::parse_duration;
+use crate::storage;
+use crate::{Task, TodoApp};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+pub const DEFAULTS_FILE: &str = "defaults.json";
+
+/// Values given to new tasks.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Defaults {
+    pub priority: Option<Priority>,
+    /// Reminders to add, as seconds before the due date. Only used when the
+    /// task has a due date.
+    pub reminders: Vec<i64>,
+    /// Expected effort, in seconds.
+    pub estimate_secs: Option<i64>,
+    /// Icon shown for tasks without their own. Not copied to the tasks, so
+    /// changing it changes how they're all shown.
+    pub icon: Option<String>,
+}
+
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Sets the default called `name` from user input. `none` removes it;
+    /// for `remind` it removes every reminder.
+    fn set(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        let none = value.eq_ignore_ascii_case("none");
+        match name {
+            "priority" if none => self.priority = None,
+            "priority" => {
+                self.priority = Some(
+                    Priority::parse(value)
+                        .ok_or("Unknown priority, expected low, normal, high or urgent")?,
+                )
+            }
+            "remind" if none => self.reminders.clear(),
+            "remind" => {
+                let offset = parse_duration(value)?.num_seconds();
+                if !self.reminders.contains(&offset) {
+                    self.reminders.push(offset);
+                    self.reminders.sort_by_key(|offset| -offset);
+                }
+            }
+            "estimate" if none => self.estimate_secs = None,
+            "estimate" => self.estimate_secs = Some(parse_duration(value)?.num_seconds()),
+            "icon" if none => self.icon = None,
+            "icon" => self.icon = Some(parse_icon(value)?),
+            _ => return Err("Unknown default, expected priority, remind, estimate or icon"),
+        }
+        Ok(())
+    }
+}
+
+/// A user's defaults for their projects and tags, by name.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserDefaults {
+    pub projects: BTreeMap<String, Defaults>,
+    pub tags: BTreeMap<String, Defaults>,
+}
+
+/// Whether defaults belong to a project or a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Project,
+    Tag,
+}
+
+impl TodoApp {
+    /// The current user's project and tag defaults.
+    pub fn defaults(&self) -> Result<UserDefaults, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        Ok(self.defaults.get(user_id).cloned().unwrap_or_default())
+    }
+
+    /// Sets one default of a project or tag; see `Defaults::set`.
+    pub fn set_default(
+        &mut self,
+        scope: Scope,
+        name: &str,
+        default: &str,
+        value: &str,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if name.is_empty() {
+            return Err("Project or tag name cannot be empty");
+        }
+        let user_defaults = self.defaults.entry(user_id.clone()).or_default();
+        let (all, name) = match scope {
+            Scope::Project => (&mut user_defaults.projects, name.to_string()),
+            Scope::Tag => (&mut user_defaults.tags, name.to_lowercase()),
+        };
+        let defaults = all.entry(name.clone()).or_default();
+        defaults.set(default, value)?;
+        if defaults.is_empty() {
+            all.remove(&name);
+        }
+        self.save_defaults(&format!(
+            "Set {} default of {} for user {}",
+            default, name, user_id
+        ))
+        .map_err(|_| "Failed to save defaults")
+    }
+
+    /// Removes every default of a project or tag.
+    pub fn clear_defaults(&mut self, scope: Scope, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let user_defaults = self.defaults.get_mut(&user_id);
+        let removed = user_defaults.and_then(|user_defaults| match scope {
+            Scope::Project => user_defaults.projects.remove(name),
+            Scope::Tag => user_defaults.tags.remove(&name.to_lowercase()),
+        });
+        removed.ok_or("No defaults set for it")?;
+        self.save_defaults(&format!("Clear defaults of {} for user {}", name, user_id))
+            .map_err(|_| "Failed to save defaults")
+    }
+
+    /// Gives a new task the defaults of its project and tags. A tag's
+    /// defaults win over the project's; of several tags, the highest
+    /// priority and longest estimate are used and all reminders are added.
+    pub fn apply_defaults(&self, task: &mut Task) {
+        let Some(user_defaults) = self.defaults.get(&task.user_id) else {
+            return;
+        };
+        let mut applied = task
+            .project
+            .as_ref()
+            .and_then(|project| user_defaults.projects.get(project))
+            .cloned()
+            .unwrap_or_default();
+        let tags: Vec<&Defaults> = task
+            .tags
+            .iter()
+            .filter_map(|tag| user_defaults.tags.get(tag))
+            .collect();
+        if let Some(priority) = tags.iter().filter_map(|defaults| defaults.priority).max() {
+            applied.priority = Some(priority);
+        }
+        if let Some(estimate) = tags
+            .iter()
+            .filter_map(|defaults| defaults.estimate_secs)
+            .max()
+        {
+            applied.estimate_secs = Some(estimate);
+        }
+        for offset in tags.iter().flat_map(|defaults| &defaults.reminders) {
+            if !applied.reminders.contains(offset) {
+                applied.reminders.push(*offset);
+            }
+        }
+
+        if let Some(priority) = applied.priority {
+            task.priority = priority;
+        }
+        if let Some(estimate) = applied.estimate_secs {
+            task.estimate_secs = Some(estimate);
+        }
+        if task.due_date.is_some() {
+            for offset in applied.reminders {
+                if !task.reminders.iter().any(|r| r.offset_secs == offset) {
+                    task.reminders.push(Reminder {
+                        offset_secs: offset,
+                        notified: false,
+                    });
+                }
+            }
+            task.reminders.sort_by_key(|r| -r.offset_secs);
+        }
+    }
+
+    /// Saves all users' defaults. `message` describes the change being saved.
+    pub fn save_defaults(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(
+            self.storage.as_ref(),
+            DEFAULTS_FILE,
+            &self.defaults,
+            message,
+        )
+        .inspect_err(|e| log::error(&format!("Failed to save defaults ({}): {}", message, e)))
+    }
+
+    /// Loads all users' defaults. If the file doesn't exist, it's ignored.
+    pub fn load_defaults(&mut self) -> io::Result<()> {
+        if let Some(defaults) = storage::load_json(self.storage.as_ref(), DEFAULTS_FILE)? {
+            self.defaults = defaults;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Dependencies between tasks: a task is blocked until every task it
+//! depends on is completed.
+
+use crate::{Task, TodoApp};
+
+impl TodoApp {
+    /// Makes task `task_id` wait for task `on_id` to be completed.
+    pub fn add_dependency(&mut self, task_id: u32, on_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if task_id == on_id {
+            return Err("A task can't depend on itself");
+        }
+        let on = self.tasks.get(&on_id).ok_or("Task not found")?;
+        if !self.workspaces.can_access(on, &user_id) {
+            return Err("Not authorized to view this task");
+        }
+        let on_uuid = on.uuid.clone();
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if task.depends_on.contains(&on_uuid) {
+            return Err("The task already depends on that task");
+        }
+        if self.depends_on(on, &task.uuid) {
+            return Err("That would make the tasks wait for each other");
+        }
+
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        task.depends_on.insert(on_uuid);
+        task.touch();
+        self.save_tasks(&format!("Make task #{} depend on #{}", task_id, on_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// Stops task `task_id` waiting for task `on_id`.
+    pub fn remove_dependency(&mut self, task_id: u32, on_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let on_uuid = self.tasks.get(&on_id).ok_or("Task not found")?.uuid.clone();
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if !task.depends_on.remove(&on_uuid) {
+            return Err("The task doesn't depend on that task");
+        }
+        task.touch();
+        self.save_tasks(&format!(
+            "Make task #{} no longer depend on #{}",
+            task_id, on_id
+        ))
+        .map_err(|_| "Failed to save tasks")
+    }
+
+    /// The tasks `task` is still waiting for. Dependencies on deleted tasks
+    /// are ignored.
+    pub fn blockers(&self, task: &Task) -> Vec<&Task> {
+        self.tasks
+            .values()
+            .filter(|other| !other.completed && task.depends_on.contains(&other.uuid))
+            .collect()
+    }
+
+    pub fn is_blocked(&self, task: &Task) -> bool {
+        !self.blockers(task).is_empty()
+    }
+
+    /// Whether `task` depends on the task with `uuid`, directly or through
+    /// other tasks.
+    fn depends_on(&self, task: &Task, uuid: &str) -> bool {
+        let mut seen = Vec::new();
+        let mut stack = vec![task];
+        while let Some(task) = stack.pop() {
+            if task.depends_on.contains(uuid) {
+                return true;
+            }
+            for other in self.tasks.values() {
+                if task.depends_on.contains(&other.uuid) && !seen.contains(&other.uuid) {
+                    seen.push(other.uuid.clone());
+                    stack.push(other);
+                }
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,87 @@
+//! Daily summary of due, overdue and recently completed tasks.
+
+use crate::i18n::{self, tr, trf};
+use crate::mail::Mailer;
+use crate::schedule::{local_date, start_of_today};
+use crate::{Task, TodoApp};
+use chrono::{Duration, NaiveDate};
+
+/// The tasks a digest reports on.
+pub struct Digest<'a> {
+    pub date: NaiveDate,
+    pub due_today: Vec<&'a Task>,
+    pub overdue: Vec<&'a Task>,
+    pub completed_yesterday: Vec<&'a Task>,
+}
+
+impl TodoApp {
+    /// Collects today's digest for the current user.
+    pub fn digest(&self) -> Result<Digest<'_>, &'static str> {
+        let today = local_date(start_of_today());
+        let yesterday = today - Duration::days(1);
+        let mut digest = Digest {
+            date: today,
+            due_today: Vec::new(),
+            overdue: Vec::new(),
+            completed_yesterday: Vec::new(),
+        };
+
+        let mut tasks = self.list_tasks()?;
+        tasks.sort_by_key(|task| (task.due_date, task.id));
+        for task in tasks {
+            if task.completed {
+                if task.completed_at.map(local_date) == Some(yesterday) {
+                    digest.completed_yesterday.push(task);
+                }
+                continue;
+            }
+            match task.due_date.map(local_date) {
+                Some(date) if date == today => digest.due_today.push(task),
+                Some(date) if date < today => digest.overdue.push(task),
+                _ => {}
+            }
+        }
+        Ok(digest)
+    }
+
+    /// Emails the current user's digest to the address on their account,
+    /// if they get notifications by email.
+    pub fn send_digest(&self, mailer: &dyn Mailer) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        if !self.settings()?.notifications.email {
+            return Err("Email notifications are off; turn them on with: settings notify-email on");
+        }
+        let email = self
+            .users
+            .get(user_id)
+            .and_then(|user| user.email.as_deref())
+            .ok_or("No email address set")?;
+        let digest = self.digest()?;
+        let subject = trf("Todo digest for {}", &[&i18n::format_day(digest.date)]);
+        mailer
+            .send(email, &subject, &render(&digest))
+            .map_err(|_| "Failed to send email")
+    }
+}
+
+/// Renders a digest as plain text.
+pub fn render(digest: &Digest) -> String {
+    let mut out = trf("Todo digest for {}", &[&i18n::format_day(digest.date)]);
+    out.push('\n');
+    for (heading, tasks) in [
+        ("Due today ({}):", &digest.due_today),
+        ("Overdue ({}):", &digest.overdue),
+        ("Completed yesterday ({}):", &digest.completed_yesterday),
+    ] {
+        out.push('\n');
+        out.push_str(&trf(heading, &[&tasks.len()]));
+        out.push('\n');
+        if tasks.is_empty() {
+            out.push_str(&format!("  {}\n", tr("nothing")));
+        }
+        for task in tasks {
+            out.push_str(&format!("  #{} {}\n", task.id, task.title));
+        }
+    }
+    out
+}
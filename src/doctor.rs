@@ -0,0 +1,351 @@
+//! Checking the installation for problems (`Lab3 doctor`): damaged data
+//! files, files the app can't write, saves or git commands that were cut
+//! off, an invalid `config.json`, missing backups, tasks whose owner no
+//! longer exists and a system clock that disagrees with the data. Each problem comes with a suggested fix.
+//!
+//! Nothing is changed. Problems make the command exit with status 1, so it
+//! can be used in scripts; warnings don't.
+
+use crate::config::Config;
+use crate::formats::DataFormat;
+use crate::fsck;
+use crate::i18n::{format_datetime, tr, trf};
+use crate::orphans;
+use crate::rules;
+use crate::schedule;
+use crate::storage::{self, Storage, StorageKind, GIT_DIR};
+use crate::theme;
+use crate::tokens::TOKENS_FILE;
+use crate::{Task, User, TASKS_FILE, USERS_FILE};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How far in the future a recorded change may be before the clock is
+/// taken to be wrong, allowing for devices that are slightly off.
+const CLOCK_TOLERANCE: Duration = Duration::days(1);
+
+/// Days after which a git remote that hasn't been synced with is reported.
+const SYNC_MAX_AGE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    /// Worth fixing, but the app works.
+    Warning,
+    /// The app fails or loses data until it's fixed.
+    Problem,
+}
+
+/// Something wrong, with what to do about it.
+struct Finding {
+    severity: Severity,
+    message: String,
+    fix: String,
+}
+
+impl Finding {
+    fn warning(message: String, fix: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            fix,
+        }
+    }
+
+    fn problem(message: String, fix: String) -> Self {
+        Self {
+            severity: Severity::Problem,
+            message,
+            fix,
+        }
+    }
+}
+
+/// Runs every check and prints what was found. Returns whether there were
+/// no problems.
+pub fn run(storage: &dyn Storage) -> io::Result<bool> {
+    let (config, config_findings) = check_config();
+    let sections = [
+        (tr("Data files"), check_data_files(storage)),
+        (tr("Permissions"), check_permissions()),
+        (tr("Locks"), check_locks(config.storage)),
+        (tr("Config"), config_findings),
+        (tr("Backups"), check_backups(&config)),
+        (tr("Owners"), check_owners(storage)),
+        (tr("Clock"), check_clock(storage)),
+    ];
+
+    let mut problems = 0;
+    let mut warnings = 0;
+    for (name, findings) in &sections {
+        if findings.is_empty() {
+            println!("{}", trf("{}: OK", &[name]));
+            continue;
+        }
+        for finding in findings {
+            let label = match finding.severity {
+                Severity::Warning => {
+                    warnings += 1;
+                    tr("warning")
+                }
+                Severity::Problem => {
+                    problems += 1;
+                    tr("problem")
+                }
+            };
+            println!("{}: {}: {}", name, label, finding.message);
+            println!("  {}", trf("Fix: {}", &[&finding.fix]));
+        }
+    }
+    if problems == 0 && warnings == 0 {
+        println!("{}", tr("No problems found"));
+    } else {
+        println!(
+            "{}",
+            trf("{} problem(s), {} warning(s)", &[&problems, &warnings])
+        );
+    }
+    Ok(problems == 0)
+}
+
+/// Where the data file `name` is stored, in whichever format it exists.
+fn data_path(name: &str) -> Option<String> {
+    DataFormat::ALL
+        .iter()
+        .map(|format| format.path(name))
+        .find(|path| Path::new(path).exists())
+}
+
+fn check_data_files(storage: &dyn Storage) -> Vec<Finding> {
+    fsck::damaged(storage)
+        .into_iter()
+        .map(|(name, error)| {
+            Finding::problem(
+                trf("{} can't be read: {}", &[&name, &error]),
+                tr("Run fsck to restore it from its backup or recover what's readable").to_string(),
+            )
+        })
+        .collect()
+}
+
+fn check_permissions() -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if fs::metadata(".").is_ok_and(|metadata| metadata.permissions().readonly()) {
+        findings.push(Finding::problem(
+            tr("The data directory is read-only, so nothing can be saved").to_string(),
+            tr("Make the directory writable, e.g. with chmod u+w .").to_string(),
+        ));
+    }
+    for path in fsck::DATA_FILES.iter().filter_map(|name| data_path(name)) {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.permissions().readonly() {
+            findings.push(Finding::problem(
+                trf("{} is read-only, so changes to it can't be saved", &[&path]),
+                trf("Make it writable, e.g. with chmod u+w {}", &[&path]),
+            ));
+        }
+    }
+    // Password hashes and API tokens shouldn't be readable by anyone else.
+    #[cfg(unix)]
+    for path in [USERS_FILE, TOKENS_FILE]
+        .iter()
+        .filter_map(|name| data_path(name))
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.permissions().mode() & 0o077 != 0 {
+            findings.push(Finding::warning(
+                trf("{} can be read by other users of this machine", &[&path]),
+                trf("Restrict it to your account with chmod 600 {}", &[&path]),
+            ));
+        }
+    }
+    findings
+}
+
+fn check_locks(kind: StorageKind) -> Vec<Finding> {
+    // Saves write to a temporary file first; one left behind means a save
+    // was cut off or is still running.
+    let mut findings: Vec<Finding> = fsck::DATA_FILES
+        .iter()
+        .flat_map(|name| DataFormat::ALL.map(|format| format!("{}.tmp", format.path(name))))
+        .filter(|path| Path::new(path).exists())
+        .map(|path| {
+            Finding::warning(
+                trf("{} was left behind by a save that didn't finish", &[&path]),
+                trf(
+                    "If Lab3 isn't running, remove {}; the data file itself is intact",
+                    &[&path],
+                ),
+            )
+        })
+        .collect();
+    if kind == StorageKind::Git {
+        let lock = format!("{}/index.lock", GIT_DIR);
+        if Path::new(&lock).exists() {
+            findings.push(Finding::problem(
+                tr("The git storage is locked, so changes can't be committed").to_string(),
+                trf("If neither Lab3 nor git is running, remove {}", &[&lock]),
+            ));
+        }
+        let merge_head = format!("{}/MERGE_HEAD", GIT_DIR);
+        if Path::new(&merge_head).exists() {
+            findings.push(Finding::problem(
+                tr("A sync stopped in the middle of a merge").to_string(),
+                trf(
+                    "Run git --git-dir={} --work-tree=. merge --abort, then sync again",
+                    &[&GIT_DIR],
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+/// Loads the config, reporting what's wrong with it. The defaults stand in
+/// for a config file that can't be read.
+fn check_config() -> (Config, Vec<Finding>) {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            let finding = Finding::problem(
+                trf("config.json can't be read: {}", &[&e]),
+                tr("Correct config.json; the app doesn't start until then").to_string(),
+            );
+            return (Config::default(), vec![finding]);
+        }
+    };
+    let mut findings = Vec::new();
+    if let Some(Err(e)) = config.timezone.as_deref().map(schedule::parse_timezone) {
+        findings.push(Finding::problem(
+            tr(e).to_string(),
+            tr("Set timezone to local, UTC or an offset like +02:00").to_string(),
+        ));
+    }
+    if let Err(e) = rules::validate(&config.rules) {
+        findings.push(Finding::problem(
+            e,
+            tr("Correct the rule in config.json; no rules run until then").to_string(),
+        ));
+    }
+    if let Err(e) = theme::customize(&config.theme, config.theme_file.as_deref()) {
+        findings.push(Finding::warning(
+            e,
+            tr("Correct the theme; the built-in colours are used meanwhile").to_string(),
+        ));
+    }
+    (config, findings)
+}
+
+fn check_backups(config: &Config) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // A backup of each data file is kept from its second save on, unless
+    // the retention policy keeps none.
+    for path in fsck::DATA_FILES
+        .iter()
+        .filter(|_| config.retention.backups > 0)
+        .filter_map(|name| data_path(name))
+    {
+        let backup = format!("{}.bak", path);
+        if !Path::new(&backup).exists() {
+            findings.push(Finding::warning(
+                trf("{} has no backup yet", &[&path]),
+                trf(
+                    "One is made the next time it's saved; meanwhile keep a copy of {}",
+                    &[&path],
+                ),
+            ));
+        }
+    }
+    if config.storage != StorageKind::Git {
+        return findings;
+    }
+    if config.git_remote.is_none() {
+        findings.push(Finding::warning(
+            tr("The data is only kept on this machine").to_string(),
+            tr("Set git_remote in config.json and run sync").to_string(),
+        ));
+        return findings;
+    }
+    // Fetching, the first step of every sync, updates FETCH_HEAD.
+    let last_sync = fs::metadata(format!("{}/FETCH_HEAD", GIT_DIR))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+    match last_sync {
+        None => findings.push(Finding::warning(
+            tr("The data was never synced with the git remote").to_string(),
+            tr("Run sync").to_string(),
+        )),
+        Some(time) if Utc::now() - time > Duration::days(SYNC_MAX_AGE_DAYS) => {
+            findings.push(Finding::warning(
+                trf("The last sync was on {}", &[&format_datetime(time)]),
+                tr("Run sync").to_string(),
+            ))
+        }
+        Some(_) => {}
+    }
+    findings
+}
+
+/// Points out tasks owned by users that don't exist, which no one can list.
+fn check_owners(storage: &dyn Storage) -> Vec<Finding> {
+    let (Ok(Some(tasks)), Ok(users)) = (
+        storage::load_json::<HashMap<String, Task>>(storage, TASKS_FILE),
+        storage::load_json::<BTreeMap<String, User>>(storage, USERS_FILE),
+    ) else {
+        return Vec::new();
+    };
+    let orphans = orphans::find(tasks.values(), &users.unwrap_or_default());
+    orphans::count_by_owner(&orphans)
+        .into_iter()
+        .map(|(owner, count)| {
+            Finding::warning(
+                trf(
+                    "{} task(s) belong to {}, who isn't a registered user",
+                    &[&count, &owner],
+                ),
+                tr("As an admin, run orphans to reassign, export or delete them").to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Compares the clock with the times recorded in the tasks. A clock that is
+/// behind makes due dates and reminders fire late and new changes look
+/// older than existing ones.
+fn check_clock(storage: &dyn Storage) -> Vec<Finding> {
+    let now = Utc::now();
+    let fix =
+        tr("Set the system clock to the right time; due dates and reminders are compared with it");
+    if now < Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap() {
+        return vec![Finding::problem(
+            trf("The system clock says it's {}", &[&format_datetime(now)]),
+            fix.to_string(),
+        )];
+    }
+    let Ok(Some(tasks)) = storage::load_json::<HashMap<String, Task>>(storage, TASKS_FILE) else {
+        return Vec::new();
+    };
+    let latest = tasks
+        .values()
+        .flat_map(|task| [Some(task.last_changed()), task.completed_at])
+        .flatten()
+        .max();
+    match latest {
+        Some(latest) if latest > now + CLOCK_TOLERANCE => vec![Finding::problem(
+            trf(
+                "The system clock is behind: tasks were changed as late as {}",
+                &[&format_datetime(latest)],
+            ),
+            fix.to_string(),
+        )],
+        _ => Vec::new(),
+    }
+}
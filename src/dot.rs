@@ -0,0 +1,64 @@
+//! Graphviz DOT output of how tasks depend on and link to each other, e.g.
+//! for `dot -Tsvg`.
+
+use crate::Task;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// `tasks` and the dependencies and links between them as a DOT digraph.
+/// Only tasks connected to another of `tasks` are drawn. Dependencies point
+/// from the task that must be done first; links are dashed.
+pub fn render(tasks: &[&Task]) -> String {
+    let by_uuid: BTreeMap<&str, &Task> = tasks
+        .iter()
+        .map(|task| (task.uuid.as_str(), *task))
+        .collect();
+    let by_id: BTreeMap<u32, &Task> = tasks.iter().map(|task| (task.id, *task)).collect();
+
+    // (from, to, whether it's a link)
+    let mut edges = Vec::new();
+    for task in tasks {
+        for blocker in task
+            .depends_on
+            .iter()
+            .filter_map(|uuid| by_uuid.get(uuid.as_str()))
+        {
+            edges.push((blocker.id, task.id, false));
+        }
+        for linked in task.relates_to.iter().filter_map(|id| by_id.get(id)) {
+            edges.push((task.id, linked.id, true));
+        }
+    }
+    let connected: BTreeSet<u32> = edges
+        .iter()
+        .flat_map(|(from, to, _)| [*from, *to])
+        .collect();
+
+    let mut out = String::from("digraph tasks {\n  rankdir=LR;\n  node [shape=box];\n");
+    for task in connected.iter().filter_map(|id| by_id.get(id)) {
+        let label = escape(&format!("#{} {}", task.id, task.title));
+        if task.completed {
+            out.push_str(&format!(
+                "  t{} [label=\"{}\", style=filled, fillcolor=lightgray];\n",
+                task.id, label
+            ));
+        } else {
+            out.push_str(&format!("  t{} [label=\"{}\"];\n", task.id, label));
+        }
+    }
+    for (from, to, link) in edges {
+        if link {
+            out.push_str(&format!("  t{} -> t{} [style=dashed];\n", from, to));
+        } else {
+            out.push_str(&format!("  t{} -> t{};\n", from, to));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escapes text for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,123 @@
+//! Copying tasks and whole projects.
+
+use crate::crypto;
+use crate::{Task, TaskEvent, TodoApp};
+use chrono::{DateTime, Utc};
+
+impl TodoApp {
+    /// Creates a pending copy of a task with a fresh ID, optionally due on a
+    /// new date. Returns the ID of the copy.
+    pub fn duplicate_task(
+        &mut self,
+        task_id: u32,
+        due_date: Option<DateTime<Utc>>,
+    ) -> Result<u32, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_access(task, &user_id) {
+            return Err("Not authorized to view this task");
+        }
+        if !self.workspaces.can_add(task.workspace.as_deref(), &user_id) {
+            return Err("Not authorized to add tasks to this workspace");
+        }
+        self.check_quota(1)?;
+
+        let copy = self.copy_of(task, due_date.or(task.due_date), task.project.clone());
+        let copy_id = copy.id;
+        self.insert_task(copy);
+        self.next_task_id += 1;
+        self.save_tasks(&format!("Add task #{}", copy_id))
+            .map_err(|_| "Failed to save tasks")?;
+        self.emit(TaskEvent::Created, &self.tasks[&copy_id]);
+        Ok(copy_id)
+    }
+
+    /// Copies every task of one of the current user's projects into a new
+    /// project. Returns the IDs of the copies.
+    pub fn clone_project(
+        &mut self,
+        project: &str,
+        new_project: &str,
+    ) -> Result<Vec<u32>, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if new_project.is_empty() {
+            return Err("Project name cannot be empty");
+        }
+        if !self
+            .workspaces
+            .can_add(self.active_workspace.as_deref(), &user_id)
+        {
+            return Err("Not authorized to add tasks to this workspace");
+        }
+        let tasks = self.list_tasks()?;
+        if tasks
+            .iter()
+            .any(|task| task.project.as_deref() == Some(new_project))
+        {
+            return Err("Project already exists");
+        }
+        let mut originals: Vec<&Task> = tasks
+            .into_iter()
+            .filter(|task| task.project.as_deref() == Some(project))
+            .collect();
+        if originals.is_empty() {
+            return Err("Project not found");
+        }
+        originals.sort_by_key(|task| task.id);
+        self.check_quota(originals.len() as u32)?;
+
+        let copies: Vec<Task> = originals
+            .iter()
+            .enumerate()
+            .map(|(offset, task)| {
+                let mut copy = self.copy_of(task, task.due_date, Some(new_project.to_string()));
+                copy.id += offset as u32;
+                copy
+            })
+            .collect();
+        let copy_ids: Vec<u32> = copies.iter().map(|task| task.id).collect();
+        for copy in copies {
+            self.insert_task(copy);
+            self.next_task_id += 1;
+        }
+        self.save_tasks(&format!("Clone project {} as {}", project, new_project))
+            .map_err(|_| "Failed to save tasks")?;
+        for copy_id in &copy_ids {
+            self.emit(TaskEvent::Created, &self.tasks[copy_id]);
+        }
+        Ok(copy_ids)
+    }
+
+    /// A pending copy of `task` under the next free task ID.
+    fn copy_of(
+        &self,
+        task: &Task,
+        due_date: Option<DateTime<Utc>>,
+        project: Option<String>,
+    ) -> Task {
+        let mut copy = task.clone();
+        copy.id = self.next_task_id;
+        copy.uuid = crypto::uuid_v4();
+        if let Some(user_id) = &self.current_user {
+            copy.user_id = user_id.clone();
+        }
+        copy.completed = false;
+        copy.completed_at = None;
+        copy.expires_at = None;
+        copy.cancelled_at = None;
+        copy.time_entries.clear();
+        copy.created_at = Utc::now();
+        copy.due_date = due_date;
+        copy.project = project;
+        copy.version = 1;
+        copy.updated_at = None;
+        copy.snoozed_until = None;
+        for reminder in &mut copy.reminders {
+            reminder.notified = false;
+        }
+        for reminder in &mut copy.cron_reminders {
+            reminder.since = copy.created_at;
+        }
+        copy
+    }
+}
@@ -0,0 +1,54 @@
+//! Editing longer text, such as multi-line descriptions, in an external editor.
+
+use crate::lock;
+use std::env;
+use std::fs;
+use std::io;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Opens `initial` in the user's editor and returns the saved text.
+///
+/// The editor is `editor` if given, otherwise `$VISUAL`, `$EDITOR` or `vi`.
+/// It may include arguments, e.g. `code --wait`.
+pub fn edit_text(initial: &str, editor: Option<&str>) -> io::Result<String> {
+    edit_file(initial, editor, "md")
+}
+
+/// Like `edit_text`, with the file named with `extension` so the editor
+/// can tell its format, e.g. `yaml`.
+pub fn edit_file(initial: &str, editor: Option<&str>, extension: &str) -> io::Result<String> {
+    let editor = editor
+        .map(str::to_string)
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    let path = env::temp_dir().join(format!(
+        "todo-{}-{}.{}",
+        std::process::id(),
+        nanos,
+        extension
+    ));
+    fs::write(&path, initial)?;
+
+    let status = Command::new(program).args(words).arg(&path).status();
+    // Time spent in the editor isn't time away from the app.
+    lock::record_input();
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, status
+        )));
+    }
+    Ok(text?.trim_end().to_string())
+}
@@ -0,0 +1,346 @@
+//! Password-protected files, for archives kept somewhere others may read
+//! them, such as a cloud drive. The contents are encrypted with AES-256-GCM
+//! under a key derived from the password with PBKDF2-HMAC-SHA256, so a wrong
+//! password or a damaged file is detected rather than decrypted to garbage.
+//!
+//! The file is the header (`MAGIC`, the PBKDF2 iterations as a big-endian
+//! `u32`, a 16-byte salt and a 12-byte nonce), then the ciphertext and the
+//! 16-byte tag. The header is authenticated along with the contents.
+
+use crate::crypto;
+
+const MAGIC: &[u8; 8] = b"LAB3ENC\x01";
+
+/// PBKDF2 iterations for new files. Files record their own, so this can be
+/// raised without breaking older ones.
+#[cfg(not(test))]
+const ITERATIONS: u32 = 600_000;
+
+/// Unoptimized test builds would take many seconds per file.
+#[cfg(test)]
+const ITERATIONS: u32 = MIN_ITERATIONS;
+
+/// Fewest iterations accepted, so a forged header can't weaken the key.
+const MIN_ITERATIONS: u32 = 100_000;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+
+/// Whether `data` is a file written by `encrypt`.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts `data` with `password`.
+pub fn encrypt(data: &[u8], password: &str) -> Result<Vec<u8>, &'static str> {
+    encrypt_with(data, password, ITERATIONS)
+}
+
+/// Encrypts `data` with a key derived from `password` in `iterations`.
+fn encrypt_with(data: &[u8], password: &str, iterations: u32) -> Result<Vec<u8>, &'static str> {
+    let salt = crypto::random_bytes().map_err(|_| crypto::NO_RANDOM)?;
+    let nonce: [u8; NONCE_LEN] = crypto::random_bytes().map_err(|_| crypto::NO_RANDOM)?
+        [..NONCE_LEN]
+        .try_into()
+        .unwrap_or_default();
+
+    let mut out = Vec::with_capacity(HEADER_LEN + data.len() + TAG_LEN);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&iterations.to_be_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let aes = Aes256::new(&crypto::pbkdf2_hmac_sha256(
+        password.as_bytes(),
+        &salt,
+        iterations,
+    ));
+    let mut ciphertext = data.to_vec();
+    aes.apply_ctr(&nonce, &mut ciphertext);
+    let tag = aes.tag(&nonce, &out, &ciphertext);
+    out.extend_from_slice(&ciphertext);
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+/// Decrypts a file written by `encrypt`.
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, &'static str> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN + TAG_LEN {
+        return Err("Not an encrypted file");
+    }
+    let (header, rest) = data.split_at(HEADER_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let (iterations, rest) = header[MAGIC.len()..].split_at(4);
+    let (salt, nonce) = rest.split_at(SALT_LEN);
+    let iterations = u32::from_be_bytes(iterations.try_into().unwrap_or_default());
+    if iterations < MIN_ITERATIONS {
+        return Err("Not an encrypted file");
+    }
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().unwrap_or_default();
+
+    let aes = Aes256::new(&crypto::pbkdf2_hmac_sha256(
+        password.as_bytes(),
+        salt,
+        iterations,
+    ));
+    if !crypto::constant_time_eq(&aes.tag(&nonce, header, ciphertext), tag) {
+        return Err("Wrong password, or the file is damaged");
+    }
+    let mut plaintext = ciphertext.to_vec();
+    aes.apply_ctr(&nonce, &mut plaintext);
+    Ok(plaintext)
+}
+
+const SBOX: [u8; 256] = sbox();
+
+/// The AES S-box, built from the multiplicative inverses in GF(2^8) and the
+/// affine transform.
+const fn sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    // `p` runs through every non-zero element as powers of 3, `q` through
+    // their inverses.
+    let (mut p, mut q) = (1u8, 1u8);
+    loop {
+        p ^= (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        sbox[p as usize] =
+            q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4) ^ 0x63;
+        if p == 1 {
+            break;
+        }
+    }
+    sbox[0] = 0x63;
+    sbox
+}
+
+/// Multiplies by x in GF(2^8).
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// AES-256 with its round keys expanded.
+struct Aes256 {
+    round_keys: [[u8; 16]; 15],
+}
+
+impl Aes256 {
+    fn new(key: &[u8; 32]) -> Self {
+        let mut words = [[0u8; 4]; 60];
+        for (word, chunk) in words.iter_mut().zip(key.chunks(4)) {
+            word.copy_from_slice(chunk);
+        }
+        let mut rcon = 1u8;
+        for i in 8..60 {
+            let mut temp = words[i - 1];
+            if i % 8 == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if i % 8 == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for (j, b) in temp.iter().enumerate() {
+                words[i][j] = words[i - 8][j] ^ b;
+            }
+        }
+        let mut round_keys = [[0u8; 16]; 15];
+        for (round_key, words) in round_keys.iter_mut().zip(words.chunks(4)) {
+            for (chunk, word) in round_key.chunks_mut(4).zip(words) {
+                chunk.copy_from_slice(word);
+            }
+        }
+        Self { round_keys }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let add_round_key = |block: &mut [u8; 16], key: &[u8; 16]| {
+            for (b, k) in block.iter_mut().zip(key) {
+                *b ^= k;
+            }
+        };
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..15 {
+            // SubBytes and ShiftRows: byte `r` of column `c` comes from
+            // column `c + r`.
+            let state = *block;
+            for c in 0..4 {
+                for r in 0..4 {
+                    block[4 * c + r] = SBOX[state[4 * ((c + r) % 4) + r] as usize];
+                }
+            }
+            if round < 14 {
+                for column in block.chunks_mut(4) {
+                    let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+                    let all = a0 ^ a1 ^ a2 ^ a3;
+                    column[0] ^= all ^ xtime(a0 ^ a1);
+                    column[1] ^= all ^ xtime(a1 ^ a2);
+                    column[2] ^= all ^ xtime(a2 ^ a3);
+                    column[3] ^= all ^ xtime(a3 ^ a0);
+                }
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    /// The counter block `counter` for `nonce`.
+    fn counter_block(nonce: &[u8; NONCE_LEN], counter: u32) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[..NONCE_LEN].copy_from_slice(nonce);
+        block[NONCE_LEN..].copy_from_slice(&counter.to_be_bytes());
+        block
+    }
+
+    /// Encrypts or decrypts `data` in counter mode, starting at counter 2
+    /// as GCM does.
+    fn apply_ctr(&self, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+        for (counter, chunk) in (2u32..).zip(data.chunks_mut(16)) {
+            let mut keystream = Self::counter_block(nonce, counter);
+            self.encrypt_block(&mut keystream);
+            for (b, k) in chunk.iter_mut().zip(keystream) {
+                *b ^= k;
+            }
+        }
+    }
+
+    /// The GCM tag of `ciphertext` and the additional data `aad`.
+    fn tag(&self, nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+        let mut h = [0u8; 16];
+        self.encrypt_block(&mut h);
+        let h = u128::from_be_bytes(h);
+
+        let mut x = 0u128;
+        for data in [aad, ciphertext] {
+            for chunk in data.chunks(16) {
+                let mut block = [0u8; 16];
+                block[..chunk.len()].copy_from_slice(chunk);
+                x = gf_mul(x ^ u128::from_be_bytes(block), h);
+            }
+        }
+        let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+        x = gf_mul(x ^ lengths, h);
+
+        let mut tag = Self::counter_block(nonce, 1);
+        self.encrypt_block(&mut tag);
+        (u128::from_be_bytes(tag) ^ x).to_be_bytes()
+    }
+}
+
+/// Multiplies in GF(2^128) with GCM's bit order.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let (mut z, mut v) = (0u128, y);
+    for i in (0..128).rev() {
+        if (x >> i) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Encrypts with `key` as AES-256-GCM does, returning the ciphertext
+    /// and the tag.
+    fn gcm(key: &str, nonce: &str, aad: &str, plaintext: &str) -> (Vec<u8>, [u8; 16]) {
+        let aes = Aes256::new(&hex(key).try_into().unwrap());
+        let nonce: [u8; NONCE_LEN] = hex(nonce).try_into().unwrap();
+        let mut ciphertext = hex(plaintext);
+        aes.apply_ctr(&nonce, &mut ciphertext);
+        let tag = aes.tag(&nonce, &hex(aad), &ciphertext);
+        (ciphertext, tag)
+    }
+
+    #[test]
+    fn aes_256_matches_fips_197() {
+        let aes = Aes256::new(
+            &hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+                .try_into()
+                .unwrap(),
+        );
+        let mut block: [u8; 16] = hex("00112233445566778899aabbccddeeff").try_into().unwrap();
+        aes.encrypt_block(&mut block);
+        assert_eq!(block.to_vec(), hex("8ea2b7ca516745bfeafc49904b496089"));
+    }
+
+    // Test cases 13 to 16 of the GCM specification that SP 800-38D is
+    // based on, the ones with 256-bit keys and 96-bit IVs.
+    #[test]
+    fn gcm_matches_the_known_answers() {
+        let zero_key = "0000000000000000000000000000000000000000000000000000000000000000";
+        let zero_nonce = "000000000000000000000000";
+        let (ciphertext, tag) = gcm(zero_key, zero_nonce, "", "");
+        assert!(ciphertext.is_empty());
+        assert_eq!(tag.to_vec(), hex("530f8afbc74536b9a963b4f1c4cb738b"));
+
+        let (ciphertext, tag) = gcm(zero_key, zero_nonce, "", "00000000000000000000000000000000");
+        assert_eq!(ciphertext, hex("cea7403d4d606b6e074ec5d3baf39d18"));
+        assert_eq!(tag.to_vec(), hex("d0d1c8a799996bf0265b98b5d48ab919"));
+
+        let key = "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308";
+        let nonce = "cafebabefacedbaddecaf888";
+        let plaintext = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                         1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+        let expected = "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+                        8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad";
+        let (ciphertext, tag) = gcm(key, nonce, "", plaintext);
+        assert_eq!(ciphertext, hex(expected));
+        assert_eq!(tag.to_vec(), hex("b094dac5d93471bdec1a502270e3cc6c"));
+
+        let aad = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+        let (ciphertext, tag) = gcm(key, nonce, aad, &plaintext[..120]);
+        assert_eq!(ciphertext, hex(&expected[..120]));
+        assert_eq!(tag.to_vec(), hex("76fc6ece0f4e1768cddf8853bb2d551b"));
+    }
+
+    #[test]
+    fn decrypts_what_it_encrypted() {
+        let data = "Привіт, archive".as_bytes();
+        let encrypted = encrypt_with(data, "hunter2", MIN_ITERATIONS).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.len(), HEADER_LEN + data.len() + TAG_LEN);
+        assert_eq!(decrypt(&encrypted, "hunter2").unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_a_wrong_password_or_any_changed_byte() {
+        let encrypted = encrypt_with(b"tasks", "hunter2", MIN_ITERATIONS).unwrap();
+        assert!(decrypt(&encrypted, "hunter3").is_err());
+
+        let mut ciphertext_flipped = encrypted.clone();
+        ciphertext_flipped[HEADER_LEN] ^= 1;
+        assert!(decrypt(&ciphertext_flipped, "hunter2").is_err());
+
+        // The salt is part of the authenticated header.
+        let mut header_flipped = encrypted.clone();
+        header_flipped[MAGIC.len() + 4] ^= 1;
+        assert!(decrypt(&header_flipped, "hunter2").is_err());
+
+        let mut tag_flipped = encrypted;
+        *tag_flipped.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tag_flipped, "hunter2").is_err());
+    }
+
+    #[test]
+    fn rejects_too_few_iterations() {
+        let encrypted = encrypt_with(b"tasks", "hunter2", MIN_ITERATIONS - 1).unwrap();
+        assert_eq!(decrypt(&encrypted, "hunter2"), Err("Not an encrypted file"));
+    }
+}
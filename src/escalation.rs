@@ -0,0 +1,49 @@
+//! Priority aging: with `escalate_after_days` set in the config, open tasks
+//! overdue by more than that many days have their priority raised a level
+//! when the app starts, and again each time as many more days pass.
+
+use crate::log;
+use crate::{TaskEvent, TodoApp};
+use chrono::{Duration, Utc};
+
+/// Shown in the audit log as the user making escalations.
+const ESCALATION_USER: &str = "system";
+
+impl TodoApp {
+    /// Raises the priority of every open task overdue by more than `days`
+    /// days and not escalated in the last `days` days. Returns the IDs of
+    /// the escalated tasks.
+    pub fn escalate_overdue(&mut self, days: u32) -> Result<Vec<u32>, &'static str> {
+        let now = Utc::now();
+        let period = Duration::days(i64::from(days));
+        let mut escalated = Vec::new();
+        for task in self.tasks.values_mut() {
+            let overdue = task.due_date.is_some_and(|due| due + period < now);
+            let due_again = task.escalated_at.is_none_or(|at| at + period <= now);
+            let raised = task.priority.raised();
+            if task.completed || !overdue || !due_again || raised == task.priority {
+                continue;
+            }
+            log::info(&format!(
+                "Escalated task #{} from {} to {}",
+                task.id,
+                task.priority.name(),
+                raised.name()
+            ));
+            task.priority = raised;
+            task.escalated_at = Some(now);
+            task.touch();
+            escalated.push(task.id);
+        }
+        if escalated.is_empty() {
+            return Ok(escalated);
+        }
+
+        self.save_tasks(&format!("Escalate {} overdue tasks", escalated.len()))
+            .map_err(|_| "Failed to save tasks")?;
+        for task_id in &escalated {
+            self.emit_by(ESCALATION_USER, TaskEvent::Escalated, &self.tasks[task_id]);
+        }
+        Ok(escalated)
+    }
+}
@@ -0,0 +1,60 @@
+//! Tasks that expire: one with `expires_at` set is cancelled if it isn't
+//! completed by then, e.g. an offer that runs out. Expired tasks are
+//! cancelled when the app starts and by the reminder daemon.
+
+use crate::log;
+use crate::{TaskEvent, TodoApp};
+use chrono::{DateTime, Utc};
+
+/// Shown in the audit log as the user cancelling expired tasks.
+const EXPIRY_USER: &str = "system";
+
+impl TodoApp {
+    /// Sets when a task expires, or makes it not expire with `None`.
+    pub fn set_expiry(
+        &mut self,
+        task_id: u32,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if task.completed {
+            return Err("Task is already closed");
+        }
+
+        task.expires_at = expires_at;
+        task.touch();
+        self.save_tasks(&format!("Set expiry of task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Cancels every open task of any user that expired by `now`. Returns
+    /// the IDs of the cancelled tasks.
+    pub fn cancel_expired(&mut self, now: DateTime<Utc>) -> Result<Vec<u32>, &'static str> {
+        let mut cancelled = Vec::new();
+        for task in self.tasks.values_mut() {
+            if task.completed || task.expires_at.is_none_or(|at| at > now) {
+                continue;
+            }
+            log::info(&format!("Cancelled expired task #{}", task.id));
+            task.completed = true;
+            task.cancelled_at = Some(now);
+            task.touch();
+            cancelled.push(task.id);
+        }
+        if cancelled.is_empty() {
+            return Ok(cancelled);
+        }
+
+        self.save_tasks(&format!("Cancel {} expired tasks", cancelled.len()))
+            .map_err(|_| "Failed to save tasks")?;
+        for task_id in &cancelled {
+            self.emit_by(EXPIRY_USER, TaskEvent::Cancelled, &self.tasks[task_id]);
+        }
+        Ok(cancelled)
+    }
+}
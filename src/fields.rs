@@ -0,0 +1,52 @@
+//! User-defined custom fields on tasks, such as `client` or `ticket`.
+
+use crate::{Task, TodoApp};
+
+impl TodoApp {
+    /// Sets a custom field on a task, replacing any previous value.
+    pub fn set_custom_field(
+        &mut self,
+        task_id: u32,
+        name: &str,
+        value: &str,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if name.is_empty() {
+            return Err("Field name cannot be empty");
+        }
+
+        task.custom.insert(name.to_string(), value.to_string());
+        task.touch();
+        self.save_tasks(&format!("Set field {} on task #{}", name, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Removes a custom field from a task.
+    pub fn unset_custom_field(&mut self, task_id: u32, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.custom.remove(name).ok_or("Task has no such field")?;
+        task.touch();
+        self.save_tasks(&format!("Unset field {} on task #{}", name, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Lists the current user's tasks whose custom field `name` equals `value`.
+    pub fn tasks_with_field(&self, name: &str, value: &str) -> Result<Vec<&Task>, &'static str> {
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| task.custom.get(name).is_some_and(|v| v == value))
+            .collect())
+    }
+}
@@ -0,0 +1,92 @@
+//! Saved filters: named searches such as `urgent-work` for
+//! `tag:work AND priority>=high AND pending`, kept in each user's settings
+//! and listed with `filter <name>` or from the menu. Filters are written in
+//! the query language of `query`.
+
+use crate::query::Query;
+use crate::{Task, TodoApp};
+
+/// Most filters a user can save.
+const MAX_FILTERS: usize = 20;
+
+/// Checks a filter name: letters, digits, `-` and `_`.
+fn validate_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Invalid filter name, use letters, digits, - and _");
+    }
+    Ok(())
+}
+
+impl TodoApp {
+    /// The current user's saved filters by name, with their expressions.
+    pub fn saved_filters(&self) -> Vec<(String, String)> {
+        self.settings()
+            .map(|settings| settings.filters.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Saves a filter for the current user, replacing one with the same name.
+    pub fn save_filter(&mut self, name: &str, expression: &str) -> Result<(), String> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        validate_name(name)?;
+        Query::parse(expression)?;
+        let settings = self.settings.entry(user_id.clone()).or_default();
+        if !settings.filters.contains_key(name) && settings.filters.len() >= MAX_FILTERS {
+            return Err("Too many saved filters, delete one first".to_string());
+        }
+        settings
+            .filters
+            .insert(name.to_string(), expression.trim().to_string());
+        self.save_settings(&format!("Save filter {} of user {}", name, user_id))
+            .map_err(|_| "Failed to save settings")?;
+        Ok(())
+    }
+
+    /// Deletes one of the current user's saved filters.
+    pub fn delete_filter(&mut self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let removed = self
+            .settings
+            .get_mut(&user_id)
+            .and_then(|settings| settings.filters.remove(name));
+        if removed.is_none() {
+            return Err("Filter not found");
+        }
+        self.save_settings(&format!("Delete filter {} of user {}", name, user_id))
+            .map_err(|_| "Failed to save settings")?;
+        Ok(())
+    }
+
+    /// The tasks of the current list that match `query`.
+    pub fn query_tasks(&self, query: &str) -> Result<Vec<&Task>, String> {
+        let query = Query::parse(query)?;
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| query.matches(task))
+            .collect())
+    }
+
+    /// The tasks of the current list that match the saved filter `name`.
+    pub fn filtered_tasks(&self, name: &str) -> Result<Vec<&Task>, String> {
+        let expression = self
+            .settings()?
+            .filters
+            .remove(name)
+            .ok_or("Filter not found")?;
+        self.query_tasks(&expression)
+    }
+}
+
+/// The saved filter picked by a menu shortcut like `f2`, if `choice` is one.
+pub fn shortcut(app: &TodoApp, choice: &str) -> Option<String> {
+    let number: usize = choice.strip_prefix('f')?.parse().ok()?;
+    app.saved_filters()
+        .into_iter()
+        .nth(number.checked_sub(1)?)
+        .map(|(name, _)| name)
+}
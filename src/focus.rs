@@ -0,0 +1,91 @@
+//! The focus list: a few tasks the user picks to work on today, whatever
+//! their due dates. The list belongs to the day it was made and is empty
+//! again after local midnight.
+
+use crate::schedule::local_date;
+use crate::{Task, TodoApp};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Most tasks the focus list can hold, to keep it short.
+pub const MAX_FOCUS: usize = 5;
+
+/// Tasks picked for a day, stored with the user's settings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Focus {
+    /// Day the list was made for.
+    pub day: Option<NaiveDate>,
+    /// UUIDs of the tasks, in the order they were picked.
+    pub tasks: Vec<String>,
+}
+
+impl TodoApp {
+    /// The tasks in today's focus list, in the order they were picked.
+    pub fn focus_tasks(&self) -> Result<Vec<&Task>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let focus = self.settings()?.focus;
+        if focus.day != Some(local_date(Utc::now())) {
+            return Ok(Vec::new());
+        }
+        Ok(focus
+            .tasks
+            .iter()
+            .filter_map(|uuid| self.tasks.values().find(|task| task.uuid == *uuid))
+            .filter(|task| self.workspaces.can_access(task, user_id))
+            .collect())
+    }
+
+    /// Adds a task to today's focus list, starting a new list if the last
+    /// one was for an earlier day.
+    pub fn add_focus(&mut self, task_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_access(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        let uuid = task.uuid.clone();
+        let today = local_date(Utc::now());
+        let focus = &mut self.settings.entry(user_id.clone()).or_default().focus;
+        if focus.day != Some(today) {
+            *focus = Focus {
+                day: Some(today),
+                tasks: Vec::new(),
+            };
+        }
+        if focus.tasks.contains(&uuid) {
+            return Err("The task is already in focus");
+        }
+        if focus.tasks.len() >= MAX_FOCUS {
+            return Err("The focus list is full");
+        }
+        focus.tasks.push(uuid);
+        self.save_settings(&format!("Focus on task #{} for user {}", task_id, user_id))
+            .map_err(|_| "Failed to save settings")
+    }
+
+    /// Takes a task off today's focus list.
+    pub fn remove_focus(&mut self, task_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let uuid = self
+            .tasks
+            .get(&task_id)
+            .ok_or("Task not found")?
+            .uuid
+            .clone();
+        let focus = &mut self.settings.entry(user_id.clone()).or_default().focus;
+        if focus.day != Some(local_date(Utc::now())) || !focus.tasks.contains(&uuid) {
+            return Err("The task isn't in focus");
+        }
+        focus.tasks.retain(|focused| *focused != uuid);
+        self.save_settings(&format!("Unfocus task #{} for user {}", task_id, user_id))
+            .map_err(|_| "Failed to save settings")
+    }
+
+    /// Empties today's focus list.
+    pub fn clear_focus(&mut self) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        self.settings.entry(user_id.clone()).or_default().focus = Focus::default();
+        self.save_settings(&format!("Clear focus of user {}", user_id))
+            .map_err(|_| "Failed to save settings")
+    }
+}
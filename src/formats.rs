@@ -0,0 +1,68 @@
+//! Formats the file storage backend can keep data files in.
+//!
+//! The app always works with JSON; other formats are converted on the way
+//! to and from disk. A data file keeps the logical name `tasks.json` and is
+//! stored as `tasks.yaml` or `tasks.toml` in the other formats.
+
+use crate::toml;
+use crate::yaml;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl DataFormat {
+    pub const ALL: [Self; 3] = [Self::Json, Self::Yaml, Self::Toml];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Where the data file `name` (e.g. `tasks.json`) is stored in this
+    /// format. Other files, such as backups, keep their name.
+    pub fn path(self, name: &str) -> String {
+        match name.strip_suffix(".json") {
+            Some(stem) => format!("{}.{}", stem, self.extension()),
+            None => name.to_string(),
+        }
+    }
+
+    /// Converts JSON text to this format.
+    pub fn encode(self, json: &str) -> io::Result<String> {
+        match self {
+            Self::Json => Ok(json.to_string()),
+            Self::Yaml => Ok(yaml::encode(&serde_json::from_str(json)?)),
+            Self::Toml => toml::encode(&serde_json::from_str(json)?),
+        }
+    }
+
+    /// Converts text in this format to JSON.
+    pub fn decode(self, text: &str) -> io::Result<String> {
+        let value = match self {
+            Self::Json => return Ok(text.to_string()),
+            Self::Yaml => yaml::decode(text)?,
+            Self::Toml => toml::decode(text)?,
+        };
+        Ok(serde_json::to_string(&value)?)
+    }
+}
@@ -0,0 +1,295 @@
+//! Checking and repairing damaged data files (`Lab3 fsck`).
+//!
+//! A damaged file is restored from its backup when the backup is readable;
+//! otherwise every entry that can still be parsed is salvaged.
+
+use crate::defaults::{UserDefaults, DEFAULTS_FILE};
+use crate::groups::{Group, GROUPS_FILE};
+use crate::habits::{Habit, HABITS_FILE};
+use crate::i18n::{tr, trf};
+use crate::log;
+use crate::settings::{UserSettings, SETTINGS_FILE};
+use crate::shares::{Share, SHARES_FILE};
+use crate::shutdown;
+use crate::snapshots::{Snapshot, SNAPSHOTS_FILE};
+use crate::storage::{self, Storage};
+use crate::tokens::{Token, TOKENS_FILE};
+use crate::trash::{Trashed, TRASH_FILE};
+use crate::usernames;
+use crate::workspaces::{Workspace, WORKSPACES_FILE};
+use crate::{confirm, Task, User, TASKS_FILE, USERS_FILE};
+use chrono::NaiveDate;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+
+/// Names of the data files checked.
+pub const DATA_FILES: [&str; 11] = [
+    TASKS_FILE,
+    USERS_FILE,
+    SETTINGS_FILE,
+    TOKENS_FILE,
+    WORKSPACES_FILE,
+    GROUPS_FILE,
+    HABITS_FILE,
+    SNAPSHOTS_FILE,
+    DEFAULTS_FILE,
+    SHARES_FILE,
+    TRASH_FILE,
+];
+
+/// Checks every data file, offering to repair the damaged ones. Returns
+/// whether all files are readable afterwards. Nothing is written in dry-run mode.
+pub fn run(storage: &dyn Storage, dry_run: bool) -> io::Result<bool> {
+    let tasks = check::<Task>(storage, TASKS_FILE, dry_run)?;
+    let users = check::<User>(storage, USERS_FILE, dry_run)?;
+    let settings = check::<UserSettings>(storage, SETTINGS_FILE, dry_run)?;
+    let tokens = check::<Token>(storage, TOKENS_FILE, dry_run)?;
+    let workspaces = check::<Workspace>(storage, WORKSPACES_FILE, dry_run)?;
+    let groups = check::<Group>(storage, GROUPS_FILE, dry_run)?;
+    let habits = check::<BTreeMap<String, Habit>>(storage, HABITS_FILE, dry_run)?;
+    let snapshots = check::<BTreeMap<NaiveDate, Snapshot>>(storage, SNAPSHOTS_FILE, dry_run)?;
+    let defaults = check::<UserDefaults>(storage, DEFAULTS_FILE, dry_run)?;
+    let shares = check::<Share>(storage, SHARES_FILE, dry_run)?;
+    let trash = check::<Trashed>(storage, TRASH_FILE, dry_run)?;
+    if users {
+        flag_duplicate_usernames(storage)?;
+    }
+    Ok(tasks
+        && users
+        && settings
+        && tokens
+        && workspaces
+        && groups
+        && habits
+        && snapshots
+        && defaults
+        && shares
+        && trash)
+}
+
+/// The data files that can't be read, with what's wrong with each. Nothing
+/// is repaired.
+pub fn damaged(storage: &dyn Storage) -> Vec<(&'static str, String)> {
+    [
+        damage::<Task>(storage, TASKS_FILE),
+        damage::<User>(storage, USERS_FILE),
+        damage::<UserSettings>(storage, SETTINGS_FILE),
+        damage::<Token>(storage, TOKENS_FILE),
+        damage::<Workspace>(storage, WORKSPACES_FILE),
+        damage::<Group>(storage, GROUPS_FILE),
+        damage::<BTreeMap<String, Habit>>(storage, HABITS_FILE),
+        damage::<BTreeMap<NaiveDate, Snapshot>>(storage, SNAPSHOTS_FILE),
+        damage::<UserDefaults>(storage, DEFAULTS_FILE),
+        damage::<Share>(storage, SHARES_FILE),
+        damage::<Trashed>(storage, TRASH_FILE),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// What's wrong with one data file, a JSON object of `T` values, if anything.
+fn damage<T: DeserializeOwned>(
+    storage: &dyn Storage,
+    name: &'static str,
+) -> Option<(&'static str, String)> {
+    let error = match storage.read(name) {
+        Ok(None) => return None,
+        Ok(Some(contents)) => serde_json::from_str::<HashMap<String, T>>(&contents)
+            .err()?
+            .to_string(),
+        Err(e) => e.to_string(),
+    };
+    Some((name, error))
+}
+
+/// Points out accounts whose usernames only differ in case or in how their
+/// accents are encoded. They were registered before usernames were
+/// normalized; each can still log in with its exact name, but not with
+/// another spelling, so they should be merged or renamed.
+fn flag_duplicate_usernames(storage: &dyn Storage) -> io::Result<()> {
+    let users: BTreeMap<String, User> =
+        storage::load_json(storage, USERS_FILE)?.unwrap_or_default();
+    for group in usernames::duplicates(users.keys().map(String::as_str)) {
+        let names = group.join(", ");
+        log::warn(&format!(
+            "Usernames differing only in case or accents: {}",
+            names
+        ));
+        println!(
+            "{}",
+            trf(
+                "{}: accounts that differ only in case or accents: {}",
+                &[&USERS_FILE, &names]
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Checks one data file, a JSON object of `T` values, and repairs it if needed.
+fn check<T: DeserializeOwned + Serialize>(
+    storage: &dyn Storage,
+    name: &str,
+    dry_run: bool,
+) -> io::Result<bool> {
+    let Some(contents) = storage.read(name)? else {
+        return Ok(true);
+    };
+    let error = match serde_json::from_str::<HashMap<String, T>>(&contents) {
+        Ok(_) => {
+            println!("{}", trf("{}: OK", &[&name]));
+            return Ok(true);
+        }
+        Err(error) => error,
+    };
+    log::error(&format!("{} is damaged: {}", name, error));
+    println!(
+        "{}",
+        trf(
+            "{}: damaged at line {}, column {}",
+            &[&name, &error.line(), &error.column()]
+        )
+    );
+
+    if let Some(backup) = storage.backup(name)? {
+        if let Ok(entries) = serde_json::from_str::<HashMap<String, T>>(&backup) {
+            let prompt = trf(
+                "Restore {} from the backup with {} entries?",
+                &[&name, &entries.len()],
+            );
+            if confirm(&prompt)? {
+                return repair(storage, name, &contents, &backup, dry_run);
+            }
+        }
+    }
+
+    let (entries, skipped) = salvage::<T>(&contents);
+    println!(
+        "{}",
+        trf(
+            "Recovered {} entries, {} could not be read.",
+            &[&entries.len(), &skipped]
+        )
+    );
+    if !confirm(tr("Save the recovered entries?"))? {
+        println!("{}", tr("Cancelled"));
+        return Ok(false);
+    }
+    let entries: BTreeMap<String, T> = entries.into_iter().collect();
+    let json = storage::to_json(&entries)?;
+    repair(storage, name, &contents, &json, dry_run)
+}
+
+/// Replaces a damaged file, keeping the damaged contents next to it as
+/// `<name>.damaged` for manual inspection.
+fn repair(
+    storage: &dyn Storage,
+    name: &str,
+    damaged: &str,
+    contents: &str,
+    dry_run: bool,
+) -> io::Result<bool> {
+    if dry_run {
+        println!("{}", tr("Dry run: nothing was changed"));
+        return Ok(false);
+    }
+    let damaged_copy = format!("{}.damaged", name);
+    fs::write(&damaged_copy, damaged)?;
+    let writing = shutdown::hold();
+    storage.write(name, contents, &format!("Repair {}", name))?;
+    drop(writing);
+    log::info(&format!(
+        "Repaired {}, damaged copy kept as {}",
+        name, damaged_copy
+    ));
+    println!(
+        "{}",
+        trf(
+            "{} repaired. The damaged file was kept as {}.",
+            &[&name, &damaged_copy]
+        )
+    );
+    Ok(true)
+}
+
+/// Leniently parses a JSON object, keeping every `"key": {...}` entry that
+/// is still valid and skipping the rest. Returns the entries and the number
+/// of entries skipped. Stops at the first entry that is cut off.
+fn salvage<T: DeserializeOwned>(contents: &str) -> (Vec<(String, T)>, usize) {
+    let bytes = contents.as_bytes();
+    let mut entries: Vec<(String, T)> = Vec::new();
+    let mut skipped = 0;
+    let mut pos = contents.find('{').map_or(0, |start| start + 1);
+
+    while let Some(offset) = contents[pos..].find('"') {
+        let key_start = pos + offset;
+        let Some(key_end) = string_end(bytes, key_start) else {
+            break;
+        };
+        let after_key = contents[key_end..].trim_start();
+        let Some(value) = after_key.strip_prefix(':').map(str::trim_start) else {
+            // Not a key; resynchronize on the next quote.
+            pos = key_start + 1;
+            continue;
+        };
+        if !value.starts_with('{') {
+            pos = key_end;
+            continue;
+        }
+        let value_start = contents.len() - value.len();
+        let Some(value_end) = object_end(bytes, value_start) else {
+            skipped += 1;
+            break;
+        };
+        let key = &contents[key_start + 1..key_end - 1];
+        match serde_json::from_str::<T>(&contents[value_start..value_end]) {
+            Ok(entry) if !entries.iter().any(|(k, _)| k == key) => {
+                entries.push((key.to_string(), entry));
+            }
+            _ => skipped += 1,
+        }
+        pos = value_end;
+    }
+    (entries, skipped)
+}
+
+/// Position just past the string starting with the quote at `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Position just past the object starting with the brace at `start`.
+fn object_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i = string_end(bytes, i)?;
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
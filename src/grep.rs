@@ -0,0 +1,472 @@
+//! Searching task titles and descriptions with regular expressions.
+//!
+//! Patterns support the common subset of regular expression syntax:
+//! literals, `.`, character classes such as `[a-z]` and `[^0-9]`, the
+//! escapes `\d`, `\w` and `\s` (and their negations), anchors `^` and `$`,
+//! groups, alternation with `|`, and the repetitions `*`, `+`, `?` and
+//! `{n}`, `{n,}`, `{n,m}`.
+
+use crate::{Task, TodoApp};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    /// Alternatives, each a sequence.
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// A compiled regular expression.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    /// The whole pattern, as a group of its alternatives.
+    root: Node,
+    ignore_case: bool,
+}
+
+impl Pattern {
+    pub fn new(source: &str, ignore_case: bool) -> Result<Self, &'static str> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err("Unmatched ) in pattern");
+        }
+        Ok(Self {
+            root: Node::Group(alternatives),
+            ignore_case,
+        })
+    }
+
+    /// Byte ranges of the non-overlapping matches in `text`, from the left.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain([text.len()])
+            .collect();
+        let mut found = Vec::new();
+        let mut start = 0;
+        while start <= chars.len() {
+            match self.match_at(&chars, start) {
+                Some(end) => {
+                    if end > start {
+                        found.push(offsets[start]..offsets[end]);
+                    }
+                    start = end.max(start + 1);
+                }
+                None => start += 1,
+            }
+        }
+        found
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        (0..=chars.len()).any(|start| self.match_at(&chars, start).is_some())
+    }
+
+    /// The end of the longest-preferred match starting at `start`.
+    fn match_at(&self, text: &[char], start: usize) -> Option<usize> {
+        let mut end = None;
+        self.match_here(std::slice::from_ref(&self.root), text, start, &mut |pos| {
+            end = Some(pos);
+            true
+        });
+        end
+    }
+
+    /// Matches `nodes` at `pos`, calling `then` with where each way of
+    /// matching them ends until it accepts one.
+    fn match_here(
+        &self,
+        nodes: &[Node],
+        text: &[char],
+        pos: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        let Some((node, rest)) = nodes.split_first() else {
+            return then(pos);
+        };
+        match node {
+            Node::Group(alternatives) => alternatives.iter().any(|alternative| {
+                self.match_here(alternative, text, pos, &mut |end| {
+                    self.match_here(rest, text, end, then)
+                })
+            }),
+            Node::Repeat { node, min, max } => {
+                self.repeat(node, *min, *max, rest, text, pos, 0, then)
+            }
+            Node::Start => pos == 0 && self.match_here(rest, text, pos, then),
+            Node::End => pos == text.len() && self.match_here(rest, text, pos, then),
+            _ => {
+                pos < text.len()
+                    && self.matches_char(node, text[pos])
+                    && self.match_here(rest, text, pos + 1, then)
+            }
+        }
+    }
+
+    /// Matches `node` as many times as it can, giving back repetitions
+    /// until `rest` matches too.
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        rest: &[Node],
+        text: &[char],
+        pos: usize,
+        count: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        if max.is_none_or(|max| count < max) {
+            let once = std::slice::from_ref(node);
+            // Repetitions that match nothing would go on forever.
+            let more = self.match_here(once, text, pos, &mut |next| {
+                (next != pos || count < min)
+                    && self.repeat(node, min, max, rest, text, next, count + 1, then)
+            });
+            if more {
+                return true;
+            }
+        }
+        count >= min && self.match_here(rest, text, pos, then)
+    }
+
+    fn matches_char(&self, node: &Node, c: char) -> bool {
+        let variants = if self.ignore_case {
+            let mut variants: Vec<char> = c.to_lowercase().chain(c.to_uppercase()).collect();
+            variants.push(c);
+            variants
+        } else {
+            vec![c]
+        };
+        match node {
+            Node::Char(expected) => variants.contains(expected),
+            Node::Any => c != '\n',
+            Node::Class { ranges, negated } => {
+                let inside = variants
+                    .iter()
+                    .any(|c| ranges.iter().any(|(low, high)| (low..=high).contains(&c)));
+                inside != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Sequences separated by `|`, up to a `)` or the end.
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, &'static str> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, &'static str> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            let node = match c {
+                '|' | ')' => break,
+                '*' | '+' | '?' => {
+                    self.pos += 1;
+                    let (min, max) = match c {
+                        '*' => (0, None),
+                        '+' => (1, None),
+                        _ => (0, Some(1)),
+                    };
+                    repeat(&mut nodes, min, max)?;
+                    continue;
+                }
+                '{' if self.counts_ahead() => {
+                    let (min, max) = self.counts()?;
+                    repeat(&mut nodes, min, max)?;
+                    continue;
+                }
+                '(' => {
+                    self.pos += 1;
+                    let alternatives = self.alternatives()?;
+                    if self.next() != Some(')') {
+                        return Err("Unclosed ( in pattern");
+                    }
+                    Node::Group(alternatives)
+                }
+                '[' => {
+                    self.pos += 1;
+                    self.class()?
+                }
+                '.' => {
+                    self.pos += 1;
+                    Node::Any
+                }
+                '^' => {
+                    self.pos += 1;
+                    Node::Start
+                }
+                '$' => {
+                    self.pos += 1;
+                    Node::End
+                }
+                '\\' => {
+                    self.pos += 1;
+                    self.escape()?
+                }
+                _ => {
+                    self.pos += 1;
+                    Node::Char(c)
+                }
+            };
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    /// Whether a `{` starts counted repetition rather than being literal.
+    fn counts_ahead(&self) -> bool {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        rest.split_once('}').is_some_and(|(counts, _)| {
+            !counts.is_empty() && counts.chars().all(|c| c.is_ascii_digit() || c == ',')
+        })
+    }
+
+    fn counts(&mut self) -> Result<(usize, Option<usize>), &'static str> {
+        self.pos += 1;
+        let mut counts = String::new();
+        while let Some(c) = self.next() {
+            if c == '}' {
+                break;
+            }
+            counts.push(c);
+        }
+        let number = |text: &str| {
+            text.parse::<usize>()
+                .map_err(|_| "Invalid repetition count")
+        };
+        let (min, max) = match counts.split_once(',') {
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+            None => (number(&counts)?, Some(number(&counts)?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err("Invalid repetition count");
+        }
+        Ok((min, max))
+    }
+
+    /// A character class, after its `[`.
+    fn class(&mut self) -> Result<Node, &'static str> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("Unclosed [ in pattern")?;
+            match c {
+                ']' if !first => break,
+                '\\' => match self.escape()? {
+                    Node::Char(c) => ranges.push((c, c)),
+                    Node::Class {
+                        ranges: escaped,
+                        negated: false,
+                    } => ranges.extend(escaped),
+                    _ => return Err("Negated escapes can't be used in [ ]"),
+                },
+                _ => {
+                    let ends_range = self.chars.get(self.pos + 1).is_some_and(|c| *c != ']');
+                    if self.peek() == Some('-') && ends_range {
+                        self.pos += 1;
+                        let high = self.next().ok_or("Unclosed [ in pattern")?;
+                        if high < c {
+                            return Err("Invalid range in [ ]");
+                        }
+                        ranges.push((c, high));
+                    } else {
+                        ranges.push((c, c));
+                    }
+                }
+            }
+            first = false;
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+
+    /// An escape, after its backslash.
+    fn escape(&mut self) -> Result<Node, &'static str> {
+        let c = self.next().ok_or("Pattern ends with \\")?;
+        let class = |ranges: &[(char, char)], negated| Node::Class {
+            ranges: ranges.to_vec(),
+            negated,
+        };
+        const DIGIT: &[(char, char)] = &[('0', '9')];
+        const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+        const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\r')];
+        Ok(match c {
+            'd' => class(DIGIT, false),
+            'D' => class(DIGIT, true),
+            'w' => class(WORD, false),
+            'W' => class(WORD, true),
+            's' => class(SPACE, false),
+            'S' => class(SPACE, true),
+            'n' => Node::Char('\n'),
+            't' => Node::Char('\t'),
+            c => Node::Char(c),
+        })
+    }
+}
+
+/// Makes the last node of `nodes` repeat.
+fn repeat(nodes: &mut Vec<Node>, min: usize, max: Option<usize>) -> Result<(), &'static str> {
+    match nodes.pop() {
+        Some(Node::Start | Node::End | Node::Repeat { .. }) | None => {
+            Err("Nothing to repeat in pattern")
+        }
+        Some(node) => {
+            nodes.push(Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            });
+            Ok(())
+        }
+    }
+}
+
+/// The part of a task a match is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Description,
+}
+
+impl Field {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Description => "description",
+        }
+    }
+}
+
+/// A line of a task that matches.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub task_id: u32,
+    pub field: Field,
+    /// Counted from 1 within the field.
+    pub line_number: usize,
+    pub line: String,
+    /// Byte ranges of the matched text within `line`.
+    pub ranges: Vec<Range<usize>>,
+    /// Lines of the field just before and after this one.
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GrepOptions {
+    /// Lines of context to include around each match.
+    pub context: usize,
+    /// Search completed tasks too.
+    pub include_completed: bool,
+}
+
+/// The matching lines of one field of a task.
+fn grep_field(
+    pattern: &Pattern,
+    task: &Task,
+    field: Field,
+    text: &str,
+    context: usize,
+) -> Vec<Match> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            if !pattern.is_match(line) {
+                return None;
+            }
+            let after_end = (index + 1 + context).min(lines.len());
+            Some(Match {
+                task_id: task.id,
+                field,
+                line_number: index + 1,
+                line: line.to_string(),
+                ranges: pattern.find_all(line),
+                before: lines[index.saturating_sub(context)..index]
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect(),
+                after: lines[index + 1..after_end]
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+impl TodoApp {
+    /// The lines of titles and descriptions in the current list that
+    /// match `pattern`, task by task in list order.
+    pub fn grep(
+        &self,
+        pattern: &Pattern,
+        options: GrepOptions,
+    ) -> Result<Vec<Match>, &'static str> {
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| options.include_completed || !task.completed)
+            .flat_map(|task| {
+                let mut matches =
+                    grep_field(pattern, task, Field::Title, &task.title, options.context);
+                matches.extend(grep_field(
+                    pattern,
+                    task,
+                    Field::Description,
+                    &task.description,
+                    options.context,
+                ));
+                matches
+            })
+            .collect())
+    }
+}
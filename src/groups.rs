@@ -0,0 +1,156 @@
+//! Named groups of users, such as `family` or `team-backend`, that can be
+//! given a permission in a workspace in one step (`workspace grant`). Adding
+//! someone to a group or removing them changes what they may do in every
+//! workspace the group has a permission in. The user who creates a group
+//! manages its members.
+
+use crate::log;
+use crate::storage;
+use crate::TodoApp;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io;
+
+pub const GROUPS_FILE: &str = "groups.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub name: String,
+    /// Created the group and manages its members.
+    pub owner: String,
+    /// Usernames of the members, the owner among them.
+    pub members: BTreeSet<String>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl TodoApp {
+    /// Creates a group owned by the current user, who is its first member.
+    pub fn create_group(&mut self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("Group names can't be empty or contain spaces");
+        }
+        if self.workspaces.groups.contains_key(name) {
+            return Err("Group already exists");
+        }
+
+        self.workspaces.groups.insert(
+            name.to_string(),
+            Group {
+                name: name.to_string(),
+                owner: user_id.clone(),
+                members: BTreeSet::from([user_id.clone()]),
+                created_at: Utc::now(),
+            },
+        );
+        self.save_groups(&format!("Create group {}", name))
+            .map_err(|_| "Failed to save groups")?;
+        log::info(&format!("{} created group {}", user_id, name));
+        Ok(())
+    }
+
+    /// Deletes a group, taking away its permissions in every workspace.
+    pub fn delete_group(&mut self, name: &str) -> Result<(), &'static str> {
+        self.check_group_owner(name)?;
+        let group = self
+            .workspaces
+            .groups
+            .remove(name)
+            .ok_or("Group not found")?;
+        let message = format!("Delete group {}", name);
+        if self.save_groups(&message).is_err() {
+            self.workspaces.groups.insert(name.to_string(), group);
+            return Err("Failed to save groups");
+        }
+        if self.workspaces.revoke_everywhere(name) {
+            self.save_workspaces(&message)
+                .map_err(|_| "Failed to save workspaces")?;
+        }
+        Ok(())
+    }
+
+    /// Adds a user to a group. Only its owner may.
+    pub fn add_to_group(&mut self, name: &str, username: &str) -> Result<(), &'static str> {
+        self.check_group_owner(name)?;
+        let username = self
+            .find_user(username)
+            .ok_or("User not found")?
+            .to_string();
+        let group = self
+            .workspaces
+            .groups
+            .get_mut(name)
+            .ok_or("Group not found")?;
+        if !group.members.insert(username.clone()) {
+            return Err("User is already a member");
+        }
+        self.save_groups(&format!("Add {} to group {}", username, name))
+            .map_err(|_| "Failed to save groups")?;
+        Ok(())
+    }
+
+    /// Removes a user from a group. Only its owner may, and the owner can't
+    /// be removed.
+    pub fn remove_from_group(&mut self, name: &str, username: &str) -> Result<(), &'static str> {
+        self.check_group_owner(name)?;
+        let username = self.find_user(username).unwrap_or(username).to_string();
+        let group = self
+            .workspaces
+            .groups
+            .get_mut(name)
+            .ok_or("Group not found")?;
+        if group.owner == username {
+            return Err("The owner can't be removed");
+        }
+        if !group.members.remove(&username) {
+            return Err("User is not a member");
+        }
+        self.save_groups(&format!("Remove {} from group {}", username, name))
+            .map_err(|_| "Failed to save groups")?;
+        Ok(())
+    }
+
+    /// The groups the current user is a member of, by name.
+    pub fn my_groups(&self) -> Result<Vec<&Group>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        Ok(self
+            .workspaces
+            .groups
+            .values()
+            .filter(|group| group.members.contains(user_id))
+            .collect())
+    }
+
+    fn check_group_owner(&self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        match self.workspaces.groups.get(name) {
+            None => Err("Group not found"),
+            Some(group) if group.owner == *user_id => Ok(()),
+            Some(_) => Err("Only the group's owner can do this"),
+        }
+    }
+
+    /// Saves all groups. `message` describes the change being saved.
+    pub fn save_groups(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(
+            self.storage.as_ref(),
+            GROUPS_FILE,
+            &self.workspaces.groups,
+            message,
+        )
+        .inspect_err(|e| log::error(&format!("Failed to save groups ({}): {}", message, e)))
+    }
+
+    /// Loads all groups. If the file doesn't exist, it's ignored.
+    pub fn load_groups(&mut self) -> io::Result<()> {
+        if let Some(groups) = storage::load_json(self.storage.as_ref(), GROUPS_FILE)? {
+            self.workspaces.groups = groups;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Habits: things to do every day or a few days a week, tracked as a streak
+//! rather than completed once like a task.
+
+use crate::log;
+use crate::schedule;
+use crate::storage;
+use crate::TodoApp;
+use chrono::{serde::ts_seconds, DateTime, Datelike, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+pub const HABITS_FILE: &str = "habits.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Habit {
+    pub frequency: Frequency,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    /// Days the habit was done on, in the user's timezone.
+    #[serde(default)]
+    pub checks: BTreeSet<NaiveDate>,
+}
+
+/// How often a habit should be done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    /// On this many days of each week, Monday to Sunday.
+    Weekly(u8),
+}
+
+impl Frequency {
+    /// Parses `daily`, or `weekly` with the number of days a week (1 if
+    /// not given).
+    pub fn parse(name: &str, times: Option<&str>) -> Result<Self, &'static str> {
+        match (name, times) {
+            ("daily", None) => Ok(Self::Daily),
+            ("weekly", None) => Ok(Self::Weekly(1)),
+            ("weekly", Some(times)) => times
+                .parse()
+                .ok()
+                .filter(|times| (1..=7).contains(times))
+                .map(Self::Weekly)
+                .ok_or("Invalid number of days a week, expected 1 to 7"),
+            _ => Err("Invalid frequency, expected daily or weekly"),
+        }
+    }
+}
+
+impl Habit {
+    /// The start of the period (day or week) that `date` falls in.
+    fn period_of(&self, date: NaiveDate) -> NaiveDate {
+        match self.frequency {
+            Frequency::Daily => date,
+            Frequency::Weekly(_) => {
+                date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+            }
+        }
+    }
+
+    /// The start of the period before the one starting on `start`.
+    fn previous_period(&self, start: NaiveDate) -> NaiveDate {
+        match self.frequency {
+            Frequency::Daily => start - Days::new(1),
+            Frequency::Weekly(_) => start - Days::new(7),
+        }
+    }
+
+    /// Whether the habit was done often enough in the period starting on `start`.
+    fn met(&self, start: NaiveDate) -> bool {
+        match self.frequency {
+            Frequency::Daily => self.checks.contains(&start),
+            Frequency::Weekly(times) => {
+                let end = start + Days::new(7);
+                self.checks.range(start..end).count() >= usize::from(times)
+            }
+        }
+    }
+
+    /// Consecutive periods (days or weeks) up to `today` in which the habit
+    /// was done often enough. The current period only counts once it's met,
+    /// so an unfinished day or week doesn't break the streak.
+    pub fn streak(&self, today: NaiveDate) -> u32 {
+        let mut period = self.period_of(today);
+        if !self.met(period) {
+            period = self.previous_period(period);
+        }
+        let mut streak = 0;
+        while self.met(period) {
+            streak += 1;
+            period = self.previous_period(period);
+        }
+        streak
+    }
+}
+
+impl TodoApp {
+    /// The current user's habits by name.
+    pub fn habits(&self) -> Result<BTreeMap<String, Habit>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        Ok(self.habits.get(user_id).cloned().unwrap_or_default())
+    }
+
+    pub fn add_habit(&mut self, name: &str, frequency: Frequency) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if name.trim().is_empty() {
+            return Err("The habit needs a name");
+        }
+        let habits = self.habits.entry(user_id.clone()).or_default();
+        if habits.contains_key(name) {
+            return Err("A habit with this name already exists");
+        }
+        habits.insert(
+            name.to_string(),
+            Habit {
+                frequency,
+                created_at: Utc::now(),
+                checks: BTreeSet::new(),
+            },
+        );
+        self.save_habits(&format!("Add habit {} for user {}", name, user_id))
+            .map_err(|_| "Failed to save habits")
+    }
+
+    pub fn remove_habit(&mut self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        self.habits
+            .get_mut(&user_id)
+            .and_then(|habits| habits.remove(name))
+            .ok_or("Habit not found")?;
+        self.save_habits(&format!("Remove habit {} of user {}", name, user_id))
+            .map_err(|_| "Failed to save habits")
+    }
+
+    /// Marks a habit as done on `date`, or not done if `done` is false.
+    /// Returns the streak afterwards.
+    pub fn check_habit(
+        &mut self,
+        name: &str,
+        date: NaiveDate,
+        done: bool,
+    ) -> Result<u32, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let today = schedule::local_date(Utc::now());
+        if date > today {
+            return Err("Habits can't be checked off in advance");
+        }
+        let habit = self
+            .habits
+            .get_mut(&user_id)
+            .and_then(|habits| habits.get_mut(name))
+            .ok_or("Habit not found")?;
+        let changed = if done {
+            habit.checks.insert(date)
+        } else {
+            habit.checks.remove(&date)
+        };
+        let streak = habit.streak(today);
+        if changed {
+            let action = if done { "Check" } else { "Uncheck" };
+            self.save_habits(&format!(
+                "{} habit {} of user {} on {}",
+                action, name, user_id, date
+            ))
+            .map_err(|_| "Failed to save habits")?;
+        }
+        Ok(streak)
+    }
+
+    /// Saves all users' habits. `message` describes the change being saved.
+    fn save_habits(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), HABITS_FILE, &self.habits, message)
+            .inspect_err(|e| log::error(&format!("Failed to save habits ({}): {}", message, e)))
+    }
+
+    /// Loads all users' habits. If the file doesn't exist, it's ignored.
+    pub fn load_habits(&mut self) -> io::Result<()> {
+        if let Some(habits) = storage::load_json(self.storage.as_ref(), HABITS_FILE)? {
+            self.habits = habits;
+        }
+        Ok(())
+    }
+}
+
+/// A month of `habits` as a grid, one row per habit and one column per day:
+/// `#` where a habit was done, `.` where it wasn't and blank before the
+/// habit existed or after `today`.
+pub fn render_grid(habits: &BTreeMap<String, Habit>, month: NaiveDate, today: NaiveDate) -> String {
+    let first = month.with_day(1).unwrap_or(month);
+    let days: Vec<NaiveDate> = first
+        .iter_days()
+        .take_while(|day| day.month() == first.month())
+        .collect();
+    let width = habits
+        .keys()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = format!("{}\n", first.format("%Y-%m"));
+    let tens: String = days
+        .iter()
+        .map(|day| match day.day() / 10 {
+            0 => ' ',
+            tens => char::from_digit(tens, 10).unwrap_or(' '),
+        })
+        .collect();
+    let ones: String = days
+        .iter()
+        .map(|day| char::from_digit(day.day() % 10, 10).unwrap_or(' '))
+        .collect();
+    out.push_str(&format!("{:width$}  {}\n", "", tens, width = width));
+    out.push_str(&format!("{:width$}  {}\n", "", ones, width = width));
+    for (name, habit) in habits {
+        let created = schedule::local_date(habit.created_at);
+        let row: String = days
+            .iter()
+            .map(|day| {
+                if habit.checks.contains(day) {
+                    '#'
+                } else if *day < created || *day > today {
+                    ' '
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:width$}  {}\n",
+            name,
+            row.trim_end(),
+            width = width
+        ));
+    }
+    out
+}
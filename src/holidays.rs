@@ -0,0 +1,310 @@
+//! Working days: weekends and public holidays are days off. Each user picks
+//! their holidays with the `holidays` setting, either a country from the
+//! bundled list (UA, US, GB, DE, PL) or a file with one holiday per line:
+//!
+//! ```text
+//! # Company days off
+//! 2026-12-31 New Year's Eve
+//! 12-24 Christmas Eve
+//! ```
+//!
+//! A `MM-DD` date is a holiday every year. Rollover, `due:next-workday` and
+//! `due:+Nwd` in quick-add, and the agenda use the calendar to skip days
+//! off.
+
+use crate::log;
+use crate::TodoApp;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// How a holiday's date is found in a given year.
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    /// The same month and day every year.
+    Fixed(u32, u32),
+    /// The nth weekday of a month; a negative n counts from the end.
+    Nth(u32, Weekday, i8),
+    /// Days after Western Easter Sunday.
+    Easter(i64),
+    /// Days after Orthodox Easter Sunday.
+    OrthodoxEaster(i64),
+}
+
+const UA: &[(Rule, &str)] = &[
+    (Rule::Fixed(1, 1), "New Year's Day"),
+    (Rule::Fixed(3, 8), "International Women's Day"),
+    (Rule::OrthodoxEaster(0), "Easter"),
+    (Rule::OrthodoxEaster(49), "Trinity"),
+    (Rule::Fixed(5, 1), "Labour Day"),
+    (Rule::Fixed(5, 8), "Day of Remembrance and Victory"),
+    (Rule::Fixed(6, 28), "Constitution Day"),
+    (Rule::Fixed(7, 15), "Statehood Day"),
+    (Rule::Fixed(8, 24), "Independence Day"),
+    (Rule::Fixed(10, 1), "Defenders Day"),
+    (Rule::Fixed(12, 25), "Christmas Day"),
+];
+
+const US: &[(Rule, &str)] = &[
+    (Rule::Fixed(1, 1), "New Year's Day"),
+    (Rule::Nth(1, Weekday::Mon, 3), "Martin Luther King Jr. Day"),
+    (Rule::Nth(2, Weekday::Mon, 3), "Washington's Birthday"),
+    (Rule::Nth(5, Weekday::Mon, -1), "Memorial Day"),
+    (Rule::Fixed(6, 19), "Juneteenth"),
+    (Rule::Fixed(7, 4), "Independence Day"),
+    (Rule::Nth(9, Weekday::Mon, 1), "Labor Day"),
+    (Rule::Nth(10, Weekday::Mon, 2), "Columbus Day"),
+    (Rule::Fixed(11, 11), "Veterans Day"),
+    (Rule::Nth(11, Weekday::Thu, 4), "Thanksgiving Day"),
+    (Rule::Fixed(12, 25), "Christmas Day"),
+];
+
+const GB: &[(Rule, &str)] = &[
+    (Rule::Fixed(1, 1), "New Year's Day"),
+    (Rule::Easter(-2), "Good Friday"),
+    (Rule::Easter(1), "Easter Monday"),
+    (Rule::Nth(5, Weekday::Mon, 1), "Early May Bank Holiday"),
+    (Rule::Nth(5, Weekday::Mon, -1), "Spring Bank Holiday"),
+    (Rule::Nth(8, Weekday::Mon, -1), "Summer Bank Holiday"),
+    (Rule::Fixed(12, 25), "Christmas Day"),
+    (Rule::Fixed(12, 26), "Boxing Day"),
+];
+
+const DE: &[(Rule, &str)] = &[
+    (Rule::Fixed(1, 1), "New Year's Day"),
+    (Rule::Easter(-2), "Good Friday"),
+    (Rule::Easter(1), "Easter Monday"),
+    (Rule::Fixed(5, 1), "Labour Day"),
+    (Rule::Easter(39), "Ascension Day"),
+    (Rule::Easter(50), "Whit Monday"),
+    (Rule::Fixed(10, 3), "German Unity Day"),
+    (Rule::Fixed(12, 25), "Christmas Day"),
+    (Rule::Fixed(12, 26), "Second Day of Christmas"),
+];
+
+const PL: &[(Rule, &str)] = &[
+    (Rule::Fixed(1, 1), "New Year's Day"),
+    (Rule::Fixed(1, 6), "Epiphany"),
+    (Rule::Easter(0), "Easter Sunday"),
+    (Rule::Easter(1), "Easter Monday"),
+    (Rule::Fixed(5, 1), "Labour Day"),
+    (Rule::Fixed(5, 3), "Constitution Day"),
+    (Rule::Easter(49), "Pentecost Sunday"),
+    (Rule::Easter(60), "Corpus Christi"),
+    (Rule::Fixed(8, 15), "Assumption Day"),
+    (Rule::Fixed(11, 1), "All Saints' Day"),
+    (Rule::Fixed(11, 11), "Independence Day"),
+    (Rule::Fixed(12, 24), "Christmas Eve"),
+    (Rule::Fixed(12, 25), "Christmas Day"),
+    (Rule::Fixed(12, 26), "Second Day of Christmas"),
+];
+
+/// The bundled holidays, by ISO country code.
+const COUNTRIES: &[(&str, &[(Rule, &str)])] =
+    &[("UA", UA), ("US", US), ("GB", GB), ("DE", DE), ("PL", PL)];
+
+/// Which days are working days.
+#[derive(Debug, Clone, Default)]
+pub struct WorkCalendar {
+    rules: &'static [(Rule, &'static str)],
+    dates: BTreeMap<NaiveDate, String>,
+    /// Holidays every year, by month and day.
+    yearly: BTreeMap<(u32, u32), String>,
+}
+
+impl WorkCalendar {
+    /// The bundled holidays of a country, e.g. `UA`.
+    pub fn country(code: &str) -> Option<Self> {
+        let (_, rules) = COUNTRIES
+            .iter()
+            .find(|(country, _)| country.eq_ignore_ascii_case(code))?;
+        Some(Self {
+            rules,
+            ..Self::default()
+        })
+    }
+
+    /// Reads a holidays file.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut calendar = Self::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (date, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let name = name.trim().to_string();
+            if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                calendar.dates.insert(date, name);
+            } else if let Some(day) = parse_month_day(date) {
+                calendar.yearly.insert(day, name);
+            } else {
+                return Err(format!(
+                    "{}:{}: expected YYYY-MM-DD or MM-DD",
+                    path,
+                    number + 1
+                ));
+            }
+        }
+        Ok(calendar)
+    }
+
+    /// Holidays of a country code or from a file, as in the `holidays`
+    /// setting.
+    pub fn load(source: &str) -> Result<Self, String> {
+        match Self::country(source) {
+            Some(calendar) => Ok(calendar),
+            None => Self::from_file(source),
+        }
+    }
+
+    /// The name of the holiday on `date`, if it's one.
+    pub fn holiday(&self, date: NaiveDate) -> Option<&str> {
+        if let Some(name) = self.dates.get(&date) {
+            return Some(name);
+        }
+        if let Some(name) = self.yearly.get(&(date.month(), date.day())) {
+            return Some(name);
+        }
+        self.rules
+            .iter()
+            .find(|(rule, _)| rule_date(*rule, date.year()) == Some(date))
+            .map(|(_, name)| *name)
+    }
+
+    /// Whether `date` is neither a weekend nor a holiday.
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && self.holiday(date).is_none()
+    }
+
+    /// The first working day on or after `date`. Gives up after a year of
+    /// days off, in case a file lists every day.
+    pub fn next_working_day(&self, date: NaiveDate) -> NaiveDate {
+        date.iter_days()
+            .take(366)
+            .find(|day| self.is_working_day(*day))
+            .unwrap_or(date)
+    }
+
+    /// The working day `days` working days after `date`, or the last date
+    /// there is if that comes first.
+    pub fn add_working_days(&self, date: NaiveDate, days: u32) -> NaiveDate {
+        let mut date = date;
+        for _ in 0..days {
+            let Some(next) = date.succ_opt() else {
+                break;
+            };
+            date = self.next_working_day(next);
+        }
+        date
+    }
+
+    /// The holidays in `year`, in order.
+    pub fn holidays_in(&self, year: i32) -> Vec<(NaiveDate, String)> {
+        let mut holidays: BTreeMap<NaiveDate, String> = self
+            .rules
+            .iter()
+            .filter_map(|(rule, name)| Some((rule_date(*rule, year)?, name.to_string())))
+            .collect();
+        for ((month, day), name) in &self.yearly {
+            if let Some(date) = NaiveDate::from_ymd_opt(year, *month, *day) {
+                holidays.insert(date, name.clone());
+            }
+        }
+        for (date, name) in &self.dates {
+            if date.year() == year {
+                holidays.insert(*date, name.clone());
+            }
+        }
+        holidays.into_iter().collect()
+    }
+}
+
+/// Whether `code` is a country in the bundled list.
+pub fn is_known_country(code: &str) -> bool {
+    WorkCalendar::country(code).is_some()
+}
+
+fn parse_month_day(text: &str) -> Option<(u32, u32)> {
+    let (month, day) = text.split_once('-')?;
+    let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+    // 2000 is a leap year, so the 29th of February passes.
+    NaiveDate::from_ymd_opt(2000, month, day)?;
+    Some((month, day))
+}
+
+fn rule_date(rule: Rule, year: i32) -> Option<NaiveDate> {
+    match rule {
+        Rule::Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+        Rule::Nth(month, weekday, n) if n < 0 => {
+            let next_month = if month == 12 {
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)?
+            };
+            let last = next_month.pred_opt()?;
+            let back =
+                (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+            let weeks = u64::from(n.unsigned_abs() - 1) * 7;
+            last.checked_sub_days(Days::new(u64::from(back) + weeks))
+        }
+        Rule::Nth(month, weekday, n) => {
+            NaiveDate::from_weekday_of_month_opt(year, month, weekday, u8::try_from(n).ok()?)
+        }
+        Rule::Easter(offset) => offset_date(easter(year)?, offset),
+        Rule::OrthodoxEaster(offset) => offset_date(orthodox_easter(year)?, offset),
+    }
+}
+
+fn offset_date(date: NaiveDate, offset: i64) -> Option<NaiveDate> {
+    date.checked_add_signed(chrono::Duration::days(offset))
+}
+
+/// Western Easter Sunday, by the anonymous Gregorian algorithm.
+fn easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, u32::try_from(month).ok()?, u32::try_from(day).ok()?)
+}
+
+/// Orthodox Easter Sunday: Meeus' Julian algorithm, moved to the Gregorian
+/// calendar (13 days apart from 1900 to 2099).
+fn orthodox_easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 4;
+    let b = year % 7;
+    let c = year % 19;
+    let d = (19 * c + 15) % 30;
+    let e = (2 * a + 4 * b - d + 34) % 7;
+    let month = (d + e + 114) / 31;
+    let day = (d + e + 114) % 31 + 1;
+    let julian =
+        NaiveDate::from_ymd_opt(year, u32::try_from(month).ok()?, u32::try_from(day).ok()?)?;
+    julian.checked_add_days(Days::new(13))
+}
+
+impl TodoApp {
+    /// The current user's working days. A holidays file that can't be read
+    /// is logged and only weekends are days off.
+    pub fn work_calendar(&self) -> WorkCalendar {
+        let Some(source) = self.settings().ok().and_then(|settings| settings.holidays) else {
+            return WorkCalendar::default();
+        };
+        WorkCalendar::load(&source).unwrap_or_else(|e| {
+            log::warn(&format!("Failed to load holidays: {}", e));
+            WorkCalendar::default()
+        })
+    }
+}
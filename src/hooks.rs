@@ -0,0 +1,116 @@
+//! User scripts run on task events.
+//!
+//! An executable named after the event (e.g. `hooks/on-task-completed`)
+//! receives the task as JSON on stdin.
+
+use crate::log;
+use crate::{Task, TaskEvent};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Settings for the hooks directory, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub enabled: bool,
+    /// Directory containing the hook scripts.
+    pub dir: PathBuf,
+    /// Seconds a hook may run before it is killed.
+    pub timeout_secs: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from("hooks"),
+            timeout_secs: 5,
+        }
+    }
+}
+
+/// Runs the hook script for an event, if one exists.
+#[derive(Default)]
+pub struct Hooks {
+    config: HooksConfig,
+}
+
+impl Hooks {
+    pub fn new(config: HooksConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs the script for `event` with `task` on stdin. Failures are reported
+    /// but never abort the operation that triggered the hook.
+    pub fn run(&self, event: TaskEvent, task: &Task) {
+        if !self.config.enabled {
+            return;
+        }
+        let script = self.config.dir.join(script_name(event));
+        if !script.is_file() {
+            return;
+        }
+        if let Err(e) = self.execute(&script, task) {
+            let message = format!("Hook {} failed: {}", script.display(), e);
+            log::warn(&message);
+            eprintln!("{}", message);
+        }
+    }
+
+    fn execute(&self, script: &Path, task: &Task) -> io::Result<()> {
+        let json = serde_json::to_string(task)?;
+        let mut child = Command::new(script).stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Written on its own thread so a hook that doesn't read a task
+            // bigger than the pipe can still time out. If it closes the
+            // pipe early, or is killed, the write fails; that's fine.
+            thread::spawn(move || {
+                let _ = stdin.write_all(json.as_bytes());
+            });
+        }
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        match wait_timeout(&mut child, timeout)? {
+            Some(status) if status.success() => Ok(()),
+            Some(status) => Err(io::Error::other(format!("exited with {}", status))),
+            None => {
+                child.kill()?;
+                child.wait()?;
+                Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+            }
+        }
+    }
+}
+
+fn script_name(event: TaskEvent) -> &'static str {
+    match event {
+        TaskEvent::Created => "on-task-added",
+        TaskEvent::Completed => "on-task-completed",
+        TaskEvent::Deleted => "on-task-deleted",
+        TaskEvent::Assigned => "on-task-assigned",
+        TaskEvent::Escalated => "on-task-escalated",
+        TaskEvent::Reminded => "on-task-reminded",
+        TaskEvent::Cancelled => "on-task-cancelled",
+    }
+}
+
+/// Waits for the child to exit, returning `None` if it's still running after `timeout`.
+fn wait_timeout(
+    child: &mut Child,
+    timeout: Duration,
+) -> io::Result<Option<std::process::ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
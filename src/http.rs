@@ -0,0 +1,214 @@
+//! Minimal blocking HTTP/1.1 client used by the integrations, and the
+//! base64 encoding shared with the server.
+//!
+//! Only plain `http://` URLs are supported; HTTPS endpoints need to be
+//! reached through a local proxy.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Response status code and body.
+pub struct Response {
+    pub status: u16,
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    pub body: String,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Whether `host` is `localhost` or a loopback address, so credentials
+/// sent to it in the clear don't leave the machine.
+#[cfg_attr(
+    not(any(feature = "caldav", feature = "imap", feature = "bot")),
+    allow(dead_code)
+)]
+pub fn is_loopback_host(host: &str) -> bool {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether `url` is a plain-HTTP URL on the loopback interface.
+#[cfg_attr(not(any(feature = "caldav", feature = "bot")), allow(dead_code))]
+pub fn is_loopback_url(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return false;
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    is_loopback_host(host)
+}
+
+/// Sends a request with the given extra headers and returns the response.
+pub fn send(method: &str, url: &str, headers: &[(&str, &str)], body: &str) -> io::Result<Response> {
+    let (host, port, path) = parse_url(url)?;
+    let mut stream = TcpStream::connect((host.as_str(), port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, host);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    // Decoded only once the chunks are joined, as a chunk may end in the
+    // middle of a character.
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let split = find(&response, b"\r\n\r\n");
+    let head = String::from_utf8_lossy(&response[..split.unwrap_or(response.len())]);
+    let body = split.map_or(&[][..], |split| &response[split + 4..]);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed HTTP response"))?;
+    let body = if head
+        .to_ascii_lowercase()
+        .contains("transfer-encoding: chunked")
+    {
+        String::from_utf8_lossy(&decode_chunked(body)).into_owned()
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    };
+    Ok(Response { status, body })
+}
+
+/// Splits an `http://host[:port]/path` URL into its parts.
+/// Fails unless `url` is one `send` can reach.
+pub fn check_url(url: &str) -> io::Result<()> {
+    parse_url(url).map(|_| ())
+}
+
+fn parse_url(url: &str) -> io::Result<(String, u16, String)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid("Only http:// URLs are supported"))?;
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse().map_err(|_| invalid("Invalid port in URL"))?,
+        ),
+        None => (authority, 80),
+    };
+    Ok((host.to_string(), port, format!("/{}", path)))
+}
+
+/// Where `needle` first appears in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn decode_chunked(body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    let mut rest = body;
+    while let Some(line_end) = find(rest, b"\r\n") {
+        let size = std::str::from_utf8(&rest[..line_end])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .unwrap_or(0);
+        let tail = &rest[line_end + 2..];
+        if size == 0 || tail.len() < size {
+            break;
+        }
+        decoded.extend_from_slice(&tail[..size]);
+        rest = tail[size..].strip_prefix(b"\r\n").unwrap_or(&tail[size..]);
+    }
+    decoded
+}
+
+/// Encodes bytes as standard padded base64.
+pub fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_characters_split_across_chunks() {
+        let letter = "й".as_bytes();
+        let mut body = b"1\r\n".to_vec();
+        body.extend_from_slice(&letter[..1]);
+        body.extend_from_slice(b"\r\n3\r\n");
+        body.extend_from_slice(&letter[1..]);
+        body.extend_from_slice(b"ok\r\n0\r\n\r\n");
+        assert_eq!(decode_chunked(&body), "йok".as_bytes());
+    }
+
+    #[test]
+    fn only_loopback_hosts_count_as_local() {
+        for host in [
+            "localhost",
+            "LocalHost",
+            "127.0.0.1",
+            "127.8.0.1",
+            "::1",
+            "[::1]",
+        ] {
+            assert!(is_loopback_host(host), "{}", host);
+        }
+        for host in ["imap.example.com", "10.0.0.1", "localhost.example.com", ""] {
+            assert!(!is_loopback_host(host), "{}", host);
+        }
+    }
+
+    #[test]
+    fn only_plain_http_urls_on_loopback_count_as_local() {
+        assert!(is_loopback_url("http://localhost:8081/api"));
+        assert!(is_loopback_url("http://[::1]:8080/dav/"));
+        assert!(is_loopback_url("http://127.0.0.1"));
+        assert!(!is_loopback_url("https://localhost/api"));
+        assert!(!is_loopback_url("http://slack.com/api"));
+        assert!(!is_loopback_url("http://localhost.evil.com/api"));
+    }
+
+    #[test]
+    fn only_plain_http_urls_can_be_sent_to() {
+        assert!(check_url("http://localhost:8443/services/T0/B0/x").is_ok());
+        assert!(check_url("http://homeassistant.lan/api/webhook/todo").is_ok());
+        assert!(check_url("https://hooks.slack.com/services/T0/B0/x").is_err());
+        assert!(check_url("http://localhost:port/").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Translation of user-facing messages.
+//!
+//! Messages are looked up gettext-style: the English text is the message id,
+//! and each locale ships a `.po` catalog mapping it to a translation. Missing
+//! translations fall back to the English text.
+
+use crate::schedule;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::sync::{OnceLock, RwLock};
+
+/// Interface language, selected with the `language` config option.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Uk,
+}
+
+struct Locale {
+    messages: HashMap<&'static str, &'static str>,
+    datetime_format: &'static str,
+    date_format: &'static str,
+}
+
+impl Locale {
+    fn english() -> Self {
+        Self {
+            messages: HashMap::new(),
+            datetime_format: "%Y-%m-%d %H:%M",
+            date_format: "%Y-%m-%d",
+        }
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// The user's own date format, overriding the locale's.
+static DATE_FORMAT: RwLock<Option<String>> = RwLock::new(None);
+
+/// Selects the interface language. Only the first call has any effect.
+pub fn init(language: Language) {
+    let locale = match language {
+        Language::En => Locale::english(),
+        Language::Uk => Locale {
+            messages: parse_po(include_str!("../locales/uk.po")),
+            datetime_format: "%d.%m.%Y %H:%M",
+            date_format: "%d.%m.%Y",
+        },
+    };
+    let _ = LOCALE.set(locale);
+}
+
+fn locale() -> &'static Locale {
+    LOCALE.get_or_init(Locale::english)
+}
+
+/// Translates a message, falling back to the message itself.
+pub fn tr(message: &str) -> &str {
+    locale().messages.get(message).copied().unwrap_or(message)
+}
+
+/// Translates a message and fills its `{}` placeholders with `args` in order.
+pub fn trf(message: &str, args: &[&dyn Display]) -> String {
+    let mut parts = tr(message).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+/// Sets the user's date format; `None` goes back to the locale's format.
+pub fn set_date_format(format: Option<String>) {
+    if let Ok(mut date_format) = DATE_FORMAT.write() {
+        *date_format = format;
+    }
+}
+
+/// Whether `format` is a usable `strftime`-style date format.
+pub fn is_valid_date_format(format: &str) -> bool {
+    let mut out = String::new();
+    write!(out, "{}", NaiveDate::default().format(format)).is_ok()
+}
+
+/// Formats a timestamp in the user's timezone and the locale's date and time format.
+pub fn format_datetime(time: DateTime<Utc>) -> String {
+    let time = time.with_timezone(&schedule::timezone());
+    match DATE_FORMAT.read().ok().and_then(|format| format.clone()) {
+        Some(format) => format!("{} {}", time.format(&format), time.format("%H:%M")),
+        None => time.format(locale().datetime_format).to_string(),
+    }
+}
+
+/// Formats the date part of a timestamp in the user's timezone and the locale's format.
+pub fn format_date(time: DateTime<Utc>) -> String {
+    format_day(schedule::local_date(time))
+}
+
+/// Formats a calendar date in the user's or the locale's format.
+pub fn format_day(date: NaiveDate) -> String {
+    match DATE_FORMAT.read().ok().and_then(|format| format.clone()) {
+        Some(format) => date.format(&format).to_string(),
+        None => date.format(locale().date_format).to_string(),
+    }
+}
+
+/// Parses the `msgid`/`msgstr` pairs of a `.po` catalog. Entries with an
+/// empty translation are skipped so they fall back to English.
+fn parse_po(source: &'static str) -> HashMap<&'static str, &'static str> {
+    let mut messages = HashMap::new();
+    let mut msgid = None;
+    for line in source.lines() {
+        if let Some(id) = line.strip_prefix("msgid ") {
+            msgid = Some(unquote(id));
+        } else if let (Some(text), Some(id)) = (line.strip_prefix("msgstr "), msgid.take()) {
+            let text = unquote(text);
+            if !id.is_empty() && !text.is_empty() {
+                messages.insert(id, text);
+            }
+        }
+    }
+    messages
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_start_matches('"').trim_end_matches('"')
+}
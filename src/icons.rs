@@ -0,0 +1,111 @@
+//! Icons shown before task titles: a task's own, or else that of its
+//! project or first tag with one, set with `defaults <project|tag> <name>
+//! icon <icon>`. An emoji is shown as is where the terminal's locale is
+//! UTF-8; elsewhere a few common ones become ASCII and the rest `*`.
+
+use crate::{Task, TodoApp};
+use std::env;
+use std::sync::OnceLock;
+
+/// Most characters in an icon; emoji sequences take several.
+const MAX_ICON_LENGTH: usize = 8;
+
+/// Shown for icons with no ASCII stand-in on terminals without Unicode.
+const FALLBACK: &str = "*";
+
+/// ASCII stand-ins for common emoji.
+const ASCII: &[(&str, &str)] = &[
+    ("⭐", "*"),
+    ("🔥", "!"),
+    ("❗", "!"),
+    ("✅", "v"),
+    ("🏠", "H"),
+    ("💼", "W"),
+    ("🐛", "B"),
+    ("📅", "D"),
+    ("📞", "T"),
+    ("🛒", "$"),
+    ("💡", "i"),
+];
+
+/// Checks that an icon is one short word, such as an emoji.
+pub fn parse_icon(icon: &str) -> Result<String, &'static str> {
+    let icon = icon.trim();
+    if icon.is_empty()
+        || icon.chars().count() > MAX_ICON_LENGTH
+        || icon.chars().any(|c| c.is_control() || c.is_whitespace())
+    {
+        return Err("Invalid icon, expected an emoji or a few characters");
+    }
+    Ok(icon.to_string())
+}
+
+/// Whether the locale says the terminal shows UTF-8.
+fn unicode_terminal() -> bool {
+    static UNICODE: OnceLock<bool> = OnceLock::new();
+    *UNICODE.get_or_init(|| {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+/// `icon` as the terminal can show it.
+pub fn render(icon: &str) -> &str {
+    if icon.is_ascii() || unicode_terminal() {
+        return icon;
+    }
+    let emoji = icon.trim_end_matches('\u{fe0f}');
+    ASCII
+        .iter()
+        .find(|(known, _)| *known == emoji)
+        .map_or(FALLBACK, |(_, ascii)| ascii)
+}
+
+impl TodoApp {
+    /// Sets a task's icon, or removes it with `None`.
+    pub fn set_task_icon(&mut self, task_id: u32, icon: Option<&str>) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let icon = icon.map(parse_icon).transpose()?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.icon = icon;
+        task.touch();
+        self.save_tasks(&format!("Change icon of task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// The icon shown for `task`: its own, or its project's, or that of the
+    /// first of its tags with one.
+    pub fn task_icon<'a>(&'a self, task: &'a Task) -> Option<&'a str> {
+        if let Some(icon) = &task.icon {
+            return Some(icon);
+        }
+        let defaults = self.defaults.get(&task.user_id)?;
+        task.project
+            .as_ref()
+            .and_then(|project| defaults.projects.get(project))
+            .and_then(|defaults| defaults.icon.as_deref())
+            .or_else(|| {
+                task.tags
+                    .iter()
+                    .filter_map(|tag| defaults.tags.get(tag))
+                    .find_map(|defaults| defaults.icon.as_deref())
+            })
+    }
+
+    /// `task`'s title with its icon in front, if it has one.
+    pub fn title_with_icon(&self, task: &Task) -> String {
+        match self.task_icon(task) {
+            Some(icon) => format!("{} {}", render(icon), task.title),
+            None => task.title.clone(),
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! Optional email-in: new messages in an IMAP mailbox become tasks
+//! (`Lab3 --email-in`). The subject is the title, the plain-text body the
+//! description and the sender is kept in the task's `from` field.
+//!
+//! Messages are only read, never changed on the server; the ones already
+//! turned into tasks are remembered in `imap.json`. Like the other
+//! integrations this speaks plain IMAP, sending the password in the clear,
+//! so `host` must be this machine: IMAPS servers are reached through a
+//! local TLS-terminating proxy such as stunnel, and other hosts are refused.
+
+use crate::http;
+use crate::i18n::{tr, trf};
+use crate::log;
+use crate::validation::MAX_TITLE_LENGTH;
+use crate::TodoApp;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+const IMAP_STATE_FILE: &str = "imap.json";
+
+/// Custom field the sender is recorded in.
+const FROM_FIELD: &str = "from";
+
+/// Mailbox settings, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImapConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub mailbox: String,
+    /// Account the tasks are created for.
+    pub user: String,
+    /// Seconds between checks for new mail.
+    pub poll_secs: u64,
+}
+
+impl Default for ImapConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 143,
+            username: String::new(),
+            password: String::new(),
+            mailbox: "INBOX".to_string(),
+            user: String::new(),
+            poll_secs: 60,
+        }
+    }
+}
+
+/// Which messages have been turned into tasks. UIDs only stay valid while
+/// the mailbox's UIDVALIDITY does, so message IDs are kept too.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    uid_validity: u32,
+    last_uid: u32,
+    message_ids: BTreeSet<String>,
+}
+
+fn load_state() -> io::Result<State> {
+    match fs::read_to_string(IMAP_STATE_FILE) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_state(state: &State) -> io::Result<()> {
+    let json = serde_json::to_string(state)?;
+    fs::write(IMAP_STATE_FILE, json)?;
+    Ok(())
+}
+
+/// Fails unless the mailbox is on this machine, as the password is sent
+/// in the clear.
+fn check_host(config: &ImapConfig) -> io::Result<()> {
+    if http::is_loopback_host(&config.host) {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        tr("The IMAP host must be localhost, e.g. a local TLS proxy such as stunnel"),
+    ))
+}
+
+/// A server response line, with the literals (`{n}` then n bytes) in it.
+struct Line {
+    text: String,
+    literals: Vec<Vec<u8>>,
+}
+
+struct ImapClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    tag: u32,
+}
+
+impl ImapClient {
+    fn connect(config: &ImapConfig) -> io::Result<Self> {
+        check_host(config)?;
+        let stream = TcpStream::connect((config.host.as_str(), config.port))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let mut client = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            tag: 0,
+        };
+        let greeting = client.read_line()?;
+        if !greeting.text.starts_with("* OK") {
+            return Err(io::Error::other(format!(
+                "unexpected greeting: {}",
+                greeting.text
+            )));
+        }
+        client.command(&format!(
+            "LOGIN {} {}",
+            quote(&config.username),
+            quote(&config.password)
+        ))?;
+        Ok(client)
+    }
+
+    fn read_line(&mut self) -> io::Result<Line> {
+        let mut line = Line {
+            text: String::new(),
+            literals: Vec::new(),
+        };
+        loop {
+            let mut raw = Vec::new();
+            if self.reader.read_until(b'\n', &mut raw)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let part = String::from_utf8_lossy(&raw);
+            let part = part.trim_end_matches(['\r', '\n']);
+            line.text.push_str(part);
+            let Some(size) = literal_size(part) else {
+                return Ok(line);
+            };
+            let mut literal = vec![0; size];
+            self.reader.read_exact(&mut literal)?;
+            line.literals.push(literal);
+        }
+    }
+
+    /// Sends a command and returns the untagged lines of its response.
+    fn command(&mut self, command: &str) -> io::Result<Vec<Line>> {
+        self.tag += 1;
+        let tag = format!("A{} ", self.tag);
+        write!(self.writer, "{}{}\r\n", tag, command)?;
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            let Some(status) = line.text.strip_prefix(&tag) else {
+                lines.push(line);
+                continue;
+            };
+            if status.starts_with("OK") {
+                return Ok(lines);
+            }
+            return Err(io::Error::other(status.to_string()));
+        }
+    }
+}
+
+/// `text` as an IMAP quoted string.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The size of the literal a line ends with, e.g. 42 for `... {42}`.
+fn literal_size(line: &str) -> Option<usize> {
+    line.strip_suffix('}')?.rsplit_once('{')?.1.parse().ok()
+}
+
+/// A message turned into a task.
+struct Message {
+    id: Option<String>,
+    subject: String,
+    from: String,
+    body: String,
+}
+
+impl Message {
+    fn parse(header: &[u8], text: &[u8]) -> Self {
+        let headers = parse_headers(&String::from_utf8_lossy(header));
+        let get = |name: &str| headers.get(name).map(String::as_str).unwrap_or_default();
+        Self {
+            id: headers.get("message-id").map(|id| id.trim().to_string()),
+            subject: decode_words(get("subject")).trim().to_string(),
+            from: decode_words(get("from")).trim().to_string(),
+            body: text_body(&headers, text).trim().to_string(),
+        }
+    }
+
+    /// The subject as a valid title.
+    fn title(&self) -> String {
+        let title: String = self
+            .subject
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .take(MAX_TITLE_LENGTH)
+            .collect();
+        match title.trim() {
+            "" => tr("(no subject)").to_string(),
+            title => title.to_string(),
+        }
+    }
+}
+
+/// Header names, lowercased, and values, with folded lines joined. Only
+/// the first of repeated headers is kept.
+fn parse_headers(text: &str) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
+    let mut current: Option<(String, String)> = None;
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = &mut current {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some((name, value)) = current.take() {
+            headers.entry(name).or_insert(value);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            current = Some((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    if let Some((name, value)) = current {
+        headers.entry(name).or_insert(value);
+    }
+    headers
+}
+
+/// A parameter of a header value, e.g. `boundary` of a `Content-Type`.
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The plain-text body of a message or MIME part: the first `text/plain`
+/// part of a multipart message, decoded. Other content is left out.
+fn text_body(headers: &BTreeMap<String, String>, body: &[u8]) -> String {
+    let content_type = headers
+        .get("content-type")
+        .map(|value| value.to_lowercase())
+        .unwrap_or_else(|| "text/plain".to_string());
+    if content_type.starts_with("multipart/") {
+        let Some(boundary) = headers
+            .get("content-type")
+            .and_then(|value| parameter(value, "boundary"))
+        else {
+            return String::new();
+        };
+        let text = String::from_utf8_lossy(body);
+        let delimiter = format!("--{}", boundary);
+        return text
+            .split(&delimiter)
+            .skip(1)
+            .take_while(|part| !part.starts_with("--"))
+            .find_map(|part| {
+                let part = part.trim_start_matches(['\r', '\n']);
+                let (head, body) = part
+                    .split_once("\r\n\r\n")
+                    .or_else(|| part.split_once("\n\n"))
+                    .unwrap_or((part, ""));
+                let text = text_body(&parse_headers(head), body.as_bytes());
+                (!text.trim().is_empty()).then_some(text)
+            })
+            .unwrap_or_default();
+    }
+    if !content_type.starts_with("text/plain") {
+        return String::new();
+    }
+    let encoding = headers
+        .get("content-transfer-encoding")
+        .map(|value| value.trim().to_lowercase());
+    let decoded = match encoding.as_deref() {
+        Some("base64") => base64_decode(&String::from_utf8_lossy(body)),
+        Some("quoted-printable") => quoted_printable_decode(&String::from_utf8_lossy(body), false),
+        _ => body.to_vec(),
+    };
+    String::from_utf8_lossy(&decoded).replace("\r\n", "\n")
+}
+
+/// Decodes the `=?charset?B?...?=` and `=?charset?Q?...?=` words of a
+/// header. Text in charsets other than UTF-8 and ASCII may come out garbled.
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = &rest[start + 2..];
+        let decoded = word.split_once('?').and_then(|(_, word)| {
+            let (encoding, word) = word.split_once('?')?;
+            let (text, after) = word.split_once("?=")?;
+            let bytes = match encoding {
+                "B" | "b" => base64_decode(text),
+                "Q" | "q" => quoted_printable_decode(text, true),
+                _ => return None,
+            };
+            Some((String::from_utf8_lossy(&bytes).into_owned(), after))
+        });
+        match decoded {
+            Some((text, after)) => {
+                let between = &rest[..start];
+                // Whitespace between encoded words isn't part of the text.
+                if !(after_word && between.trim().is_empty()) {
+                    out.push_str(between);
+                }
+                out.push_str(&text);
+                rest = after;
+                after_word = true;
+            }
+            None => {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn base64_decode(text: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => continue,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    out
+}
+
+/// Decodes quoted-printable text; in headers (`header`), `_` is a space.
+fn quoted_printable_decode(text: &str, header: bool) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if bytes[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if bytes[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' => {
+                let hex = text
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Turns the messages not seen before into tasks. Returns how many were
+/// created.
+fn poll(app: &mut TodoApp, config: &ImapConfig, state: &mut State) -> io::Result<usize> {
+    let mut client = ImapClient::connect(config)?;
+    let selected = client.command(&format!("SELECT {}", quote(&config.mailbox)))?;
+    let uid_validity = selected
+        .iter()
+        .find_map(|line| {
+            let rest = line.text.split_once("[UIDVALIDITY ")?.1;
+            rest.split(']').next()?.trim().parse().ok()
+        })
+        .unwrap_or(0);
+    if uid_validity != state.uid_validity {
+        state.uid_validity = uid_validity;
+        state.last_uid = 0;
+    }
+
+    let found = client.command(&format!("UID SEARCH UID {}:*", state.last_uid + 1))?;
+    let mut uids: Vec<u32> = found
+        .iter()
+        .filter_map(|line| line.text.strip_prefix("* SEARCH"))
+        .flat_map(|uids| uids.split_whitespace().filter_map(|uid| uid.parse().ok()))
+        .filter(|uid| *uid > state.last_uid)
+        .collect();
+    uids.sort_unstable();
+
+    let mut created = 0;
+    for uid in uids {
+        let fetched = client.command(&format!(
+            "UID FETCH {} (BODY.PEEK[HEADER] BODY.PEEK[TEXT])",
+            uid
+        ))?;
+        let Some(line) = fetched.iter().find(|line| line.literals.len() == 2) else {
+            log::warn(&format!("IMAP message {} couldn't be fetched", uid));
+            continue;
+        };
+        // Literals come in the order the server lists the parts.
+        let header_first = match (line.text.find("BODY[HEADER]"), line.text.find("BODY[TEXT]")) {
+            (Some(header), Some(text)) => header < text,
+            _ => true,
+        };
+        let (header, text) = match header_first {
+            true => (&line.literals[0], &line.literals[1]),
+            false => (&line.literals[1], &line.literals[0]),
+        };
+        let message = Message::parse(header, text);
+        if message
+            .id
+            .as_ref()
+            .is_some_and(|id| state.message_ids.contains(id))
+        {
+            state.last_uid = uid;
+            save_state(state)?;
+            continue;
+        }
+
+        let mut task = app
+            .new_task(&message.title(), &message.body, None)
+            .map_err(io::Error::other)?;
+        if !message.from.is_empty() {
+            task.custom
+                .insert(FROM_FIELD.to_string(), message.from.clone());
+        }
+        let task_id = app.create_task(task).map_err(io::Error::other)?;
+        log::info(&format!(
+            "Created task #{} from email by {}",
+            task_id, message.from
+        ));
+        println!(
+            "{}",
+            trf("Task #{} added from {}", &[&task_id, &message.from])
+        );
+        state.last_uid = uid;
+        state.message_ids.extend(message.id);
+        save_state(state)?;
+        created += 1;
+    }
+    let _ = client.command("LOGOUT");
+    Ok(created)
+}
+
+/// Checks the mailbox every `poll_secs` seconds and adds new messages as
+/// tasks of the configured user, forever.
+pub fn run(app: &mut TodoApp, config: &ImapConfig) -> io::Result<()> {
+    let user = app
+        .find_user(&config.user)
+        .ok_or_else(|| io::Error::other(trf("No such user: {}", &[&config.user])))?
+        .to_string();
+    check_host(config)?;
+    app.current_user = Some(user);
+    let mut state = load_state()?;
+    println!("{}", tr("Checking for new mail"));
+    loop {
+        // Pick up changes made by other sessions since the last check.
+        app.load_tasks()?;
+        if let Err(e) = poll(app, config, &mut state) {
+            log::warn(&format!("Failed to check mail: {}", e));
+        }
+        thread::sleep(Duration::from_secs(config.poll_secs.max(1)));
+    }
+}
@@ -0,0 +1,153 @@
+//! Merging imported tasks into the task list. An imported task already
+//! exists here when a task has its UUID or, failing that, the current user
+//! has a task with the same title and due day. What happens to it is up to
+//! a `Resolution`, picked up front or for each task as it comes.
+
+use crate::crypto;
+use crate::{Task, TodoApp};
+use std::collections::HashMap;
+
+/// What to do with an imported task that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the existing task and leave the imported one out.
+    Skip,
+    /// Replace the existing task's details with the imported ones.
+    Overwrite,
+    /// Add the imported task as a new one next to the existing task.
+    Duplicate,
+}
+
+impl Resolution {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(Self::Skip),
+            "overwrite" => Some(Self::Overwrite),
+            "duplicate" => Some(Self::Duplicate),
+            _ => None,
+        }
+    }
+}
+
+/// What became of one imported task, with the IDs of the tasks involved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Added(u32),
+    Skipped { existing: u32 },
+    Overwritten(u32),
+    Duplicated { id: u32, existing: u32 },
+}
+
+/// An imported task and what became of it.
+pub struct Decision {
+    pub title: String,
+    pub outcome: Outcome,
+}
+
+impl TodoApp {
+    /// The task an imported one duplicates, if any.
+    fn existing_task(&self, user_id: &str, task: &Task) -> Option<u32> {
+        let same_day = |a: &Task, b: &Task| {
+            a.due_date.map(|due| due.date_naive()) == b.due_date.map(|due| due.date_naive())
+        };
+        self.tasks
+            .values()
+            .find(|existing| !task.uuid.is_empty() && existing.uuid == task.uuid)
+            .or_else(|| {
+                self.own_tasks(user_id).find(|existing| {
+                    existing.title.to_lowercase() == task.title.to_lowercase()
+                        && same_day(existing, task)
+                })
+            })
+            .map(|existing| existing.id)
+    }
+
+    /// Adds imported `tasks` for the current user, asking `resolve` about
+    /// each one that already exists. Task IDs and links between the
+    /// imported tasks are renumbered to match. Doesn't save.
+    pub fn merge_imported(
+        &mut self,
+        tasks: Vec<Task>,
+        resolve: &mut dyn FnMut(&Task, &Task) -> Resolution,
+    ) -> Result<Vec<Decision>, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+
+        // Decide everything first, so nothing changes if the quota is hit.
+        let mut plan = Vec::new();
+        for task in tasks {
+            let resolution = match self.existing_task(&user_id, &task) {
+                Some(existing) => {
+                    let mut resolution = resolve(&self.tasks[&existing], &task);
+                    if resolution == Resolution::Overwrite
+                        && !self.workspaces.can_edit(&self.tasks[&existing], &user_id)
+                    {
+                        resolution = Resolution::Skip;
+                    }
+                    Some((existing, resolution))
+                }
+                None => None,
+            };
+            plan.push((task, resolution));
+        }
+        let added = plan
+            .iter()
+            .filter(|(_, resolution)| matches!(resolution, None | Some((_, Resolution::Duplicate))))
+            .count();
+        self.check_quota(added as u32)?;
+
+        // Where each imported task's links should now point.
+        let mut next_id = self.next_task_id;
+        let ids: HashMap<u32, u32> = plan
+            .iter()
+            .map(|(task, resolution)| match resolution {
+                Some((existing, Resolution::Skip | Resolution::Overwrite)) => (task.id, *existing),
+                None | Some((_, Resolution::Duplicate)) => {
+                    next_id += 1;
+                    (task.id, next_id - 1)
+                }
+            })
+            .collect();
+
+        let mut decisions = Vec::new();
+        for (mut task, resolution) in plan {
+            task.relates_to = task
+                .relates_to
+                .iter()
+                .filter_map(|id| ids.get(id).copied())
+                .collect();
+            let title = task.title.clone();
+            let outcome = match resolution {
+                Some((existing, Resolution::Skip)) => Outcome::Skipped { existing },
+                Some((existing, Resolution::Overwrite)) => {
+                    let old = &self.tasks[&existing];
+                    task.id = existing;
+                    task.uuid = old.uuid.clone();
+                    task.user_id = old.user_id.clone();
+                    task.workspace = old.workspace.clone();
+                    task.version = old.version;
+                    task.touch();
+                    self.insert_task(task);
+                    Outcome::Overwritten(existing)
+                }
+                Some((existing, Resolution::Duplicate)) => {
+                    task.id = self.next_task_id;
+                    task.uuid = crypto::uuid_v4();
+                    self.insert_task(task);
+                    self.next_task_id += 1;
+                    Outcome::Duplicated {
+                        id: self.next_task_id - 1,
+                        existing,
+                    }
+                }
+                None => {
+                    task.id = self.next_task_id;
+                    self.insert_task(task);
+                    self.next_task_id += 1;
+                    Outcome::Added(self.next_task_id - 1)
+                }
+            };
+            decisions.push(Decision { title, outcome });
+        }
+        Ok(decisions)
+    }
+}
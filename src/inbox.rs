@@ -0,0 +1,74 @@
+//! Capturing tasks now and organizing them later, as in GTD. `inbox <title>`
+//! adds a task with nothing but a title, without asking anything, so an idea
+//! is written down before it's lost. `triage` then goes through the inbox
+//! one task at a time to give each a project, a due date and a priority.
+
+use crate::priority::Priority;
+use crate::rules::{self, Trigger};
+use crate::{Task, TodoApp};
+use chrono::{DateTime, Utc};
+
+/// What triage decided for a task. Details left `None` stay as they are.
+#[derive(Debug, Default)]
+pub struct Triage {
+    pub project: Option<String>,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Option<Priority>,
+}
+
+impl TodoApp {
+    /// Adds a task to the inbox with just its title, returning its ID.
+    pub fn capture(&mut self, title: &str) -> Result<u32, &'static str> {
+        let mut task = self.new_task(title, "", None)?;
+        task.inbox = true;
+        self.create_task(task)
+    }
+
+    /// The open tasks in the inbox of the active workspace, or the current
+    /// user's personal inbox, oldest first.
+    pub fn inbox(&self) -> Result<Vec<&Task>, &'static str> {
+        let mut tasks: Vec<&Task> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| task.inbox && !task.completed)
+            .collect();
+        tasks.sort_by_key(|task| (task.created_at, task.id));
+        Ok(tasks)
+    }
+
+    /// Organizes a task from the inbox and takes it out. The defaults of its
+    /// project and tags fill in what triage didn't decide, as for tasks added
+    /// with `quick`.
+    pub fn triage_task(&mut self, task_id: u32, triage: Triage) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if !task.inbox {
+            return Err("Task is not in the inbox");
+        }
+        if triage.project.as_deref().is_some_and(str::is_empty) {
+            return Err("Project name cannot be empty");
+        }
+
+        let mut task = task.clone();
+        if triage.project.is_some() {
+            task.project = triage.project;
+        }
+        if triage.due_date.is_some() {
+            task.due_date = triage.due_date;
+        }
+        self.apply_defaults(&mut task);
+        if let Some(priority) = triage.priority {
+            task.priority = priority;
+        }
+        task.inbox = false;
+        rules::apply(&self.rules, &mut task, Trigger::Updated);
+        task.touch();
+        self.insert_task(task);
+        self.save_tasks(&format!("Triage task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+}
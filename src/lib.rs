@@ -0,0 +1,1664 @@
+//! A multi-user todo app. `run` is the command line the `Lab3` binary
+//! starts; `server::Api` serves its HTTP API from inside another service.
+
+use chrono::{serde::ts_seconds, serde::ts_seconds_option, DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod accessibility;
+mod account;
+mod activity;
+mod agenda;
+mod aliases;
+mod assignees;
+mod attachments;
+mod benchdata;
+#[cfg(feature = "bot")]
+mod bot;
+mod bulk;
+#[cfg(feature = "caldav")]
+mod caldav;
+mod calendar;
+mod cli;
+mod commands;
+mod comments;
+mod config;
+mod contexts;
+mod cron;
+mod crypto;
+mod defaults;
+mod dependencies;
+mod digest;
+mod doctor;
+mod dot;
+mod duplicate;
+mod editor;
+mod encryption;
+mod escalation;
+mod expiration;
+mod fields;
+mod filters;
+mod focus;
+mod formats;
+mod fsck;
+mod grep;
+mod groups;
+mod habits;
+mod holidays;
+mod hooks;
+mod http;
+mod i18n;
+mod icons;
+#[cfg(feature = "imap")]
+mod imap;
+mod importing;
+mod inbox;
+mod lineedit;
+mod links;
+mod lock;
+mod log;
+mod mail;
+mod markdown;
+mod merge;
+mod metrics;
+mod next;
+mod notifications;
+mod notifier;
+mod ordering;
+mod orphans;
+mod passwords;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod picker;
+mod planner;
+mod presence;
+mod priority;
+mod progress;
+mod query;
+mod quickadd;
+mod quota;
+mod reminderqueue;
+mod reminders;
+mod report;
+mod retention;
+mod rules;
+mod schedule;
+pub mod server;
+mod settings;
+mod setup;
+mod shares;
+mod shutdown;
+mod snapshots;
+mod stale;
+mod statsexport;
+mod storage;
+mod suggestions;
+mod tags;
+mod tar;
+mod terminal;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod timesheet;
+mod todotxt;
+mod tokens;
+mod toml;
+mod transaction;
+mod trash;
+mod usagestats;
+mod usernames;
+mod validation;
+mod webhooks;
+mod websocket;
+mod workspaces;
+mod yaml;
+
+use attachments::Attachment;
+use cli::Cli;
+use config::{Config, LOCAL_USER};
+use defaults::{UserDefaults, DEFAULTS_FILE};
+use groups::GROUPS_FILE;
+use habits::{Habit, HABITS_FILE};
+use hooks::{Hooks, HooksConfig};
+use i18n::{tr, trf};
+use lineedit::LineEditor;
+use mail::{Mailer, SendmailMailer};
+use notifier::Notifier;
+use priority::Priority;
+use quota::Quota;
+use reminders::{CronReminder, Reminder};
+use retention::Retention;
+use rules::{Rule, Trigger};
+use settings::{Role, UserSettings, SETTINGS_FILE};
+use shares::{Share, SHARES_FILE};
+use snapshots::{Snapshot, SNAPSHOTS_FILE};
+use storage::{FileStorage, GitStorage, Storage, StorageKind};
+use timesheet::TimeEntry;
+use tokens::Token;
+use trash::{Trashed, TRASH_FILE};
+use webhooks::Webhooks;
+use workspaces::{Workspaces, WORKSPACES_FILE};
+
+const TASKS_FILE: &str = "tasks.json";
+const USERS_FILE: &str = "users.json";
+
+/// Whether confirmation prompts are skipped, from `--yes` or the config file.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Error returned when a task was changed by someone else in the meantime.
+const CONFLICT: &str = "Conflict: the task was changed since it was loaded";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Task {
+    /// Short ID for referring to the task interactively.
+    id: u32,
+    /// Identity of the task across machines, used when syncing, importing
+    /// and exporting. Numeric IDs may collide between data sets.
+    #[serde(default)]
+    uuid: String,
+    title: String,
+    description: String,
+    completed: bool,
+    #[serde(with = "ts_seconds")]
+    created_at: DateTime<Utc>,
+    user_id: String,
+    #[serde(default, with = "ts_seconds_option")]
+    due_date: Option<DateTime<Utc>>,
+    /// Whether other users may view this task.
+    #[serde(default)]
+    public: bool,
+    #[serde(default)]
+    reminders: Vec<Reminder>,
+    /// Reminders repeating on a cron schedule.
+    #[serde(default)]
+    cron_reminders: Vec<CronReminder>,
+    #[serde(default, with = "ts_seconds_option")]
+    completed_at: Option<DateTime<Utc>>,
+    /// User-defined fields, e.g. `client` or `ticket`.
+    #[serde(default)]
+    custom: BTreeMap<String, String>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    /// Name of the project the task belongs to.
+    #[serde(default)]
+    project: Option<String>,
+    /// Pinned tasks are always listed first.
+    #[serde(default)]
+    pinned: bool,
+    /// Workspace the task belongs to; `None` for personal tasks.
+    #[serde(default)]
+    workspace: Option<String>,
+    /// User responsible for the task, who may differ from its creator.
+    #[serde(default)]
+    assignee: Option<String>,
+    #[serde(default)]
+    tags: BTreeSet<String>,
+    #[serde(default)]
+    priority: Priority,
+    /// UUIDs of the tasks that must be completed before this one.
+    #[serde(default)]
+    depends_on: BTreeSet<String>,
+    /// Set by snoozing: the task isn't suggested as a next action until then.
+    #[serde(default, with = "ts_seconds_option")]
+    snoozed_until: Option<DateTime<Utc>>,
+    /// Expected effort, in seconds.
+    #[serde(default)]
+    estimate_secs: Option<i64>,
+    /// Where the task can be done, e.g. `home`; `None` if anywhere.
+    #[serde(default)]
+    context: Option<String>,
+    /// IDs of related tasks this task links to.
+    #[serde(default)]
+    relates_to: Vec<u32>,
+    /// Place in the manual order of its project, from 1; `None` until the
+    /// user moves a task of the project, which puts it after the others.
+    #[serde(default)]
+    position: Option<u32>,
+    /// How far along the task is, in percent, as set by hand.
+    #[serde(default)]
+    progress: Option<u8>,
+    /// When the task's priority was last raised for being overdue.
+    #[serde(default, with = "ts_seconds_option")]
+    escalated_at: Option<DateTime<Utc>>,
+    /// When the task is cancelled if it isn't completed by then.
+    #[serde(default, with = "ts_seconds_option")]
+    expires_at: Option<DateTime<Utc>>,
+    /// When the task was cancelled for expiring. Cancelled tasks are also
+    /// marked completed, so they're closed everywhere, but they don't count
+    /// as done.
+    #[serde(default, with = "ts_seconds_option")]
+    cancelled_at: Option<DateTime<Utc>>,
+    /// Time logged on the task.
+    #[serde(default)]
+    time_entries: Vec<TimeEntry>,
+    /// Emoji or short text shown before the title.
+    #[serde(default)]
+    icon: Option<String>,
+    /// Incremented on every change, so stale edits can be detected.
+    #[serde(default)]
+    version: u32,
+    /// When the task was last changed; `None` if it hasn't been since it
+    /// was created.
+    #[serde(default, with = "ts_seconds_option")]
+    updated_at: Option<DateTime<Utc>>,
+    /// Captured with `inbox` and waiting to be triaged.
+    #[serde(default)]
+    inbox: bool,
+}
+
+impl Task {
+    /// Records a change made by a user.
+    fn touch(&mut self) {
+        self.version += 1;
+        self.updated_at = Some(Utc::now());
+    }
+
+    /// When the task was last changed or, failing that, created.
+    fn last_changed(&self) -> DateTime<Utc> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+}
+
+/// Kinds of task changes reported to integrations such as webhooks and hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TaskEvent {
+    Created,
+    Completed,
+    Deleted,
+    /// The task was assigned to someone other than the user making the change.
+    Assigned,
+    /// The task's priority was raised for being overdue too long.
+    Escalated,
+    /// A reminder of the task fired.
+    Reminded,
+    /// The task expired before it was completed.
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct User {
+    username: String,
+    password: String,
+    /// Address that digests are emailed to.
+    #[serde(default)]
+    email: Option<String>,
+    /// Task limits set by an admin, overriding the configured default.
+    #[serde(default)]
+    quota: Option<Quota>,
+}
+
+struct TodoApp {
+    tasks: BTreeMap<u32, Task>,
+    /// IDs of the tasks each user created, so their tasks can be found
+    /// without scanning everyone's. Kept up to date by `insert_task` and
+    /// `remove_task`.
+    tasks_by_user: HashMap<String, BTreeSet<u32>>,
+    users: BTreeMap<String, User>,
+    current_user: Option<String>,
+    /// Preferences of each user, keyed by username.
+    settings: BTreeMap<String, UserSettings>,
+    /// API tokens, keyed by the hash of the token.
+    tokens: BTreeMap<String, Token>,
+    workspaces: Workspaces,
+    /// Workspace whose tasks are shown; `None` for the personal task list.
+    active_workspace: Option<String>,
+    /// Habits of each user, keyed by username and then habit name.
+    habits: BTreeMap<String, BTreeMap<String, Habit>>,
+    /// Daily task counts of each user, keyed by username and then day.
+    snapshots: BTreeMap<String, BTreeMap<NaiveDate, Snapshot>>,
+    /// Project and tag defaults of each user, keyed by username.
+    defaults: BTreeMap<String, UserDefaults>,
+    /// Share links, keyed by the hash of the link's token.
+    shares: BTreeMap<String, Share>,
+    /// Deleted tasks, keyed by their UUID.
+    trash: BTreeMap<String, Trashed>,
+    /// URL that share links start with.
+    share_url: String,
+    /// Editor command for bulk edits, if set in the config.
+    editor: Option<String>,
+    next_task_id: u32,
+    storage: Box<dyn Storage>,
+    webhooks: Webhooks,
+    hooks: Hooks,
+    mailer: Box<dyn Mailer>,
+    /// When set, changes are reported instead of saved and no integrations run.
+    dry_run: bool,
+    /// Task limits of users without their own quota.
+    default_quota: Quota,
+    /// Users allowed to administer other accounts.
+    admins: Vec<String>,
+    /// Automation rules run when tasks are created or edited.
+    rules: Vec<Rule>,
+    /// How long deleted tasks and history are kept.
+    retention: Retention,
+    /// Whether commands run and tasks created are counted for `usage`.
+    track_usage: bool,
+}
+
+impl TodoApp {
+    /// Create a new, empty `TodoApp` persisting through `storage`.
+    fn new(storage: Box<dyn Storage>) -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            tasks_by_user: HashMap::new(),
+            users: BTreeMap::new(),
+            current_user: None,
+            settings: BTreeMap::new(),
+            tokens: BTreeMap::new(),
+            workspaces: Workspaces::default(),
+            active_workspace: None,
+            habits: BTreeMap::new(),
+            snapshots: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            shares: BTreeMap::new(),
+            trash: BTreeMap::new(),
+            share_url: server::ServerConfig::default().base_url(),
+            editor: None,
+            next_task_id: 1,
+            storage,
+            webhooks: Webhooks::default(),
+            hooks: Hooks::default(),
+            mailer: Box::new(SendmailMailer::new(Default::default())),
+            dry_run: false,
+            default_quota: Quota::default(),
+            admins: Vec::new(),
+            rules: Vec::new(),
+            retention: Retention::default(),
+            track_usage: false,
+        }
+    }
+
+    /// Turns on dry-run mode: nothing is written to storage and webhooks and
+    /// hooks are disabled, but every change that would be saved is reported.
+    fn set_dry_run(&mut self) {
+        self.dry_run = true;
+        self.webhooks = Webhooks::default();
+        self.hooks = Hooks::new(HooksConfig {
+            enabled: false,
+            ..Default::default()
+        });
+    }
+
+    /// In dry-run mode, reports the change described by `message` and returns
+    /// `true` so the caller skips saving it.
+    fn skip_save(&self, message: &str) -> bool {
+        if self.dry_run {
+            println!("{}", trf("Dry run, not saved: {}", &[&message]));
+        }
+        self.dry_run
+    }
+
+    /// Registers a new user under the normalized username. Returns an error
+    /// if the username is taken, in any case or spelling of its accents.
+    fn register(&mut self, username: &str, password: &str) -> Result<(), &'static str> {
+        let username = &usernames::normalize(&validation::username(username)?);
+        if self
+            .users
+            .keys()
+            .any(|name| usernames::normalize(name) == *username)
+        {
+            return Err("Username already exists");
+        }
+
+        let password = passwords::hash(password)?;
+        self.users.insert(
+            username.to_string(),
+            User {
+                username: username.to_string(),
+                password,
+                email: None,
+                quota: None,
+            },
+        );
+        self.save_users(&format!("Register user {}", username))
+            .map_err(|_| "Failed to save users")?;
+        log::info(&format!("Registered user {}", username));
+        Ok(())
+    }
+
+    /// Logs in a user if the credentials are valid.
+    fn login(&mut self, username: &str, password: &str) -> Result<(), &'static str> {
+        let username = self.find_user(username).unwrap_or(username).to_string();
+        match self.users.get(&username) {
+            Some(user) if passwords::verify(&user.password, password) => {
+                self.current_user = Some(username.clone());
+                self.apply_settings();
+                // A snapshot that fails to save is retried by autosave.
+                let _ = self.record_snapshot();
+                log::info(&format!("User {} logged in", username));
+                Ok(())
+            }
+            _ => {
+                log::warn(&format!("Failed login as {}", username));
+                metrics::record_auth_failure();
+                Err("Invalid username or password")
+            }
+        }
+    }
+
+    /// Logs out and into another account in one step. The current user stays
+    /// logged in if the credentials are wrong. Data is reloaded so the new
+    /// user sees changes saved by other sessions.
+    fn switch_user(&mut self, username: &str, password: &str) -> Result<(), &'static str> {
+        self.current_user.as_ref().ok_or("Not logged in")?;
+        match self
+            .find_user(username)
+            .and_then(|username| self.users.get(username))
+        {
+            Some(user) if passwords::verify(&user.password, password) => {}
+            _ => {
+                log::warn(&format!("Failed switch to user {}", username));
+                return Err("Invalid username or password");
+            }
+        }
+
+        self.logout();
+        self.load_tasks().map_err(|_| "Failed to load tasks")?;
+        self.load_settings()
+            .map_err(|_| "Failed to load settings")?;
+        self.load_workspaces()
+            .map_err(|_| "Failed to load workspaces")?;
+        self.load_groups().map_err(|_| "Failed to load groups")?;
+        self.load_habits().map_err(|_| "Failed to load habits")?;
+        self.load_snapshots()
+            .map_err(|_| "Failed to load snapshots")?;
+        self.load_defaults()
+            .map_err(|_| "Failed to load defaults")?;
+        self.load_shares().map_err(|_| "Failed to load shares")?;
+        self.load_tokens().map_err(|_| "Failed to load tokens")?;
+        self.load_trash().map_err(|_| "Failed to load the trash")?;
+        self.login(username, password)
+    }
+
+    /// A task for the currently logged-in user, not yet added.
+    fn new_task(
+        &self,
+        title: &str,
+        description: &str,
+        due_date: Option<DateTime<Utc>>,
+    ) -> Result<Task, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if !self
+            .workspaces
+            .can_add(self.active_workspace.as_deref(), &user_id)
+        {
+            return Err("Not authorized to add tasks to this workspace");
+        }
+        Ok(Task {
+            id: self.next_task_id,
+            uuid: crypto::uuid_v4(),
+            title: validation::title(title)?,
+            description: description.to_string(),
+            completed: false,
+            created_at: Utc::now(),
+            user_id,
+            due_date,
+            public: false,
+            reminders: Vec::new(),
+            cron_reminders: Vec::new(),
+            completed_at: None,
+            custom: BTreeMap::new(),
+            attachments: Vec::new(),
+            project: None,
+            pinned: false,
+            workspace: self.active_workspace.clone(),
+            assignee: None,
+            tags: BTreeSet::new(),
+            priority: Priority::default(),
+            depends_on: BTreeSet::new(),
+            snoozed_until: None,
+            estimate_secs: None,
+            context: None,
+            relates_to: Vec::new(),
+            position: None,
+            progress: None,
+            escalated_at: None,
+            expires_at: None,
+            cancelled_at: None,
+            time_entries: Vec::new(),
+            icon: None,
+            version: 1,
+            updated_at: None,
+            inbox: false,
+        })
+    }
+
+    /// Adds `task` under the next free ID and returns the ID.
+    fn create_task(&mut self, mut task: Task) -> Result<u32, &'static str> {
+        self.check_quota(1)?;
+        let task_id = self.next_task_id;
+        task.id = task_id;
+        rules::apply(&self.rules, &mut task, Trigger::Created);
+        self.insert_task(task);
+        self.next_task_id += 1;
+        self.save_tasks(&format!("Add task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        self.emit(TaskEvent::Created, &self.tasks[&task_id]);
+        Ok(task_id)
+    }
+
+    /// Tells webhooks and hook scripts about a task event and records it in
+    /// the activity feed as a change by the current user.
+    fn emit(&self, event: TaskEvent, task: &Task) {
+        self.notify_integrations(event, task);
+        self.record_activity(event, task);
+    }
+
+    /// `emit` for a change made by `user` rather than the current user, such
+    /// as the app itself.
+    fn emit_by(&self, user: &str, event: TaskEvent, task: &Task) {
+        self.notify_integrations(event, task);
+        self.record_activity_by(user, event, task);
+    }
+
+    /// Tells webhooks and hook scripts about a task event without recording
+    /// it in the activity feed.
+    fn notify_integrations(&self, event: TaskEvent, task: &Task) {
+        self.webhooks.dispatch(event, task);
+        self.hooks.run(event, task);
+    }
+
+    /// Marks a task as completed if it belongs to the current user.
+    fn complete_task(&mut self, task_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.completed = true;
+        task.completed_at = Some(Utc::now());
+        task.touch();
+        self.save_tasks(&format!("Complete task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        self.emit(TaskEvent::Completed, &self.tasks[&task_id]);
+        Ok(())
+    }
+
+    /// Edits the title and description of a user's task. Fails with
+    /// `CONFLICT` if the task changed since `expected_version` was read.
+    fn edit_task(
+        &mut self,
+        task_id: u32,
+        expected_version: u32,
+        title: &str,
+        description: &str,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if task.version != expected_version {
+            return Err(CONFLICT);
+        }
+
+        task.title = validation::title(title)?;
+        task.description = description.to_string();
+        rules::apply(&self.rules, task, Trigger::Updated);
+        task.touch();
+        self.save_tasks(&format!("Edit task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Deletes a task if it belongs to the current user.
+    fn delete_task(&mut self, task_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to delete this task");
+        }
+
+        let task = self.remove_task(task_id).ok_or("Task not found")?;
+        self.remove_links_to(task_id);
+        self.save_tasks(&format!("Delete task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        self.move_to_trash(std::slice::from_ref(&task), &user_id);
+        self.emit(TaskEvent::Deleted, &task);
+        Ok(())
+    }
+
+    /// Lists the tasks of the active workspace, or the current user's personal
+    /// tasks, in their preferred order, pinned tasks first.
+    fn list_tasks(&self) -> Result<Vec<&Task>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let sort = self.settings()?.sort;
+        let mut tasks: Vec<&Task> = match &self.active_workspace {
+            Some(workspace) => self
+                .tasks
+                .values()
+                .filter(|task| task.workspace.as_ref() == Some(workspace))
+                .collect(),
+            None => self
+                .own_tasks(user_id)
+                .filter(|task| task.workspace.is_none())
+                .collect(),
+        };
+        tasks.sort_by(|a, b| b.pinned.cmp(&a.pinned).then_with(|| sort.compare(a, b)));
+        Ok(tasks)
+    }
+
+    /// Pins a task to the top of lists, or unpins it.
+    fn set_task_pinned(&mut self, task_id: u32, pinned: bool) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.pinned = pinned;
+        task.touch();
+        let action = if pinned { "Pin" } else { "Unpin" };
+        self.save_tasks(&format!("{} task #{}", action, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Moves a task into a project, or out of any project with `None`.
+    fn set_task_project(
+        &mut self,
+        task_id: u32,
+        project: Option<&str>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if project.is_some_and(str::is_empty) {
+            return Err("Project name cannot be empty");
+        }
+
+        task.project = project.map(str::to_string);
+        task.touch();
+        self.save_tasks(&format!("Move task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Sets how long a task is expected to take, or removes the estimate.
+    fn set_task_estimate(
+        &mut self,
+        task_id: u32,
+        estimate: Option<Duration>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.estimate_secs = estimate.map(|estimate| estimate.num_seconds());
+        task.touch();
+        self.save_tasks(&format!("Estimate task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Shares a task with other users, or makes it private again.
+    fn set_task_public(&mut self, task_id: u32, public: bool) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.public = public;
+        task.touch();
+        let action = if public { "Publish" } else { "Unpublish" };
+        self.save_tasks(&format!("{} task #{}", action, task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Lists another user's public tasks. Private tasks are never returned.
+    fn list_public_tasks(&self, username: &str) -> Result<Vec<&Task>, &'static str> {
+        self.current_user.as_ref().ok_or("Not logged in")?;
+        let username = self.find_user(username).ok_or("User not found")?;
+        Ok(self
+            .own_tasks(username)
+            .filter(|task| task.public)
+            .collect())
+    }
+
+    /// The tasks `username` created, by ID.
+    fn own_tasks<'a>(&'a self, username: &str) -> impl Iterator<Item = &'a Task> + 'a {
+        self.tasks_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.tasks.get(id))
+    }
+
+    /// Adds or replaces a task, updating the index of each user's tasks.
+    fn insert_task(&mut self, task: Task) {
+        self.remove_task(task.id);
+        self.tasks_by_user
+            .entry(task.user_id.clone())
+            .or_default()
+            .insert(task.id);
+        self.tasks.insert(task.id, task);
+    }
+
+    /// Removes a task, updating the index of each user's tasks.
+    fn remove_task(&mut self, task_id: u32) -> Option<Task> {
+        let task = self.tasks.remove(&task_id)?;
+        if let Some(ids) = self.tasks_by_user.get_mut(&task.user_id) {
+            ids.remove(&task_id);
+            if ids.is_empty() {
+                self.tasks_by_user.remove(&task.user_id);
+            }
+        }
+        Some(task)
+    }
+
+    /// Saves all tasks to a JSON file. `message` describes the change being saved.
+    fn save_tasks(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), TASKS_FILE, &self.tasks, message)
+            .inspect_err(|e| log::error(&format!("Failed to save tasks ({}): {}", message, e)))
+    }
+
+    /// Loads tasks from the JSON file. If the file doesn't exist, it's ignored.
+    fn load_tasks(&mut self) -> io::Result<()> {
+        // It's okay if no tasks file exists yet.
+        if let Some(tasks) = storage::load_json(self.storage.as_ref(), TASKS_FILE)? {
+            self.tasks = tasks;
+            // Tasks saved before UUIDs existed get one derived from what
+            // identifies them, so every load on every machine agrees on it
+            // until it's stored on the next save.
+            for task in self.tasks.values_mut().filter(|task| task.uuid.is_empty()) {
+                task.uuid = crypto::uuid_v5(&format!(
+                    "task/{}/{}/{}",
+                    task.user_id,
+                    task.id,
+                    task.created_at.timestamp()
+                ));
+            }
+            self.next_task_id = self.tasks.keys().max().map_or(1, |max| max + 1);
+            self.tasks_by_user.clear();
+            for task in self.tasks.values() {
+                self.tasks_by_user
+                    .entry(task.user_id.clone())
+                    .or_default()
+                    .insert(task.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves all users to a JSON file. `message` describes the change being saved.
+    fn save_users(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), USERS_FILE, &self.users, message)
+            .inspect_err(|e| log::error(&format!("Failed to save users ({}): {}", message, e)))
+    }
+
+    /// Loads users from a JSON file. If the file doesn't exist, it's ignored.
+    /// Passwords saved before they were hashed are hashed and saved again.
+    fn load_users(&mut self) -> io::Result<()> {
+        if let Some(users) = storage::load_json(self.storage.as_ref(), USERS_FILE)? {
+            self.users = users;
+        }
+        let mut rehashed = false;
+        for user in self.users.values_mut() {
+            if !passwords::is_hashed(&user.password) {
+                user.password = passwords::hash(&user.password).map_err(io::Error::other)?;
+                rehashed = true;
+            }
+        }
+        if rehashed {
+            // The plaintext stays on disk until this succeeds, but the
+            // hashes in memory still work.
+            let _ = self.save_users("Hash stored passwords");
+        }
+        Ok(())
+    }
+
+    /// Sets the email address of the current user's account.
+    fn set_email(&mut self, email: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if !email.contains('@') {
+            return Err("Invalid email address");
+        }
+        let user = self.users.get_mut(&user_id).ok_or("User not found")?;
+        user.email = Some(email.to_string());
+        self.save_users(&format!("Set email of user {}", user_id))
+            .map_err(|_| "Failed to save users")?;
+        Ok(())
+    }
+
+    /// Logs in as the local default user without checking any credentials.
+    fn login_local(&mut self) {
+        self.current_user = Some(LOCAL_USER.to_string());
+        self.apply_settings();
+        let _ = self.record_snapshot();
+    }
+
+    /// Logs out the current user.
+    fn logout(&mut self) {
+        if let Some(username) = &self.current_user {
+            log::info(&format!("User {} logged out", username));
+        }
+        self.active_workspace = None;
+        self.current_user = None;
+    }
+
+    fn is_logged_in(&self) -> bool {
+        self.current_user.is_some()
+    }
+}
+
+/// Helper function to print a prompt and read a trimmed line of input.
+fn prompt_input(prompt: &str) -> Result<String, io::Error> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let stdin = io::stdin();
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    lock::record_input();
+    Ok(line.trim().to_string())
+}
+
+/// Prints today's focus list, if the user picked any tasks for it.
+fn print_focus(app: &TodoApp) {
+    let Ok(tasks) = app.focus_tasks() else {
+        return;
+    };
+    if tasks.is_empty() {
+        return;
+    }
+    println!("\n== {} ==", tr("Today's focus"));
+    for task in tasks {
+        let marker = if task.completed { "[x]" } else { "[ ]" };
+        println!("{} #{} {}", marker, task.id, task.title);
+    }
+}
+
+/// Says which context the list is limited to, if any.
+fn print_context_note(app: &TodoApp) {
+    if let Some(context) = app.current_context() {
+        println!(
+            "{}",
+            trf(
+                "Showing tasks for @{} and tasks without a context (`list --all` shows all)",
+                &[&context]
+            )
+        );
+    }
+}
+
+/// Points out stale tasks if the user asked to be told about them on login.
+fn print_stale_warning(app: &TodoApp) {
+    if let Some((count, days)) = app.stale_warning() {
+        println!(
+            "{}",
+            trf(
+                "{} task(s) haven't changed in over {} days, see `stale`",
+                &[&count, &days]
+            )
+        );
+    }
+}
+
+/// Asks for a task ID, offering the current list's pending tasks to pick from.
+/// An empty result means the user cancelled.
+fn pick_pending_task(app: &TodoApp) -> io::Result<String> {
+    let tasks: Vec<&Task> = app
+        .list_tasks()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|task| !task.completed)
+        .collect();
+    picker::pick_task(tr("Task ID: "), &tasks)
+}
+
+/// Asks a yes/no question, defaulting to no. Always yes with `--yes`.
+fn confirm(prompt: &str) -> io::Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    let answer = prompt_input(&format!("{} {}", prompt, tr("[y/N] ")))?;
+    Ok(answer.eq_ignore_ascii_case("y"))
+}
+
+/// Reads a description, opening the external editor when `--editor` is set
+/// or the user answers `:edit`. `current` is the text the editor starts with.
+fn prompt_description(config: &Config, prompt: &str, current: &str) -> io::Result<String> {
+    if !config.use_editor {
+        let input = prompt_input(&format!("{}{}", tr("(:edit opens the editor) "), prompt))?;
+        if input != ":edit" {
+            return Ok(input);
+        }
+    }
+    editor::edit_text(current, config.editor.as_deref())
+}
+
+/// Parses an optional `YYYY-MM-DD` date; blank input means no date.
+fn parse_due_date(input: &str) -> Result<Option<DateTime<Utc>>, &'static str> {
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let date = NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .map_err(|_| "Invalid date, expected YYYY-MM-DD")?;
+    Ok(Some(schedule::start_of_day(date)))
+}
+
+/// Prints the details of a task. The tasks it waits for are only shown to
+/// users with access to it.
+fn print_task(app: &TodoApp, task: &Task) {
+    if task.pinned {
+        println!(
+            "\n{} {}",
+            trf("ID: {}", &[&app.display_id(task)]),
+            tr("(pinned)")
+        );
+    } else {
+        println!("\n{}", trf("ID: {}", &[&app.display_id(task)]));
+    }
+    println!("{}", trf("Title: {}", &[&app.title_with_icon(task)]));
+    let description = markdown::render(&task.description).replace('\n', "\n    ");
+    println!("{}", trf("Description: {}", &[&description]));
+    let status = if task.cancelled_at.is_some() {
+        theme::paint(tr("Cancelled"), Role::Completed)
+    } else if task.completed {
+        theme::paint(tr("Completed"), Role::Completed)
+    } else {
+        theme::paint(tr("Pending"), Role::Pending)
+    };
+    println!("{}", trf("Status: {}", &[&status]));
+    println!(
+        "{}",
+        trf("Created: {}", &[&i18n::format_datetime(task.created_at)])
+    );
+    if let Some(updated_at) = task.updated_at {
+        println!(
+            "{}",
+            trf("Last changed: {}", &[&i18n::format_datetime(updated_at)])
+        );
+    }
+    if let Some(due) = task.due_date {
+        let due_text = i18n::format_date(due);
+        let due_text = if !task.completed && due < schedule::start_of_today() {
+            if accessibility::screen_reader() {
+                trf("{} (overdue)", &[&due_text])
+            } else {
+                theme::paint(&due_text, Role::Overdue)
+            }
+        } else {
+            due_text
+        };
+        println!("{}", trf("Due: {}", &[&due_text]));
+    }
+    match task.expires_at {
+        Some(at) if task.cancelled_at.is_some() => {
+            println!("{}", trf("Expired: {}", &[&i18n::format_datetime(at)]))
+        }
+        Some(at) if !task.completed => {
+            println!("{}", trf("Expires: {}", &[&i18n::format_datetime(at)]))
+        }
+        _ => {}
+    }
+    if let Some(project) = &task.project {
+        println!("{}", trf("Project: {}", &[project]));
+    }
+    if let Some(context) = &task.context {
+        println!("{}", trf("Context: @{}", &[context]));
+    }
+    if let Some(workspace) = &task.workspace {
+        println!(
+            "{}",
+            trf("Workspace: {} (created by {})", &[workspace, &task.user_id])
+        );
+    }
+    if let Some(assignee) = &task.assignee {
+        println!("{}", trf("Assigned to: {}", &[assignee]));
+    }
+    let priority = theme::paint_priority(tr(task.priority.name()), task.priority);
+    match task.escalated_at.filter(|_| !task.completed) {
+        Some(at) => println!(
+            "{}",
+            trf(
+                "Priority: {} (escalated {})",
+                &[&priority, &i18n::format_date(at)]
+            )
+        ),
+        None if task.priority != Priority::Normal => {
+            println!("{}", trf("Priority: {}", &[&priority]))
+        }
+        None => {}
+    }
+    if let Some(estimate) = task.estimate_secs {
+        println!(
+            "{}",
+            trf(
+                "Estimate: {}",
+                &[&schedule::format_duration(Duration::seconds(estimate))]
+            )
+        );
+    }
+    if !task.time_entries.is_empty() {
+        println!(
+            "{}",
+            trf(
+                "Time spent: {}",
+                &[&schedule::format_duration(timesheet::time_spent(task))]
+            )
+        );
+    }
+    if let Some(percent) = app.progress(task).filter(|_| !task.completed) {
+        println!("{}", trf("Progress: {}", &[&progress::bar(percent)]));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task
+            .tags
+            .iter()
+            .map(|tag| theme::paint_tag(&format!("@{}", tag), tag))
+            .collect();
+        println!("{}", trf("Tags: {}", &[&tags.join(" ")]));
+    }
+    if let Some(user_id) = app
+        .current_user
+        .as_ref()
+        .filter(|user_id| app.workspaces.can_access(task, user_id))
+    {
+        for blocker in app
+            .blockers(task)
+            .into_iter()
+            .filter(|blocker| app.workspaces.can_access(blocker, user_id))
+        {
+            println!(
+                "{}",
+                trf("Waiting on: #{} {}", &[&blocker.id, &blocker.title])
+            );
+        }
+    }
+    for linked in app.links(task) {
+        println!("{}", trf("Links to: #{} {}", &[&linked.id, &linked.title]));
+    }
+    for linking in app.backlinks(task) {
+        println!(
+            "{}",
+            trf("Linked from: #{} {}", &[&linking.id, &linking.title])
+        );
+    }
+    if let Some(until) = task.snoozed_until.filter(|until| *until > Utc::now()) {
+        println!(
+            "{}",
+            trf("Snoozed until: {}", &[&i18n::format_datetime(until)])
+        );
+    }
+    if task.public {
+        println!("{}", tr("Visibility: public"));
+    }
+    for reminder in &task.reminders {
+        println!("{}", trf("Reminder: {}", &[&reminders::describe(reminder)]));
+    }
+    for reminder in &task.cron_reminders {
+        println!(
+            "{}",
+            trf(
+                "Reminder: {}",
+                &[&trf("on schedule {}", &[&reminder.schedule])]
+            )
+        );
+    }
+    for (name, value) in &task.custom {
+        println!("{}: {}", name, value);
+    }
+    for attachment in &task.attachments {
+        println!(
+            "{}",
+            trf(
+                "Attachment: {} ({})",
+                &[&attachment.name, &attachment.location]
+            )
+        );
+    }
+}
+
+/// Prints an error message in the interface language.
+fn print_error(message: &str) {
+    log::error(message);
+    println!("{}", trf("Error: {}", &[&tr(message)]));
+}
+
+/// Runs a single command given on the command line, e.g. from cron, and exits.
+/// In multi-user mode the password comes from `TODO_PASSWORD` or a prompt.
+fn run_once(app: &mut TodoApp, cli: &Cli) -> io::Result<()> {
+    if !app.is_logged_in() {
+        let username = match &cli.user {
+            Some(username) => username.clone(),
+            None => prompt_input(tr("Username: "))?,
+        };
+        let password = match std::env::var("TODO_PASSWORD") {
+            Ok(password) => password,
+            Err(_) => prompt_input(tr("Password: "))?,
+        };
+        if let Err(e) = app.login(&username, &password) {
+            print_error(e);
+            std::process::exit(1);
+        }
+    }
+
+    let args: Vec<&str> = cli.command.iter().map(String::as_str).collect();
+    if let Err(e) = commands::execute(app, &args) {
+        print_error(&e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// The storage backend chosen in `config`, for the data files in `dir`.
+fn open_storage(config: &Config, dir: &Path) -> io::Result<Box<dyn Storage + Send>> {
+    let files = FileStorage::new(config.data_format)
+        .in_dir(dir)
+        .keep_backups(config.retention.backups);
+    Ok(match config.storage {
+        StorageKind::File => Box::new(files),
+        StorageKind::Git => Box::new(GitStorage::open(files, config.git_remote.clone())?),
+    })
+}
+
+/// An app with the storage, integrations and policies of `config`, for
+/// the data files in `dir`, before any data is loaded.
+fn open_app(config: &Config, dir: &Path) -> io::Result<TodoApp> {
+    let mut app = TodoApp::new(open_storage(config, dir)?);
+    app.webhooks = Webhooks::new(config.webhooks.clone());
+    app.hooks = Hooks::new(config.hooks.clone());
+    app.mailer = Box::new(SendmailMailer::new(config.mail.clone()));
+    app.default_quota = config.quota;
+    app.admins = config.admins.clone();
+    app.retention = config.retention;
+    app.track_usage = config.usage_stats;
+    app.share_url = config.server.base_url();
+    app.editor = config.editor.clone();
+    match rules::validate(&config.rules) {
+        Ok(()) => app.rules = config.rules.clone(),
+        Err(e) => print_error(&e),
+    }
+    Ok(app)
+}
+
+/// Loads every data file, stopping at the first that fails with its name.
+fn load_data(app: &mut TodoApp) -> Result<(), (&'static str, io::Error)> {
+    for (name, load) in [
+        (
+            TASKS_FILE,
+            TodoApp::load_tasks as fn(&mut TodoApp) -> io::Result<()>,
+        ),
+        (USERS_FILE, TodoApp::load_users),
+        (SETTINGS_FILE, TodoApp::load_settings),
+        (WORKSPACES_FILE, TodoApp::load_workspaces),
+        (GROUPS_FILE, TodoApp::load_groups),
+        (HABITS_FILE, TodoApp::load_habits),
+        (SNAPSHOTS_FILE, TodoApp::load_snapshots),
+        (DEFAULTS_FILE, TodoApp::load_defaults),
+        (SHARES_FILE, TodoApp::load_shares),
+        (TRASH_FILE, TodoApp::load_trash),
+    ] {
+        if let Err(e) = load(app) {
+            log::error(&format!("Failed to load {}: {}", name, e));
+            return Err((name, e));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the app with the command line arguments of the process.
+pub fn run() -> io::Result<()> {
+    let cli = Cli::parse(std::env::args().skip(1));
+    log::set_verbose(cli.has_flag("--verbose"));
+    let doctor = cli
+        .command
+        .first()
+        .is_some_and(|command| command == "doctor");
+    let first_run = cli.command.is_empty()
+        && cli.flags.is_empty()
+        && terminal::is_interactive()
+        && Config::is_first_run();
+    let mut config = match Config::load() {
+        // `doctor` reports what's wrong with the config itself.
+        Err(_) if doctor => Config::default(),
+        result => result?,
+    };
+    config.apply_args(&cli.flags);
+    if let Some(dir) = &config.data_dir {
+        std::env::set_current_dir(dir)?;
+    }
+    if first_run {
+        setup::choose_data_dir(&mut config)?;
+    }
+    i18n::init(config.language);
+    accessibility::set(config.accessibility);
+    markdown::set_enabled(
+        config.markdown && !accessibility::screen_reader() && io::stdout().is_terminal(),
+    );
+    ASSUME_YES.store(config.assume_yes, Ordering::Relaxed);
+    storage::set_pretty_json(config.pretty_json);
+    match config.timezone.as_deref().map(schedule::parse_timezone) {
+        Some(Ok(offset)) => schedule::set_default_timezone(offset),
+        Some(Err(e)) => print_error(e),
+        None => {}
+    }
+    if let Err(e) = theme::customize(&config.theme, config.theme_file.as_deref()) {
+        print_error(&e);
+    }
+    theme::activate(settings::ColorScheme::default());
+
+    // The working directory is the data directory by now.
+    let dir = Path::new("");
+    shutdown::install(open_storage(&config, dir)?);
+    let mut app = open_app(&config, dir)?;
+    if cli.command.first().is_some_and(|command| command == "fsck") {
+        let dry_run = cli.has_flag("--dry-run");
+        if !fsck::run(app.storage.as_ref(), dry_run)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if doctor {
+        if !doctor::run(app.storage.as_ref())? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    match load_data(&mut app) {
+        Ok(()) => {}
+        Err((name, e)) if e.kind() == io::ErrorKind::InvalidData => {
+            eprintln!("{}", trf("{} is damaged: {}", &[&name, &e]));
+            eprintln!("{}", tr("Run fsck to check and repair the data files."));
+            std::process::exit(1);
+        }
+        Err((_, e)) => return Err(e),
+    }
+    if cli.has_flag("--dry-run") {
+        app.set_dry_run();
+    }
+    if first_run {
+        setup::run(&mut app, &mut config)?;
+    }
+    if let Some(count) = &cli.bench_data {
+        let generated = count
+            .parse()
+            .map_err(|_| "Invalid task count")
+            .and_then(|count| app.generate_bench_data(count));
+        if let Err(e) = generated {
+            print_error(e);
+            std::process::exit(1);
+        }
+        println!(
+            "{}",
+            trf(
+                "Added {} tasks for user {}",
+                &[count, &benchdata::BENCH_USER]
+            )
+        );
+        return Ok(());
+    }
+    if let Some(days) = config.escalate_after_days {
+        if let Err(e) = app.escalate_overdue(days) {
+            print_error(e);
+        }
+    }
+    if let Err(e) = app.cancel_expired(Utc::now()) {
+        print_error(e);
+    }
+    if let Err(e) = app.compact(Utc::now()) {
+        print_error(e);
+    }
+
+    if cli.command.is_empty() && config.autosave_secs > 0 {
+        shutdown::start_autosave(std::time::Duration::from_secs(config.autosave_secs));
+    }
+
+    if cli.has_flag("--daemon") {
+        return reminders::run_daemon(&mut app);
+    }
+    if cli.has_flag("--serve") {
+        return server::run(&mut app, &config.server);
+    }
+    #[cfg(feature = "imap")]
+    if cli.has_flag("--email-in") {
+        return match &config.imap {
+            Some(imap_config) => imap::run(&mut app, imap_config),
+            None => Err(io::Error::other(tr("No imap section in config.json"))),
+        };
+    }
+    #[cfg(feature = "bot")]
+    if cli.has_flag("--bot") {
+        return match &config.bot {
+            Some(bot_config) => bot::run(&mut app, bot_config),
+            None => Err(io::Error::other(tr("No bot section in config.json"))),
+        };
+    }
+
+    if config.single_user {
+        app.login_local();
+        if cli.command.is_empty() {
+            print_stale_warning(&app);
+        }
+    }
+
+    if !cli.command.is_empty() {
+        return run_once(&mut app, &cli);
+    }
+
+    let mut editor = LineEditor::open(lineedit::HISTORY_FILE);
+    let notifier = terminal::is_interactive().then(Notifier::start);
+    let idle_lock = config
+        .lock_after_mins
+        .filter(|mins| *mins > 0 && !config.single_user)
+        .map(|mins| lock::IdleLock::start(std::time::Duration::from_secs(mins * 60)));
+    loop {
+        if let Some(notifier) = &notifier {
+            notifier.watch(&app);
+        }
+        if !app.is_logged_in() {
+            // Display menu for non-logged in users
+            println!("\n{}", tr("Welcome to Todo App!"));
+            println!("1. {}", tr("Login"));
+            println!("2. {}", tr("Register"));
+            println!("3. {}", tr("Exit"));
+
+            let choice = editor.read_line(tr("Select an option: "), &|_| Vec::new())?;
+            match choice.as_str() {
+                "1" => {
+                    let username = prompt_input(tr("Username: "))?;
+                    let password = prompt_input(tr("Password: "))?;
+                    match app.login(&username, &password) {
+                        Ok(_) => {
+                            println!("{}", tr("Login successful!"));
+                            print_stale_warning(&app);
+                        }
+                        Err(e) => print_error(e),
+                    }
+                }
+                "2" => {
+                    let username = prompt_input(tr("Username: "))?;
+                    let password = prompt_input(tr("Password: "))?;
+                    match app.register(&username, &password) {
+                        Ok(_) => println!("{}", tr("Registration successful!")),
+                        Err(e) => print_error(e),
+                    }
+                }
+                "3" => {
+                    println!("{}", tr("Goodbye!"));
+                    break;
+                }
+                _ => println!("{}", tr("Invalid choice")),
+            }
+        } else {
+            // Display menu for logged in users
+            println!("\n{}", tr("Todo App Menu:"));
+            println!("1. {}", tr("Add Task"));
+            println!("2. {}", tr("List Tasks"));
+            println!("3. {}", tr("Complete Task"));
+            println!("4. {}", tr("Edit Task"));
+            println!("5. {}", tr("Delete Task"));
+            println!(
+                "6. {}",
+                tr(if config.single_user { "Exit" } else { "Logout" })
+            );
+            if !config.single_user {
+                println!("7. {}", tr("Switch User"));
+            }
+            #[cfg(feature = "caldav")]
+            println!("8. {}", tr("Sync Calendar"));
+            for (i, (name, _)) in app.saved_filters().iter().enumerate() {
+                println!("f{}. {}", i + 1, trf("Filter: {}", &[name]));
+            }
+            println!("{}", tr("Or type a command (help for a list)"));
+
+            let choice = editor.read_line(tr("Select an option: "), &|line| {
+                commands::complete(&app, line)
+            })?;
+            if let Some(idle_lock) = &idle_lock {
+                // Whatever was typed while locked is dropped.
+                let locked = idle_lock.is_locked();
+                idle_lock.touch();
+                if locked {
+                    lock::unlock(&mut app)?;
+                    continue;
+                }
+            }
+            if let Some(name) = filters::shortcut(&app, &choice) {
+                commands::run(&mut app, &format!("filter {}", name));
+                continue;
+            }
+            match choice.as_str() {
+                "1" => {
+                    let title = prompt_input(tr("Title: "))?;
+                    let picked = suggestions::offer(&app, &title)?;
+                    let description = prompt_description(&config, tr("Description: "), "")?;
+                    let due_date = prompt_input(tr("Due Date (YYYY-MM-DD, blank for none): "))?;
+                    match parse_due_date(&due_date)
+                        .and_then(|due| app.add_suggested_task(&title, &description, due, picked))
+                    {
+                        Ok(_) => println!("{}", tr("Task added successfully!")),
+                        Err(e) => print_error(e),
+                    }
+                }
+                "2" => {
+                    print_focus(&app);
+                    print_context_note(&app);
+                    match app.context_tasks() {
+                        Ok(tasks) => {
+                            for task in tasks {
+                                print_task(&app, task);
+                            }
+                        }
+                        Err(e) => print_error(e),
+                    }
+                }
+                "3" => {
+                    let id_str = pick_pending_task(&app)?;
+                    if id_str.is_empty() {
+                        println!("{}", tr("Cancelled"));
+                        continue;
+                    }
+                    match app.resolve_task_id(&id_str) {
+                        Ok(task_id) => match app.complete_task(task_id) {
+                            Ok(_) => println!("{}", tr("Task marked as completed!")),
+                            Err(e) => print_error(e),
+                        },
+                        Err(e) => print_error(&e),
+                    }
+                }
+                "4" => {
+                    let id_str = pick_pending_task(&app)?;
+                    if id_str.is_empty() {
+                        println!("{}", tr("Cancelled"));
+                        continue;
+                    }
+                    let editing = app.resolve_task_id(&id_str).ok();
+                    if let Some(task_id) = editing {
+                        for username in app.editors_of(task_id) {
+                            println!("{}", trf("{} is editing this task too", &[&username]));
+                        }
+                        app.mark_editing(Some(task_id));
+                    }
+                    let title = prompt_input(tr("New Title: "))?;
+                    let (current, version) = app
+                        .resolve_task_id(&id_str)
+                        .ok()
+                        .and_then(|task_id| app.tasks.get(&task_id))
+                        .filter(|task| {
+                            app.current_user
+                                .as_ref()
+                                .is_some_and(|user| app.workspaces.can_access(task, user))
+                        })
+                        .map(|task| (task.description.clone(), task.version))
+                        .unwrap_or_default();
+                    let description =
+                        prompt_description(&config, tr("New Description: "), &current)?;
+                    if editing.is_some() {
+                        app.mark_editing(None);
+                    }
+                    // Pick up changes other sessions saved while the user was typing.
+                    app.load_tasks()?;
+                    match app.resolve_task_id(&id_str) {
+                        Ok(task_id) => {
+                            match app.edit_task(task_id, version, &title, &description) {
+                                Ok(_) => println!("{}", tr("Task updated successfully!")),
+                                Err(e) => print_error(e),
+                            }
+                        }
+                        Err(e) => print_error(&e),
+                    }
+                }
+                "5" => {
+                    let id_str = pick_pending_task(&app)?;
+                    if id_str.is_empty() {
+                        println!("{}", tr("Cancelled"));
+                        continue;
+                    }
+                    match app.resolve_task_id(&id_str) {
+                        Ok(task_id) => {
+                            let title = app
+                                .tasks
+                                .get(&task_id)
+                                .filter(|task| {
+                                    app.current_user
+                                        .as_ref()
+                                        .is_some_and(|user| app.workspaces.can_access(task, user))
+                                })
+                                .map(|task| task.title.clone());
+                            if let Some(title) = title {
+                                if !confirm(&trf("Delete task #{} {}?", &[&task_id, &title]))? {
+                                    println!("{}", tr("Cancelled"));
+                                    continue;
+                                }
+                            }
+                            match app.delete_task(task_id) {
+                                Ok(_) => println!("{}", tr("Task deleted successfully!")),
+                                Err(e) => print_error(e),
+                            }
+                        }
+                        Err(e) => print_error(&e),
+                    }
+                }
+                "6" if config.single_user => {
+                    println!("{}", tr("Goodbye!"));
+                    break;
+                }
+                "6" => {
+                    app.logout();
+                    println!("{}", tr("Logged out successfully!"));
+                }
+                "7" if !config.single_user => {
+                    let username = prompt_input(tr("Username: "))?;
+                    let password = prompt_input(tr("Password: "))?;
+                    match app.switch_user(&username, &password) {
+                        Ok(_) => println!("{}", trf("Switched to {}", &[&username])),
+                        Err(e) => print_error(e),
+                    }
+                }
+                #[cfg(feature = "caldav")]
+                "8" if app.dry_run => print_error("Not available in dry-run mode"),
+                #[cfg(feature = "caldav")]
+                "8" => match &config.caldav {
+                    Some(caldav_config) => match caldav::sync(&mut app, caldav_config) {
+                        Ok(report) => println!(
+                            "{}",
+                            trf(
+                                "Pushed {} task(s), {} completed remotely, {} failed.",
+                                &[&report.pushed, &report.completed, &report.failed]
+                            )
+                        ),
+                        Err(e) => print_error(e),
+                    },
+                    None => print_error("CalDAV is not configured"),
+                },
+                line if commands::is_command(line) => commands::run(&mut app, line),
+                _ => println!("{}", tr("Invalid choice")),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemoryStorage;
+
+    /// Tasks saved before tasks had UUIDs.
+    const LEGACY_TASKS: &str = r#"{
+        "1": {"id": 1, "title": "Renew passport", "description": "", "completed": false,
+              "created_at": 1700000000, "user_id": "alice"},
+        "2": {"id": 2, "title": "Renew passport", "description": "", "completed": false,
+              "created_at": 1700000000, "user_id": "bob"}
+    }"#;
+
+    fn loaded(storage: &MemoryStorage) -> TodoApp {
+        let mut app = TodoApp::new(Box::new(storage.clone()));
+        app.load_tasks().unwrap();
+        app
+    }
+
+    #[test]
+    fn legacy_tasks_get_the_same_uuid_on_every_load() {
+        let storage = MemoryStorage::default();
+        storage.put(TASKS_FILE, LEGACY_TASKS);
+        let first = loaded(&storage);
+        let mut second = loaded(&storage);
+        second.load_tasks().unwrap();
+
+        for id in [1, 2] {
+            assert!(!first.tasks[&id].uuid.is_empty());
+            assert_eq!(first.tasks[&id].uuid, second.tasks[&id].uuid);
+        }
+        assert_ne!(first.tasks[&1].uuid, first.tasks[&2].uuid);
+        assert_eq!(first.tasks[&1].uuid.as_bytes()[14], b'5');
+    }
+
+    #[test]
+    fn stored_uuids_are_kept() {
+        let storage = MemoryStorage::default();
+        storage.put(TASKS_FILE, LEGACY_TASKS);
+        let app = loaded(&storage);
+        app.save_tasks("Save").unwrap();
+        let uuid = app.tasks[&1].uuid.clone();
+
+        assert!(storage.get(TASKS_FILE).unwrap().contains(&uuid));
+        assert_eq!(loaded(&storage).tasks[&1].uuid, uuid);
+    }
+
+    #[test]
+    fn only_password_hashes_are_saved() {
+        let storage = MemoryStorage::default();
+        let mut app = testing::app_on(&storage);
+        testing::register(&mut app, &["alice"]);
+
+        assert!(!storage.get(USERS_FILE).unwrap().contains(testing::PASSWORD));
+        assert!(app.login("alice", "wrong").is_err());
+        testing::login(&mut app, "alice");
+    }
+
+    #[test]
+    fn passwords_saved_before_hashing_are_hashed_on_load() {
+        let storage = MemoryStorage::default();
+        storage.put(
+            USERS_FILE,
+            r#"{"alice": {"username": "alice", "password": "hunter2"}}"#,
+        );
+        let mut app = testing::app_on(&storage);
+        app.load_users().unwrap();
+
+        assert!(passwords::is_hashed(&app.users["alice"].password));
+        assert!(!storage.get(USERS_FILE).unwrap().contains("hunter2"));
+        assert!(app.login("alice", "hunter3").is_err());
+        app.login("alice", "hunter2").unwrap();
+    }
+}
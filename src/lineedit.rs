@@ -0,0 +1,261 @@
+//! Line editing for the interactive menu: cursor movement, history kept
+//! between sessions, reverse search with Ctrl-R and Tab completion.
+//!
+//! Without raw terminal input (see `terminal`) lines are read as typed.
+
+use crate::log;
+use crate::notifier;
+use crate::prompt_input;
+use crate::terminal::{self, Key, RawMode};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+/// File the history is kept in, next to the data files.
+pub const HISTORY_FILE: &str = "history.txt";
+
+/// Entries kept in the history.
+const HISTORY_SIZE: usize = 500;
+
+/// Suggests complete lines for the text before the cursor.
+pub type Completer<'a> = &'a dyn Fn(&str) -> Vec<String>;
+
+pub struct LineEditor {
+    history: Vec<String>,
+    path: String,
+}
+
+impl LineEditor {
+    /// Opens the editor with the history saved in `path`, if any.
+    pub fn open(path: &str) -> Self {
+        let history: Vec<String> = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        let mut editor = Self {
+            history,
+            path: path.to_string(),
+        };
+        if editor.history.len() > HISTORY_SIZE {
+            editor.history.drain(..editor.history.len() - HISTORY_SIZE);
+            let mut text = editor.history.join("\n");
+            text.push('\n');
+            if let Err(e) = fs::write(path, text) {
+                log::warn(&format!("Failed to trim {}: {}", path, e));
+            }
+        }
+        editor
+    }
+
+    /// Reads a trimmed line, with editing when the terminal allows it.
+    pub fn read_line(&mut self, prompt: &str, complete: Completer) -> io::Result<String> {
+        if !terminal::is_interactive() {
+            return prompt_input(prompt);
+        }
+        let Some(raw) = RawMode::enable() else {
+            return prompt_input(prompt);
+        };
+        let mut line = Line {
+            prompt,
+            text: Vec::new(),
+            cursor: 0,
+        };
+        let result = self.edit(&mut line, complete);
+        notifier::hide_prompt();
+        drop(raw);
+        let text = result?.trim().to_string();
+        self.remember(&text);
+        Ok(text)
+    }
+
+    fn edit(&self, line: &mut Line, complete: Completer) -> io::Result<String> {
+        let mut stdin = io::stdin().lock();
+        // Position while browsing the history; `history.len()` is the new line.
+        let mut browsing = self.history.len();
+        let mut draft = String::new();
+        loop {
+            line.draw()?;
+            match terminal::read_key(&mut stdin)? {
+                Key::Enter => {
+                    println!();
+                    return Ok(line.to_string());
+                }
+                Key::Ctrl('c') => {
+                    println!("^C");
+                    line.set("");
+                    browsing = self.history.len();
+                }
+                Key::Char(c) => line.insert(c),
+                Key::Left | Key::Ctrl('b') => line.cursor = line.cursor.saturating_sub(1),
+                Key::Right | Key::Ctrl('f') => line.cursor = (line.cursor + 1).min(line.text.len()),
+                Key::Home | Key::Ctrl('a') => line.cursor = 0,
+                Key::End | Key::Ctrl('e') => line.cursor = line.text.len(),
+                Key::Backspace if line.cursor > 0 => {
+                    line.cursor -= 1;
+                    line.text.remove(line.cursor);
+                }
+                Key::Delete | Key::Ctrl('d') if line.cursor < line.text.len() => {
+                    line.text.remove(line.cursor);
+                }
+                Key::Ctrl('u') => {
+                    line.text.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                Key::Ctrl('k') => line.text.truncate(line.cursor),
+                Key::Ctrl('w') => {
+                    let end = line.cursor;
+                    while line.cursor > 0 && line.text[line.cursor - 1] == ' ' {
+                        line.cursor -= 1;
+                    }
+                    while line.cursor > 0 && line.text[line.cursor - 1] != ' ' {
+                        line.cursor -= 1;
+                    }
+                    line.text.drain(line.cursor..end);
+                }
+                Key::Up | Key::Ctrl('p') if browsing > 0 => {
+                    if browsing == self.history.len() {
+                        draft = line.to_string();
+                    }
+                    browsing -= 1;
+                    line.set(&self.history[browsing]);
+                }
+                Key::Down | Key::Ctrl('n') if browsing < self.history.len() => {
+                    browsing += 1;
+                    line.set(self.history.get(browsing).unwrap_or(&draft));
+                }
+                Key::Ctrl('r') => {
+                    if let Some(found) = self.search(line, &mut stdin)? {
+                        println!();
+                        return Ok(found);
+                    }
+                }
+                Key::Tab => line.complete(complete)?,
+                _ => {}
+            }
+        }
+    }
+
+    /// Searches the history backwards for the text typed so far. Enter runs
+    /// the match, Ctrl-R finds an older one, other keys edit the match and
+    /// Ctrl-C or Ctrl-G goes back to the line as it was.
+    fn search(&self, line: &mut Line, stdin: &mut impl io::Read) -> io::Result<Option<String>> {
+        let mut query = String::new();
+        let mut before = self.history.len();
+        let mut found: Option<usize> = None;
+        loop {
+            let matched = found.map_or("", |i| self.history[i].as_str());
+            notifier::draw_prompt(&format!("(reverse-i-search)`{}': {}", query, matched), 0)?;
+            match terminal::read_key(stdin)? {
+                Key::Enter => return Ok(Some(matched.to_string())),
+                Key::Ctrl('c' | 'g') => return Ok(None),
+                Key::Ctrl('r') => {
+                    if let Some(i) = found {
+                        before = i;
+                    }
+                }
+                Key::Char(c) => {
+                    query.push(c);
+                    before = self.history.len();
+                }
+                Key::Backspace => {
+                    query.pop();
+                    before = self.history.len();
+                }
+                _ => {
+                    if found.is_some() {
+                        line.set(matched);
+                    }
+                    return Ok(None);
+                }
+            }
+            if let Some(i) = self.history[..before]
+                .iter()
+                .rposition(|entry| entry.contains(&query))
+            {
+                found = Some(i);
+            } else if query.is_empty() {
+                found = None;
+            }
+        }
+    }
+
+    /// Adds `entry` to the history, unless it's blank or repeats the last one.
+    fn remember(&mut self, entry: &str) {
+        if entry.is_empty() || self.history.last().is_some_and(|last| last == entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+        let saved = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", entry));
+        if let Err(e) = saved {
+            log::warn(&format!("Failed to save history to {}: {}", self.path, e));
+        }
+    }
+}
+
+/// The line being edited.
+struct Line<'a> {
+    prompt: &'a str,
+    text: Vec<char>,
+    /// Index in `text` the cursor is before.
+    cursor: usize,
+}
+
+impl Line<'_> {
+    fn set(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn draw(&self) -> io::Result<()> {
+        notifier::draw_prompt(
+            &format!("{}{}", self.prompt, self),
+            self.text.len() - self.cursor,
+        )
+    }
+
+    /// Completes the text before the cursor as far as all suggestions agree,
+    /// listing them when that doesn't add anything.
+    fn complete(&mut self, complete: Completer) -> io::Result<()> {
+        let before: String = self.text[..self.cursor].iter().collect();
+        let suggestions = complete(&before);
+        let Some(first) = suggestions.first() else {
+            return Ok(());
+        };
+        let mut common: Vec<char> = first.chars().collect();
+        for suggestion in &suggestions[1..] {
+            let same = common
+                .iter()
+                .zip(suggestion.chars())
+                .take_while(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+                .count();
+            common.truncate(same);
+        }
+        if suggestions.len() == 1 {
+            common.push(' ');
+        }
+        if common.len() > self.cursor {
+            self.text.splice(..self.cursor, common.iter().copied());
+            self.cursor = common.len();
+        } else if suggestions.len() > 1 {
+            println!();
+            println!("{}", suggestions.join("    "));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.text.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
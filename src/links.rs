@@ -0,0 +1,119 @@
+//! Links between related tasks. A link goes from one task to another; the
+//! other task shows it as a backlink.
+
+use crate::{Task, TodoApp};
+use std::collections::BTreeSet;
+
+impl TodoApp {
+    /// Links task `task_id` to task `to_id`.
+    pub fn link_tasks(&mut self, task_id: u32, to_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if task_id == to_id {
+            return Err("A task can't be linked to itself");
+        }
+        let to = self.tasks.get(&to_id).ok_or("Task not found")?;
+        if !self.workspaces.can_access(to, &user_id) {
+            return Err("Not authorized to view this task");
+        }
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if task.relates_to.contains(&to_id) {
+            return Err("The tasks are already linked");
+        }
+
+        task.relates_to.push(to_id);
+        task.touch();
+        self.save_tasks(&format!("Link task #{} to #{}", task_id, to_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// Removes the link from task `task_id` to task `to_id`.
+    pub fn unlink_tasks(&mut self, task_id: u32, to_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if !task.relates_to.contains(&to_id) {
+            return Err("The tasks aren't linked");
+        }
+
+        task.relates_to.retain(|id| *id != to_id);
+        task.touch();
+        self.save_tasks(&format!("Unlink task #{} from #{}", task_id, to_id))
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// The tasks `task` links to that the current user may see.
+    pub fn links(&self, task: &Task) -> Vec<&Task> {
+        task.relates_to
+            .iter()
+            .filter_map(|id| self.tasks.get(id))
+            .filter(|linked| self.can_see(linked))
+            .collect()
+    }
+
+    /// The tasks linking to `task` that the current user may see.
+    pub fn backlinks(&self, task: &Task) -> Vec<&Task> {
+        self.tasks
+            .values()
+            .filter(|other| other.relates_to.contains(&task.id) && self.can_see(other))
+            .collect()
+    }
+
+    /// Drops every link to a task that is being deleted, so the ID doesn't
+    /// point at whatever task gets it next. Doesn't save.
+    pub fn remove_links_to(&mut self, task_id: u32) {
+        for task in self.tasks.values_mut() {
+            if task.relates_to.contains(&task_id) {
+                task.relates_to.retain(|id| *id != task_id);
+                task.touch();
+            }
+        }
+    }
+
+    /// Whether the current user may see `task`.
+    pub fn can_see(&self, task: &Task) -> bool {
+        self.current_user
+            .as_ref()
+            .is_some_and(|user_id| self.workspaces.can_access(task, user_id))
+    }
+}
+
+/// The tasks linked from `root` as an indented tree. Tasks in `seen`, the
+/// ones already shown, aren't expanded again; shown tasks are added to it.
+pub fn render_tree(app: &TodoApp, root: &Task, seen: &mut BTreeSet<u32>) -> String {
+    let mut out = format!("#{} {}\n", root.id, root.title);
+    seen.insert(root.id);
+    render_children(app, root, "", seen, &mut out);
+    out
+}
+
+fn render_children(
+    app: &TodoApp,
+    task: &Task,
+    indent: &str,
+    seen: &mut BTreeSet<u32>,
+    out: &mut String,
+) {
+    let links = app.links(task);
+    for (i, linked) in links.iter().enumerate() {
+        let last = i + 1 == links.len();
+        let branch = if last { "`-- " } else { "|-- " };
+        if !seen.insert(linked.id) {
+            out.push_str(&format!(
+                "{}{}#{} {} (...)\n",
+                indent, branch, linked.id, linked.title
+            ));
+            continue;
+        }
+        out.push_str(&format!(
+            "{}{}#{} {}\n",
+            indent, branch, linked.id, linked.title
+        ));
+        let indent = format!("{}{}", indent, if last { "    " } else { "|   " });
+        render_children(app, linked, &indent, seen, out);
+    }
+}
@@ -0,0 +1,103 @@
+//! Locking the menu after inactivity, for shared machines. With
+//! `lock_after_mins` set in the config, the screen is cleared once the menu
+//! prompt has waited that long without input, and the next input asks for
+//! the password first. A wrong password logs the user out. Any key or line
+//! typed, at the menu or a prompt, and returning from the editor count as
+//! input (see `record_input`).
+
+use crate::i18n::{tr, trf};
+use crate::notifier;
+use crate::passwords;
+use crate::{prompt_input, TodoApp};
+use std::io;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the thread checks whether the timeout passed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When input was last seen, and whether the screen was cleared since the
+/// menu was last unlocked. `None` until an `IdleLock` is started.
+static ACTIVITY: Mutex<Option<Activity>> = Mutex::new(None);
+
+struct Activity {
+    last_input: Instant,
+    cleared: bool,
+}
+
+pub struct IdleLock;
+
+impl IdleLock {
+    /// Starts the thread clearing the screen after `timeout` without input.
+    pub fn start(timeout: Duration) -> Self {
+        *activity() = Some(Activity {
+            last_input: Instant::now(),
+            cleared: false,
+        });
+        let notice = trf(
+            "Locked after {} minute(s) without input. Press Enter to unlock.",
+            &[&(timeout.as_secs() / 60)],
+        );
+        thread::spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            if let Some(activity) = activity().as_mut() {
+                if !activity.cleared && activity.last_input.elapsed() >= timeout {
+                    activity.cleared = notifier::clear_screen(&notice);
+                }
+            }
+        });
+        Self
+    }
+
+    /// Whether the screen was cleared since the menu was last unlocked.
+    /// Typing after that doesn't unlock it.
+    pub fn is_locked(&self) -> bool {
+        activity().as_ref().is_some_and(|activity| activity.cleared)
+    }
+
+    /// Unlocks the menu and restarts the timeout.
+    pub fn touch(&self) {
+        if let Some(activity) = activity().as_mut() {
+            activity.last_input = Instant::now();
+            activity.cleared = false;
+        }
+    }
+}
+
+/// Restarts the timeout, without unlocking a menu that's locked already.
+pub fn record_input() {
+    if let Some(activity) = activity().as_mut() {
+        activity.last_input = Instant::now();
+    }
+}
+
+fn activity() -> MutexGuard<'static, Option<Activity>> {
+    ACTIVITY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Asks the logged-in user for their password, logging them out if it's
+/// wrong. Returns whether the menu was unlocked.
+pub fn unlock(app: &mut TodoApp) -> io::Result<bool> {
+    let Some(username) = app.current_user.clone() else {
+        return Ok(false);
+    };
+    println!(
+        "{}",
+        trf(
+            "Locked. Enter the password of {} to continue.",
+            &[&username]
+        )
+    );
+    let password = prompt_input(tr("Password: "))?;
+    if app
+        .users
+        .get(&username)
+        .is_some_and(|user| passwords::verify(&user.password, &password))
+    {
+        return Ok(true);
+    }
+    app.logout();
+    println!("{}", tr("Wrong password, logged out"));
+    Ok(false)
+}
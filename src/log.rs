@@ -0,0 +1,103 @@
+//! Diagnostic log written to `todo.log` in the data directory.
+//!
+//! Info and more severe messages are always logged. With `--verbose` (`-v`)
+//! debug messages are logged too and everything is echoed to stderr. The log
+//! is rotated once it grows past `MAX_SIZE`, keeping `KEEP` old files.
+
+use chrono::Utc;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const LOG_FILE: &str = "todo.log";
+
+/// Size in bytes after which the log is rotated.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Number of rotated logs kept as `todo.log.1`, `todo.log.2`, ...
+const KEEP: u32 = 3;
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Serializes writes so lines from webhook threads don't interleave.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Severity of a log message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Turns debug messages and echoing to stderr on or off.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn error(message: &str) {
+    log(Level::Error, message);
+}
+
+pub fn warn(message: &str) {
+    log(Level::Warn, message);
+}
+
+pub fn info(message: &str) {
+    log(Level::Info, message);
+}
+
+pub fn debug(message: &str) {
+    log(Level::Debug, message);
+}
+
+/// Appends a message to the log. Logging never fails the operation being
+/// logged, so problems writing the log are ignored.
+pub fn log(level: Level, message: &str) {
+    let verbose = VERBOSE.load(Ordering::Relaxed);
+    if level == Level::Debug && !verbose {
+        return;
+    }
+    let line = format!(
+        "{} {:<5} {}",
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        level.name(),
+        message
+    );
+    if verbose {
+        eprintln!("{}", line);
+    }
+
+    let _guard = LOCK.lock();
+    rotate();
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(LOG_FILE) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Shifts `todo.log` to `todo.log.1` and so on once it's too big.
+fn rotate() {
+    if fs::metadata(LOG_FILE).map_or(true, |meta| meta.len() < MAX_SIZE) {
+        return;
+    }
+    for i in (1..KEEP).rev() {
+        let _ = fs::rename(
+            format!("{}.{}", LOG_FILE, i),
+            format!("{}.{}", LOG_FILE, i + 1),
+        );
+    }
+    let _ = fs::rename(LOG_FILE, format!("{}.1", LOG_FILE));
+}
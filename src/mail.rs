@@ -0,0 +1,64 @@
+//! Outgoing email.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Sends plain-text email messages.
+pub trait Mailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> io::Result<()>;
+}
+
+/// Mail settings, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailConfig {
+    /// Sender address of outgoing mail.
+    pub from: String,
+    /// `sendmail`-compatible program that delivers the message.
+    pub sendmail: String,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            from: "todo@localhost".to_string(),
+            sendmail: "sendmail".to_string(),
+        }
+    }
+}
+
+/// Delivers mail by piping it to a `sendmail`-compatible program.
+pub struct SendmailMailer {
+    config: MailConfig,
+}
+
+impl SendmailMailer {
+    pub fn new(config: MailConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Mailer for SendmailMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> io::Result<()> {
+        let mut child = Command::new(&self.config.sendmail)
+            .arg("-t")
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            write!(
+                stdin,
+                "From: {}\nTo: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}",
+                self.config.from, to, subject, body
+            )?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{} exited with {}",
+                self.config.sendmail, status
+            )));
+        }
+        Ok(())
+    }
+}
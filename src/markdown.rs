@@ -0,0 +1,109 @@
+//! Terminal rendering of the Markdown subset used in task descriptions:
+//! headings, bullet and numbered lists, bold, italics, inline code and links.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const BOLD: &str = "\x1b[1m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CODE: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns Markdown rendering on or off. When off, text is shown as written.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Renders Markdown text for the terminal, or returns it unchanged when
+/// rendering is disabled.
+pub fn render(text: &str) -> String {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+    text.lines().map(render_line).collect::<Vec<_>>().join("\n")
+}
+
+fn render_line(line: &str) -> String {
+    let indent_len = line.len() - line.trim_start().len();
+    let (indent, content) = line.split_at(indent_len);
+
+    let heading_level = content.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&heading_level) && content[heading_level..].starts_with(' ') {
+        let heading = render_inline(content[heading_level..].trim());
+        return format!("{}{}{}{}{}", indent, BOLD, UNDERLINE, heading, RESET);
+    }
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(item) = content.strip_prefix(marker) {
+            return format!("{}  • {}", indent, render_inline(item));
+        }
+    }
+    let digits = content.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && content[digits..].starts_with(". ") {
+        let (number, item) = content.split_at(digits + 2);
+        return format!("{}  {}{}", indent, number, render_inline(item));
+    }
+    format!("{}{}", indent, render_inline(content))
+}
+
+/// Renders emphasis, code spans and links within a line.
+fn render_inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut previous = ' ';
+    while let Some(c) = rest.chars().next() {
+        // Underscores inside words, as in `snake_case`, are not emphasis.
+        let intraword = c == '_' && previous.is_alphanumeric();
+        let styled = match c {
+            '`' => span(rest, "`", CODE),
+            _ if intraword => None,
+            '*' if rest.starts_with("**") => span(rest, "**", BOLD),
+            '_' if rest.starts_with("__") => span(rest, "__", BOLD),
+            '*' | '_' => span(rest, &rest[..1], ITALIC),
+            '[' => link(rest),
+            _ => None,
+        };
+        match styled {
+            Some((rendered, consumed)) => {
+                out.push_str(&rendered);
+                previous = rest[..consumed].chars().last().unwrap_or(' ');
+                rest = &rest[consumed..];
+            }
+            None => {
+                out.push(c);
+                previous = c;
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Styles text enclosed in `delimiter`, returning the output and the number of bytes consumed.
+fn span(text: &str, delimiter: &str, style: &str) -> Option<(String, usize)> {
+    let inner = &text[delimiter.len()..];
+    let end = inner.find(delimiter).filter(|end| *end > 0)?;
+    let content = &inner[..end];
+    let content = if style == CODE {
+        content.to_string()
+    } else {
+        render_inline(content)
+    };
+    Some((
+        format!("{}{}{}", style, content, RESET),
+        delimiter.len() * 2 + end,
+    ))
+}
+
+/// Renders `[label](url)` as an underlined label followed by the URL.
+fn link(text: &str) -> Option<(String, usize)> {
+    let label_end = text.find("](")?;
+    let url_len = text[label_end + 2..].find(')')?;
+    let label = &text[1..label_end];
+    let url = &text[label_end + 2..label_end + 2 + url_len];
+    Some((
+        format!("{}{}{} ({})", UNDERLINE, render_inline(label), RESET, url),
+        label_end + 2 + url_len + 1,
+    ))
+}
@@ -0,0 +1,526 @@
+//! Three-way merge of data files, used when syncing diverged devices.
+//!
+//! Each field is merged against the common ancestor: a field changed on
+//! only one side takes that side's value, so two devices editing different
+//! fields of a task both win. A field both sides changed differently is a
+//! `Conflict`, settled by a `Resolver`: the local or the remote value wins,
+//! or, with no answer, the local value is kept and for tasks the remote
+//! version is added as a separate conflict copy.
+
+use crate::crypto;
+use crate::log;
+use crate::storage;
+use crate::TASKS_FILE;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// The side of a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+impl Side {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "local" => Some(Self::Local),
+            "remote" => Some(Self::Remote),
+            _ => None,
+        }
+    }
+}
+
+/// A field changed differently on both sides since they diverged.
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    /// The data file.
+    pub file: &'a str,
+    /// The task's ID and title, for tasks.
+    pub task: Option<(&'a str, &'a str)>,
+    /// Path of the field, e.g. `title` or `fields.sprint`.
+    pub field: String,
+    /// `Null` when the field didn't exist.
+    pub base: &'a Value,
+    pub local: &'a Value,
+    pub remote: &'a Value,
+}
+
+/// Picks the winner of a conflict, or `None` to keep both.
+pub type Resolver<'a> = dyn FnMut(&Conflict) -> Option<Side> + 'a;
+
+/// Merges the local and remote versions of the data file `name`, given the
+/// version they both started from, if any.
+pub fn merge_file(
+    name: &str,
+    base: Option<&str>,
+    ours: &str,
+    theirs: &str,
+    resolve: &mut Resolver,
+) -> serde_json::Result<String> {
+    let base = match base {
+        Some(base) => serde_json::from_str(base)?,
+        None => Value::Object(Map::new()),
+    };
+    let ours = serde_json::from_str(ours)?;
+    let theirs = serde_json::from_str(theirs)?;
+    let merged = match (base, ours, theirs) {
+        (Value::Object(base), Value::Object(ours), Value::Object(theirs)) if name == TASKS_FILE => {
+            Value::Object(merge_tasks(&base, ours, &theirs, resolve))
+        }
+        (base, ours, theirs) => {
+            let (mut merged, conflicts) = merge_value(&base, &ours, &theirs);
+            let mut kept = Vec::new();
+            for path in conflicts {
+                let conflict = Conflict {
+                    file: name,
+                    task: None,
+                    field: path.join("."),
+                    base: at(&base, &path),
+                    local: at(&ours, &path),
+                    remote: at(&theirs, &path),
+                };
+                match resolve(&conflict) {
+                    Some(Side::Remote) => take_remote(&mut merged, &theirs, &path),
+                    Some(Side::Local) => {}
+                    None => kept.push(conflict.field),
+                }
+            }
+            if !kept.is_empty() {
+                log::warn(&format!(
+                    "Sync conflict in {} ({}), kept the local version",
+                    name,
+                    kept.join(", ")
+                ));
+            }
+            merged
+        }
+    };
+    storage::to_json(&merged)
+}
+
+/// Merges two values field by field. Returns the merged value and the
+/// paths of the fields changed differently on both sides, which keep the
+/// local value.
+fn merge_value(base: &Value, ours: &Value, theirs: &Value) -> (Value, Vec<Vec<String>>) {
+    if ours == theirs || theirs == base {
+        return (ours.clone(), Vec::new());
+    }
+    if ours == base {
+        return (theirs.clone(), Vec::new());
+    }
+    let (Value::Object(ours_map), Value::Object(theirs_map)) = (ours, theirs) else {
+        return (ours.clone(), vec![Vec::new()]);
+    };
+
+    let empty = Map::new();
+    let base_map = base.as_object().unwrap_or(&empty);
+    let mut merged = Map::new();
+    let mut conflicts = Vec::new();
+    let keys = ours_map
+        .keys()
+        .chain(theirs_map.keys().filter(|key| !ours_map.contains_key(*key)));
+    for key in keys {
+        let field = |map: &Map<String, Value>| map.get(key).cloned().unwrap_or(Value::Null);
+        let (value, field_conflicts) =
+            merge_value(&field(base_map), &field(ours_map), &field(theirs_map));
+        conflicts.extend(field_conflicts.into_iter().map(|mut path| {
+            path.insert(0, key.clone());
+            path
+        }));
+        // A field removed on the winning side stays removed.
+        if !value.is_null() || ours_map.contains_key(key) && theirs_map.contains_key(key) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    (Value::Object(merged), conflicts)
+}
+
+/// Merges task maps keyed by ID. Tasks are matched by UUID, since both
+/// devices may have given the same ID to different new tasks.
+fn merge_tasks(
+    base: &Map<String, Value>,
+    mut ours: Map<String, Value>,
+    theirs: &Map<String, Value>,
+    resolve: &mut Resolver,
+) -> Map<String, Value> {
+    let base_by_uuid = by_uuid(base);
+    let theirs_by_uuid = by_uuid(theirs);
+
+    // Tasks deleted remotely are deleted here too, unless they were changed here.
+    ours.retain(|_, task| {
+        let uuid = uuid_of(task);
+        theirs_by_uuid.contains_key(uuid) || base_by_uuid.get(uuid).is_none_or(|base| *base != task)
+    });
+    let our_ids: HashMap<String, String> = ours
+        .iter()
+        .map(|(id, task)| (uuid_of(task).to_string(), id.clone()))
+        .collect();
+
+    for (uuid, their_task) in theirs_by_uuid {
+        let base_task = base_by_uuid.get(uuid).copied();
+        let Some(our_task) = our_ids.get(uuid).and_then(|id| ours.get(id)).cloned() else {
+            // New remotely, or deleted here and changed remotely.
+            if base_task != Some(their_task) {
+                insert_new(&mut ours, their_task.clone());
+            }
+            continue;
+        };
+        if our_task == *their_task {
+            continue;
+        }
+
+        let base_task = base_task.map_or(Value::Null, without_bookkeeping);
+        let their_fields = without_bookkeeping(their_task);
+        let (mut merged, conflicts) =
+            merge_value(&base_task, &without_bookkeeping(&our_task), &their_fields);
+        let id = our_ids[uuid].clone();
+        let title = our_task["title"].as_str().unwrap_or_default();
+        let mut kept = Vec::new();
+        for path in conflicts {
+            let conflict = Conflict {
+                file: TASKS_FILE,
+                task: Some((&id, title)),
+                field: path.join("."),
+                base: at(&base_task, &path),
+                local: at(&our_task, &path),
+                remote: at(their_task, &path),
+            };
+            match resolve(&conflict) {
+                Some(Side::Remote) => take_remote(&mut merged, &their_fields, &path),
+                Some(Side::Local) => {}
+                None => kept.push(conflict.field),
+            }
+        }
+        let latest = |field: &str| {
+            [&our_task, their_task]
+                .iter()
+                .filter_map(|task| task[field].as_i64())
+                .max()
+        };
+        if let Some(merged) = merged.as_object_mut() {
+            merged.insert(
+                "version".to_string(),
+                Value::from(latest("version").unwrap_or(0) + 1),
+            );
+            merged.insert(
+                "updated_at".to_string(),
+                latest("updated_at").map_or(Value::Null, Value::from),
+            );
+        }
+        if !kept.is_empty() {
+            log::warn(&format!(
+                "Sync conflict in task #{} ({}), added the remote version as a copy",
+                id,
+                kept.join(", ")
+            ));
+            insert_new(&mut ours, conflict_copy(their_task));
+        }
+        ours.insert(id, merged);
+    }
+    ours
+}
+
+/// The value at `path` in `value`, `Null` if there's none.
+fn at<'a>(value: &'a Value, path: &[String]) -> &'a Value {
+    path.iter()
+        .try_fold(value, |value, key| value.get(key))
+        .unwrap_or(&Value::Null)
+}
+
+/// Replaces the value at `path` in `merged` by the one in `theirs`, removing
+/// it if `theirs` has none.
+fn take_remote(merged: &mut Value, theirs: &Value, path: &[String]) {
+    let Some((key, parents)) = path.split_last() else {
+        *merged = theirs.clone();
+        return;
+    };
+    let parent = parents
+        .iter()
+        .try_fold(merged, |value, key| value.get_mut(key));
+    if let Some(Value::Object(parent)) = parent {
+        match at(theirs, path) {
+            Value::Null if at(theirs, parents).get(key).is_none() => {
+                parent.remove(key);
+            }
+            value => {
+                parent.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Tasks by UUID. Tasks without one are left out, as they can't be matched.
+fn by_uuid(tasks: &Map<String, Value>) -> HashMap<&str, &Value> {
+    tasks
+        .values()
+        .filter(|task| !uuid_of(task).is_empty())
+        .map(|task| (uuid_of(task), task))
+        .collect()
+}
+
+fn uuid_of(task: &Value) -> &str {
+    task["uuid"].as_str().unwrap_or_default()
+}
+
+/// The task without its version and change time, which change on every
+/// edit and so would always conflict.
+fn without_bookkeeping(task: &Value) -> Value {
+    let mut task = task.clone();
+    if let Some(task) = task.as_object_mut() {
+        task.remove("version");
+        task.remove("updated_at");
+    }
+    task
+}
+
+/// A copy of the remote version of a conflicting task, as a new task.
+fn conflict_copy(task: &Value) -> Value {
+    let mut copy = task.clone();
+    copy["uuid"] = Value::from(crypto::uuid_v4());
+    let title = copy["title"].as_str().unwrap_or_default();
+    copy["title"] = Value::from(format!("{} (conflict)", title));
+    copy
+}
+
+/// Adds a task, giving it a new ID if its own is taken.
+fn insert_new(tasks: &mut Map<String, Value>, mut task: Value) {
+    let mut id = task["id"].as_u64().unwrap_or(0);
+    if id == 0 || tasks.contains_key(&id.to_string()) {
+        id = tasks
+            .keys()
+            .filter_map(|id| id.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        task["id"] = Value::from(id);
+    }
+    tasks.insert(id.to_string(), task);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const UUID: &str = "6f0c3a52-1d8e-4b7a-9c3f-2a5e8d7b4c10";
+
+    fn task(fields: Value) -> Value {
+        let mut task = json!({
+            "id": 1,
+            "uuid": UUID,
+            "title": "Buy milk",
+            "description": "",
+            "completed": false,
+            "priority": "normal",
+            "version": 1,
+            "updated_at": 1700000000,
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            task[key] = value.clone();
+        }
+        task
+    }
+
+    fn tasks(tasks: &[Value]) -> String {
+        let tasks: Map<String, Value> = tasks
+            .iter()
+            .map(|task| (task["id"].to_string(), task.clone()))
+            .collect();
+        Value::Object(tasks).to_string()
+    }
+
+    /// Merges the task files, answering every conflict with `prefer`.
+    /// Returns the merged tasks and the fields that conflicted.
+    fn merge(
+        base: &[Value],
+        ours: &[Value],
+        theirs: &[Value],
+        prefer: Option<Side>,
+    ) -> (Map<String, Value>, Vec<String>) {
+        let mut conflicts = Vec::new();
+        let mut resolve = |conflict: &Conflict| {
+            assert_eq!(conflict.task.map(|(id, _)| id), Some("1"));
+            conflicts.push(conflict.field.clone());
+            prefer
+        };
+        let merged = merge_file(
+            TASKS_FILE,
+            Some(&tasks(base)),
+            &tasks(ours),
+            &tasks(theirs),
+            &mut resolve,
+        )
+        .unwrap();
+        let Value::Object(merged) = serde_json::from_str(&merged).unwrap() else {
+            panic!("not a task map: {}", merged);
+        };
+        (merged, conflicts)
+    }
+
+    #[test]
+    fn edits_to_different_fields_both_win() {
+        let base = task(json!({}));
+        let ours = task(json!({"title": "Buy oat milk", "version": 2, "updated_at": 1700000100}));
+        let theirs = task(json!({"completed": true, "version": 3, "updated_at": 1700000200}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[theirs], None);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged.len(), 1);
+        let merged = &merged["1"];
+        assert_eq!(merged["title"], "Buy oat milk");
+        assert_eq!(merged["completed"], true);
+        assert_eq!(merged["version"], 4);
+        assert_eq!(merged["updated_at"], 1700000200);
+    }
+
+    #[test]
+    fn edits_to_different_keys_of_a_nested_field_both_win() {
+        let base = task(json!({"custom": {"client": "Acme"}}));
+        let ours = task(json!({"custom": {"client": "Acme", "ticket": "T-1"}}));
+        let theirs = task(json!({"custom": {"client": "Initech"}}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[theirs], None);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            merged["1"]["custom"],
+            json!({"client": "Initech", "ticket": "T-1"})
+        );
+    }
+
+    #[test]
+    fn the_same_edit_on_both_sides_is_not_a_conflict() {
+        let base = task(json!({}));
+        let ours = task(json!({"title": "Buy oat milk", "version": 2}));
+        let theirs = task(json!({"title": "Buy oat milk", "version": 5}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[theirs], None);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged["1"]["title"], "Buy oat milk");
+        assert_eq!(merged["1"]["version"], 6);
+    }
+
+    #[test]
+    fn preferring_local_keeps_the_local_edit_of_the_same_field() {
+        let base = task(json!({}));
+        let ours = task(json!({"title": "Buy oat milk", "completed": true}));
+        let theirs = task(json!({"title": "Buy soy milk", "priority": "high"}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[theirs], Some(Side::Local));
+
+        assert_eq!(conflicts, ["title"]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged["1"]["title"], "Buy oat milk");
+        assert_eq!(merged["1"]["completed"], true);
+        assert_eq!(merged["1"]["priority"], "high");
+    }
+
+    #[test]
+    fn preferring_remote_takes_the_remote_edit_of_the_same_field() {
+        let base = task(json!({"project": "Home"}));
+        let ours = task(json!({"title": "Buy oat milk", "project": "Errands"}));
+        let mut theirs = task(json!({"title": "Buy soy milk", "completed": true}));
+        theirs.as_object_mut().unwrap().remove("project");
+        let (merged, mut conflicts) = merge(&[base], &[ours], &[theirs], Some(Side::Remote));
+
+        conflicts.sort();
+        assert_eq!(conflicts, ["project", "title"]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged["1"]["title"], "Buy soy milk");
+        assert_eq!(merged["1"]["completed"], true);
+        // The field removed remotely is removed here too.
+        assert!(merged["1"].get("project").is_none());
+    }
+
+    #[test]
+    fn unresolved_conflicts_keep_local_and_add_the_remote_as_a_copy() {
+        let base = task(json!({}));
+        let ours = task(json!({"title": "Buy oat milk"}));
+        let theirs = task(json!({"title": "Buy soy milk", "completed": true}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[theirs], None);
+
+        assert_eq!(conflicts, ["title"]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged["1"]["title"], "Buy oat milk");
+        assert_eq!(merged["1"]["completed"], true);
+        let copy = &merged["2"];
+        assert_eq!(copy["id"], 2);
+        assert_eq!(copy["title"], "Buy soy milk (conflict)");
+        assert_ne!(copy["uuid"], UUID);
+    }
+
+    #[test]
+    fn a_task_deleted_here_but_edited_remotely_comes_back() {
+        let base = task(json!({}));
+        let theirs = task(json!({"title": "Buy oat milk"}));
+        let (merged, conflicts) = merge(&[base], &[], &[theirs], None);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged["1"]["title"], "Buy oat milk");
+    }
+
+    #[test]
+    fn a_task_deleted_remotely_but_edited_here_is_kept() {
+        let base = task(json!({}));
+        let ours = task(json!({"title": "Buy oat milk"}));
+        let (merged, conflicts) = merge(&[base], &[ours], &[], None);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged["1"]["title"], "Buy oat milk");
+    }
+
+    #[test]
+    fn unchanged_tasks_deleted_on_either_side_stay_deleted() {
+        let both = [
+            task(json!({})),
+            task(json!({"id": 2, "uuid": "other", "title": "Walk the dog"})),
+        ];
+        let (merged, _) = merge(&both, &both[1..], &both, None);
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["2"]);
+        let (merged, _) = merge(&both, &both, &both[1..], None);
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["2"]);
+    }
+
+    #[test]
+    fn new_tasks_with_the_same_id_on_both_sides_are_both_kept() {
+        let base = task(json!({}));
+        let ours = task(json!({"id": 2, "uuid": "ours", "title": "Call mum"}));
+        let theirs = task(json!({"id": 2, "uuid": "theirs", "title": "Call dad"}));
+        let (merged, conflicts) = merge(
+            std::slice::from_ref(&base),
+            &[base.clone(), ours],
+            &[base.clone(), theirs],
+            None,
+        );
+
+        assert!(conflicts.is_empty());
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged["2"]["title"], "Call mum");
+        assert_eq!(merged["3"]["title"], "Call dad");
+        assert_eq!(merged["3"]["id"], 3);
+    }
+
+    #[test]
+    fn other_files_merge_field_by_field() {
+        let base = r#"{"theme": "dark", "sort": "due", "digest": {"hour": 8}}"#;
+        let ours = r#"{"theme": "light", "sort": "due", "digest": {"hour": 9}}"#;
+        let theirs = r#"{"theme": "dark", "sort": "priority", "digest": {"hour": 7}}"#;
+        for (prefer, hour) in [(Some(Side::Local), 9), (Some(Side::Remote), 7), (None, 9)] {
+            let mut conflicts = Vec::new();
+            let mut resolve = |conflict: &Conflict| {
+                assert_eq!(conflict.task, None);
+                assert_eq!((conflict.local, conflict.remote), (&json!(9), &json!(7)));
+                conflicts.push(conflict.field.clone());
+                prefer
+            };
+            let merged =
+                merge_file("settings.json", Some(base), ours, theirs, &mut resolve).unwrap();
+            let merged: Value = serde_json::from_str(&merged).unwrap();
+
+            assert_eq!(conflicts, ["digest.hour"]);
+            assert_eq!(
+                merged,
+                json!({"theme": "light", "sort": "priority", "digest": {"hour": hour}})
+            );
+        }
+    }
+}
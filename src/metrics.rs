@@ -0,0 +1,166 @@
+//! Counters for monitoring: requests answered in server mode, failed
+//! logins and how long the data files take to save. They count from the
+//! start of the process and are shown, with the task counts, by `stats` and
+//! in server mode at `/metrics` in the Prometheus text format.
+
+use crate::TodoApp;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Requests answered, by status code.
+static REQUESTS: Mutex<BTreeMap<u16, u64>> = Mutex::new(BTreeMap::new());
+static AUTH_FAILURES: AtomicU64 = AtomicU64::new(0);
+static SAVES: AtomicU64 = AtomicU64::new(0);
+static SAVE_FAILURES: AtomicU64 = AtomicU64::new(0);
+static SAVE_MICROS: AtomicU64 = AtomicU64::new(0);
+static SLOWEST_SAVE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a request answered with `status`.
+pub fn record_request(status: u16) {
+    let mut requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+    *requests.entry(status).or_default() += 1;
+}
+
+/// Counts a failed login or a request with an invalid token.
+pub fn record_auth_failure() {
+    AUTH_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a save of a data file that took `elapsed`.
+pub fn record_save(elapsed: Duration, succeeded: bool) {
+    let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+    SAVES.fetch_add(1, Ordering::Relaxed);
+    if !succeeded {
+        SAVE_FAILURES.fetch_add(1, Ordering::Relaxed);
+    }
+    SAVE_MICROS.fetch_add(micros, Ordering::Relaxed);
+    SLOWEST_SAVE_MICROS.fetch_max(micros, Ordering::Relaxed);
+}
+
+/// The counters and task counts at one moment.
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    pub requests: BTreeMap<u16, u64>,
+    pub auth_failures: u64,
+    pub saves: u64,
+    pub save_failures: u64,
+    pub save_time: Duration,
+    pub slowest_save: Duration,
+    pub open_tasks: u64,
+    pub completed_tasks: u64,
+    /// Open tasks past their due date.
+    pub overdue_tasks: u64,
+}
+
+impl Metrics {
+    /// Mean time a save took.
+    pub fn average_save(&self) -> Duration {
+        match self.saves {
+            0 => Duration::ZERO,
+            saves => self.save_time / u32::try_from(saves).unwrap_or(u32::MAX),
+        }
+    }
+}
+
+impl TodoApp {
+    /// The counters so far, with the counts of all users' tasks.
+    pub fn metrics(&self) -> Metrics {
+        let mut metrics = Metrics {
+            requests: REQUESTS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            auth_failures: AUTH_FAILURES.load(Ordering::Relaxed),
+            saves: SAVES.load(Ordering::Relaxed),
+            save_failures: SAVE_FAILURES.load(Ordering::Relaxed),
+            save_time: Duration::from_micros(SAVE_MICROS.load(Ordering::Relaxed)),
+            slowest_save: Duration::from_micros(SLOWEST_SAVE_MICROS.load(Ordering::Relaxed)),
+            ..Metrics::default()
+        };
+        let now = Utc::now();
+        for task in self.tasks.values() {
+            if task.completed {
+                metrics.completed_tasks += 1;
+            } else {
+                metrics.open_tasks += 1;
+                if task.due_date.is_some_and(|due| due < now) {
+                    metrics.overdue_tasks += 1;
+                }
+            }
+        }
+        metrics
+    }
+}
+
+/// `metrics` in the Prometheus text exposition format.
+pub fn render_prometheus(metrics: &Metrics) -> String {
+    let mut out = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (suffix, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, suffix, value);
+        }
+    };
+    let requests: Vec<(String, String)> = metrics
+        .requests
+        .iter()
+        .map(|(status, count)| (format!("{{status=\"{}\"}}", status), count.to_string()))
+        .collect();
+    family(
+        "todo_http_requests_total",
+        "counter",
+        "HTTP requests answered, by status code.",
+        &requests,
+    );
+    family(
+        "todo_tasks",
+        "gauge",
+        "Tasks of all users, by status.",
+        &[
+            (
+                "{status=\"open\"}".to_string(),
+                metrics.open_tasks.to_string(),
+            ),
+            (
+                "{status=\"completed\"}".to_string(),
+                metrics.completed_tasks.to_string(),
+            ),
+        ],
+    );
+    family(
+        "todo_tasks_overdue",
+        "gauge",
+        "Open tasks past their due date.",
+        &[(String::new(), metrics.overdue_tasks.to_string())],
+    );
+    family(
+        "todo_auth_failures_total",
+        "counter",
+        "Failed logins and requests with an invalid token.",
+        &[(String::new(), metrics.auth_failures.to_string())],
+    );
+    family(
+        "todo_save_duration_seconds",
+        "summary",
+        "Time taken to save data files.",
+        &[
+            (
+                "_sum".to_string(),
+                metrics.save_time.as_secs_f64().to_string(),
+            ),
+            ("_count".to_string(), metrics.saves.to_string()),
+        ],
+    );
+    family(
+        "todo_save_failures_total",
+        "counter",
+        "Saves of data files that failed.",
+        &[(String::new(), metrics.save_failures.to_string())],
+    );
+    out
+}
@@ -0,0 +1,59 @@
+//! Suggests what to work on next: pending tasks that aren't blocked or
+//! snoozed, ranked by a scoring function.
+
+use crate::priority::Priority;
+use crate::schedule::start_of_today;
+use crate::{Task, TodoApp};
+use chrono::{DateTime, Utc};
+
+/// Ranks a task at the given time; higher scores are suggested first.
+pub type Score = fn(&Task, DateTime<Utc>) -> f64;
+
+/// Weighs priority most, then how soon the task is due, then how long it
+/// has been waiting.
+pub fn default_score(task: &Task, now: DateTime<Utc>) -> f64 {
+    let priority = match task.priority {
+        Priority::Low => 0.0,
+        Priority::Normal => 1.0,
+        Priority::High => 2.0,
+        Priority::Urgent => 4.0,
+    };
+    let due = match task.due_date {
+        Some(due) if due < start_of_today() => 30.0 + (now - due).num_days().min(30) as f64,
+        Some(due) => {
+            let days = (due - now).num_days();
+            if days < 7 {
+                (7 - days) as f64 * 3.0
+            } else {
+                0.0
+            }
+        }
+        None => 0.0,
+    };
+    let age = ((now - task.created_at).num_weeks() as f64).min(10.0);
+    let pinned = if task.pinned { 5.0 } else { 0.0 };
+    priority * 10.0 + due + age + pinned
+}
+
+impl TodoApp {
+    /// Up to `count` of the current list's actionable tasks, best first:
+    /// pending, doable in the current context, not waiting for other tasks
+    /// and not snoozed.
+    pub fn next_actions(&self, count: usize, score: Score) -> Result<Vec<&Task>, &'static str> {
+        let now = Utc::now();
+        let mut tasks: Vec<(f64, &Task)> = self
+            .context_tasks()?
+            .into_iter()
+            .filter(|task| !task.completed)
+            .filter(|task| task.snoozed_until.is_none_or(|until| until <= now))
+            .filter(|task| !self.is_blocked(task))
+            .map(|task| (score(task, now), task))
+            .collect();
+        tasks.sort_by(|(a, a_task), (b, b_task)| b.total_cmp(a).then(a_task.id.cmp(&b_task.id)));
+        Ok(tasks
+            .into_iter()
+            .take(count)
+            .map(|(_, task)| task)
+            .collect())
+    }
+}
@@ -0,0 +1,198 @@
+//! How each user is notified: the channels reminders go out on, quiet hours
+//! during which they wait, and the time the reminder daemon emails the
+//! daily digest. All of them are settings, e.g. `settings quiet-hours
+//! 22:00-07:00` or `settings digest-time 08:00`, and times are in the
+//! user's own timezone.
+
+use crate::i18n::{self, tr, trf};
+use crate::schedule;
+use crate::{TaskEvent, TodoApp};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process::Command;
+
+/// A user's notification preferences.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notifications {
+    /// Show reminders with `notify-send`.
+    pub desktop: bool,
+    /// Email reminders and digests to the address on the account.
+    pub email: bool,
+    /// Report reminders to webhooks and hook scripts.
+    pub webhook: bool,
+    /// When reminders and digests are held back until the end.
+    pub quiet_hours: Option<QuietHours>,
+    /// When the daemon emails the digest each day; never when unset.
+    pub digest_time: Option<NaiveTime>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            desktop: true,
+            email: false,
+            webhook: false,
+            quiet_hours: None,
+            digest_time: None,
+        }
+    }
+}
+
+/// A time of day range, which may cross midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parses a range like `22:00-07:00`.
+    pub fn parse(value: &str) -> Result<Self, &'static str> {
+        const INVALID: &str = "Invalid quiet hours, expected e.g. 22:00-07:00";
+
+        let (start, end) = value.split_once('-').ok_or(INVALID)?;
+        let start = parse_time(start).map_err(|_| INVALID)?;
+        let end = parse_time(end).map_err(|_| INVALID)?;
+        if start == end {
+            return Err(INVALID);
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Parses a time of day like `08:00`.
+pub fn parse_time(value: &str) -> Result<NaiveTime, &'static str> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| "Invalid time, expected e.g. 08:00")
+}
+
+impl TodoApp {
+    /// A user's notification preferences, without them being logged in.
+    fn notifications_of(&self, username: &str) -> Notifications {
+        self.settings
+            .get(username)
+            .map(|settings| settings.notifications.clone())
+            .unwrap_or_default()
+    }
+
+    /// `now` on the clock of a user, in their timezone.
+    pub fn local_time_of(&self, username: &str, now: DateTime<Utc>) -> DateTime<FixedOffset> {
+        let timezone = self
+            .settings
+            .get(username)
+            .and_then(|settings| settings.timezone.as_deref());
+        now.with_timezone(&schedule::timezone_of(timezone))
+    }
+
+    /// The users in their quiet hours at `now`.
+    pub fn quiet_users(&self, now: DateTime<Utc>) -> HashSet<String> {
+        self.settings
+            .iter()
+            .filter(|(username, settings)| {
+                settings
+                    .notifications
+                    .quiet_hours
+                    .is_some_and(|quiet| quiet.contains(self.local_time_of(username, now).time()))
+            })
+            .map(|(username, _)| username.clone())
+            .collect()
+    }
+
+    /// Sends a reminder that fired for a task on the channels its owner
+    /// turned on. It's always printed, for the daemon's log.
+    pub fn send_reminder(&mut self, task_id: u32) {
+        let Some(task) = self.tasks.get(&task_id) else {
+            return;
+        };
+        let message = match task.due_date {
+            Some(due) => trf(
+                "Reminder: {} is due {}",
+                &[&task.title, &i18n::format_datetime(due)],
+            ),
+            None => trf("Reminder: {}", &[&task.title]),
+        };
+        println!("[{}] {}", task.user_id, message);
+
+        let notifications = self.notifications_of(&task.user_id);
+        if notifications.desktop {
+            let _ = Command::new("notify-send")
+                .arg(tr("Todo reminder"))
+                .arg(&message)
+                .status();
+        }
+        if notifications.email {
+            let email = self
+                .users
+                .get(&task.user_id)
+                .and_then(|user| user.email.as_deref());
+            if let Some(email) = email {
+                if let Err(e) = self.mailer.send(email, tr("Todo reminder"), &message) {
+                    eprintln!("{}", trf("Failed to email {}: {}", &[&task.user_id, &e]));
+                }
+            }
+        }
+        if notifications.webhook {
+            self.notify_integrations(TaskEvent::Reminded, task);
+        }
+    }
+
+    /// The users whose digest is due at `now` and hasn't been sent today,
+    /// with the day it's for. `sent` holds the last day each user's was sent.
+    pub fn digests_due(
+        &self,
+        now: DateTime<Utc>,
+        sent: &HashMap<String, NaiveDate>,
+    ) -> Vec<(String, NaiveDate)> {
+        let quiet = self.quiet_users(now);
+        self.settings
+            .iter()
+            .filter(|(username, settings)| {
+                settings.notifications.email && !quiet.contains(*username)
+            })
+            .filter_map(|(username, settings)| {
+                let digest_time = settings.notifications.digest_time?;
+                let local = self.local_time_of(username, now);
+                let today = local.date_naive();
+                let due = local.time() >= digest_time
+                    && sent.get(username).is_none_or(|day| *day < today);
+                due.then(|| (username.clone(), today))
+            })
+            .collect()
+    }
+
+    /// Emails a user's digest as if they were logged in, in their timezone.
+    pub fn send_digest_of(&mut self, username: &str) -> Result<(), &'static str> {
+        let timezone = self
+            .settings
+            .get(username)
+            .and_then(|settings| settings.timezone.as_deref());
+        schedule::set_timezone(Some(schedule::timezone_of(timezone)));
+        let previous = self.current_user.replace(username.to_string());
+        let result = self.send_digest(self.mailer.as_ref());
+        self.current_user = previous;
+        schedule::reset_timezone();
+        result
+    }
+}
@@ -0,0 +1,227 @@
+//! Reminders shown inside the interactive menu. A background thread watches
+//! the logged-in user's reminders and, when one comes due, rings the
+//! terminal bell and prints a highlighted notice above the menu prompt,
+//! which is then redrawn with whatever was typed so far. Notices wait while
+//! any other prompt is open, so they never interrupt one. Other users
+//! starting or stopping to edit a task the user can see are announced the
+//! same way, without the bell.
+
+use crate::accessibility;
+use crate::i18n::trf;
+use crate::presence::{self, Presence};
+use crate::schedule::format_duration;
+use crate::settings::Role;
+use crate::theme;
+use crate::TodoApp;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// How often the thread checks for reminders that came due.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The menu prompt line as last drawn, and how many characters the cursor
+/// is left of its end; `None` while the menu isn't waiting for input.
+static PROMPT: Mutex<Option<(String, usize)>> = Mutex::new(None);
+
+/// A reminder that hasn't fired yet.
+struct Upcoming {
+    task_id: u32,
+    title: String,
+    due: DateTime<Utc>,
+    fire_at: DateTime<Utc>,
+}
+
+/// The logged-in user and the titles of the tasks they can see.
+#[derive(Default)]
+struct Viewer {
+    username: Option<String>,
+    titles: BTreeMap<u32, String>,
+}
+
+pub struct Notifier {
+    upcoming: Arc<Mutex<Vec<Upcoming>>>,
+    viewer: Arc<Mutex<Viewer>>,
+}
+
+impl Notifier {
+    /// Starts the thread announcing reminders.
+    pub fn start() -> Self {
+        let upcoming: Arc<Mutex<Vec<Upcoming>>> = Arc::default();
+        let viewer: Arc<Mutex<Viewer>> = Arc::default();
+        let watched = Arc::clone(&upcoming);
+        let watching = Arc::clone(&viewer);
+        thread::spawn(move || {
+            let mut announced = HashSet::new();
+            let mut editing = Presence::new();
+            loop {
+                thread::sleep(CHECK_INTERVAL);
+                let upcoming = watched.lock().unwrap_or_else(PoisonError::into_inner);
+                announce_due(&upcoming, &mut announced);
+                drop(upcoming);
+                let viewer = watching.lock().unwrap_or_else(PoisonError::into_inner);
+                announce_editing(&viewer, &mut editing);
+            }
+        });
+        Self { upcoming, viewer }
+    }
+
+    /// Watches the reminders of the user logged in to `app`, or none if
+    /// nobody is. Called before each menu prompt, as tasks may have changed.
+    pub fn watch(&self, app: &TodoApp) {
+        let upcoming = app
+            .upcoming_reminders()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(fire_at, task)| {
+                Some(Upcoming {
+                    task_id: task.id,
+                    title: task.title.clone(),
+                    due: task.due_date?,
+                    fire_at,
+                })
+            })
+            .collect();
+        *self.upcoming.lock().unwrap_or_else(PoisonError::into_inner) = upcoming;
+
+        let username = app.current_user.clone();
+        let titles = match &username {
+            Some(username) => app
+                .tasks
+                .values()
+                .filter(|task| app.workspaces.can_access(task, username))
+                .map(|task| (task.id, task.title.clone()))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        *self.viewer.lock().unwrap_or_else(PoisonError::into_inner) = Viewer { username, titles };
+    }
+}
+
+/// Draws the menu prompt line, with the cursor `back` characters left of
+/// its end, and remembers it so it can be redrawn after a notice.
+pub fn draw_prompt(line: &str, back: usize) -> io::Result<()> {
+    let mut prompt = PROMPT.lock().unwrap_or_else(PoisonError::into_inner);
+    write_prompt(line, back)?;
+    *prompt = Some((line.to_string(), back));
+    Ok(())
+}
+
+/// Notes that the menu prompt is gone, so notices wait for the next one.
+pub fn hide_prompt() {
+    *PROMPT.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Clears the screen and shows `notice` above the menu prompt, if it's
+/// showing. Returns whether it was.
+pub fn clear_screen(notice: &str) -> bool {
+    let prompt = PROMPT.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some((line, back)) = prompt.as_ref() else {
+        return false;
+    };
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "\x1b[2J\x1b[3J\x1b[H{}\r\n", notice);
+    drop(stdout);
+    let _ = write_prompt(line, *back);
+    true
+}
+
+/// Shows a notice in place of the prompt line, ringing the bell if `bell`.
+/// Screen readers get it on a line of its own, without the escape codes.
+fn write_notice(notice: &str, bell: bool) {
+    let bell = if bell { "\x07" } else { "" };
+    let mut stdout = io::stdout().lock();
+    let _ = if accessibility::screen_reader() {
+        write!(stdout, "\n{}{}\n", notice, bell)
+    } else {
+        write!(
+            stdout,
+            "\r\x1b[K{}{}\n",
+            theme::paint(notice, Role::Notice),
+            bell
+        )
+    };
+}
+
+fn write_prompt(line: &str, back: usize) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "\r{}\x1b[K", line)?;
+    if back > 0 {
+        write!(stdout, "\x1b[{}D", back)?;
+    }
+    stdout.flush()
+}
+
+/// Prints a notice for each reminder that came due and hasn't been
+/// announced yet, if the menu prompt is showing.
+fn announce_due(upcoming: &[Upcoming], announced: &mut HashSet<(u32, DateTime<Utc>)>) {
+    let prompt = PROMPT.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some((line, back)) = prompt.as_ref() else {
+        return;
+    };
+    let now = Utc::now();
+    for reminder in upcoming {
+        // Reminders whose task is long past due were missed while the app
+        // was closed; they aren't news any more.
+        if reminder.fire_at > now
+            || now > reminder.due + Duration::minutes(1)
+            || !announced.insert((reminder.task_id, reminder.fire_at))
+        {
+            continue;
+        }
+        let notice = if reminder.due > now {
+            trf(
+                "Task #{} {} is due in {}",
+                &[
+                    &reminder.task_id,
+                    &reminder.title,
+                    &format_duration(reminder.due - now),
+                ],
+            )
+        } else {
+            trf(
+                "Task #{} {} is due now",
+                &[&reminder.task_id, &reminder.title],
+            )
+        };
+        write_notice(&notice, true);
+        let _ = write_prompt(line, *back);
+    }
+}
+
+/// Prints a notice for each other user who started or stopped editing one
+/// of the viewer's tasks since the last check, if the menu prompt is
+/// showing.
+fn announce_editing(viewer: &Viewer, editing: &mut Presence) {
+    let prompt = PROMPT.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some((line, back)) = prompt.as_ref() else {
+        return;
+    };
+    let Ok(current) = presence::load() else {
+        return;
+    };
+    for change in presence::changes(editing, &current) {
+        if viewer.username.as_ref() == Some(&change.username) {
+            continue;
+        }
+        let Some(title) = viewer.titles.get(&change.task_id) else {
+            continue;
+        };
+        let notice = if change.editing {
+            trf(
+                "{} is editing task #{} {}",
+                &[&change.username, &change.task_id, title],
+            )
+        } else {
+            trf(
+                "{} stopped editing task #{} {}",
+                &[&change.username, &change.task_id, title],
+            )
+        };
+        write_notice(&notice, false);
+        let _ = write_prompt(line, *back);
+    }
+    *editing = current;
+}
@@ -0,0 +1,76 @@
+//! Manual order of the tasks in a project, used by the `manual` sort order.
+//! Moving a task renumbers its project's tasks from 1, so the positions
+//! stay gapless however often tasks are moved.
+
+use crate::settings::SortOrder;
+use crate::{Task, TodoApp};
+
+/// Where to move a task within its project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Up,
+    Down,
+    /// To a position counted from 1.
+    To(usize),
+}
+
+impl Move {
+    /// Parses `up`, `down` or `to <n>`, given as separate words.
+    pub fn parse(words: &[&str]) -> Result<Self, &'static str> {
+        match words {
+            ["up"] => Ok(Self::Up),
+            ["down"] => Ok(Self::Down),
+            ["to", position] => match position.parse() {
+                Ok(0) | Err(_) => Err("Invalid position, expected a number from 1"),
+                Ok(position) => Ok(Self::To(position)),
+            },
+            _ => Err("Expected up, down or to <position>"),
+        }
+    }
+}
+
+impl TodoApp {
+    /// Moves a task within the tasks of its project in the current list.
+    /// Returns its new position, counted from 1.
+    pub fn move_task(&mut self, task_id: u32, to: Move) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        let mut siblings: Vec<&Task> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|other| other.project == task.project)
+            .collect();
+        siblings.sort_by(|a, b| SortOrder::Manual.compare(a, b));
+        let mut ids: Vec<u32> = siblings.iter().map(|task| task.id).collect();
+        let from = ids
+            .iter()
+            .position(|id| *id == task_id)
+            .ok_or("Task not found")?;
+        let last = ids.len() - 1;
+        let to = match to {
+            Move::Up => from.saturating_sub(1),
+            Move::Down => (from + 1).min(last),
+            Move::To(position) => (position - 1).min(last),
+        };
+
+        ids.remove(from);
+        ids.insert(to, task_id);
+        for (i, id) in ids.iter().enumerate() {
+            let position = Some(i as u32 + 1);
+            if let Some(task) = self
+                .tasks
+                .get_mut(id)
+                .filter(|task| task.position != position)
+            {
+                task.position = position;
+                task.touch();
+            }
+        }
+        self.save_tasks(&format!("Move task #{} to position {}", task_id, to + 1))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(to + 1)
+    }
+}
@@ -0,0 +1,100 @@
+//! Tasks whose owner isn't a registered user any more, which can happen when
+//! the data files are edited by hand. No one can list them, so admins can
+//! reassign them to a user, export them to a file or delete them with
+//! `orphans`, and `doctor` points them out.
+
+use crate::{Task, TodoApp, User};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// The tasks among `tasks` owned by none of `users`, ordered by ID.
+pub fn find<'a>(
+    tasks: impl IntoIterator<Item = &'a Task>,
+    users: &BTreeMap<String, User>,
+) -> Vec<&'a Task> {
+    let mut orphans: Vec<&Task> = tasks
+        .into_iter()
+        .filter(|task| !users.contains_key(&task.user_id))
+        .collect();
+    orphans.sort_by_key(|task| task.id);
+    orphans
+}
+
+/// How many orphaned tasks each missing owner left.
+pub fn count_by_owner(orphans: &[&Task]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for task in orphans {
+        *counts.entry(task.user_id.clone()).or_default() += 1;
+    }
+    counts
+}
+
+impl TodoApp {
+    /// The orphaned tasks, for admins only.
+    pub fn orphaned_tasks(&self) -> Result<Vec<&Task>, &'static str> {
+        self.require_admin()?;
+        Ok(find(self.tasks.values(), &self.users))
+    }
+
+    /// Gives every orphaned task to `username`. Returns how many were
+    /// reassigned.
+    pub fn reassign_orphans(&mut self, username: &str) -> Result<usize, &'static str> {
+        let username = self
+            .find_user(username)
+            .ok_or("User not found")?
+            .to_string();
+        let orphans: Vec<Task> = self.orphaned_tasks()?.into_iter().cloned().collect();
+        if orphans.is_empty() {
+            return Ok(0);
+        }
+        let reassigned = orphans.len();
+        let mut transaction = self.begin();
+        for mut task in orphans {
+            task.user_id = username.clone();
+            task.touch();
+            transaction.insert_task(task);
+        }
+        let message = format!("Reassign {} orphaned tasks to {}", reassigned, username);
+        transaction.commit(&message)?;
+        Ok(reassigned)
+    }
+
+    /// Writes the orphaned tasks to `path` as a JSON array. Returns how
+    /// many were written.
+    pub fn export_orphans(&self, path: &str) -> Result<usize, String> {
+        let orphans = self.orphaned_tasks()?;
+        let json = serde_json::to_string_pretty(&orphans).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(orphans.len())
+    }
+
+    /// Moves every orphaned task to the trash. Returns how many were
+    /// deleted.
+    pub fn delete_orphans(&mut self) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let ids: Vec<u32> = self.orphaned_tasks()?.iter().map(|task| task.id).collect();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut transaction = self.begin();
+        let mut deleted = Vec::new();
+        for task_id in &ids {
+            if let Some(task) = transaction.remove_task(*task_id) {
+                transaction.remove_links_to(*task_id);
+                deleted.push(task);
+            }
+        }
+        let message = format!("Delete {} orphaned tasks", deleted.len());
+        transaction.commit(&message)?;
+        self.move_to_trash(&deleted, &user_id);
+        Ok(deleted.len())
+    }
+
+    fn require_admin(&self) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        if !self.admins.contains(user_id) {
+            return Err("Only admins can manage orphaned tasks");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+//! Stored passwords. Only a salted PBKDF2-HMAC-SHA256 hash of each password
+//! is kept, as `pbkdf2-sha256$<iterations>$<salt>$<hash>` with the salt and
+//! hash in hex. Accounts saved before that hold the password itself until
+//! the users file is next loaded.
+
+use crate::crypto;
+
+const SCHEME: &str = "pbkdf2-sha256";
+
+/// Iterations for new hashes. Hashes record their own, so this can be
+/// raised without breaking older ones.
+#[cfg(not(test))]
+const ITERATIONS: u32 = 100_000;
+
+/// Unoptimized test builds would take seconds per hash.
+#[cfg(test)]
+const ITERATIONS: u32 = 10;
+
+/// Hashes `password` with a new random salt.
+pub fn hash(password: &str) -> Result<String, &'static str> {
+    let salt = crypto::random_bytes().map_err(|_| crypto::NO_RANDOM)?;
+    Ok(hash_with(password, &salt, ITERATIONS))
+}
+
+fn hash_with(password: &str, salt: &[u8], iterations: u32) -> String {
+    let hash = crypto::pbkdf2_hmac_sha256(password.as_bytes(), salt, iterations);
+    format!(
+        "{}${}${}${}",
+        SCHEME,
+        iterations,
+        crypto::to_hex(salt),
+        crypto::to_hex(&hash)
+    )
+}
+
+/// Whether `stored` is a hash rather than a password saved before
+/// passwords were hashed.
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with(&format!("{}$", SCHEME))
+}
+
+/// Whether `password` is the one `stored` was made from, compared in
+/// constant time.
+pub fn verify(stored: &str, password: &str) -> bool {
+    if !is_hashed(stored) {
+        // Hashing both sides hides the length of the stored password.
+        return crypto::constant_time_eq(
+            &crypto::sha256(stored.as_bytes()),
+            &crypto::sha256(password.as_bytes()),
+        );
+    }
+    let mut parts = stored.split('$').skip(1);
+    let (Some(iterations), Some(salt), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let (Ok(iterations), Some(salt)) = (iterations.parse(), crypto::from_hex(salt)) else {
+        return false;
+    };
+    crypto::constant_time_eq(
+        hash_with(password, &salt, iterations).as_bytes(),
+        stored.as_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_only_the_hashed_password() {
+        let stored = hash("correct horse").unwrap();
+        assert!(is_hashed(&stored));
+        assert!(!stored.contains("correct horse"));
+        assert!(verify(&stored, "correct horse"));
+        assert!(!verify(&stored, "correct horse "));
+        assert!(!verify(&stored, ""));
+    }
+
+    #[test]
+    fn salts_each_hash() {
+        assert_ne!(hash("hunter2").unwrap(), hash("hunter2").unwrap());
+    }
+
+    #[test]
+    fn matches_a_known_hash() {
+        // The first block of RFC 7914's PBKDF2-HMAC-SHA256 example.
+        let stored = hash_with("passwd", b"salt", 1);
+        assert_eq!(
+            stored,
+            "pbkdf2-sha256$1$73616c74$\
+             55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+        assert!(verify(&stored, "passwd"));
+    }
+
+    #[test]
+    fn still_verifies_passwords_saved_before_hashing() {
+        assert!(!is_hashed("hunter2"));
+        assert!(verify("hunter2", "hunter2"));
+        assert!(!verify("hunter2", "hunter3"));
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        for stored in [
+            "pbkdf2-sha256$",
+            "pbkdf2-sha256$many$73616c74$00",
+            "pbkdf2-sha256$1$zz$00",
+            "pbkdf2-sha256$1$73616c74$00$00",
+        ] {
+            assert!(!verify(stored, "passwd"), "{}", stored);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Writing plain text as a PDF document (the `pdf` feature), for printing
+//! the planner. Lines are set in Courier on A4 pages, as many pages as the
+//! text needs. Courier only covers Latin-1, so other characters print as
+//! `?`.
+
+/// A4 in points.
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 10;
+
+/// Distance between lines, in points.
+const LEADING: u32 = 12;
+
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LEADING) as usize;
+
+/// The PDF document showing `text`.
+pub fn render(text: &str) -> Vec<u8> {
+    let lines: Vec<&str> = text.lines().collect();
+    let pages: Vec<&[&str]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // Objects 1 to 3 are the catalog, page tree and font; each page then
+    // takes two, the page and its contents.
+    let kids: Vec<String> = (0..pages.len())
+        .map(|page| format!("{} 0 R", 4 + page * 2))
+        .collect();
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+    ];
+    for (page, lines) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                5 + page * 2
+            )
+            .into_bytes(),
+        );
+        let contents = page_contents(lines);
+        let mut stream = format!("<< /Length {} >>\nstream\n", contents.len()).into_bytes();
+        stream.extend_from_slice(&contents);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+/// The content stream drawing `lines` from the top of a page.
+fn page_contents(lines: &[&str]) -> Vec<u8> {
+    let mut out = format!(
+        "BT\n/F1 {} Tf\n{} TL\n{} {} Td\n",
+        FONT_SIZE,
+        LEADING,
+        MARGIN,
+        PAGE_HEIGHT - MARGIN
+    )
+    .into_bytes();
+    for line in lines {
+        out.push(b'(');
+        out.extend(encode(line));
+        out.extend_from_slice(b") Tj T*\n");
+    }
+    out.extend_from_slice(b"ET");
+    out
+}
+
+/// `text` as the body of a PDF string in WinAnsiEncoding.
+fn encode(text: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => out.extend_from_slice(&[b'\\', c as u8]),
+            c if !c.is_control() => out.push(u8::try_from(c).unwrap_or(b'?')),
+            _ => out.push(b'?'),
+        }
+    }
+    out
+}
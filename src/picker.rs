@@ -0,0 +1,155 @@
+//! Choosing a task from a list with the arrow keys instead of typing its ID.
+//!
+//! The picker needs raw terminal input (see `terminal`); without it, or for
+//! screen readers, the caller gets a plain "Task ID" prompt.
+
+use crate::accessibility;
+use crate::i18n::tr;
+use crate::terminal::{self, Key, RawMode};
+use crate::Task;
+use std::io::{self, Write};
+
+/// Rows of the list shown at once.
+const VISIBLE: usize = 10;
+
+/// Asks for a task, returning its ID as typed or picked, or an empty string
+/// if the user cancelled. Typing filters `tasks` by title or ID; Enter with
+/// a filter that matches nothing returns the filter, so tasks that aren't
+/// listed can still be chosen by ID.
+pub fn pick_task(prompt: &str, tasks: &[&Task]) -> io::Result<String> {
+    if tasks.is_empty() || !terminal::is_interactive() || accessibility::screen_reader() {
+        return crate::prompt_input(prompt);
+    }
+    let Some(_raw) = RawMode::enable() else {
+        return crate::prompt_input(prompt);
+    };
+    let mut picker = Picker {
+        prompt,
+        tasks,
+        filter: String::new(),
+        selected: 0,
+        offset: 0,
+        drawn: 0,
+    };
+    let result = picker.run();
+    picker.clear()?;
+    let choice = result?;
+    println!("{}{}", prompt, choice);
+    Ok(choice)
+}
+
+struct Picker<'a> {
+    prompt: &'a str,
+    tasks: &'a [&'a Task],
+    filter: String,
+    /// Index of the highlighted task among the matches.
+    selected: usize,
+    /// Index of the first match on screen.
+    offset: usize,
+    /// Lines printed by the last `draw`, to be overwritten by the next.
+    drawn: usize,
+}
+
+impl Picker<'_> {
+    fn run(&mut self) -> io::Result<String> {
+        let mut stdin = io::stdin().lock();
+        loop {
+            self.draw()?;
+            match terminal::read_key(&mut stdin)? {
+                Key::Enter => {
+                    let matches = self.matches();
+                    return Ok(match matches.get(self.selected) {
+                        Some(task) => task.id.to_string(),
+                        None => self.filter.trim().to_string(),
+                    });
+                }
+                Key::Ctrl('c' | 'd') | Key::Escape => return Ok(String::new()),
+                Key::Up | Key::Ctrl('p') => self.move_by(-1),
+                Key::Down | Key::Ctrl('n') => self.move_by(1),
+                Key::Backspace => {
+                    self.filter.pop();
+                    self.reset();
+                }
+                Key::Char(c) => {
+                    self.filter.push(c);
+                    self.reset();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Tasks whose ID starts with the filter or whose title contains it,
+    /// ignoring case.
+    fn matches(&self) -> Vec<&Task> {
+        let filter = self.filter.trim().to_lowercase();
+        self.tasks
+            .iter()
+            .copied()
+            .filter(|task| {
+                task.id.to_string().starts_with(&filter)
+                    || task.title.to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+
+    fn reset(&mut self) {
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    fn move_by(&mut self, step: isize) {
+        let count = self.matches().len();
+        if count == 0 {
+            return;
+        }
+        self.selected = (self.selected as isize + step).rem_euclid(count as isize) as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + VISIBLE {
+            self.offset = self.selected + 1 - VISIBLE;
+        }
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        self.clear()?;
+        let matches = self.matches();
+        let mut lines = vec![format!(
+            "{} ({})",
+            tr("Up/Down to move, type to filter, Enter to select, Ctrl-C to cancel"),
+            matches.len()
+        )];
+        if matches.is_empty() {
+            lines.push(format!("  {}", tr("No matching tasks")));
+        }
+        for (i, task) in matches.iter().enumerate().skip(self.offset).take(VISIBLE) {
+            let line = format!("{:>4}  {}", task.id, task.title);
+            if i == self.selected {
+                lines.push(format!("\x1b[7m> {}\x1b[0m", line));
+            } else {
+                lines.push(format!("  {}", line));
+            }
+        }
+        let mut stdout = io::stdout().lock();
+        for line in &lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        write!(stdout, "{}{}", self.prompt, self.filter)?;
+        stdout.flush()?;
+        self.drawn = lines.len();
+        Ok(())
+    }
+
+    /// Erases the list and the prompt line.
+    fn clear(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\r")?;
+        if self.drawn > 0 {
+            write!(stdout, "\x1b[{}A", self.drawn)?;
+        }
+        write!(stdout, "\x1b[J")?;
+        stdout.flush()?;
+        self.drawn = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! A daily planner to print: today's focus list, the tasks due today and
+//! overdue, and the habits to check off, with room for notes. The layout is
+//! plain monochrome text with `[ ]` boxes to tick with a pen, and can be
+//! written as PDF with the `pdf` feature.
+
+use crate::habits::Frequency;
+use crate::i18n::{self, tr, trf};
+use crate::schedule::{local_date, start_of_today};
+use crate::{Task, TodoApp};
+use chrono::{Datelike, Days, NaiveDate};
+
+/// Characters in a line of the planner; fits a page in a 10 point font.
+const WIDTH: usize = 72;
+
+/// Blank lines left in the focus list for tasks picked on paper.
+const BLANK_FOCUS_LINES: usize = 2;
+
+/// Lines left for notes at the bottom.
+const NOTE_LINES: usize = 6;
+
+/// What goes on a day's planner.
+pub struct Planner<'a> {
+    pub date: NaiveDate,
+    pub focus: Vec<&'a Task>,
+    pub due_today: Vec<&'a Task>,
+    pub overdue: Vec<&'a Task>,
+    pub habits: Vec<HabitLine>,
+}
+
+/// A habit to check off on the planner.
+pub struct HabitLine {
+    pub name: String,
+    /// Whether it was done today.
+    pub done: bool,
+    /// For weekly habits, how many times it was done this week out of how
+    /// many.
+    pub week: Option<(usize, u8)>,
+}
+
+impl TodoApp {
+    /// Collects today's planner for the current user.
+    pub fn planner(&self) -> Result<Planner<'_>, &'static str> {
+        let date = local_date(start_of_today());
+        let agenda = self.agenda(1)?;
+        let focus = self.focus_tasks()?;
+        // Focused tasks are only listed once, under the focus list.
+        let unfocused = |task: &&Task| !focus.iter().any(|focused| focused.id == task.id);
+        let due_today = agenda
+            .days
+            .into_iter()
+            .flat_map(|(_, tasks)| tasks)
+            .filter(unfocused)
+            .collect();
+        let overdue = agenda.overdue.into_iter().filter(unfocused).collect();
+        let monday = date - Days::new(u64::from(date.weekday().num_days_from_monday()));
+        let habits = self
+            .habits()?
+            .into_iter()
+            .map(|(name, habit)| {
+                let week = match habit.frequency {
+                    Frequency::Daily => None,
+                    Frequency::Weekly(times) => {
+                        Some((habit.checks.range(monday..=date).count(), times))
+                    }
+                };
+                HabitLine {
+                    done: habit.checks.contains(&date),
+                    name,
+                    week,
+                }
+            })
+            .collect();
+        Ok(Planner {
+            date,
+            focus,
+            due_today,
+            overdue,
+            habits,
+        })
+    }
+}
+
+/// Renders a planner as text for printing.
+pub fn render(planner: &Planner) -> String {
+    let weekday = planner.date.format("%A").to_string();
+    let title = tr("Daily planner").to_uppercase();
+    let date = format!("{} {}", tr(&weekday), i18n::format_day(planner.date));
+    let gap = WIDTH.saturating_sub(title.chars().count() + date.chars().count());
+    let mut out = format!("{}{}{}\n", title, " ".repeat(gap.max(1)), date);
+    out.push_str(&"=".repeat(WIDTH));
+    out.push('\n');
+
+    heading(&mut out, tr("Focus"));
+    for task in &planner.focus {
+        task_line(&mut out, task, None);
+    }
+    for _ in 0..BLANK_FOCUS_LINES {
+        out.push_str(&format!("  [ ] {}\n", "_".repeat(WIDTH - 6)));
+    }
+
+    heading(&mut out, tr("Due today"));
+    if planner.due_today.is_empty() {
+        out.push_str(&format!("  {}\n", tr("nothing")));
+    }
+    for task in &planner.due_today {
+        task_line(&mut out, task, None);
+    }
+
+    if !planner.overdue.is_empty() {
+        heading(&mut out, tr("Overdue"));
+        for task in &planner.overdue {
+            let due = task.due_date.map(|due| i18n::format_day(local_date(due)));
+            task_line(&mut out, task, due.map(|due| trf("due {}", &[&due])));
+        }
+    }
+
+    if !planner.habits.is_empty() {
+        heading(&mut out, tr("Habits"));
+        for habit in &planner.habits {
+            let (name, mark) = (&habit.name, if habit.done { 'x' } else { ' ' });
+            match habit.week {
+                Some((checks, times)) => out.push_str(&format!(
+                    "  [{}] {} ({})\n",
+                    mark,
+                    name,
+                    trf("{} of {} this week", &[&checks, &times])
+                )),
+                None => out.push_str(&format!("  [{}] {}\n", mark, name)),
+            }
+        }
+    }
+
+    heading(&mut out, tr("Notes"));
+    for _ in 0..NOTE_LINES {
+        out.push_str(&format!("  {}\n", "_".repeat(WIDTH - 2)));
+    }
+    out
+}
+
+fn heading(out: &mut String, text: &str) {
+    out.push('\n');
+    out.push_str(&text.to_uppercase());
+    out.push('\n');
+}
+
+/// A task with a box to tick, ticked if it's done, cut to fit the line.
+fn task_line(out: &mut String, task: &Task, note: Option<String>) {
+    let mark = if task.completed { 'x' } else { ' ' };
+    let mut line = format!("  [{}] #{} {}", mark, task.id, task.title);
+    if let Some(note) = note {
+        line.push_str(&format!(" ({})", note));
+    }
+    if line.chars().count() > WIDTH {
+        line = line.chars().take(WIDTH - 3).collect::<String>() + "...";
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
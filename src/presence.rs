@@ -0,0 +1,142 @@
+//! Who is editing which task right now. The interactive menu marks a task
+//! while its new title and description are typed, and server clients mark
+//! one by sending `{"editing": <task id>}` over their `/events` WebSocket,
+//! then `{"editing": null}` when done. The server streams `editing` and
+//! `stopped_editing` events to the other users who can see the task, and the
+//! menu shows them as notices.
+//!
+//! Marks are kept in `presence.json` rather than in storage, as they don't
+//! need a history, and expire in case a session ends without clearing its
+//! own.
+
+use crate::log;
+use crate::TodoApp;
+use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+const PRESENCE_FILE: &str = "presence.json";
+
+/// How long a mark lasts unless it's renewed.
+const EXPIRES_AFTER_MINS: i64 = 10;
+
+/// A task a user is editing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Editing {
+    pub task_id: u32,
+    #[serde(with = "ts_seconds")]
+    pub since: DateTime<Utc>,
+}
+
+/// Which task each user is editing, by username.
+pub type Presence = BTreeMap<String, Editing>;
+
+/// A user starting or stopping to edit a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub username: String,
+    pub task_id: u32,
+    pub editing: bool,
+}
+
+/// The marks that haven't expired.
+pub fn load() -> io::Result<Presence> {
+    let mut presence: Presence = match fs::read_to_string(PRESENCE_FILE) {
+        Ok(contents) => serde_json::from_str(&contents)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Presence::new(),
+        Err(e) => return Err(e),
+    };
+    let cutoff = Utc::now() - Duration::minutes(EXPIRES_AFTER_MINS);
+    presence.retain(|_, editing| editing.since > cutoff);
+    Ok(presence)
+}
+
+fn save(presence: &Presence) -> io::Result<()> {
+    let json = serde_json::to_string(presence)?;
+    fs::write(PRESENCE_FILE, json)?;
+    Ok(())
+}
+
+/// Marks `username` as editing a task, or as editing none with `None`.
+/// Marking the same task again renews the mark.
+pub fn set_editing(username: &str, task_id: Option<u32>) -> io::Result<()> {
+    let mut presence = load()?;
+    let previous = match task_id {
+        Some(task_id) => presence.insert(
+            username.to_string(),
+            Editing {
+                task_id,
+                since: Utc::now(),
+            },
+        ),
+        None => presence.remove(username),
+    };
+    if task_id.is_none() && previous.is_none() {
+        return Ok(());
+    }
+    save(&presence)
+}
+
+/// The users who started or stopped editing a task between two snapshots.
+/// Moving to another task is a stop followed by a start.
+pub fn changes(previous: &Presence, current: &Presence) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (username, editing) in previous {
+        if current.get(username).map(|now| now.task_id) != Some(editing.task_id) {
+            changes.push(Change {
+                username: username.clone(),
+                task_id: editing.task_id,
+                editing: false,
+            });
+        }
+    }
+    for (username, editing) in current {
+        if previous.get(username).map(|before| before.task_id) != Some(editing.task_id) {
+            changes.push(Change {
+                username: username.clone(),
+                task_id: editing.task_id,
+                editing: true,
+            });
+        }
+    }
+    changes
+}
+
+impl TodoApp {
+    /// Marks the current user as editing a task, or as editing none with
+    /// `None`. Failing to do so only loses the notice for others, so it's
+    /// logged rather than reported.
+    pub fn mark_editing(&self, task_id: Option<u32>) {
+        let Some(user_id) = &self.current_user else {
+            return;
+        };
+        if self.dry_run {
+            return;
+        }
+        if let Err(e) = set_editing(user_id, task_id) {
+            log::warn(&format!("Failed to save presence of {}: {}", user_id, e));
+        }
+    }
+
+    /// The other users editing a task the current user can see.
+    pub fn editors_of(&self, task_id: u32) -> Vec<String> {
+        let Some(user_id) = &self.current_user else {
+            return Vec::new();
+        };
+        let visible = self
+            .tasks
+            .get(&task_id)
+            .is_some_and(|task| self.workspaces.can_access(task, user_id));
+        if !visible {
+            return Vec::new();
+        }
+        load()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(username, editing)| username != user_id && editing.task_id == task_id)
+            .map(|(username, _)| username)
+            .collect()
+    }
+}
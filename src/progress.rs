@@ -0,0 +1,77 @@
+//! How far along a task is, as a percentage. It's set by hand, or worked out
+//! from the tasks it depends on when it has any, which then act as its
+//! subtasks. Completing a task counts as 100% whatever its progress.
+
+use crate::accessibility;
+use crate::{Task, TodoApp};
+
+/// Cells in a progress bar.
+const BAR_WIDTH: usize = 10;
+
+/// Parses a percentage from 0 to 100, with or without a `%` sign.
+pub fn parse_percent(input: &str) -> Result<u8, &'static str> {
+    input
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+        .filter(|percent| *percent <= 100)
+        .ok_or("Invalid progress, expected a percentage from 0 to 100")
+}
+
+/// A bar such as `[####------] 40%`, or just `40%` for screen readers.
+pub fn bar(percent: u8) -> String {
+    if accessibility::screen_reader() {
+        return format!("{}%", percent);
+    }
+    let filled = usize::from(percent) * BAR_WIDTH / 100;
+    format!(
+        "[{}{}] {}%",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        percent
+    )
+}
+
+impl TodoApp {
+    /// Sets how far along a task is, or clears it with `None`.
+    pub fn set_task_progress(
+        &mut self,
+        task_id: u32,
+        progress: Option<u8>,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.progress = progress;
+        task.touch();
+        let message = match progress {
+            Some(percent) => format!("Set progress of task #{} to {}%", task_id, percent),
+            None => format!("Clear progress of task #{}", task_id),
+        };
+        self.save_tasks(&message)
+            .map_err(|_| "Failed to save tasks")
+    }
+
+    /// How far along `task` is: the share of the tasks it depends on that
+    /// are completed if it has any, otherwise the progress set by hand.
+    /// `None` if neither applies.
+    pub fn progress(&self, task: &Task) -> Option<u8> {
+        if task.completed {
+            return Some(100);
+        }
+        let subtasks: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|other| task.depends_on.contains(&other.uuid))
+            .collect();
+        if subtasks.is_empty() {
+            return task.progress;
+        }
+        let completed = subtasks.iter().filter(|subtask| subtask.completed).count();
+        u8::try_from(completed * 100 / subtasks.len()).ok()
+    }
+}
@@ -0,0 +1,546 @@
+//! The filter language used by `list --query`, saved filters and the HTTP
+//! API, e.g. `status:pending AND (tag:home OR priority:high) AND
+//! due<2025-01-01`.
+//!
+//! Terms are `field<op>value` with `:` or `=` for equality and `<`, `<=`,
+//! `>`, `>=` for fields with an order:
+//!
+//! - `status:` `pending`, `completed`, `overdue` or `cancelled`, also
+//!   allowed on their own, e.g. `pending`
+//! - `tag:`, `project:`, `context:` and `assignee:`
+//! - `title:` text the title contains, ignoring case
+//! - `priority` compared with `low`, `normal`, `high` or `urgent`
+//! - `due` compared with a `YYYY-MM-DD` date; `due:none` for no due date
+//!
+//! Terms are combined with `AND`, `OR` and `NOT` and grouped with
+//! parentheses. `AND` binds tighter than `OR`, and terms next to each other
+//! are joined with `AND`. Keywords and field names ignore case.
+
+use crate::i18n::{tr, trf};
+use crate::priority::Priority;
+use crate::schedule::{local_date, start_of_today};
+use crate::Task;
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+
+/// A parsed query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Status(Status),
+    Tag(String),
+    Project(String),
+    Context(String),
+    Assignee(String),
+    Title(String),
+    /// Priorities comparing to the given one in one of the orderings.
+    Priority(Vec<Ordering>, Priority),
+    /// Due dates comparing to the given one in one of the orderings.
+    Due(Vec<Ordering>, NaiveDate),
+    NoDueDate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Completed,
+    Overdue,
+    Cancelled,
+}
+
+/// Comparison operators, longest first so `<=` isn't read as `<`.
+const OPERATORS: [(&str, &[Ordering]); 6] = [
+    (">=", &[Ordering::Greater, Ordering::Equal]),
+    ("<=", &[Ordering::Less, Ordering::Equal]),
+    (":", &[Ordering::Equal]),
+    ("=", &[Ordering::Equal]),
+    (">", &[Ordering::Greater]),
+    ("<", &[Ordering::Less]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(&'a str),
+}
+
+/// A token and the column it starts at, from 1.
+type Located<'a> = (Token<'a>, usize);
+
+/// An error message pointing at the column it was found at.
+fn error_at(column: usize, message: String) -> String {
+    trf("{} (at column {})", &[&message, &column])
+}
+
+fn tokenize(input: &str) -> Vec<Located<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let column = |byte: usize| input[..byte].chars().count() + 1;
+    for (i, c) in input.char_indices().chain([(input.len(), ' ')]) {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(s) = start.take() {
+                let word = &input[s..i];
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, column(s)));
+            }
+            match c {
+                '(' => tokens.push((Token::Open, column(i))),
+                ')' => tokens.push((Token::Close, column(i))),
+                _ => {}
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<Located<'a>>,
+    next: usize,
+    /// Column just past the end of the input, for errors at the end.
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.end, |(_, column)| *column)
+    }
+
+    fn advance(&mut self) -> Option<Located<'a>> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    /// `and ("OR" and)*`
+    fn or(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    /// `unary (["AND"] unary)*`
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.advance();
+                }
+                Some(Token::Word(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(query),
+            }
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+    }
+
+    /// `"NOT" unary | "(" or ")" | term`
+    fn unary(&mut self) -> Result<Query, String> {
+        let column = self.column();
+        match self.advance() {
+            Some((Token::Not, _)) => Ok(Query::Not(Box::new(self.unary()?))),
+            Some((Token::Open, _)) => {
+                let query = self.or()?;
+                match self.advance() {
+                    Some((Token::Close, _)) => Ok(query),
+                    _ => Err(error_at(
+                        column,
+                        tr("This parenthesis is never closed").to_string(),
+                    )),
+                }
+            }
+            Some((Token::Word(word), column)) => Term::parse(word)
+                .map(Query::Term)
+                .map_err(|e| error_at(column, e)),
+            Some((Token::Close, column)) => Err(error_at(
+                column,
+                tr("Unexpected ), no parenthesis is open").to_string(),
+            )),
+            Some((Token::And | Token::Or, column)) => Err(error_at(
+                column,
+                tr("Expected a term before AND or OR").to_string(),
+            )),
+            None => Err(error_at(
+                column,
+                tr("Expected a term at the end").to_string(),
+            )),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(input),
+            next: 0,
+            end: input.chars().count() + 1,
+        };
+        if parser.tokens.is_empty() {
+            return Err(tr("Empty query").to_string());
+        }
+        let query = parser.or()?;
+        match parser.advance() {
+            None => Ok(query),
+            Some((Token::Close, column)) => Err(error_at(
+                column,
+                tr("Unexpected ), no parenthesis is open").to_string(),
+            )),
+            Some((_, column)) => Err(error_at(column, tr("Unexpected text").to_string())),
+        }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(task) && b.matches(task),
+            Self::Or(a, b) => a.matches(task) || b.matches(task),
+            Self::Not(query) => !query.matches(task),
+            Self::Term(term) => term.matches(task),
+        }
+    }
+}
+
+impl Status {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(Self::Pending),
+            "completed" | "done" => Some(Self::Completed),
+            "overdue" => Some(Self::Overdue),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+impl Term {
+    fn parse(word: &str) -> Result<Self, String> {
+        let lowercase = word.to_lowercase();
+        if let Some(status) = Status::parse(&lowercase) {
+            return Ok(Self::Status(status));
+        }
+        let field_len = lowercase
+            .find(|c: char| !c.is_alphabetic())
+            .ok_or_else(|| trf("Unknown term {}, expected e.g. tag:home", &[&word]))?;
+        let (field, rest) = lowercase.split_at(field_len);
+        let (orderings, value) = OPERATORS
+            .iter()
+            .find_map(|(op, orderings)| rest.strip_prefix(op).map(|value| (*orderings, value)))
+            .ok_or_else(|| trf("Expected :, =, <, <=, > or >= after {}", &[&field]))?;
+        // Values keep their case except where it doesn't matter.
+        let prefix = lowercase.len() - value.len();
+        let original = word
+            .char_indices()
+            .nth(lowercase[..prefix].chars().count())
+            .map_or("", |(i, _)| &word[i..]);
+        if value.is_empty() {
+            return Err(trf("Expected a value after {}", &[&word]));
+        }
+        let equality = orderings == [Ordering::Equal];
+        let term = match field {
+            "priority" => {
+                let priority = Priority::parse(value).ok_or_else(|| {
+                    trf(
+                        "Unknown priority {}, expected low, normal, high or urgent",
+                        &[&value],
+                    )
+                })?;
+                return Ok(Self::Priority(orderings.to_vec(), priority));
+            }
+            "due" if equality && value == "none" => return Ok(Self::NoDueDate),
+            "due" => {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+                    trf("Invalid date {}, expected YYYY-MM-DD", &[&value])
+                })?;
+                return Ok(Self::Due(orderings.to_vec(), date));
+            }
+            _ if !equality => {
+                return Err(trf("{} can only be compared with : or =", &[&field]));
+            }
+            "status" => Self::Status(Status::parse(value).ok_or_else(|| {
+                trf(
+                    "Unknown status {}, expected pending, completed, overdue or cancelled",
+                    &[&value],
+                )
+            })?),
+            "tag" => Self::Tag(value.trim_start_matches('@').to_string()),
+            "project" => Self::Project(original.trim_start_matches('+').to_string()),
+            "context" => Self::Context(value.trim_start_matches('@').to_string()),
+            "assignee" => Self::Assignee(original.to_string()),
+            "title" => Self::Title(value.to_string()),
+            _ => {
+                return Err(trf(
+                    "Unknown field {}, expected status, tag, project, context, assignee, title, priority or due",
+                    &[&field],
+                ))
+            }
+        };
+        Ok(term)
+    }
+
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            Self::Status(Status::Pending) => !task.completed,
+            Self::Status(Status::Completed) => task.completed && task.cancelled_at.is_none(),
+            Self::Status(Status::Overdue) => {
+                !task.completed && task.due_date.is_some_and(|due| due < start_of_today())
+            }
+            Self::Status(Status::Cancelled) => task.cancelled_at.is_some(),
+            Self::Tag(tag) => task.tags.contains(tag),
+            Self::Project(project) => task.project.as_ref() == Some(project),
+            Self::Context(context) => task.context.as_ref() == Some(context),
+            Self::Assignee(assignee) => task.assignee.as_ref() == Some(assignee),
+            Self::Title(text) => task.title.to_lowercase().contains(text),
+            Self::Priority(orderings, priority) => orderings.contains(&task.priority.cmp(priority)),
+            Self::Due(orderings, date) => task
+                .due_date
+                .is_some_and(|due| orderings.contains(&local_date(due).cmp(date))),
+            Self::NoDueDate => task.due_date.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn term(term: Term) -> Query {
+        Query::Term(term)
+    }
+
+    fn and(a: Query, b: Query) -> Query {
+        Query::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: Query, b: Query) -> Query {
+        Query::Or(Box::new(a), Box::new(b))
+    }
+
+    fn not(query: Query) -> Query {
+        Query::Not(Box::new(query))
+    }
+
+    fn tag(name: &str) -> Query {
+        term(Term::Tag(name.to_string()))
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn task(fields: serde_json::Value) -> Task {
+        let mut task = serde_json::json!({
+            "id": 1,
+            "title": "Buy milk",
+            "description": "",
+            "completed": false,
+            "created_at": 0,
+            "user_id": "alice",
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            task[key] = value.clone();
+        }
+        serde_json::from_value(task).unwrap()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            Query::parse("tag:a OR tag:b AND tag:c").unwrap(),
+            or(tag("a"), and(tag("b"), tag("c")))
+        );
+        assert_eq!(
+            Query::parse("tag:a AND tag:b OR tag:c").unwrap(),
+            or(and(tag("a"), tag("b")), tag("c"))
+        );
+    }
+
+    #[test]
+    fn adjacent_terms_are_joined_with_and() {
+        assert_eq!(
+            Query::parse("tag:a tag:b or tag:c").unwrap(),
+            or(and(tag("a"), tag("b")), tag("c"))
+        );
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term_only() {
+        assert_eq!(
+            Query::parse("NOT tag:a tag:b").unwrap(),
+            and(not(tag("a")), tag("b"))
+        );
+        assert_eq!(Query::parse("not not tag:a").unwrap(), not(not(tag("a"))));
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(
+            Query::parse("tag:a AND (tag:b OR tag:c)").unwrap(),
+            and(tag("a"), or(tag("b"), tag("c")))
+        );
+        assert_eq!(
+            Query::parse("NOT(tag:a OR tag:b)").unwrap(),
+            not(or(tag("a"), tag("b")))
+        );
+        assert_eq!(Query::parse("((tag:a))").unwrap(), tag("a"));
+    }
+
+    #[test]
+    fn parses_each_field() {
+        let parse = |input| match Query::parse(input).unwrap() {
+            Query::Term(term) => term,
+            query => panic!("{} parsed to {:?}", input, query),
+        };
+        assert_eq!(parse("pending"), Term::Status(Status::Pending));
+        assert_eq!(parse("Status:Done"), Term::Status(Status::Completed));
+        assert_eq!(parse("status=overdue"), Term::Status(Status::Overdue));
+        assert_eq!(parse("cancelled"), Term::Status(Status::Cancelled));
+        assert_eq!(parse("tag:@Home"), Term::Tag("home".to_string()));
+        assert_eq!(parse("project:+Work"), Term::Project("Work".to_string()));
+        assert_eq!(parse("context:@Desk"), Term::Context("desk".to_string()));
+        assert_eq!(parse("assignee:Bob"), Term::Assignee("Bob".to_string()));
+        assert_eq!(parse("TITLE:Milk"), Term::Title("milk".to_string()));
+        assert_eq!(parse("due:none"), Term::NoDueDate);
+    }
+
+    #[test]
+    fn parses_each_operator() {
+        let high = Priority::High;
+        let cases = [
+            ("priority:high", vec![Ordering::Equal]),
+            ("priority=high", vec![Ordering::Equal]),
+            ("priority<high", vec![Ordering::Less]),
+            ("priority<=high", vec![Ordering::Less, Ordering::Equal]),
+            ("priority>high", vec![Ordering::Greater]),
+            ("priority>=high", vec![Ordering::Greater, Ordering::Equal]),
+        ];
+        for (input, orderings) in cases {
+            assert_eq!(
+                Query::parse(input).unwrap(),
+                term(Term::Priority(orderings, high)),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            Query::parse("due<2025-01-01").unwrap(),
+            term(Term::Due(vec![Ordering::Less], date(2025, 1, 1)))
+        );
+        assert_eq!(
+            Query::parse("due>=2025-01-01").unwrap(),
+            term(Term::Due(
+                vec![Ordering::Greater, Ordering::Equal],
+                date(2025, 1, 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_terms() {
+        assert_eq!(Query::parse("").unwrap_err(), "Empty query");
+        assert_eq!(Query::parse("   ").unwrap_err(), "Empty query");
+        assert_eq!(
+            Query::parse("tag<home").unwrap_err(),
+            "tag can only be compared with : or = (at column 1)"
+        );
+        assert_eq!(
+            Query::parse("tag:").unwrap_err(),
+            "Expected a value after tag: (at column 1)"
+        );
+        assert_eq!(
+            Query::parse("colour:red").unwrap_err(),
+            "Unknown field colour, expected status, tag, project, context, assignee, title, priority or due (at column 1)"
+        );
+        assert_eq!(
+            Query::parse("tag~x").unwrap_err(),
+            "Expected :, =, <, <=, > or >= after tag (at column 1)"
+        );
+        assert_eq!(
+            Query::parse("milk").unwrap_err(),
+            "Unknown term milk, expected e.g. tag:home (at column 1)"
+        );
+        assert!(Query::parse("priority:soon").is_err());
+        assert!(Query::parse("due<tomorrow").is_err());
+        assert!(Query::parse("status:later").is_err());
+    }
+
+    #[test]
+    fn errors_report_the_column() {
+        let error = |input| Query::parse(input).unwrap_err();
+        assert_eq!(
+            error("tag:a AND (tag:b"),
+            "This parenthesis is never closed (at column 11)"
+        );
+        assert_eq!(
+            error("tag:a )"),
+            "Unexpected ), no parenthesis is open (at column 7)"
+        );
+        assert_eq!(
+            error(") tag:a"),
+            "Unexpected ), no parenthesis is open (at column 1)"
+        );
+        assert_eq!(
+            error("tag:a OR OR tag:b"),
+            "Expected a term before AND or OR (at column 10)"
+        );
+        assert_eq!(
+            error("tag:a AND"),
+            "Expected a term at the end (at column 10)"
+        );
+        assert_eq!(
+            error("tag:a  priority:soon"),
+            "Unknown priority soon, expected low, normal, high or urgent (at column 8)"
+        );
+        // Columns count characters, not bytes.
+        assert_eq!(
+            error("title:молоко tag:"),
+            "Expected a value after tag: (at column 14)"
+        );
+    }
+
+    #[test]
+    fn matches_tasks() {
+        let matches = |query, task: &Task| Query::parse(query).unwrap().matches(task);
+        let task = task(serde_json::json!({
+            "tags": ["home"],
+            "project": "Chores",
+            "context": "shop",
+            "assignee": "bob",
+            "priority": "high",
+            "due_date": Utc.with_ymd_and_hms(2025, 1, 10, 12, 0, 0).unwrap().timestamp(),
+        }));
+        assert!(matches("pending tag:home project:Chores", &task));
+        assert!(matches("context:shop assignee:bob title:MILK", &task));
+        assert!(matches("priority>=normal AND priority<urgent", &task));
+        assert!(matches("due>2025-01-01 due<2025-02-01", &task));
+        assert!(matches("completed OR NOT tag:work", &task));
+        assert!(!matches("tag:work OR project:chores", &task));
+        assert!(!matches("due:none", &task));
+        assert!(!matches("NOT (tag:home OR tag:work)", &task));
+    }
+}
@@ -0,0 +1,251 @@
+//! Adding a task from a single line, with its details written inline:
+//!
+//! ```text
+//! Pay rent +home @finance !high due:friday
+//! ```
+//!
+//! `+name` puts the task in a project, `@name` adds a tag, `!level` sets the
+//! priority and `due:when` the due date, which may skip days off with
+//! `next-workday` or `+3wd`; the remaining words make up the
+//! title. The project's and tags' defaults fill in what isn't given. A leading backslash keeps a
+//! word in the title, e.g. `\@home`.
+
+use crate::holidays::WorkCalendar;
+use crate::priority::Priority;
+use crate::schedule;
+use crate::TodoApp;
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use std::collections::BTreeSet;
+
+/// Most working days `+Nwd` may look ahead, about 100 years.
+const MAX_WORKING_DAYS: u32 = 26_100;
+
+/// A task as described on a quick-add line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickAdd {
+    pub title: String,
+    pub project: Option<String>,
+    pub tags: BTreeSet<String>,
+    pub priority: Option<Priority>,
+    pub due_date: Option<NaiveDate>,
+}
+
+/// Splits a quick-add line into the title and inline details. Relative due
+/// dates such as `tomorrow` or `friday` are counted from `today`, and
+/// working days by `calendar`.
+pub fn parse_quick_add(
+    input: &str,
+    today: NaiveDate,
+    calendar: &WorkCalendar,
+) -> Result<QuickAdd, &'static str> {
+    let mut title = Vec::new();
+    let mut project = None;
+    let mut tags = BTreeSet::new();
+    let mut priority = None;
+    let mut due_date = None;
+    for word in input.split_whitespace() {
+        if let Some(literal) = word.strip_prefix('\\') {
+            title.push(literal);
+        } else if let Some(name) = word.strip_prefix('+').filter(|name| !name.is_empty()) {
+            if project.is_some() {
+                return Err("Only one project may be given");
+            }
+            project = Some(name.to_string());
+        } else if let Some(tag) = word.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            tags.insert(tag.to_lowercase());
+        } else if let Some(level) = word.strip_prefix('!').filter(|level| !level.is_empty()) {
+            if priority.is_some() {
+                return Err("Only one priority may be given");
+            }
+            priority = Some(
+                Priority::parse(level)
+                    .ok_or("Unknown priority, expected low, normal, high or urgent")?,
+            );
+        } else if let Some(when) = word.strip_prefix("due:") {
+            if due_date.is_some() {
+                return Err("Only one due date may be given");
+            }
+            due_date = Some(parse_when(when, today, calendar)?);
+        } else {
+            title.push(word);
+        }
+    }
+    if title.is_empty() {
+        return Err("The task needs a title");
+    }
+    Ok(QuickAdd {
+        title: title.join(" "),
+        project,
+        tags,
+        priority,
+        due_date,
+    })
+}
+
+/// Parses `today`, `tomorrow`, `next-workday`, a weekday (the next one, or
+/// today if it matches), `+3d`/`+2w`/`+5wd` (working days) or a `YYYY-MM-DD`
+/// date.
+pub fn parse_when(
+    when: &str,
+    today: NaiveDate,
+    calendar: &WorkCalendar,
+) -> Result<NaiveDate, &'static str> {
+    const INVALID: &str = "Invalid due date, expected today, tomorrow, next-workday, a weekday, +Nd, +Nw, +Nwd or YYYY-MM-DD";
+    let when = when.to_lowercase();
+    match when.as_str() {
+        "today" => return Ok(today),
+        "tomorrow" => return today.checked_add_days(Days::new(1)).ok_or(INVALID),
+        "next-workday" => return Ok(calendar.add_working_days(today, 1)),
+        _ => {}
+    }
+    if let Ok(weekday) = when.parse::<Weekday>() {
+        let ahead =
+            (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+        return today
+            .checked_add_days(Days::new(u64::from(ahead)))
+            .ok_or(INVALID);
+    }
+    if let Some(offset) = when.strip_prefix('+') {
+        if let Some(count) = offset.strip_suffix("wd") {
+            let count = count
+                .parse()
+                .ok()
+                .filter(|count| *count <= MAX_WORKING_DAYS)
+                .ok_or(INVALID)?;
+            return Ok(calendar.add_working_days(today, count));
+        }
+        let days = if let Some(count) = offset.strip_suffix('d') {
+            count.parse().map_err(|_| INVALID)?
+        } else if let Some(count) = offset.strip_suffix('w') {
+            count
+                .parse::<u64>()
+                .map_err(|_| INVALID)?
+                .checked_mul(7)
+                .ok_or(INVALID)?
+        } else {
+            return Err(INVALID);
+        };
+        return today.checked_add_days(Days::new(days)).ok_or(INVALID);
+    }
+    NaiveDate::parse_from_str(&when, "%Y-%m-%d").map_err(|_| INVALID)
+}
+
+impl TodoApp {
+    /// Adds a task from a quick-add line, returning its ID.
+    pub fn quick_add(&mut self, input: &str) -> Result<u32, &'static str> {
+        let today = schedule::local_date(chrono::Utc::now());
+        let quick = parse_quick_add(input, today, &self.work_calendar())?;
+        let due_date = quick.due_date.map(schedule::start_of_day);
+        let mut task = self.new_task(&quick.title, "", due_date)?;
+        task.project = quick.project;
+        task.tags = quick.tags;
+        self.apply_defaults(&mut task);
+        if let Some(priority) = quick.priority {
+            task.priority = priority;
+        }
+        self.create_task(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Wednesday.
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 15).unwrap()
+    }
+
+    fn parse(input: &str) -> Result<QuickAdd, &'static str> {
+        parse_quick_add(input, today(), &WorkCalendar::default())
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_every_token_kind() {
+        let quick = parse("Pay rent +home @Finance @bills !high due:friday").unwrap();
+        assert_eq!(quick.title, "Pay rent");
+        assert_eq!(quick.project.as_deref(), Some("home"));
+        assert_eq!(
+            quick.tags,
+            BTreeSet::from(["bills".to_string(), "finance".to_string()])
+        );
+        assert_eq!(quick.priority, Some(Priority::High));
+        assert_eq!(quick.due_date, Some(date(2024, 5, 17)));
+    }
+
+    #[test]
+    fn keeps_title_words_between_tokens() {
+        let quick = parse("Call @phone mum back").unwrap();
+        assert_eq!(quick.title, "Call mum back");
+        assert_eq!(quick.project, None);
+        assert_eq!(quick.priority, None);
+        assert_eq!(quick.due_date, None);
+    }
+
+    #[test]
+    fn bare_sigils_stay_in_the_title() {
+        assert_eq!(parse("1 + 1 @ ! due").unwrap().title, "1 + 1 @ ! due");
+    }
+
+    #[test]
+    fn backslash_keeps_a_token_in_the_title() {
+        let quick = parse(r"Email \@home about \+project \!now").unwrap();
+        assert_eq!(quick.title, "Email @home about +project !now");
+        assert!(quick.tags.is_empty());
+        assert_eq!(quick.project, None);
+        assert_eq!(quick.priority, None);
+    }
+
+    #[test]
+    fn rejects_duplicate_tokens() {
+        assert_eq!(parse("x +a +b"), Err("Only one project may be given"));
+        assert_eq!(parse("x !low !high"), Err("Only one priority may be given"));
+        assert_eq!(
+            parse("x due:today due:tomorrow"),
+            Err("Only one due date may be given")
+        );
+    }
+
+    #[test]
+    fn allows_many_tags() {
+        assert_eq!(parse("x @a @b @a").unwrap().tags.len(), 2);
+    }
+
+    #[test]
+    fn rejects_unknown_priority_and_missing_title() {
+        assert!(parse("x !soon").is_err());
+        assert_eq!(parse("+home @work"), Err("The task needs a title"));
+    }
+
+    #[test]
+    fn parses_due_dates() {
+        let calendar = WorkCalendar::default();
+        let when = |input| parse_when(input, today(), &calendar);
+        assert_eq!(when("today"), Ok(today()));
+        assert_eq!(when("Tomorrow"), Ok(date(2024, 5, 16)));
+        assert_eq!(when("wednesday"), Ok(today()));
+        assert_eq!(when("mon"), Ok(date(2024, 5, 20)));
+        assert_eq!(when("+3d"), Ok(date(2024, 5, 18)));
+        assert_eq!(when("+2w"), Ok(date(2024, 5, 29)));
+        assert_eq!(when("+3wd"), Ok(date(2024, 5, 20)));
+        assert_eq!(when("next-workday"), Ok(date(2024, 5, 16)));
+        assert_eq!(when("2024-12-31"), Ok(date(2024, 12, 31)));
+        assert!(when("someday").is_err());
+        assert!(when("+3").is_err());
+        assert!(when("2024-13-01").is_err());
+    }
+
+    #[test]
+    fn rejects_due_dates_out_of_range() {
+        let calendar = WorkCalendar::default();
+        let when = |input| parse_when(input, today(), &calendar);
+        assert!(when("+3000000000000000000w").is_err());
+        assert!(when("+18446744073709551615d").is_err());
+        assert!(when("+4000000000wd").is_err());
+        assert!(when("+26101wd").is_err());
+    }
+}
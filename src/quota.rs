@@ -0,0 +1,97 @@
+//! Limits on how many tasks each user may have, for shared installations.
+
+use crate::TodoApp;
+use serde::{Deserialize, Serialize};
+
+/// Task limits of a user. `None` means unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    /// Most pending tasks the user may have at once.
+    pub max_open: Option<u32>,
+    /// Most tasks, pending or completed, the user may have.
+    pub max_total: Option<u32>,
+}
+
+/// A user's task counts next to their limits.
+pub struct Usage {
+    pub open: u32,
+    pub total: u32,
+    pub quota: Quota,
+}
+
+impl TodoApp {
+    /// The limits that apply to `username`: their own, or the configured default.
+    pub fn quota_of(&self, username: &str) -> Quota {
+        self.users
+            .get(username)
+            .and_then(|user| user.quota)
+            .unwrap_or(self.default_quota)
+    }
+
+    /// How many tasks the current user has, and their limits.
+    pub fn usage(&self) -> Result<Usage, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        // Tasks count against their creator, whichever workspace they're in.
+        let tasks: Vec<_> = self.own_tasks(user_id).collect();
+        Ok(Usage {
+            open: tasks.iter().filter(|task| !task.completed).count() as u32,
+            total: tasks.len() as u32,
+            quota: self.quota_of(user_id),
+        })
+    }
+
+    /// Fails if the current user can't create `count` more pending tasks.
+    pub fn check_quota(&self, count: u32) -> Result<(), &'static str> {
+        let usage = self.usage()?;
+        if usage
+            .quota
+            .max_open
+            .is_some_and(|max| usage.open + count > max)
+        {
+            return Err("Task limit reached: too many open tasks");
+        }
+        if usage
+            .quota
+            .max_total
+            .is_some_and(|max| usage.total + count > max)
+        {
+            return Err("Task limit reached: too many tasks in total");
+        }
+        Ok(())
+    }
+
+    /// Sets a user's own limits, or goes back to the default with `None`.
+    /// Only admins may change quotas.
+    pub fn set_quota(&mut self, username: &str, quota: Option<Quota>) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        if !self.admins.contains(user_id) {
+            return Err("Only admins can change quotas");
+        }
+        let username = &self
+            .find_user(username)
+            .ok_or("User not found")?
+            .to_string();
+        let user = self.users.get_mut(username).ok_or("User not found")?;
+        user.quota = quota;
+        self.save_users(&format!("Set quota of user {}", username))
+            .map_err(|_| "Failed to save users")?;
+        Ok(())
+    }
+}
+
+/// Parses a limit: a number, or `none` for unlimited.
+pub fn parse_limit(input: &str) -> Result<Option<u32>, &'static str> {
+    if input.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    input
+        .parse()
+        .map(Some)
+        .map_err(|_| "Invalid limit, expected a number or none")
+}
+
+/// Formats a limit, showing unlimited as `-`.
+pub fn format_limit(limit: Option<u32>) -> String {
+    limit.map_or_else(|| "-".to_string(), |limit| limit.to_string())
+}
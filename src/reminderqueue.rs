@@ -0,0 +1,274 @@
+//! The reminder daemon's queue of tasks, ordered by when their next reminder
+//! fires, so each check only works out the reminders of the tasks that are
+//! due. Syncing still goes over every task after the daemon reloads them,
+//! but only to compare versions. A task is queued again when its version or
+//! its owner's timezone changes, which includes firing a reminder; the
+//! entries this leaves behind are skipped when they come up.
+
+use crate::log;
+use crate::{Task, TodoApp};
+use chrono::{DateTime, FixedOffset, Utc};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// How a task was last queued.
+struct Queued {
+    version: u32,
+    timezone: FixedOffset,
+    /// `None` once the task has no reminders left to fire, or after it's
+    /// been taken as due.
+    fire_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct ReminderQueue {
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, u32)>>,
+    queued: HashMap<u32, Queued>,
+}
+
+impl ReminderQueue {
+    /// Queues the tasks that are new or changed since the last sync and
+    /// drops the deleted ones.
+    pub fn sync(&mut self, app: &TodoApp, now: DateTime<Utc>) {
+        self.queued
+            .retain(|task_id, _| app.tasks.contains_key(task_id));
+        let mut timezones: HashMap<&str, FixedOffset> = HashMap::new();
+        for task in app.tasks.values() {
+            let timezone = *timezones
+                .entry(&task.user_id)
+                .or_insert_with(|| *app.local_time_of(&task.user_id, now).offset());
+            let unchanged = self.queued.get(&task.id).is_some_and(|queued| {
+                queued.version == task.version && queued.timezone == timezone
+            });
+            if unchanged {
+                continue;
+            }
+            let fire_at = next_fire(task, timezone);
+            if let Some(fire_at) = fire_at {
+                self.heap.push(Reverse((fire_at, task.id)));
+            }
+            self.queued.insert(
+                task.id,
+                Queued {
+                    version: task.version,
+                    timezone,
+                    fire_at,
+                },
+            );
+        }
+        // Don't let skipped entries pile up when tasks change often.
+        if self.heap.len() > 2 * self.queued.len() + 64 {
+            self.heap = self
+                .queued
+                .iter()
+                .filter_map(|(task_id, queued)| Some(Reverse((queued.fire_at?, *task_id))))
+                .collect();
+        }
+    }
+
+    /// Takes the tasks with a reminder due at `now`, soonest first, each
+    /// with its owner's timezone.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<(u32, FixedOffset)> {
+        let mut due = Vec::new();
+        while let Some(Reverse((fire_at, task_id))) = self.heap.peek().copied() {
+            if fire_at > now {
+                break;
+            }
+            self.heap.pop();
+            if let Some(queued) = self.queued.get_mut(&task_id) {
+                if queued.fire_at == Some(fire_at) {
+                    queued.fire_at = None;
+                    due.push((task_id, queued.timezone));
+                }
+            }
+        }
+        due
+    }
+
+    /// Has a task queued again on the next sync, e.g. when its reminders
+    /// have to wait for the owner's quiet hours to end.
+    pub fn retry(&mut self, task_id: u32) {
+        self.queued.remove(&task_id);
+    }
+}
+
+/// When the first reminder of `task` left to fire is due, with cron
+/// schedules read in `timezone`.
+fn next_fire(task: &Task, timezone: FixedOffset) -> Option<DateTime<Utc>> {
+    if task.completed {
+        return None;
+    }
+    let relative = task.due_date.into_iter().flat_map(|due| {
+        task.reminders
+            .iter()
+            .filter(|reminder| !reminder.notified)
+            .map(move |reminder| reminder.fire_at(due))
+    });
+    let repeating = task.cron_reminders.iter().filter_map(|reminder| {
+        let next = reminder.next_fire(timezone);
+        if next.is_none() {
+            log::warn(&format!(
+                "Task #{}: reminder schedule {:?} is invalid or never fires",
+                task.id, reminder.schedule
+            ));
+        }
+        next
+    });
+    relative.chain(repeating).min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminders::Reminder;
+    use crate::testing;
+    use chrono::{Duration, TimeZone};
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, hour, 0, 0).unwrap()
+    }
+
+    /// A task due at `due` with one reminder an hour before.
+    fn task(id: u32, due: DateTime<Utc>) -> Task {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "title": "Call the bank",
+            "description": "",
+            "completed": false,
+            "created_at": 0,
+            "user_id": "alice",
+            "due_date": due.timestamp(),
+            "reminders": [{ "offset_secs": 3600 }],
+            "version": 1,
+        }))
+        .unwrap()
+    }
+
+    fn app(tasks: Vec<Task>) -> TodoApp {
+        let mut app = testing::app();
+        for task in tasks {
+            app.insert_task(task);
+        }
+        app
+    }
+
+    fn due_ids(queue: &mut ReminderQueue, now: DateTime<Utc>) -> Vec<u32> {
+        queue.take_due(now).into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn takes_tasks_once_their_reminder_is_due() {
+        let app = app(vec![task(1, at(12)), task(2, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        assert!(due_ids(&mut queue, at(8)).is_empty());
+        assert_eq!(due_ids(&mut queue, at(9)), vec![2]);
+        assert_eq!(due_ids(&mut queue, at(12)), vec![1]);
+        assert!(due_ids(&mut queue, at(13)).is_empty());
+    }
+
+    #[test]
+    fn version_bump_requeues_at_the_new_time() {
+        let mut app = app(vec![task(1, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+
+        let task = app.tasks.get_mut(&1).unwrap();
+        task.due_date = Some(at(15));
+        task.version += 1;
+        queue.sync(&app, at(1));
+        // The entry for the old time is stale and skipped.
+        assert!(due_ids(&mut queue, at(9)).is_empty());
+        assert_eq!(due_ids(&mut queue, at(14)), vec![1]);
+    }
+
+    #[test]
+    fn unchanged_tasks_are_not_requeued() {
+        let app = app(vec![task(1, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        assert_eq!(due_ids(&mut queue, at(9)), vec![1]);
+        queue.sync(&app, at(9));
+        assert!(due_ids(&mut queue, at(10)).is_empty());
+    }
+
+    #[test]
+    fn stale_entries_for_the_same_time_fire_once() {
+        let mut app = app(vec![task(1, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        // A change that keeps the reminder time leaves a second entry.
+        app.tasks.get_mut(&1).unwrap().version += 1;
+        queue.sync(&app, at(1));
+        assert_eq!(queue.heap.len(), 2);
+        assert_eq!(due_ids(&mut queue, at(9)), vec![1]);
+        assert!(queue.heap.is_empty());
+    }
+
+    #[test]
+    fn fired_reminders_move_on_to_the_next_one() {
+        let mut app = app(vec![task(1, at(10))]);
+        let task = app.tasks.get_mut(&1).unwrap();
+        task.reminders.push(Reminder {
+            offset_secs: 60 * 60 * 3,
+            notified: false,
+        });
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        assert_eq!(due_ids(&mut queue, at(7)), vec![1]);
+
+        // What the daemon does when the first reminder fires.
+        let task = app.tasks.get_mut(&1).unwrap();
+        task.reminders[1].notified = true;
+        task.version += 1;
+        queue.sync(&app, at(7));
+        assert!(due_ids(&mut queue, at(8)).is_empty());
+        assert_eq!(due_ids(&mut queue, at(9)), vec![1]);
+    }
+
+    #[test]
+    fn deleted_and_completed_tasks_are_dropped() {
+        let mut app = app(vec![task(1, at(10)), task(2, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        app.remove_task(1);
+        let task = app.tasks.get_mut(&2).unwrap();
+        task.completed = true;
+        task.version += 1;
+        queue.sync(&app, at(1));
+        assert!(due_ids(&mut queue, at(12)).is_empty());
+        assert!(!queue.queued.contains_key(&1));
+    }
+
+    #[test]
+    fn retry_requeues_a_task_held_back_by_quiet_hours() {
+        let app = app(vec![task(1, at(10))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        assert_eq!(due_ids(&mut queue, at(9)), vec![1]);
+
+        // The owner is in quiet hours, so the daemon hands the task back.
+        queue.retry(1);
+        queue.sync(&app, at(9));
+        assert_eq!(due_ids(&mut queue, at(9) + Duration::minutes(1)), vec![1]);
+        // Once taken without a retry, it stays out of the queue.
+        queue.sync(&app, at(10));
+        assert!(due_ids(&mut queue, at(11)).is_empty());
+    }
+
+    #[test]
+    fn skipped_entries_are_compacted() {
+        let mut app = app(vec![task(1, at(10)), task(2, at(11))]);
+        let mut queue = ReminderQueue::default();
+        queue.sync(&app, at(0));
+        for minute in 0..200 {
+            let task = app.tasks.get_mut(&1).unwrap();
+            task.due_date = Some(at(10) + Duration::minutes(minute));
+            task.version += 1;
+            queue.sync(&app, at(0));
+            assert!(queue.heap.len() <= 2 * queue.queued.len() + 64);
+        }
+        assert_eq!(due_ids(&mut queue, at(13)), vec![2, 1]);
+        assert!(queue.heap.is_empty());
+    }
+}
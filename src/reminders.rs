@@ -0,0 +1,243 @@
+//! Reminders relative to a task's due date or repeating on a cron schedule
+//! (see `cron`), and the daemon that fires them and emails scheduled
+//! digests.
+
+use crate::cron::Cron;
+use crate::i18n::{self, trf};
+use crate::reminderqueue::ReminderQueue;
+use crate::schedule::{self, format_duration};
+use crate::{Task, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Duration, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::thread;
+
+/// How often the daemon checks for due reminders.
+const DAEMON_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A notification fired some time before a task is due.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    /// How long before the due date the reminder fires, in seconds.
+    pub offset_secs: i64,
+    /// Set once the reminder has fired, so it isn't repeated.
+    #[serde(default)]
+    pub notified: bool,
+}
+
+impl Reminder {
+    pub fn offset(&self) -> Duration {
+        Duration::seconds(self.offset_secs)
+    }
+
+    /// When the reminder fires for a task due at `due`.
+    pub fn fire_at(&self, due: DateTime<Utc>) -> DateTime<Utc> {
+        due - self.offset()
+    }
+}
+
+/// A reminder repeating on a cron schedule, in the owner's timezone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CronReminder {
+    /// A cron expression such as `0 9 * * MON-FRI`.
+    pub schedule: String,
+    /// When the reminder was added or last fired; it fires next at the
+    /// first matching minute after that.
+    #[serde(with = "ts_seconds")]
+    pub since: DateTime<Utc>,
+}
+
+impl CronReminder {
+    /// When the reminder fires next, with the schedule read in `timezone`.
+    /// `None` if the schedule is invalid or never matches.
+    pub fn next_fire(&self, timezone: FixedOffset) -> Option<DateTime<Utc>> {
+        let cron = Cron::parse(&self.schedule).ok()?;
+        let since = self.since.with_timezone(&timezone).naive_local();
+        let next = cron.next_after(since)?;
+        next.and_local_timezone(timezone)
+            .single()
+            .map(|next| next.with_timezone(&Utc))
+    }
+}
+
+impl TodoApp {
+    /// Adds a reminder repeating on a cron schedule, checking the schedule
+    /// first.
+    pub fn add_cron_reminder(&mut self, task_id: u32, schedule: &str) -> Result<(), String> {
+        let schedule = Cron::parse(schedule)?.to_string();
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task".to_string());
+        }
+        if task.cron_reminders.iter().any(|r| r.schedule == schedule) {
+            return Err("Task already has this reminder".to_string());
+        }
+
+        task.cron_reminders.push(CronReminder {
+            schedule,
+            since: Utc::now(),
+        });
+        task.touch();
+        self.save_tasks(&format!("Add cron reminder to task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Adds a reminder firing `offset` before the task's due date.
+    pub fn add_reminder(&mut self, task_id: u32, offset: Duration) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if task.due_date.is_none() {
+            return Err("Task has no due date");
+        }
+        if task.reminders.iter().any(|r| r.offset() == offset) {
+            return Err("Task already has this reminder");
+        }
+
+        task.reminders.push(Reminder {
+            offset_secs: offset.num_seconds(),
+            notified: false,
+        });
+        task.reminders.sort_by_key(|r| -r.offset_secs);
+        task.touch();
+        self.save_tasks(&format!("Add reminder to task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Removes all reminders from a task.
+    pub fn clear_reminders(&mut self, task_id: u32) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.reminders.clear();
+        task.cron_reminders.clear();
+        task.touch();
+        self.save_tasks(&format!("Clear reminders of task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// Lists the current user's reminders that haven't fired yet, soonest
+    /// first. Cron reminders are listed once, at their next time.
+    pub fn upcoming_reminders(&self) -> Result<Vec<(DateTime<Utc>, &Task)>, &'static str> {
+        let timezone = schedule::timezone();
+        let mut upcoming: Vec<(DateTime<Utc>, &Task)> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| !task.completed)
+            .flat_map(|task| {
+                let due = task.due_date;
+                let relative = task
+                    .reminders
+                    .iter()
+                    .filter(|r| !r.notified)
+                    .filter_map(move |r| due.map(|due| (r.fire_at(due), task)));
+                let repeating = task
+                    .cron_reminders
+                    .iter()
+                    .filter_map(move |r| Some((r.next_fire(timezone)?, task)));
+                relative.chain(repeating)
+            })
+            .collect();
+        upcoming.sort_by_key(|(fire_at, task)| (*fire_at, task.id));
+        Ok(upcoming)
+    }
+
+    /// Marks every reminder of any user that is due at `now` as notified and
+    /// returns the affected tasks, taking them from `queue`. Used by the
+    /// daemon, so no login is needed. Reminders of users in their quiet
+    /// hours wait until those end. A cron reminder whose times were missed,
+    /// e.g. while the daemon was stopped, fires once for all of them.
+    fn take_due_reminders(&mut self, queue: &mut ReminderQueue, now: DateTime<Utc>) -> Vec<u32> {
+        let quiet = self.quiet_users(now);
+        let mut fired = Vec::new();
+        for (task_id, timezone) in queue.take_due(now) {
+            let Some(task) = self.tasks.get_mut(&task_id) else {
+                continue;
+            };
+            if quiet.contains(&task.user_id) {
+                queue.retry(task_id);
+                continue;
+            }
+            let mut task_fired = false;
+            if let Some(due) = task.due_date {
+                for reminder in &mut task.reminders {
+                    if !reminder.notified && reminder.fire_at(due) <= now {
+                        reminder.notified = true;
+                        task_fired = true;
+                    }
+                }
+            }
+            for reminder in &mut task.cron_reminders {
+                if reminder.next_fire(timezone).is_some_and(|next| next <= now) {
+                    reminder.since = now;
+                    task_fired = true;
+                }
+            }
+            if task_fired {
+                task.version += 1;
+                fired.push(task.id);
+            }
+        }
+        fired.sort_unstable();
+        fired
+    }
+}
+
+/// Describes when a reminder fires relative to the due date.
+pub fn describe(reminder: &Reminder) -> String {
+    trf("{} before due", &[&format_duration(reminder.offset())])
+}
+
+/// Checks for due reminders and digests every minute and sends them,
+/// forever.
+pub fn run_daemon(app: &mut TodoApp) -> io::Result<()> {
+    println!("{}", i18n::tr("Reminder daemon started"));
+    // Digests due before the daemon started count as sent, so restarting it
+    // doesn't send them twice.
+    let mut digests_sent: HashMap<String, _> = app
+        .digests_due(Utc::now(), &HashMap::new())
+        .into_iter()
+        .collect();
+    let mut queue = ReminderQueue::default();
+    loop {
+        // Pick up changes made by interactive sessions since the last check.
+        app.load_tasks()?;
+        app.load_users()?;
+        app.load_settings()?;
+        let now = Utc::now();
+        if let Err(e) = app.cancel_expired(now) {
+            eprintln!("{}", i18n::tr(e));
+        }
+        app.load_trash()?;
+        app.load_snapshots()?;
+        if let Err(e) = app.compact(now) {
+            eprintln!("{}", i18n::tr(e));
+        }
+        queue.sync(app, now);
+        let fired = app.take_due_reminders(&mut queue, now);
+        if !fired.is_empty() {
+            for task_id in &fired {
+                app.send_reminder(*task_id);
+            }
+            app.save_tasks("Fire reminders")?;
+        }
+        for (username, today) in app.digests_due(now, &digests_sent) {
+            match app.send_digest_of(&username) {
+                Ok(()) => println!("[{}] {}", username, i18n::tr("Digest sent")),
+                Err(e) => eprintln!("[{}] {}", username, i18n::tr(e)),
+            }
+            digests_sent.insert(username, today);
+        }
+        thread::sleep(DAEMON_INTERVAL);
+    }
+}
@@ -0,0 +1,252 @@
+//! A self-contained HTML report of a task list (`report html`), e.g. for
+//! sharing or archiving a sprint. Tasks are grouped by project and status;
+//! charts are inline SVG, so the page needs no other files.
+
+use crate::i18n::{self, tr, trf};
+use crate::schedule::{local_date, start_of_today};
+use crate::shares::escape_html;
+use crate::Task;
+use chrono::{DateTime, Days, Utc};
+use std::collections::BTreeMap;
+
+/// Days shown in the chart of completed tasks.
+const HISTORY_DAYS: u64 = 14;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222; }
+table.summary td { padding: 0.2em 1em 0.2em 0; }
+.overdue { color: #c0392b; }
+.meta { color: #777; font-size: 0.9em; }
+li.completed { color: #777; }
+svg text { font-size: 12px; font-family: sans-serif; }";
+
+/// The report for `tasks` as an HTML page titled `title`.
+pub fn render(title: &str, tasks: &[&Task], now: DateTime<Utc>) -> String {
+    let title = escape_html(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title
+    );
+    out.push_str(&format!(
+        "<p class=\"meta\">{}</p>\n",
+        escape_html(&trf("Generated {}", &[&i18n::format_datetime(now)]))
+    ));
+    out.push_str(&summary(tasks));
+
+    let projects = by_project(tasks);
+    if !projects.is_empty() {
+        out.push_str(&format!(
+            "<h2>{}</h2>\n",
+            escape_html(tr("Progress by project"))
+        ));
+        out.push_str(&project_chart(&projects));
+    }
+    out.push_str(&format!(
+        "<h2>{}</h2>\n",
+        escape_html(&trf("Completed in the last {} days", &[&HISTORY_DAYS]))
+    ));
+    out.push_str(&history_chart(tasks, now));
+
+    for (project, tasks) in &projects {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(project)));
+        let (completed, pending): (Vec<&Task>, Vec<&Task>) =
+            tasks.iter().partition(|task| task.completed);
+        for (heading, tasks) in [(tr("Pending"), pending), (tr("Completed"), completed)] {
+            if tasks.is_empty() {
+                continue;
+            }
+            out.push_str(&format!(
+                "<h3>{} ({})</h3>\n<ul>\n",
+                escape_html(heading),
+                tasks.len()
+            ));
+            for task in tasks {
+                out.push_str(&task_item(task));
+            }
+            out.push_str("</ul>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Tasks by project name, with tasks outside any project last.
+fn by_project<'a>(tasks: &[&'a Task]) -> Vec<(String, Vec<&'a Task>)> {
+    let mut projects: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+    let mut loose = Vec::new();
+    for task in tasks {
+        match &task.project {
+            Some(project) => projects.entry(project).or_default().push(*task),
+            None => loose.push(*task),
+        }
+    }
+    let mut grouped: Vec<(String, Vec<&Task>)> = projects
+        .into_iter()
+        .map(|(project, tasks)| (project.to_string(), tasks))
+        .collect();
+    if !loose.is_empty() {
+        grouped.push((tr("No project").to_string(), loose));
+    }
+    grouped
+}
+
+fn summary(tasks: &[&Task]) -> String {
+    let completed = tasks.iter().filter(|task| task.completed).count();
+    let today = start_of_today();
+    let overdue = tasks
+        .iter()
+        .filter(|task| !task.completed && task.due_date.is_some_and(|due| due < today))
+        .count();
+    let mut out = String::from("<table class=\"summary\">\n");
+    for (label, count) in [
+        (tr("Tasks"), tasks.len()),
+        (tr("Completed"), completed),
+        (tr("Pending"), tasks.len() - completed),
+        (tr("Overdue"), overdue),
+    ] {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td></tr>\n",
+            escape_html(label),
+            count
+        ));
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn task_item(task: &Task) -> String {
+    let mut meta = Vec::new();
+    if let Some(due) = task.due_date {
+        let due_text = escape_html(&trf("due {}", &[&i18n::format_date(due)]));
+        if !task.completed && due < start_of_today() {
+            meta.push(format!("<span class=\"overdue\">{}</span>", due_text));
+        } else {
+            meta.push(due_text);
+        }
+    }
+    if let Some(completed_at) = task.completed_at {
+        meta.push(escape_html(&trf(
+            "completed {}",
+            &[&i18n::format_date(completed_at)],
+        )));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| format!("@{}", tag)).collect();
+        meta.push(escape_html(&tags.join(" ")));
+    }
+    let class = if task.completed {
+        " class=\"completed\""
+    } else {
+        ""
+    };
+    let mut item = format!("<li{}>#{} {}", class, task.id, escape_html(&task.title));
+    if !meta.is_empty() {
+        item.push_str(&format!(" <span class=\"meta\">{}</span>", meta.join(", ")));
+    }
+    item.push_str("</li>\n");
+    item
+}
+
+/// A horizontal bar per project, split into completed and pending tasks.
+fn project_chart(projects: &[(String, Vec<&Task>)]) -> String {
+    const LABEL: usize = 160;
+    const BAR: usize = 360;
+    const ROW: usize = 24;
+    let largest = projects
+        .iter()
+        .map(|(_, tasks)| tasks.len())
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let height = projects.len() * ROW;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        LABEL + BAR + 60,
+        height
+    );
+    for (i, (project, tasks)) in projects.iter().enumerate() {
+        let y = i * ROW;
+        let completed = tasks.iter().filter(|task| task.completed).count();
+        let done_width = completed * BAR / largest;
+        let pending_width = (tasks.len() - completed) * BAR / largest;
+        out.push_str(&format!(
+            "<text x=\"0\" y=\"{}\">{}</text>\n",
+            y + 16,
+            escape_html(project)
+        ));
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"18\" fill=\"#27ae60\"/>\n",
+            LABEL,
+            y + 3,
+            done_width
+        ));
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"18\" fill=\"#bdc3c7\"/>\n",
+            LABEL + done_width,
+            y + 3,
+            pending_width
+        ));
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">{}/{}</text>\n",
+            LABEL + done_width + pending_width + 6,
+            y + 16,
+            completed,
+            tasks.len()
+        ));
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// A column per day with the number of tasks completed that day.
+fn history_chart(tasks: &[&Task], now: DateTime<Utc>) -> String {
+    const COLUMN: usize = 32;
+    const HEIGHT: usize = 120;
+    let today = local_date(now);
+    let days: Vec<_> = (0..HISTORY_DAYS)
+        .rev()
+        .filter_map(|ago| today.checked_sub_days(Days::new(ago)))
+        .collect();
+    let counts: Vec<usize> = days
+        .iter()
+        .map(|day| {
+            tasks
+                .iter()
+                .filter(|task| task.completed_at.is_some_and(|at| local_date(at) == *day))
+                .count()
+        })
+        .collect();
+    let largest = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        days.len() * COLUMN,
+        HEIGHT + 40
+    );
+    for (i, (day, count)) in days.iter().zip(&counts).enumerate() {
+        let x = i * COLUMN;
+        let bar = count * HEIGHT / largest;
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#2980b9\"/>\n",
+            x + 4,
+            20 + HEIGHT - bar,
+            COLUMN - 8,
+            bar
+        ));
+        if *count > 0 {
+            out.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                x + COLUMN / 2,
+                16 + HEIGHT - bar,
+                count
+            ));
+        }
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+            x + COLUMN / 2,
+            HEIGHT + 36,
+            day.format("%d")
+        ));
+    }
+    out.push_str("</svg>\n");
+    out
+}
@@ -0,0 +1,192 @@
+//! How long old data is kept: deleted tasks in the trash, entries of the
+//! audit log, backups of the data files and burndown snapshots. `compact`
+//! enforces the policy on startup and in the reminder daemon, and
+//! `retention status` reports how much is kept.
+
+use crate::activity;
+use crate::formats::DataFormat;
+use crate::fsck::DATA_FILES;
+use crate::log;
+use crate::storage;
+use crate::TodoApp;
+use chrono::{DateTime, Days, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+/// The retention policy, stored in `config.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Retention {
+    /// Days deleted tasks stay in the trash; unset keeps them until restored.
+    pub trash_days: Option<u32>,
+    /// Kilobytes the audit log may grow to before its oldest entries are
+    /// dropped; unset lets it grow.
+    pub audit_log_max_kb: Option<u64>,
+    /// Backups kept of each data file; 0 keeps none.
+    pub backups: u32,
+    /// Days between burndown snapshots. Snapshots closer together than that
+    /// aren't recorded, and older ones are thinned out.
+    pub snapshot_every_days: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            trash_days: Some(30),
+            audit_log_max_kb: Some(1024),
+            backups: 1,
+            snapshot_every_days: 1,
+        }
+    }
+}
+
+impl Retention {
+    /// `snapshot_every_days`, treating 0 as every day.
+    pub fn snapshot_interval(&self) -> Days {
+        Days::new(u64::from(self.snapshot_every_days.max(1)))
+    }
+}
+
+/// What one run of `compact` removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Compaction {
+    pub purged_tasks: usize,
+    pub audit_entries: usize,
+    pub backups: usize,
+    pub snapshots: usize,
+}
+
+impl Compaction {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How much old data is kept, next to the policy.
+pub struct Status {
+    pub policy: Retention,
+    pub trashed: usize,
+    pub oldest_trashed: Option<DateTime<Utc>>,
+    pub audit_log_bytes: u64,
+    pub backups: usize,
+    pub backup_bytes: u64,
+    pub snapshots: usize,
+}
+
+impl TodoApp {
+    /// Removes whatever the retention policy no longer keeps. In dry-run
+    /// mode nothing is removed.
+    pub fn compact(&mut self, now: DateTime<Utc>) -> Result<Compaction, &'static str> {
+        let mut compaction = Compaction::default();
+        if self.dry_run {
+            return Ok(compaction);
+        }
+        let policy = self.retention;
+
+        if let Some(days) = policy.trash_days {
+            let cutoff = now - Duration::days(i64::from(days));
+            let before = self.trash.len();
+            self.trash.retain(|_, trashed| trashed.deleted_at > cutoff);
+            compaction.purged_tasks = before - self.trash.len();
+            if compaction.purged_tasks > 0 {
+                self.save_trash(&format!(
+                    "Purge {} tasks from the trash",
+                    compaction.purged_tasks
+                ))
+                .map_err(|_| "Failed to save the trash")?;
+            }
+        }
+
+        let interval = policy.snapshot_interval();
+        for history in self.snapshots.values_mut() {
+            let mut last_kept = None;
+            history.retain(|day, _| {
+                let keep = last_kept.is_none_or(|last_kept| last_kept + interval <= *day);
+                if keep {
+                    last_kept = Some(*day);
+                } else {
+                    compaction.snapshots += 1;
+                }
+                keep
+            });
+        }
+        if compaction.snapshots > 0 {
+            self.save_snapshots(&format!("Thin out {} snapshots", compaction.snapshots))
+                .map_err(|_| "Failed to save snapshots")?;
+        }
+
+        if let Some(max_kb) = policy.audit_log_max_kb {
+            compaction.audit_entries =
+                activity::trim(max_kb * 1024).map_err(|_| "Failed to trim the activity log")?;
+        }
+
+        for (index, path) in backups() {
+            if index >= policy.backups {
+                fs::remove_file(&path).map_err(|_| "Failed to remove an old backup")?;
+                compaction.backups += 1;
+            }
+        }
+
+        if !compaction.is_empty() {
+            log::info(&format!(
+                "Compacted data: {} tasks purged from the trash, {} audit entries, {} backups and {} snapshots removed",
+                compaction.purged_tasks,
+                compaction.audit_entries,
+                compaction.backups,
+                compaction.snapshots
+            ));
+        }
+        Ok(compaction)
+    }
+
+    /// How much old data is kept.
+    pub fn retention_status(&self) -> io::Result<Status> {
+        let mut backup_count = 0;
+        let mut backup_bytes = 0;
+        for (_, path) in backups() {
+            backup_count += 1;
+            backup_bytes += fs::metadata(&path)?.len();
+        }
+        Ok(Status {
+            policy: self.retention,
+            trashed: self.trash.len(),
+            oldest_trashed: self.trash.values().map(|trashed| trashed.deleted_at).min(),
+            audit_log_bytes: activity::size()?,
+            backups: backup_count,
+            backup_bytes,
+            snapshots: self.snapshots.values().map(|history| history.len()).sum(),
+        })
+    }
+}
+
+/// The backups of the data files there are, with their index: 0 for the
+/// newest.
+fn backups() -> Vec<(u32, String)> {
+    let Ok(entries) = fs::read_dir(".") else {
+        return Vec::new();
+    };
+    let paths: Vec<String> = DATA_FILES
+        .iter()
+        .flat_map(|name| DataFormat::ALL.iter().map(|format| format.path(name)))
+        .collect();
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let index = paths
+                .iter()
+                .find_map(|path| backup_index(path, &file_name))?;
+            Some((index, file_name))
+        })
+        .collect()
+}
+
+/// The index of `file_name` as a backup of `path`, if it is one.
+fn backup_index(path: &str, file_name: &str) -> Option<u32> {
+    let suffix = file_name.strip_prefix(&storage::numbered_backup_name(path, 0))?;
+    if suffix.is_empty() {
+        return Some(0);
+    }
+    suffix.strip_prefix('.')?.parse().ok()
+}
@@ -0,0 +1,208 @@
+//! Automation rules from the `rules` section of the config, e.g. "when a
+//! task tagged `bug` is created, make it high priority and put it in the
+//! `Work` project":
+//!
+//! ```json
+//! {
+//!   "rules": [{
+//!     "name": "bugs",
+//!     "on": ["created"],
+//!     "when": { "tag": "bug" },
+//!     "then": { "priority": "high", "project": "Work" }
+//!   }]
+//! }
+//! ```
+//!
+//! Rules run in order when a task is created or edited; each sees the
+//! changes made by the rules before it. A rule doesn't trigger others.
+
+use crate::contexts::parse_context;
+use crate::i18n::{tr, trf};
+use crate::log;
+use crate::priority::Priority;
+use crate::{Task, TodoApp};
+use serde::{Deserialize, Serialize};
+
+/// When a rule runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    Created,
+    Updated,
+}
+
+/// Which tasks a rule applies to. Every condition given must hold; a rule
+/// without any applies to all tasks.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Condition {
+    pub tag: Option<String>,
+    pub project: Option<String>,
+    /// Text the title contains, ignoring case.
+    pub title_contains: Option<String>,
+    pub priority: Option<Priority>,
+}
+
+impl Condition {
+    fn matches(&self, task: &Task) -> bool {
+        self.tag
+            .as_ref()
+            .is_none_or(|tag| task.tags.contains(&tag.to_lowercase()))
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|project| task.project.as_ref() == Some(project))
+            && self
+                .title_contains
+                .as_ref()
+                .is_none_or(|text| task.title.to_lowercase().contains(&text.to_lowercase()))
+            && self
+                .priority
+                .is_none_or(|priority| task.priority == priority)
+    }
+}
+
+/// What a rule changes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Actions {
+    pub priority: Option<Priority>,
+    pub project: Option<String>,
+    pub context: Option<String>,
+    pub add_tags: Vec<String>,
+}
+
+impl Actions {
+    /// Changes `task`, returning a description of each change made.
+    fn apply(&self, task: &mut Task) -> Vec<String> {
+        let mut changes = Vec::new();
+        if let Some(priority) = self.priority.filter(|p| *p != task.priority) {
+            task.priority = priority;
+            changes.push(trf("priority {}", &[&tr(priority.name())]));
+        }
+        if let Some(project) = self
+            .project
+            .as_ref()
+            .filter(|project| task.project.as_ref() != Some(*project))
+        {
+            task.project = Some(project.clone());
+            changes.push(trf("project {}", &[project]));
+        }
+        if let Some(context) = self.context.as_deref().and_then(|c| parse_context(c).ok()) {
+            if task.context.as_ref() != Some(&context) {
+                changes.push(trf("context @{}", &[&context]));
+                task.context = Some(context);
+            }
+        }
+        for tag in &self.add_tags {
+            if task.tags.insert(tag.to_lowercase()) {
+                changes.push(trf("tag @{}", &[&tag.to_lowercase()]));
+            }
+        }
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.priority.is_none()
+            && self.project.is_none()
+            && self.context.is_none()
+            && self.add_tags.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// Events the rule runs on; both if empty.
+    pub on: Vec<Trigger>,
+    pub when: Condition,
+    pub then: Actions,
+}
+
+impl Rule {
+    fn runs_on(&self, trigger: Trigger) -> bool {
+        self.on.is_empty() || self.on.contains(&trigger)
+    }
+}
+
+/// Checks what the JSON format can't: that each rule is named, changes
+/// something and has a valid context and tags.
+pub fn validate(rules: &[Rule]) -> Result<(), String> {
+    for (index, rule) in rules.iter().enumerate() {
+        if rule.name.is_empty() {
+            return Err(format!("rules[{}]: every rule needs a name", index));
+        }
+        let name = &rule.name;
+        if rule.then.is_empty() {
+            return Err(format!("rules.{}: the rule changes nothing", name));
+        }
+        if let Some(context) = &rule.then.context {
+            parse_context(context).map_err(|e| format!("rules.{}: {}", name, e))?;
+        }
+        let tags = rule.then.add_tags.iter().chain(&rule.when.tag);
+        if tags
+            .into_iter()
+            .any(|tag| tag.is_empty() || tag.contains(char::is_whitespace))
+        {
+            return Err(format!(
+                "rules.{}: tags can't be empty or contain spaces",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A rule that changed a task, with the changes it made.
+pub struct Applied<'a> {
+    pub rule: &'a str,
+    pub changes: Vec<String>,
+}
+
+/// Runs the `rules` for `trigger` on `task`, in order. Returns the rules
+/// that changed it.
+fn run<'a>(rules: &'a [Rule], task: &mut Task, trigger: Trigger) -> Vec<Applied<'a>> {
+    let mut applied = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.runs_on(trigger)) {
+        if !rule.when.matches(task) {
+            continue;
+        }
+        let changes = rule.then.apply(task);
+        if !changes.is_empty() {
+            applied.push(Applied {
+                rule: &rule.name,
+                changes,
+            });
+        }
+    }
+    applied
+}
+
+/// `run`, logging the rules that changed the task. Takes the rules rather
+/// than the app so a task can be changed in place.
+pub fn apply(rules: &[Rule], task: &mut Task, trigger: Trigger) {
+    for applied in run(rules, task, trigger) {
+        log::info(&format!(
+            "Rule {} applied to task #{}: {}",
+            applied.rule,
+            task.id,
+            applied.changes.join(", ")
+        ));
+    }
+}
+
+impl TodoApp {
+    /// What the rules would change in the tasks of the current list if
+    /// they were edited now, without changing anything.
+    pub fn test_rules(&self) -> Result<Vec<(&Task, Vec<Applied<'_>>)>, &'static str> {
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter_map(|task| {
+                let applied = run(&self.rules, &mut task.clone(), Trigger::Updated);
+                (!applied.is_empty()).then_some((task, applied))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,257 @@
+//! Moving due dates around, the user's timezone, and parsing of durations
+//! such as `2h` or `3 days`.
+
+use crate::{Task, TodoApp};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, Offset, Utc};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Marker stored in `TIMEZONE` when the system timezone should be used.
+const SYSTEM_TIMEZONE: i32 = i32::MIN;
+
+/// Offset of the timezone in use in seconds east of UTC.
+static TIMEZONE: AtomicI32 = AtomicI32::new(SYSTEM_TIMEZONE);
+
+/// Longest duration `parse_duration` accepts, about 100 years.
+const MAX_DURATION_DAYS: i64 = 36_525;
+
+/// Offset of the timezone from the config file, used by users without their own.
+static DEFAULT_TIMEZONE: AtomicI32 = AtomicI32::new(SYSTEM_TIMEZONE);
+
+impl TodoApp {
+    /// Pushes a task's due date back by `duration`. A task without a due
+    /// date becomes due `duration` from now. The task is also set aside for
+    /// `duration`, so it isn't suggested as a next action in the meantime.
+    pub fn snooze_task(&mut self, task_id: u32, duration: Duration) -> Result<(), &'static str> {
+        const TOO_FAR: &str = "Snoozing that long would go past the latest supported date";
+        let current = self.tasks.get(&task_id).ok_or("Task not found")?.due_date;
+        let now = Utc::now();
+        let due = current
+            .unwrap_or(now)
+            .checked_add_signed(duration)
+            .ok_or(TOO_FAR)?;
+        let until = now.checked_add_signed(duration).ok_or(TOO_FAR)?;
+        let message = format!("Snooze task #{}", task_id);
+        self.set_due_date(task_id, due, Some(until), &message)
+    }
+
+    /// Moves a task to a new due date.
+    pub fn reschedule_task(
+        &mut self,
+        task_id: u32,
+        due: DateTime<Utc>,
+    ) -> Result<(), &'static str> {
+        self.set_due_date(task_id, due, None, &format!("Reschedule task #{}", task_id))
+    }
+
+    /// Lists the current user's pending tasks that were due before today.
+    pub fn overdue_tasks(&self) -> Result<Vec<&Task>, &'static str> {
+        let today = start_of_today();
+        let mut overdue: Vec<&Task> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| !task.completed && task.due_date.is_some_and(|due| due < today))
+            .collect();
+        overdue.sort_by_key(|task| (task.due_date, task.id));
+        Ok(overdue)
+    }
+
+    /// Moves every overdue pending task the current user may edit to
+    /// `date`, returning the moved task IDs.
+    pub fn roll_over(&mut self, date: DateTime<Utc>) -> Result<Vec<u32>, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task_ids: Vec<u32> = self
+            .overdue_tasks()?
+            .iter()
+            .filter(|task| self.workspaces.can_edit(task, &user_id))
+            .map(|task| task.id)
+            .collect();
+        if task_ids.is_empty() {
+            return Ok(task_ids);
+        }
+
+        let now = Utc::now();
+        for task_id in &task_ids {
+            let task = self.tasks.get_mut(task_id).ok_or("Task not found")?;
+            task.due_date = Some(date);
+            task.touch();
+            for reminder in &mut task.reminders {
+                reminder.notified = reminder.fire_at(date) <= now;
+            }
+        }
+        self.save_tasks(&format!("Roll over {} overdue task(s)", task_ids.len()))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(task_ids)
+    }
+
+    /// Sets the due date and re-arms reminders that now lie in the future.
+    /// `snoozed_until`, if given, replaces the time the task is snoozed until.
+    fn set_due_date(
+        &mut self,
+        task_id: u32,
+        due: DateTime<Utc>,
+        snoozed_until: Option<DateTime<Utc>>,
+        message: &str,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+
+        task.due_date = Some(due);
+        if snoozed_until.is_some() {
+            task.snoozed_until = snoozed_until;
+        }
+        task.touch();
+        let now = Utc::now();
+        for reminder in &mut task.reminders {
+            reminder.notified = reminder.fire_at(due) <= now;
+        }
+        self.save_tasks(message)
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+}
+
+/// Sets the timezone used for dates; `None` means the system timezone.
+pub fn set_timezone(offset: Option<FixedOffset>) {
+    let seconds = offset.map_or(SYSTEM_TIMEZONE, |offset| offset.local_minus_utc());
+    TIMEZONE.store(seconds, Ordering::Relaxed);
+}
+
+/// Sets the timezone from the config file and switches to it.
+pub fn set_default_timezone(offset: Option<FixedOffset>) {
+    set_timezone(offset);
+    DEFAULT_TIMEZONE.store(TIMEZONE.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Switches back to the timezone from the config file.
+pub fn reset_timezone() {
+    TIMEZONE.store(DEFAULT_TIMEZONE.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// The timezone dates are shown and entered in.
+pub fn timezone() -> FixedOffset {
+    offset(TIMEZONE.load(Ordering::Relaxed))
+}
+
+/// The timezone of a user whose `timezone` setting is `setting`, without
+/// switching to it.
+pub fn timezone_of(setting: Option<&str>) -> FixedOffset {
+    match setting.map(parse_timezone) {
+        Some(Ok(Some(offset))) => offset,
+        Some(Ok(None)) => Local::now().offset().fix(),
+        _ => offset(DEFAULT_TIMEZONE.load(Ordering::Relaxed)),
+    }
+}
+
+/// The timezone stored as `seconds` in `TIMEZONE` or `DEFAULT_TIMEZONE`.
+fn offset(seconds: i32) -> FixedOffset {
+    match seconds {
+        SYSTEM_TIMEZONE => Local::now().offset().fix(),
+        seconds => FixedOffset::east_opt(seconds).unwrap_or_else(|| Utc.fix()),
+    }
+}
+
+/// Parses `local`, `UTC` or an offset like `+02:00` / `-0530`.
+pub fn parse_timezone(input: &str) -> Result<Option<FixedOffset>, &'static str> {
+    const INVALID: &str = "Invalid timezone, expected local, UTC or an offset like +02:00";
+
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("local") {
+        return Ok(None);
+    }
+    if input.eq_ignore_ascii_case("utc") {
+        return Ok(Some(Utc.fix()));
+    }
+    let (sign, rest) = match input.split_at_checked(1) {
+        Some(("+", rest)) => (1, rest),
+        Some(("-", rest)) => (-1, rest),
+        _ => return Err(INVALID),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(INVALID);
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| INVALID)?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| INVALID)?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+        .map(Some)
+        .ok_or(INVALID)
+}
+
+/// The calendar date of `time` in the user's timezone.
+pub fn local_date(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&timezone()).date_naive()
+}
+
+/// Midnight at the start of `date` in the user's timezone.
+pub fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    (midnight - Duration::seconds(i64::from(timezone().local_minus_utc()))).and_utc()
+}
+
+/// Midnight at the start of the current day in the user's timezone.
+pub fn start_of_today() -> DateTime<Utc> {
+    start_of_day(local_date(Utc::now()))
+}
+
+/// Parses a duration like `30m`, `2h`, `1d 12h`, `3 days` or `1 week`, of at
+/// most `MAX_DURATION_DAYS`.
+pub fn parse_duration(input: &str) -> Result<Duration, &'static str> {
+    const INVALID: &str = "Invalid duration, expected e.g. 30m, 2h or 3 days";
+    const TOO_LONG: &str = "Duration too long, at most 100 years";
+    let max = Duration::days(MAX_DURATION_DAYS);
+
+    let input = input.trim().to_lowercase();
+    let mut total = Duration::zero();
+    let mut rest = input.as_str();
+    let mut parsed_any = false;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount: i64 = rest[..digits].parse().map_err(|_| INVALID)?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::minutes(1),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::hours(1),
+            "d" | "day" | "days" => Duration::days(1),
+            "w" | "week" | "weeks" => Duration::weeks(1),
+            _ => return Err(INVALID),
+        };
+        total = i32::try_from(amount)
+            .ok()
+            .and_then(|amount| unit.checked_mul(amount))
+            .and_then(|amount| total.checked_add(&amount))
+            .filter(|total| *total <= max)
+            .ok_or(TOO_LONG)?;
+        rest = rest[unit_len..].trim_start_matches([' ', ',']);
+        parsed_any = true;
+    }
+
+    if parsed_any {
+        Ok(total)
+    } else {
+        Err(INVALID)
+    }
+}
+
+/// Formats a duration compactly, e.g. `1d 2h` or `30m`.
+pub fn format_duration(duration: Duration) -> String {
+    let mut minutes = duration.num_minutes();
+    if minutes == 0 {
+        return "0m".to_string();
+    }
+    let mut parts = Vec::new();
+    for (unit, size) in [("w", 7 * 24 * 60), ("d", 24 * 60), ("h", 60), ("m", 1)] {
+        if minutes >= size {
+            parts.push(format!("{}{}", minutes / size, unit));
+            minutes %= size;
+        }
+    }
+    parts.join(" ")
+}
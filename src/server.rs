@@ -0,0 +1,1058 @@
+//! Server mode (`--serve`): a small HTTP server for frontends.
+//!
+//! Clients get a bearer token from `POST /login` and send it in an
+//! `Authorization: Bearer <token>` header. Browser WebSockets can't set
+//! headers, so the `/events` upgrade alone also takes it as `?token=`.
+//! `GET /events` upgrades to a
+//! WebSocket that streams `created`, `updated` and `deleted` events for the
+//! user's tasks. Changes are picked up from storage, so edits made by any
+//! frontend are reported. Clients say which task they're editing over the
+//! same socket, and `editing` and `stopped_editing` events tell the other
+//! users who can see it (see `presence`). `GET /share/<token>` shows a shared project
+//! without logging in, as HTML or, with `?format=json`, as JSON. `GET
+//! /tasks` lists the tasks the user can see as JSON, only those matching
+//! `?query=` if given (see `query`). `GET /metrics` reports request, task
+//! and save counters for Prometheus, to admins only unless `public_metrics`
+//! is set in the server settings.
+//! Requests are rate limited per IP and per user.
+//!
+//! Requests are read on their own threads, each given `REQUEST_TIMEOUT` in
+//! total, so a client sending slowly can't hold up the others; they're then
+//! answered one at a time.
+//!
+//! The routes are a `Router`, so they can be mounted under a path prefix
+//! (`base_path` in the server settings, for a reverse proxy) and given
+//! middleware that runs before them. Another Rust service can mount them
+//! with `Api`, passing it requests it has read itself; WebSocket upgrades
+//! of `/events` need the connection, so only this server makes them.
+
+use crate::config::Config;
+use crate::i18n::trf;
+use crate::log;
+use crate::metrics;
+use crate::presence::{self, Presence};
+use crate::query::Query;
+use crate::shares;
+use crate::websocket::WebSocket;
+use crate::workspaces::Workspaces;
+use crate::{Task, TodoApp};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often storage is checked for changed tasks.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pause between checks for new connections.
+const TICK: Duration = Duration::from_millis(50);
+
+/// How long a client may take to send its request, and to take a response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests being read at once. Connections beyond that are closed.
+const MAX_READING: usize = 64;
+
+/// Largest request head accepted, in bytes.
+const MAX_HEAD: usize = 16 * 1024;
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Length of a rate limiting window.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Settings for server mode, stored in `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address and port to listen on.
+    pub address: String,
+    /// Requests allowed per minute from one IP address, and for one user.
+    pub requests_per_minute: u32,
+    /// Address the server is reached at, used in share links. Defaults to
+    /// `http://` and the listening address.
+    pub public_url: Option<String>,
+    /// Path prefix the routes are served under, e.g. `/todo`.
+    pub base_path: Option<String>,
+    /// Whether `/metrics` is served without authentication, e.g. to a
+    /// Prometheus on the same network. Otherwise it takes an admin's token.
+    pub public_metrics: bool,
+}
+
+impl ServerConfig {
+    /// The URL share links start with, without a trailing slash.
+    pub fn base_url(&self) -> String {
+        match &self.public_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!(
+                "http://{}{}",
+                self.address,
+                self.base_path
+                    .as_deref()
+                    .unwrap_or_default()
+                    .trim_end_matches('/')
+            ),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8080".to_string(),
+            requests_per_minute: 120,
+            public_url: None,
+            base_path: None,
+            public_metrics: false,
+        }
+    }
+}
+
+/// Kinds of changes streamed to event subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Change {
+    Created,
+    Updated,
+    Deleted,
+    Editing,
+    StoppedEditing,
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    event: Change,
+    task: &'a Task,
+}
+
+/// Another user starting or stopping to edit a task.
+#[derive(Serialize)]
+struct PresenceEvent<'a> {
+    event: Change,
+    user: &'a str,
+    task_id: u32,
+}
+
+/// A message from a client: the task it's editing, or `null` for none.
+#[derive(Deserialize)]
+struct ClientMessage {
+    editing: Option<u32>,
+}
+
+/// A client listening for a user's task events.
+struct Subscriber {
+    username: String,
+    socket: WebSocket,
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// An HTTP request.
+pub struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// A request for `target`, a path with an optional query string.
+    /// Header names are case-insensitive.
+    pub fn new(method: &str, target: &str, headers: Vec<(String, String)>, body: Vec<u8>) -> Self {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers,
+            body,
+        }
+    }
+
+    /// Reads a request from `stream`, failing if it isn't all there within
+    /// `REQUEST_TIMEOUT`.
+    fn read(stream: &TcpStream) -> io::Result<Self> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "Malformed HTTP request");
+        let timed = Timed {
+            stream,
+            deadline: Instant::now() + REQUEST_TIMEOUT,
+        };
+        let mut reader = BufReader::new(timed).take((MAX_HEAD + MAX_BODY) as u64);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next().ok_or_else(invalid)?.to_string();
+        let target = parts.next().ok_or_else(invalid)?.to_string();
+
+        let mut headers = Vec::new();
+        let mut head_len = line.len();
+        loop {
+            line.clear();
+            let len = reader.read_line(&mut line)?;
+            head_len += len;
+            if len == 0 || head_len > MAX_HEAD {
+                return Err(invalid());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        let mut request = Self::new(&method, &target, headers, Vec::new());
+        let length: usize = match request.header("content-length") {
+            Some(length) => length.parse().map_err(|_| invalid())?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid());
+        }
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body)?;
+        Ok(request)
+    }
+
+    /// The value of a header; names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of a query string parameter.
+    fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The bearer token from the `Authorization` header, or from
+    /// `?token=` for a request to `/events`, `path` being the route under
+    /// the prefix. URLs end up in logs and browser history, so other routes
+    /// don't take tokens in them.
+    fn token(&self, path: &str) -> Option<&str> {
+        let in_url = || {
+            (self.method == "GET" && path == "/events")
+                .then(|| self.query_param("token"))
+                .flatten()
+        };
+        self.header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(in_url)
+            .map(str::trim)
+    }
+}
+
+/// A stream whose reads fail once `deadline` has passed, however slowly
+/// the data trickles in.
+struct Timed<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Timed<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Request took too long"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// A response status, content type and body.
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    pub fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", body),
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: String::new(),
+        }
+    }
+
+    fn json(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    fn html(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body,
+        }
+    }
+
+    /// Metrics in the Prometheus text format.
+    fn prometheus(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; version=0.0.4",
+            body,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Headers the status calls for, besides the content type and length.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        match self.status {
+            401 => vec![("WWW-Authenticate", "Bearer".to_string())],
+            429 => vec![("Retry-After", RATE_WINDOW.as_secs().to_string())],
+            _ => Vec::new(),
+        }
+    }
+
+    fn write(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in self.headers() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(self.body.as_bytes())
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Counts requests per key in fixed one-minute windows.
+struct RateLimiter {
+    limit: u32,
+    windows: HashMap<String, (Instant, u32)>,
+}
+
+impl RateLimiter {
+    fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Records a request for `key` and whether it is within the limit.
+    fn allow(&mut self, key: String) -> bool {
+        let now = Instant::now();
+        self.windows
+            .retain(|_, (start, _)| now.duration_since(*start) < RATE_WINDOW);
+        let (_, count) = self.windows.entry(key).or_insert((now, 0));
+        *count += 1;
+        *count <= self.limit
+    }
+}
+
+/// Runs before the routes. Returning a response answers the request
+/// without routing it.
+pub type Middleware = Box<dyn FnMut(&Request, SocketAddr) -> Option<Response>>;
+
+/// The server's routes, under a path prefix, and the middleware run before
+/// them.
+pub struct Router {
+    prefix: String,
+    middleware: Vec<Middleware>,
+    /// Requests per user.
+    limiter: RateLimiter,
+    public_metrics: bool,
+}
+
+/// The routes with the rate limits of `config`, at the root.
+pub fn router(config: &ServerConfig) -> Router {
+    let mut limiter = RateLimiter::new(config.requests_per_minute);
+    Router {
+        prefix: String::new(),
+        middleware: Vec::new(),
+        limiter: RateLimiter::new(config.requests_per_minute),
+        public_metrics: config.public_metrics,
+    }
+    .layer(move |_, peer| {
+        (!limiter.allow(format!("ip:{}", peer.ip())))
+            .then(|| Response::text(429, "Too many requests"))
+    })
+}
+
+/// State kept between requests.
+struct Server {
+    subscribers: Vec<Subscriber>,
+    router: Router,
+    /// Who was editing what when last checked.
+    presence: Presence,
+    /// Users whose clients marked a task as being edited.
+    marked: HashSet<String>,
+    /// Requests read so far, with the connections to answer them on.
+    requests: Receiver<(TcpStream, SocketAddr, Request)>,
+    sender: Sender<(TcpStream, SocketAddr, Request)>,
+    /// Requests still being read.
+    reading: Arc<AtomicUsize>,
+}
+
+/// Serves requests until the process is stopped.
+pub(crate) fn run(app: &mut TodoApp, config: &ServerConfig) -> io::Result<()> {
+    app.load_tokens()?;
+    let listener = TcpListener::bind(&config.address)?;
+    listener.set_nonblocking(true)?;
+    println!("{}", trf("Listening on {}", &[&config.address]));
+    log::info(&format!("Server listening on {}", config.address));
+
+    let router = match &config.base_path {
+        Some(path) => router(config).nest(path),
+        None => router(config),
+    };
+    let (sender, requests) = mpsc::channel();
+    let mut server = Server {
+        subscribers: Vec::new(),
+        router,
+        presence: presence::load().unwrap_or_default(),
+        marked: HashSet::new(),
+        requests,
+        sender,
+        reading: Arc::new(AtomicUsize::new(0)),
+    };
+    let mut previous = app.tasks.clone();
+    let mut last_poll = Instant::now();
+    loop {
+        match listener.accept() {
+            Ok((stream, peer)) => server.start_reading(stream, peer),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+        while let Ok((stream, peer, request)) = server.requests.try_recv() {
+            if let Err(e) = server.handle(app, stream, peer, request) {
+                log::warn(&format!("Request from {} failed: {}", peer, e));
+            }
+        }
+        server.poll_subscribers(app);
+
+        if last_poll.elapsed() >= POLL_INTERVAL {
+            last_poll = Instant::now();
+            reload_shared(app);
+            match app.load_tasks() {
+                Ok(()) => {
+                    for (change, task) in changes(&previous, &app.tasks) {
+                        broadcast(&mut server.subscribers, &app.workspaces, change, task);
+                    }
+                    previous = app.tasks.clone();
+                }
+                Err(e) => log::warn(&format!("Failed to reload tasks: {}", e)),
+            }
+            match presence::load() {
+                Ok(current) => {
+                    for change in presence::changes(&server.presence, &current) {
+                        broadcast_presence(&mut server.subscribers, app, &change);
+                    }
+                    server.presence = current;
+                }
+                Err(e) => log::warn(&format!("Failed to reload presence: {}", e)),
+            }
+        }
+        thread::sleep(TICK);
+    }
+}
+
+/// Rereads the users, workspaces, groups and shares other frontends may
+/// have changed, keeping the last good data if a file is being rewritten.
+fn reload_shared(app: &mut TodoApp) {
+    if let Err(e) = app.load_users() {
+        log::warn(&format!("Failed to reload users: {}", e));
+    }
+    if let Err(e) = app.load_workspaces() {
+        log::warn(&format!("Failed to reload workspaces: {}", e));
+    }
+    if let Err(e) = app.load_groups() {
+        log::warn(&format!("Failed to reload groups: {}", e));
+    }
+    if let Err(e) = app.load_shares() {
+        log::warn(&format!("Failed to reload shares: {}", e));
+    }
+}
+
+/// The routes of a `Router` over a data directory, for a service that
+/// reads requests itself. It changes nothing process-wide, so the
+/// language, timezone and `pretty_json` settings keep their defaults, and
+/// the working directory only holds the log.
+pub struct Api {
+    app: TodoApp,
+    router: Router,
+    last_poll: Instant,
+}
+
+impl Api {
+    /// Loads the config file at `config`, defaults if there is none, and
+    /// the data files in `data_dir`. The `data_dir` of the config file is
+    /// ignored.
+    pub fn open(config: &Path, data_dir: &Path, router: Router) -> io::Result<Self> {
+        let config = Config::load_from(config)?;
+        let mut app = crate::open_app(&config, data_dir)?;
+        crate::load_data(&mut app).map_err(|(_, e)| e)?;
+        app.load_tokens()?;
+        Ok(Self {
+            app,
+            router,
+            last_poll: Instant::now(),
+        })
+    }
+
+    /// Answers one request and logs it. `/events` upgrades are answered
+    /// with 501, as there's no connection to keep.
+    pub fn handle(&mut self, request: &Request, peer: SocketAddr) -> Response {
+        if self.last_poll.elapsed() >= POLL_INTERVAL {
+            self.last_poll = Instant::now();
+            reload_shared(&mut self.app);
+            if let Err(e) = self.app.load_tasks() {
+                log::warn(&format!("Failed to reload tasks: {}", e));
+            }
+        }
+        let (username, response) = self.router.route(&mut self.app, request, peer);
+        let response = response
+            .unwrap_or_else(|| Response::text(501, "WebSocket upgrades need the bundled server"));
+        metrics::record_request(response.status);
+        log::info(&format!(
+            "{} {} {} {} -> {}",
+            peer.ip(),
+            username.as_deref().unwrap_or("-"),
+            request.method,
+            self.router.logged_path(&request.path),
+            response.status
+        ));
+        response
+    }
+}
+
+impl Server {
+    /// Reads what subscribers sent, dropping closed connections. A user's
+    /// mark is cleared when their last client goes.
+    fn poll_subscribers(&mut self, app: &TodoApp) {
+        let mut received = Vec::new();
+        let mut closed = Vec::new();
+        self.subscribers.retain_mut(|subscriber| {
+            let mut messages = Vec::new();
+            let open = subscriber.socket.poll(&mut messages);
+            received.extend(
+                messages
+                    .into_iter()
+                    .map(|message| (subscriber.username.clone(), message)),
+            );
+            if !open {
+                closed.push(subscriber.username.clone());
+            }
+            open
+        });
+
+        for (username, message) in received {
+            match serde_json::from_str::<ClientMessage>(&message) {
+                Ok(message) => self.mark_editing(app, &username, message.editing),
+                Err(e) => log::warn(&format!("Ignored message from {}: {}", username, e)),
+            }
+        }
+        for username in closed {
+            let connected = self
+                .subscribers
+                .iter()
+                .any(|subscriber| subscriber.username == username);
+            if !connected && self.marked.contains(&username) {
+                self.mark_editing(app, &username, None);
+            }
+        }
+    }
+
+    /// Marks a user as editing a task they can see, or none.
+    fn mark_editing(&mut self, app: &TodoApp, username: &str, task_id: Option<u32>) {
+        if let Some(task_id) = task_id {
+            let visible = app
+                .tasks
+                .get(&task_id)
+                .is_some_and(|task| app.workspaces.can_access(task, username));
+            if !visible {
+                log::warn(&format!(
+                    "Ignored {} editing task #{} they can't see",
+                    username, task_id
+                ));
+                return;
+            }
+            self.marked.insert(username.to_string());
+        } else {
+            self.marked.remove(username);
+        }
+        if let Err(e) = presence::set_editing(username, task_id) {
+            log::warn(&format!("Failed to save presence of {}: {}", username, e));
+        }
+    }
+
+    /// Reads a request from a new connection on its own thread, to be
+    /// answered once it's all there. Closes the connection if too many
+    /// requests are being read already.
+    fn start_reading(&self, stream: TcpStream, peer: SocketAddr) {
+        if self.reading.load(Ordering::SeqCst) >= MAX_READING {
+            log::warn(&format!(
+                "Dropped connection from {}: too many requests",
+                peer
+            ));
+            return;
+        }
+        self.reading.fetch_add(1, Ordering::SeqCst);
+        let reading = Arc::clone(&self.reading);
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let request = stream
+                .set_nonblocking(false)
+                .and_then(|()| Request::read(&stream));
+            reading.fetch_sub(1, Ordering::SeqCst);
+            match request {
+                Ok(request) => {
+                    // The server only stops with the process.
+                    let _ = sender.send((stream, peer, request));
+                }
+                Err(e) => log::warn(&format!("Request from {} failed: {}", peer, e)),
+            }
+        });
+    }
+
+    /// Answers one request and logs it. WebSocket connections are kept as
+    /// subscribers.
+    fn handle(
+        &mut self,
+        app: &mut TodoApp,
+        stream: TcpStream,
+        peer: SocketAddr,
+        request: Request,
+    ) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let (username, response) = self.router.route(app, &request, peer);
+        let status = response.as_ref().map_or(101, |response| response.status);
+        metrics::record_request(status);
+        log::info(&format!(
+            "{} {} {} {} -> {}",
+            peer.ip(),
+            username.as_deref().unwrap_or("-"),
+            request.method,
+            self.router.logged_path(&request.path),
+            status
+        ));
+
+        match (response, username) {
+            (Some(response), _) => response.write(stream),
+            (None, Some(username)) => {
+                let key = request.header("sec-websocket-key").unwrap_or_default();
+                let socket = WebSocket::accept(stream, key)?;
+                self.subscribers.push(Subscriber { username, socket });
+                Ok(())
+            }
+            (None, None) => Response::text(401, "Authentication required").write(stream),
+        }
+    }
+}
+
+impl Router {
+    /// Serves the routes under `prefix`, e.g. `/todo`; other paths aren't
+    /// found.
+    pub fn nest(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Adds middleware, run after what was added before.
+    pub fn layer(
+        mut self,
+        middleware: impl FnMut(&Request, SocketAddr) -> Option<Response> + 'static,
+    ) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// `path` without the prefix, if it's under it.
+    fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match path.strip_prefix(self.prefix.as_str())? {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+
+    /// `path` as written to the log, without the token of a share link.
+    fn logged_path(&self, path: &str) -> String {
+        match self.strip_prefix(path) {
+            Some(route) if route.starts_with("/share/") => format!("{}/share/…", self.prefix),
+            _ => path.to_string(),
+        }
+    }
+
+    /// Applies middleware, rate limits and authentication, then dispatches
+    /// the request. Returns the authenticated user and the response, or no
+    /// response when the connection should be upgraded to a WebSocket.
+    pub(crate) fn route(
+        &mut self,
+        app: &mut TodoApp,
+        request: &Request,
+        peer: SocketAddr,
+    ) -> (Option<String>, Option<Response>) {
+        let Some(path) = self.strip_prefix(&request.path) else {
+            return (None, Some(Response::text(404, "Not found")));
+        };
+        for middleware in &mut self.middleware {
+            if let Some(response) = middleware(request, peer) {
+                return (None, Some(response));
+            }
+        }
+        if (request.method.as_str(), path) == ("POST", "/login") {
+            return (None, Some(login(app, request)));
+        }
+        if let Some(token) = path.strip_prefix("/share/") {
+            if request.method != "GET" {
+                return (None, Some(Response::text(404, "Not found")));
+            }
+            return (None, Some(shared_project(app, request, token)));
+        }
+        if (request.method.as_str(), path) == ("GET", "/metrics") && self.public_metrics {
+            return (None, Some(metrics_response(app)));
+        }
+
+        let Some(token) = request.token(path) else {
+            return (None, Some(Response::text(401, "Authentication required")));
+        };
+        let Some(username) = app.token_user(token).map(str::to_string) else {
+            metrics::record_auth_failure();
+            return (None, Some(Response::text(401, "Invalid token")));
+        };
+        if !self.limiter.allow(format!("user:{}", username)) {
+            return (
+                Some(username),
+                Some(Response::text(429, "Too many requests")),
+            );
+        }
+
+        let response = match (request.method.as_str(), path) {
+            ("POST", "/logout") => match app.revoke_token(token) {
+                Ok(()) => Some(Response::empty(204)),
+                Err(e) => Some(Response::text(500, e)),
+            },
+            ("GET", "/tasks") => Some(list_tasks(app, request, &username)),
+            ("GET", "/metrics") if app.admins.contains(&username) => Some(metrics_response(app)),
+            ("GET", "/metrics") => Some(Response::text(403, "Only admins can see metrics")),
+            ("GET", "/events") => {
+                let is_upgrade = request
+                    .header("upgrade")
+                    .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+                if is_upgrade && request.header("sec-websocket-key").is_some() {
+                    None
+                } else {
+                    Some(Response::text(400, "Expected a WebSocket upgrade"))
+                }
+            }
+            _ => Some(Response::text(404, "Not found")),
+        };
+        (Some(username), response)
+    }
+}
+
+fn metrics_response(app: &TodoApp) -> Response {
+    Response::prometheus(200, metrics::render_prometheus(&app.metrics()))
+}
+
+/// Issues a token for the credentials in a JSON body.
+fn login(app: &mut TodoApp, request: &Request) -> Response {
+    let Ok(credentials) = serde_json::from_slice::<LoginRequest>(&request.body) else {
+        return Response::text(400, "Expected a JSON body with username and password");
+    };
+    match app.issue_token(&credentials.username, &credentials.password) {
+        Ok(token) => Response::json(200, serde_json::json!({ "token": token }).to_string()),
+        Err(e) => Response::text(401, e),
+    }
+}
+
+/// The tasks `username` can see, filtered by the `query` parameter.
+fn list_tasks(app: &TodoApp, request: &Request, username: &str) -> Response {
+    let query = match request.query_param("query").map(percent_decode) {
+        Some(query) => match Query::parse(&query) {
+            Ok(query) => Some(query),
+            Err(e) => return Response::text(400, &e),
+        },
+        None => None,
+    };
+    let tasks: Vec<&Task> = app
+        .tasks
+        .values()
+        .filter(|task| app.workspaces.can_access(task, username))
+        .filter(|task| query.as_ref().is_none_or(|query| query.matches(task)))
+        .collect();
+    match serde_json::to_string(&tasks) {
+        Ok(json) => Response::json(200, json),
+        Err(e) => Response::text(500, &e.to_string()),
+    }
+}
+
+/// Decodes a query string value: `%XX` escapes and `+` for a space.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = value
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = byte {
+                    decoded.push(byte);
+                    i += 2;
+                } else {
+                    decoded.push(b'%');
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A shared project's tasks. Unknown, revoked and expired links all look
+/// the same.
+fn shared_project(app: &TodoApp, request: &Request, token: &str) -> Response {
+    let Some((share, tasks)) = app.shared_tasks(token) else {
+        return Response::text(404, "Not found");
+    };
+    if request.query_param("format") == Some("json") {
+        Response::json(200, shares::render_json(share, &tasks))
+    } else {
+        Response::html(200, shares::render_html(share, &tasks))
+    }
+}
+
+/// Tasks that were created, changed or deleted between two snapshots.
+fn changes<'a>(
+    previous: &'a BTreeMap<u32, Task>,
+    current: &'a BTreeMap<u32, Task>,
+) -> Vec<(Change, &'a Task)> {
+    let mut changes: Vec<(Change, &Task)> = current
+        .values()
+        .filter_map(|task| match previous.get(&task.id) {
+            None => Some((Change::Created, task)),
+            Some(old) if old.version != task.version => Some((Change::Updated, task)),
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        previous
+            .values()
+            .filter(|task| !current.contains_key(&task.id))
+            .map(|task| (Change::Deleted, task)),
+    );
+    changes.sort_by_key(|(_, task)| task.id);
+    changes
+}
+
+/// Sends an event to the subscribers who can see the task, dropping dead
+/// connections.
+fn broadcast(
+    subscribers: &mut Vec<Subscriber>,
+    workspaces: &Workspaces,
+    change: Change,
+    task: &Task,
+) {
+    let Ok(json) = serde_json::to_string(&Event {
+        event: change,
+        task,
+    }) else {
+        return;
+    };
+    subscribers.retain_mut(|subscriber| {
+        !workspaces.can_access(task, &subscriber.username)
+            || subscriber.socket.send_text(&json).is_ok()
+    });
+}
+
+/// Tells the other users who can see the task that someone started or
+/// stopped editing it, dropping dead connections.
+fn broadcast_presence(subscribers: &mut Vec<Subscriber>, app: &TodoApp, change: &presence::Change) {
+    let Some(task) = app.tasks.get(&change.task_id) else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(&PresenceEvent {
+        event: if change.editing {
+            Change::Editing
+        } else {
+            Change::StoppedEditing
+        },
+        user: &change.username,
+        task_id: change.task_id,
+    }) else {
+        return;
+    };
+    subscribers.retain_mut(|subscriber| {
+        subscriber.username == change.username
+            || !app.workspaces.can_access(task, &subscriber.username)
+            || subscriber.socket.send_text(&json).is_ok()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn route(router: &mut Router, request: Request) -> Response {
+        let mut app = testing::app();
+        let peer = SocketAddr::from(([127, 0, 0, 1], 4000));
+        let (_, response) = router.route(&mut app, &request, peer);
+        response.expect("a response")
+    }
+
+    fn get(target: &str) -> Request {
+        Request::new("GET", target, Vec::new(), Vec::new())
+    }
+
+    #[test]
+    fn nested_routes_only_answer_under_the_prefix() {
+        let mut router = router(&ServerConfig::default()).nest("/todo/");
+        assert_eq!(route(&mut router, get("/tasks")).status(), 404);
+        assert_eq!(route(&mut router, get("/todos/tasks")).status(), 404);
+        assert_eq!(route(&mut router, get("/todo/tasks")).status(), 401);
+    }
+
+    #[test]
+    fn unauthenticated_requests_are_challenged() {
+        let mut router = router(&ServerConfig::default());
+        let response = route(&mut router, get("/tasks?query=done"));
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.body(), "Authentication required\n");
+        assert_eq!(
+            response.headers(),
+            vec![("WWW-Authenticate", "Bearer".to_string())]
+        );
+    }
+
+    #[test]
+    fn middleware_answers_before_the_routes() {
+        let mut router = router(&ServerConfig::default()).layer(|request, _| {
+            request
+                .header("X-Blocked")
+                .map(|_| Response::text(403, "Blocked"))
+        });
+        let headers = vec![("x-blocked".to_string(), "1".to_string())];
+        let request = Request::new("POST", "/login", headers, Vec::new());
+        assert_eq!(route(&mut router, request).status(), 403);
+        let request = Request::new("POST", "/login", Vec::new(), b"{}".to_vec());
+        assert_eq!(route(&mut router, request).status(), 400);
+    }
+
+    #[test]
+    fn api_serves_a_data_directory_without_moving_into_it() {
+        let dir = testing::temp_dir("api");
+        let mut app = crate::open_app(&Config::default(), &dir).unwrap();
+        testing::register(&mut app, &["alice"]);
+        let working_dir = std::env::current_dir().unwrap();
+
+        let router = router(&ServerConfig::default());
+        let mut api = Api::open(&dir.join("config.json"), &dir, router).unwrap();
+        let body = format!(
+            r#"{{"username":"alice","password":"{}"}}"#,
+            testing::PASSWORD
+        );
+        let login = Request::new("POST", "/login", Vec::new(), body.into_bytes());
+        let response = api.handle(&login, SocketAddr::from(([127, 0, 0, 1], 4000)));
+
+        assert_eq!(response.status(), 200);
+        assert!(dir.join(crate::tokens::TOKENS_FILE).exists());
+        assert_eq!(std::env::current_dir().unwrap(), working_dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_events_upgrade_takes_a_token_in_the_url() {
+        let mut app = testing::app();
+        testing::register(&mut app, &["alice"]);
+        let token = app.issue_token("alice", testing::PASSWORD).unwrap();
+        let mut router = router(&ServerConfig::default());
+        let peer = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+        let request = get(&format!("/tasks?token={}", token));
+        let (user, response) = router.route(&mut app, &request, peer);
+        assert_eq!(user, None);
+        assert_eq!(response.unwrap().status(), 401);
+
+        let headers = vec![("authorization".to_string(), format!("Bearer {}", token))];
+        let request = Request::new("GET", "/tasks", headers, Vec::new());
+        let (user, response) = router.route(&mut app, &request, peer);
+        assert_eq!(user.as_deref(), Some("alice"));
+        assert_eq!(response.unwrap().status(), 200);
+
+        let headers = vec![
+            ("upgrade".to_string(), "websocket".to_string()),
+            (
+                "sec-websocket-key".to_string(),
+                "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
+            ),
+        ];
+        let target = format!("/events?token={}", token);
+        let request = Request::new("GET", &target, headers, Vec::new());
+        let (user, response) = router.route(&mut app, &request, peer);
+        assert_eq!(user.as_deref(), Some("alice"));
+        assert!(response.is_none());
+    }
+}
@@ -0,0 +1,418 @@
+//! Per-user preferences stored alongside the accounts, so they follow the
+//! user to any machine that shares the same storage.
+
+use crate::contexts;
+use crate::focus::Focus;
+use crate::holidays::{self, WorkCalendar};
+use crate::i18n::{self, tr};
+use crate::log;
+use crate::notifications::{self, Notifications, QuietHours};
+use crate::schedule;
+use crate::storage;
+use crate::theme;
+use crate::{Task, TodoApp};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BTreeMap;
+use std::io;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Preferences of a single user.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserSettings {
+    /// Timezone for dates: `local`, `UTC` or an offset like `+02:00`.
+    /// Falls back to the `timezone` config option when unset.
+    pub timezone: Option<String>,
+    /// Order tasks are listed in.
+    pub sort: SortOrder,
+    /// `strftime`-style date format such as `%d/%m/%Y`. Falls back to the
+    /// language's format when unset.
+    pub date_format: Option<String>,
+    /// Colours used for task status.
+    pub color_scheme: ColorScheme,
+    /// Workspace that was active at the end of the last session.
+    pub workspace: Option<String>,
+    /// Days without changes after which pending tasks are stale. When set,
+    /// stale tasks are pointed out on login.
+    pub stale_days: Option<u32>,
+    /// Tasks picked to work on today.
+    pub focus: Focus,
+    /// Where the user is working, e.g. `home`; lists hide tasks that must
+    /// be done elsewhere.
+    pub context: Option<String>,
+    /// How tasks are identified in lists.
+    pub ids: IdDisplay,
+    /// Whether to suggest a project and tags for new tasks from ones with
+    /// similar titles.
+    pub suggestions: bool,
+    /// How reminders and digests reach the user.
+    pub notifications: Notifications,
+    /// Saved filters by name, with their expressions.
+    pub filters: BTreeMap<String, String>,
+    /// Holidays to skip besides weekends: a country code such as `UA` or
+    /// the path of a holidays file.
+    pub holidays: Option<String>,
+}
+
+/// Order in which task lists are shown. Pinned tasks always come first.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// By project, then as arranged with `move`.
+    #[default]
+    Manual,
+    Id,
+    Due,
+    Title,
+    Created,
+}
+
+/// How task IDs are shown. Both forms are accepted wherever a task ID is
+/// expected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdDisplay {
+    /// The task's number, e.g. `12`.
+    #[default]
+    Number,
+    /// The shortest unique start of the task's UUID, e.g. `3f9a`.
+    Short,
+}
+
+/// Terminal colours for task status.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    #[default]
+    None,
+    Dark,
+    Light,
+    /// No colours, but symbols and bold text.
+    Monochrome,
+}
+
+/// What a coloured piece of text stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    Completed,
+    Pending,
+    Overdue,
+    /// Calendar days with one task due.
+    LightDay,
+    /// Calendar days with a few tasks due.
+    BusyDay,
+    /// Calendar days with many tasks due.
+    PackedDay,
+    /// Reminders shown while the menu is open.
+    Notice,
+    /// Text matched by `grep`.
+    Match,
+}
+
+impl SortOrder {
+    fn parse(value: &str) -> Result<Self, &'static str> {
+        match value {
+            "manual" => Ok(Self::Manual),
+            "id" => Ok(Self::Id),
+            "due" => Ok(Self::Due),
+            "title" => Ok(Self::Title),
+            "created" => Ok(Self::Created),
+            _ => Err("Invalid sort order, expected manual, id, due, title or created"),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Id => "id",
+            Self::Due => "due",
+            Self::Title => "title",
+            Self::Created => "created",
+        }
+    }
+
+    /// Compares two tasks in this order, breaking ties by ID.
+    pub fn compare(self, a: &Task, b: &Task) -> CmpOrdering {
+        let order = match self {
+            // Tasks not moved yet go after the others of their project.
+            Self::Manual => (&a.project, a.position.is_none(), a.position).cmp(&(
+                &b.project,
+                b.position.is_none(),
+                b.position,
+            )),
+            Self::Id => CmpOrdering::Equal,
+            // Tasks without a due date go last.
+            Self::Due => {
+                (a.due_date.is_none(), a.due_date).cmp(&(b.due_date.is_none(), b.due_date))
+            }
+            Self::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            Self::Created => a.created_at.cmp(&b.created_at),
+        };
+        order.then(a.id.cmp(&b.id))
+    }
+}
+
+impl IdDisplay {
+    fn parse(value: &str) -> Result<Self, &'static str> {
+        match value {
+            "number" => Ok(Self::Number),
+            "short" => Ok(Self::Short),
+            _ => Err("Invalid ID display, expected number or short"),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Number => "number",
+            Self::Short => "short",
+        }
+    }
+}
+
+impl ColorScheme {
+    fn parse(value: &str) -> Result<Self, &'static str> {
+        match value {
+            "none" => Ok(Self::None),
+            "dark" => Ok(Self::Dark),
+            "light" => Ok(Self::Light),
+            "monochrome" => Ok(Self::Monochrome),
+            _ => Err("Invalid color scheme, expected none, dark, light or monochrome"),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Dark => "dark",
+            Self::Light => "light",
+            Self::Monochrome => "monochrome",
+        }
+    }
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Pending => "pending",
+            Self::Overdue => "overdue",
+            Self::LightDay => "light-day",
+            Self::BusyDay => "busy-day",
+            Self::PackedDay => "packed-day",
+            Self::Notice => "notice",
+            Self::Match => "match",
+        }
+    }
+}
+
+impl UserSettings {
+    /// Names of the settings, in the order they're shown.
+    pub const NAMES: [&'static str; 14] = [
+        "timezone",
+        "sort",
+        "date-format",
+        "color-scheme",
+        "stale-days",
+        "context",
+        "ids",
+        "suggestions",
+        "notify-desktop",
+        "notify-email",
+        "notify-webhook",
+        "quiet-hours",
+        "digest-time",
+        "holidays",
+    ];
+
+    /// The current value of a setting as text; blank when unset.
+    pub fn get(&self, name: &str) -> Result<String, &'static str> {
+        match name {
+            "timezone" => Ok(self.timezone.clone().unwrap_or_default()),
+            "sort" => Ok(self.sort.name().to_string()),
+            "date-format" => Ok(self.date_format.clone().unwrap_or_default()),
+            "color-scheme" => Ok(self.color_scheme.name().to_string()),
+            "stale-days" => Ok(self
+                .stale_days
+                .map(|days| days.to_string())
+                .unwrap_or_default()),
+            "context" => Ok(self
+                .context
+                .as_ref()
+                .map(|context| format!("@{}", context))
+                .unwrap_or_default()),
+            "ids" => Ok(self.ids.name().to_string()),
+            "suggestions" => Ok(on_off(self.suggestions)),
+            "notify-desktop" => Ok(on_off(self.notifications.desktop)),
+            "notify-email" => Ok(on_off(self.notifications.email)),
+            "notify-webhook" => Ok(on_off(self.notifications.webhook)),
+            "quiet-hours" => Ok(self
+                .notifications
+                .quiet_hours
+                .map(|quiet| quiet.to_string())
+                .unwrap_or_default()),
+            "digest-time" => Ok(self
+                .notifications
+                .digest_time
+                .map(|time| time.format("%H:%M").to_string())
+                .unwrap_or_default()),
+            "holidays" => Ok(self.holidays.clone().unwrap_or_default()),
+            _ => Err("Unknown setting"),
+        }
+    }
+
+    /// Changes a setting. A blank value resets it to the default.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        let value = value.trim();
+        match name {
+            "timezone" if value.is_empty() => self.timezone = None,
+            "timezone" => {
+                schedule::parse_timezone(value)?;
+                self.timezone = Some(value.to_string());
+            }
+            "sort" if value.is_empty() => self.sort = SortOrder::default(),
+            "sort" => self.sort = SortOrder::parse(value)?,
+            "date-format" if value.is_empty() => self.date_format = None,
+            "date-format" => {
+                if !i18n::is_valid_date_format(value) {
+                    return Err("Invalid date format, expected e.g. %d/%m/%Y");
+                }
+                self.date_format = Some(value.to_string());
+            }
+            "color-scheme" if value.is_empty() => self.color_scheme = ColorScheme::default(),
+            "color-scheme" => self.color_scheme = ColorScheme::parse(value)?,
+            "stale-days" if value.is_empty() => self.stale_days = None,
+            "stale-days" => {
+                let days = value
+                    .parse()
+                    .ok()
+                    .filter(|days| *days > 0)
+                    .ok_or("Invalid number of days, expected a positive number")?;
+                self.stale_days = Some(days);
+            }
+            "context" if value.is_empty() => self.context = None,
+            "context" => self.context = Some(contexts::parse_context(value)?),
+            "ids" if value.is_empty() => self.ids = IdDisplay::default(),
+            "ids" => self.ids = IdDisplay::parse(value)?,
+            "suggestions" => self.suggestions = parse_on_off(value, false)?,
+            "notify-desktop" => self.notifications.desktop = parse_on_off(value, true)?,
+            "notify-email" => self.notifications.email = parse_on_off(value, false)?,
+            "notify-webhook" => self.notifications.webhook = parse_on_off(value, false)?,
+            "quiet-hours" if value.is_empty() => self.notifications.quiet_hours = None,
+            "quiet-hours" => self.notifications.quiet_hours = Some(QuietHours::parse(value)?),
+            "digest-time" if value.is_empty() => self.notifications.digest_time = None,
+            "digest-time" => {
+                self.notifications.digest_time = Some(notifications::parse_time(value)?)
+            }
+            "holidays" if value.is_empty() => self.holidays = None,
+            "holidays" if holidays::is_known_country(value) => {
+                self.holidays = Some(value.to_uppercase())
+            }
+            "holidays" => {
+                WorkCalendar::from_file(value).map_err(|_| {
+                    "Unknown country or unreadable holidays file, expected UA, US, GB, DE, PL or a file"
+                })?;
+                self.holidays = Some(value.to_string());
+            }
+            _ => return Err("Unknown setting"),
+        }
+        Ok(())
+    }
+
+    /// Makes these settings take effect for the rest of the session.
+    fn apply(&self) {
+        match self.timezone.as_deref().map(schedule::parse_timezone) {
+            Some(Ok(offset)) => schedule::set_timezone(offset),
+            _ => schedule::reset_timezone(),
+        }
+        i18n::set_date_format(self.date_format.clone());
+        theme::activate(self.color_scheme);
+    }
+}
+
+impl TodoApp {
+    /// The current user's settings, or the defaults if they never changed any.
+    pub fn settings(&self) -> Result<UserSettings, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        Ok(self.settings.get(user_id).cloned().unwrap_or_default())
+    }
+
+    /// Changes one of the current user's settings and applies it right away.
+    pub fn set_setting(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let settings = self.settings.entry(user_id.clone()).or_default();
+        settings.set(name, value)?;
+        settings.apply();
+        self.save_settings(&format!("Change settings of user {}", user_id))
+            .map_err(|_| "Failed to save settings")?;
+        Ok(())
+    }
+
+    /// Applies the current user's settings and reopens their last
+    /// workspace, if they're still a member. Called on login.
+    pub fn apply_settings(&mut self) {
+        let Ok(settings) = self.settings() else {
+            return;
+        };
+        settings.apply();
+        let user_id = self.current_user.as_deref().unwrap_or_default();
+        self.active_workspace = settings
+            .workspace
+            .filter(|workspace| self.workspaces.role(workspace, user_id).is_some());
+    }
+
+    /// Saves all users' settings. `message` describes the change being saved.
+    pub fn save_settings(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(
+            self.storage.as_ref(),
+            SETTINGS_FILE,
+            &self.settings,
+            message,
+        )
+        .inspect_err(|e| log::error(&format!("Failed to save settings ({}): {}", message, e)))
+    }
+
+    /// Loads all users' settings. If the file doesn't exist, it's ignored.
+    pub fn load_settings(&mut self) -> io::Result<()> {
+        if let Some(settings) = storage::load_json(self.storage.as_ref(), SETTINGS_FILE)? {
+            self.settings = settings;
+        }
+        Ok(())
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+/// Parses `on` or `off`; a blank value is `default`.
+fn parse_on_off(value: &str, default: bool) -> Result<bool, &'static str> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        "" => Ok(default),
+        _ => Err("Invalid value, expected on or off"),
+    }
+}
+
+/// Describes a setting's value for display, showing blank values as the default.
+pub fn describe(value: &str) -> String {
+    if value.is_empty() {
+        tr("(default)").to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints each setting of `settings` with its value.
+pub fn print(settings: &UserSettings) {
+    for (i, name) in UserSettings::NAMES.iter().enumerate() {
+        let value = settings.get(name).unwrap_or_default();
+        println!("{}. {}: {}", i + 1, name, describe(&value));
+    }
+}
@@ -0,0 +1,140 @@
+//! The setup run on first launch, when there's neither a config file nor
+//! data: it asks where to keep the data, whether the app is used by one
+//! person or several, the timezone and the default sort, writes the config
+//! file and creates the first account.
+//!
+//! The config goes to the user's config directory, so the app finds the
+//! data from any directory afterwards.
+
+use crate::config::{self, Config, LOCAL_USER};
+use crate::i18n::{tr, trf};
+use crate::schedule;
+use crate::settings::UserSettings;
+use crate::{confirm, print_error, prompt_input, TodoApp};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Asks where to keep the data and switches to that directory. Asked
+/// before the data is loaded, as the directory may already have some.
+pub fn choose_data_dir(config: &mut Config) -> io::Result<()> {
+    println!("{}", tr("Welcome! Let's set up the app."));
+    let data_dir = ask_data_dir()?;
+    env::set_current_dir(&data_dir)?;
+    config.data_dir = Some(data_dir.to_string_lossy().into_owned());
+    Ok(())
+}
+
+/// Asks the rest of the setup questions, applies the answers to `config`
+/// and `app`, writes the config file and logs in to the new account. No
+/// account is created if the data directory already has some.
+pub fn run(app: &mut TodoApp, config: &mut Config) -> io::Result<()> {
+    config.single_user = confirm(tr("Will you be the only user?"))?;
+
+    loop {
+        let input = prompt_input(tr("Timezone (local, UTC or e.g. +02:00) [local]: "))?;
+        if input.is_empty() {
+            break;
+        }
+        match schedule::parse_timezone(&input) {
+            Ok(offset) => {
+                schedule::set_default_timezone(offset);
+                config.timezone = Some(input);
+                break;
+            }
+            Err(e) => print_error(e),
+        }
+    }
+
+    let mut settings = UserSettings::default();
+    loop {
+        let input = prompt_input(tr(
+            "Sort tasks by (manual, id, due, title or created) [manual]: ",
+        ))?;
+        match settings.set("sort", &input) {
+            Ok(()) => break,
+            Err(e) => print_error(e),
+        }
+    }
+
+    let path = config::user_path().unwrap_or_else(|| PathBuf::from("config.json"));
+    config.save(&path)?;
+    println!("{}", trf("Config written to {}", &[&path.display()]));
+
+    if config.single_user {
+        return save_settings(app, LOCAL_USER, settings);
+    }
+    if !app.users.is_empty() {
+        println!(
+            "{}",
+            tr("The data directory has accounts already; log in with yours.")
+        );
+        return Ok(());
+    }
+    let (username, password) = create_account(app)?;
+    save_settings(app, &username, settings)?;
+    if let Err(e) = app.login(&username, &password) {
+        print_error(e);
+    }
+    Ok(())
+}
+
+/// Saves the settings picked for the new user, unless they have some already.
+fn save_settings(app: &mut TodoApp, username: &str, settings: UserSettings) -> io::Result<()> {
+    if app.settings.contains_key(username) {
+        return Ok(());
+    }
+    app.settings.insert(username.to_string(), settings);
+    app.save_settings(&format!("Set up settings of user {}", username))
+}
+
+/// Asks for the data directory until one can be created, defaulting to the
+/// user's data directory.
+fn ask_data_dir() -> io::Result<PathBuf> {
+    let default = default_data_dir();
+    loop {
+        let input = prompt_input(&trf(
+            "Where should the data be kept? [{}]: ",
+            &[&default.display()],
+        ))?;
+        let dir = if input.is_empty() {
+            default.clone()
+        } else {
+            PathBuf::from(input)
+        };
+        match fs::create_dir_all(&dir).and_then(|()| dir.canonicalize()) {
+            Ok(dir) => return Ok(dir),
+            Err(e) => print_error(&trf("Can't use {}: {}", &[&dir.display(), &e])),
+        }
+    }
+}
+
+/// `~/.local/share/lab3`, or the current directory without a home directory.
+fn default_data_dir() -> PathBuf {
+    let dir = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local").join("share"),
+            None => return PathBuf::from("."),
+        },
+    };
+    dir.join("lab3")
+}
+
+/// Asks for the first account's name and password until it's registered,
+/// returning them.
+fn create_account(app: &mut TodoApp) -> io::Result<(String, String)> {
+    println!("{}", tr("Create the first account."));
+    loop {
+        let username = prompt_input(tr("Username: "))?;
+        let password = prompt_input(tr("Password: "))?;
+        match app.register(&username, &password) {
+            Ok(()) => {
+                let username = app.find_user(&username).unwrap_or(&username).to_string();
+                return Ok((username, password));
+            }
+            Err(e) => print_error(e),
+        }
+    }
+}
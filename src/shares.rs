@@ -0,0 +1,181 @@
+//! Read-only share links for a project. Anyone with the link can see the
+//! project's tasks in server mode without logging in, until the link expires
+//! or is revoked. Like API tokens, only a hash of each link's token is stored.
+
+use crate::crypto;
+use crate::log;
+use crate::storage;
+use crate::tokens;
+use crate::{Task, TodoApp};
+use chrono::{serde::ts_seconds, serde::ts_seconds_option, DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const SHARES_FILE: &str = "shares.json";
+
+/// Characters of the token hash used to refer to a share, e.g. to revoke it.
+const ID_LENGTH: usize = 8;
+
+/// A share link, stored under the hash of its token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    /// User whose tasks are shared.
+    pub username: String,
+    pub project: String,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    /// When the link stops working; `None` if it doesn't expire.
+    #[serde(default, with = "ts_seconds_option")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Share {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+impl TodoApp {
+    /// Creates a link to the current user's tasks in `project`, valid for
+    /// `expires_in` or until revoked. Returns the link's token.
+    pub fn create_share(
+        &mut self,
+        project: &str,
+        expires_in: Option<Duration>,
+    ) -> Result<String, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if !self
+            .own_tasks(&user_id)
+            .any(|task| task.project.as_deref() == Some(project))
+        {
+            return Err("You have no tasks in this project");
+        }
+
+        let token = crypto::random_token().map_err(|_| crypto::NO_RANDOM)?;
+        self.shares.insert(
+            tokens::hash(&token),
+            Share {
+                username: user_id.clone(),
+                project: project.to_string(),
+                created_at: Utc::now(),
+                expires_at: expires_in.map(|expires_in| Utc::now() + expires_in),
+            },
+        );
+        self.save_shares(&format!("Share project {} of user {}", project, user_id))
+            .map_err(|_| "Failed to save shares")?;
+        Ok(token)
+    }
+
+    /// The current user's share links with their IDs, newest first.
+    pub fn shares(&self) -> Result<Vec<(&str, &Share)>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let mut shares: Vec<(&str, &Share)> = self
+            .shares
+            .iter()
+            .filter(|(_, share)| share.username == *user_id)
+            .map(|(hash, share)| (&hash[..ID_LENGTH], share))
+            .collect();
+        shares.sort_by_key(|(_, share)| std::cmp::Reverse(share.created_at));
+        Ok(shares)
+    }
+
+    /// Revokes one of the current user's share links by its ID.
+    pub fn revoke_share(&mut self, id: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if id.len() < ID_LENGTH {
+            return Err("Share not found");
+        }
+        let hash = self
+            .shares
+            .iter()
+            .find(|(hash, share)| share.username == user_id && hash.starts_with(id))
+            .map(|(hash, _)| hash.clone())
+            .ok_or("Share not found")?;
+        self.shares.remove(&hash);
+        self.save_shares(&format!("Revoke share {} of user {}", id, user_id))
+            .map_err(|_| "Failed to save shares")
+    }
+
+    /// The share a token belongs to and the tasks it shows, if the token is
+    /// valid and hasn't expired.
+    pub fn shared_tasks(&self, token: &str) -> Option<(&Share, Vec<&Task>)> {
+        let share = self
+            .shares
+            .get(&tokens::hash(token))
+            .filter(|share| !share.is_expired())?;
+        let tasks = self
+            .own_tasks(&share.username)
+            .filter(|task| task.project.as_deref() == Some(share.project.as_str()))
+            .collect();
+        Some((share, tasks))
+    }
+
+    /// Saves all share links. `message` describes the change being saved.
+    fn save_shares(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), SHARES_FILE, &self.shares, message)
+            .inspect_err(|e| log::error(&format!("Failed to save shares ({}): {}", message, e)))
+    }
+
+    /// Loads all share links. If the file doesn't exist, it's ignored.
+    pub fn load_shares(&mut self) -> io::Result<()> {
+        if let Some(shares) = storage::load_json(self.storage.as_ref(), SHARES_FILE)? {
+            self.shares = shares;
+        }
+        Ok(())
+    }
+}
+
+/// The shared tasks as JSON, with only the fields meant for others to see.
+pub fn render_json(share: &Share, tasks: &[&Task]) -> String {
+    let tasks: Vec<serde_json::Value> = tasks
+        .iter()
+        .map(|task| {
+            serde_json::json!({
+                "id": task.id,
+                "title": task.title,
+                "description": task.description,
+                "completed": task.completed,
+                "due_date": task.due_date.map(|due| due.timestamp()),
+                "priority": task.priority,
+                "tags": task.tags,
+            })
+        })
+        .collect();
+    serde_json::json!({ "project": share.project, "tasks": tasks }).to_string()
+}
+
+/// The shared tasks as a simple HTML page.
+pub fn render_html(share: &Share, tasks: &[&Task]) -> String {
+    let project = escape_html(&share.project);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        project, project
+    );
+    for task in tasks {
+        let mut line = escape_html(&task.title);
+        if task.completed {
+            line = format!("<s>{}</s>", line);
+        }
+        if let Some(due) = task.due_date {
+            line.push_str(&format!(" <small>({})</small>", due.format("%Y-%m-%d")));
+        }
+        if !task.description.is_empty() {
+            line.push_str(&format!("<br>{}", escape_html(&task.description)));
+        }
+        out.push_str(&format!("<li>{}</li>\n", line));
+    }
+    out.push_str("</ul>\n</body>\n</html>\n");
+    out
+}
+
+/// Escapes text for use in HTML.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,118 @@
+//! Orderly exit on Ctrl-C and `kill`, and autosave for long-running modes.
+//!
+//! SIGINT and SIGTERM are waited for on a thread of their own instead of in
+//! a signal handler, so ordinary code can run when one arrives: a data file
+//! being written is finished first, writes that failed earlier are retried,
+//! and the terminal is restored before the app exits.
+
+use crate::log;
+use crate::storage::{self, Storage};
+use crate::terminal;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// Held while a data file is written, so the app doesn't exit halfway.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Storage the pending writes are flushed to on exit.
+static STORAGE: Mutex<Option<Box<dyn Storage + Send>>> = Mutex::new(None);
+
+/// Keeps the app from exiting until the returned guard is dropped.
+pub fn hold() -> MutexGuard<'static, ()> {
+    WRITING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Exits cleanly on SIGINT and SIGTERM, flushing pending writes to
+/// `storage`. Must be called before any other thread is started, as they
+/// inherit which signals are left to the waiting thread.
+pub fn install(storage: Box<dyn Storage + Send>) {
+    *STORAGE.lock().unwrap_or_else(PoisonError::into_inner) = Some(storage);
+    #[cfg(unix)]
+    signals::wait_in_background();
+}
+
+/// Retries failed writes every `interval` for as long as the app runs.
+pub fn start_autosave(interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        flush();
+    });
+}
+
+/// Writes the data files whose last write failed, logging what's left.
+fn flush() {
+    let _writing = hold();
+    flush_held();
+}
+
+/// `flush`, for a caller already holding the lock from `hold`.
+fn flush_held() {
+    let storage = STORAGE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(storage) = storage.as_deref() {
+        let left = storage::flush_pending(storage);
+        if left > 0 {
+            log::warn(&format!("{} data files still couldn't be saved", left));
+        }
+    }
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn exit(signal: i32) -> ! {
+    // Keep holding the lock so no other write starts while exiting.
+    let _writing = hold();
+    flush_held();
+    terminal::restore();
+    log::info(&format!("Exiting on signal {}", signal));
+    println!();
+    std::process::exit(128 + signal)
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::os::raw::c_int;
+    use std::thread;
+
+    /// Room for a `sigset_t` on any Unix this builds on.
+    #[repr(C)]
+    struct SigSet([u64; 16]);
+
+    extern "C" {
+        fn sigemptyset(set: *mut SigSet) -> c_int;
+        fn sigaddset(set: *mut SigSet, signal: c_int) -> c_int;
+        fn pthread_sigmask(how: c_int, set: *const SigSet, old: *mut SigSet) -> c_int;
+        fn sigwait(set: *const SigSet, signal: *mut c_int) -> c_int;
+    }
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+    #[cfg(target_os = "linux")]
+    const SIG_BLOCK: c_int = 0;
+    #[cfg(not(target_os = "linux"))]
+    const SIG_BLOCK: c_int = 1;
+
+    /// Blocks SIGINT and SIGTERM in this thread and the ones it starts, and
+    /// starts a thread that waits for them. Child processes get the default
+    /// handling back, as the standard library clears the mask for them.
+    pub fn wait_in_background() {
+        let mut set = SigSet([0; 16]);
+        // SAFETY: `set` is a valid, writable signal set for the duration of
+        // the calls, and the signal numbers are valid.
+        let blocked = unsafe {
+            sigemptyset(&mut set) == 0
+                && sigaddset(&mut set, SIGINT) == 0
+                && sigaddset(&mut set, SIGTERM) == 0
+                && pthread_sigmask(SIG_BLOCK, &set, std::ptr::null_mut()) == 0
+        };
+        if !blocked {
+            return;
+        }
+        thread::spawn(move || {
+            let mut signal = 0;
+            // SAFETY: `set` was initialized above and `signal` is writable.
+            if unsafe { sigwait(&set, &mut signal) } == 0 {
+                super::exit(signal);
+            }
+        });
+    }
+}
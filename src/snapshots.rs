@@ -0,0 +1,167 @@
+//! Daily counts of open and completed tasks, kept to show how the list
+//! burns down over time. A snapshot is recorded each time a user logs in
+//! and with the `snapshot` command, at most one a day: later ones that day
+//! replace it.
+
+use crate::accessibility;
+use crate::i18n;
+use crate::log;
+use crate::schedule;
+use crate::storage;
+use crate::TodoApp;
+use chrono::{Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+pub const SNAPSHOTS_FILE: &str = "snapshots.json";
+
+/// Rows in a burndown chart.
+const CHART_HEIGHT: u32 = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub open: u32,
+    pub completed: u32,
+}
+
+impl TodoApp {
+    /// Records today's counts of the current user's personal tasks. Nothing
+    /// is saved if they haven't changed since the last snapshot today, or if
+    /// the last one is more recent than the retention policy asks for.
+    pub fn record_snapshot(&mut self) -> Result<Snapshot, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let mut snapshot = Snapshot::default();
+        for task in self
+            .own_tasks(&user_id)
+            .filter(|task| task.workspace.is_none())
+        {
+            if task.completed {
+                snapshot.completed += 1;
+            } else {
+                snapshot.open += 1;
+            }
+        }
+        let today = schedule::local_date(Utc::now());
+        let interval = self.retention.snapshot_interval();
+        let history = self.snapshots.entry(user_id.clone()).or_default();
+        if history
+            .range(..today)
+            .next_back()
+            .is_some_and(|(day, _)| *day + interval > today)
+        {
+            return Ok(snapshot);
+        }
+        if history.insert(today, snapshot) != Some(snapshot) {
+            self.save_snapshots(&format!("Record snapshot for user {}", user_id))
+                .map_err(|_| "Failed to save snapshots")?;
+        }
+        Ok(snapshot)
+    }
+
+    /// The current user's snapshots, by day.
+    pub fn snapshots(&self) -> Result<BTreeMap<NaiveDate, Snapshot>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        Ok(self.snapshots.get(user_id).cloned().unwrap_or_default())
+    }
+
+    /// Saves all users' snapshots. `message` describes the change being saved.
+    pub fn save_snapshots(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(
+            self.storage.as_ref(),
+            SNAPSHOTS_FILE,
+            &self.snapshots,
+            message,
+        )
+        .inspect_err(|e| log::error(&format!("Failed to save snapshots ({}): {}", message, e)))
+    }
+
+    /// Loads all users' snapshots. If the file doesn't exist, it's ignored.
+    pub fn load_snapshots(&mut self) -> io::Result<()> {
+        if let Some(snapshots) = storage::load_json(self.storage.as_ref(), SNAPSHOTS_FILE)? {
+            self.snapshots = snapshots;
+        }
+        Ok(())
+    }
+}
+
+/// A bar chart of open tasks for each of the `days` days up to `today`,
+/// one column a day. Days without a snapshot repeat the one before; days
+/// before the first snapshot are left blank. For screen readers it's a list
+/// of the days with snapshots instead.
+pub fn render_burndown(
+    history: &BTreeMap<NaiveDate, Snapshot>,
+    today: NaiveDate,
+    days: u32,
+) -> String {
+    let first = today - Days::new(u64::from(days.saturating_sub(1)));
+    if accessibility::screen_reader() {
+        return history
+            .range(first..=today)
+            .map(|(day, snapshot)| {
+                format!(
+                    "{}: {}\n",
+                    i18n::format_day(*day),
+                    i18n::trf("{} open", &[&snapshot.open])
+                )
+            })
+            .collect();
+    }
+    let mut last = history.range(..first).next_back().map(|(_, s)| s.open);
+    let counts: Vec<Option<u32>> = first
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| {
+            if let Some(snapshot) = history.get(&day) {
+                last = Some(snapshot.open);
+            }
+            last
+        })
+        .collect();
+    let max = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let label_width = max.to_string().len();
+
+    let mut out = String::new();
+    for row in (1..=CHART_HEIGHT).rev() {
+        let label = match row {
+            CHART_HEIGHT => max.to_string(),
+            _ if row == CHART_HEIGHT / 2 => (max / 2).to_string(),
+            _ => String::new(),
+        };
+        let bars: String = counts
+            .iter()
+            .map(|count| match count {
+                // A bar reaches a row once it covers most of it.
+                Some(count) if count * CHART_HEIGHT * 2 >= (row * 2 - 1) * max => '#',
+                _ => ' ',
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:>width$} |{}\n",
+            label,
+            bars.trim_end(),
+            width = label_width
+        ));
+    }
+    out.push_str(&format!(
+        "{:>width$} +{}\n",
+        0,
+        "-".repeat(counts.len()),
+        width = label_width
+    ));
+    let start = first.format("%m-%d").to_string();
+    let end = today.format("%m-%d").to_string();
+    let gap = counts.len().saturating_sub(start.len() + end.len()).max(1);
+    out.push_str(&format!(
+        "{:width$}  {}{}{}\n",
+        "",
+        start,
+        " ".repeat(gap),
+        end,
+        width = label_width
+    ));
+    out
+}
@@ -0,0 +1,76 @@
+//! Pending tasks nobody has touched in a while, with a suggestion of what to
+//! do about each.
+
+use crate::schedule::start_of_today;
+use crate::{Task, TodoApp};
+use chrono::{Duration, Utc};
+
+/// Days without changes after which a task is stale, unless the user chose
+/// otherwise with the `stale-days` setting.
+pub const DEFAULT_STALE_DAYS: u32 = 30;
+
+/// What to do with a stale task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suggestion {
+    /// It's overdue: give it a date that can still be met.
+    Reschedule,
+    /// It has been left alone for so long it's probably no longer wanted.
+    Delete,
+    /// Put it out of sight until it matters again.
+    Snooze,
+}
+
+/// A stale task and how long it has gone unchanged.
+pub struct StaleTask<'a> {
+    pub task: &'a Task,
+    pub days: i64,
+    pub suggestion: Suggestion,
+}
+
+impl TodoApp {
+    /// The current user's pending tasks unchanged for more than `days` days,
+    /// oldest first.
+    pub fn stale_tasks(&self, days: u32) -> Result<Vec<StaleTask<'_>>, &'static str> {
+        let now = Utc::now();
+        let cutoff = now - Duration::days(i64::from(days));
+        let today = start_of_today();
+        let mut stale: Vec<StaleTask> = self
+            .list_tasks()?
+            .into_iter()
+            .filter(|task| !task.completed && task.last_changed() < cutoff)
+            .map(|task| {
+                let age = (now - task.last_changed()).num_days();
+                let suggestion = if task.due_date.is_some_and(|due| due < today) {
+                    Suggestion::Reschedule
+                } else if age >= 3 * i64::from(days) {
+                    Suggestion::Delete
+                } else {
+                    Suggestion::Snooze
+                };
+                StaleTask {
+                    task,
+                    days: age,
+                    suggestion,
+                }
+            })
+            .collect();
+        stale.sort_by_key(|stale| (stale.task.last_changed(), stale.task.id));
+        Ok(stale)
+    }
+
+    /// Days after which the current user's tasks count as stale.
+    pub fn stale_days(&self) -> u32 {
+        self.settings()
+            .ok()
+            .and_then(|settings| settings.stale_days)
+            .unwrap_or(DEFAULT_STALE_DAYS)
+    }
+
+    /// How many of the current user's tasks are stale, if they asked to be
+    /// told on login.
+    pub fn stale_warning(&self) -> Option<(usize, u32)> {
+        let days = self.settings().ok()?.stale_days?;
+        let count = self.stale_tasks(days).ok()?.len();
+        (count > 0).then_some((count, days))
+    }
+}
@@ -0,0 +1,158 @@
+//! `stats --format json`: statistics of the current list as JSON for
+//! dashboards such as Grafana, or for spreadsheets. The schema is versioned
+//! by `version`: within a version, fields are only ever added, never renamed
+//! or removed. Weeks are ISO weeks in local time, such as `2026-W42`, and
+//! durations are whole seconds.
+
+use crate::schedule::local_date;
+use crate::timesheet::time_spent;
+use crate::usagestats::week_key;
+use crate::Task;
+use chrono::{DateTime, Days, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Version of the schema, raised when a field changes meaning or goes away.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Weeks in the weekly series, up to and including the current one.
+const WEEKS: u64 = 12;
+
+#[derive(Debug, Serialize)]
+pub struct StatsExport {
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub tasks: TaskCounts,
+    /// Oldest week first.
+    pub weekly: Vec<WeekCounts>,
+    /// By number of tasks, most first.
+    pub tags: Vec<TagCounts>,
+    pub time: TimeTotals,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TaskCounts {
+    pub open: u64,
+    /// Open tasks past their due date.
+    pub overdue: u64,
+    pub completed: u64,
+    pub untagged: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekCounts {
+    pub week: String,
+    pub created: u64,
+    pub completed: u64,
+    pub tracked_secs: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCounts {
+    pub tag: String,
+    pub open: u64,
+    pub completed: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TimeTotals {
+    /// Time logged on the tasks by anyone.
+    pub tracked_secs: i64,
+    /// Estimates of the tasks that have one.
+    pub estimated_secs: i64,
+    /// Time logged on the tasks that have an estimate, to compare with it.
+    pub tracked_on_estimated_secs: i64,
+    /// Tasks without a project are under `""`.
+    pub by_project: BTreeMap<String, i64>,
+    pub by_user: BTreeMap<String, i64>,
+}
+
+/// The statistics of `tasks` at `now`.
+pub fn build(tasks: &[&Task], now: DateTime<Utc>) -> StatsExport {
+    let today = local_date(now);
+    let mut weekly: Vec<WeekCounts> = (0..WEEKS)
+        .rev()
+        .filter_map(|ago| today.checked_sub_days(Days::new(ago * 7)))
+        .map(|day| WeekCounts {
+            week: week_key(day),
+            created: 0,
+            completed: 0,
+            tracked_secs: 0,
+        })
+        .collect();
+    let index: HashMap<String, usize> = weekly
+        .iter()
+        .enumerate()
+        .map(|(i, week)| (week.week.clone(), i))
+        .collect();
+    let week_of = |at: DateTime<Utc>| index.get(&week_key(local_date(at))).copied();
+
+    let mut counts = TaskCounts::default();
+    let mut tags: BTreeMap<&str, TagCounts> = BTreeMap::new();
+    let mut time = TimeTotals::default();
+    for task in tasks {
+        if task.completed {
+            counts.completed += 1;
+        } else {
+            counts.open += 1;
+            if task.due_date.is_some_and(|due| due < now) {
+                counts.overdue += 1;
+            }
+        }
+        if let Some(i) = week_of(task.created_at) {
+            weekly[i].created += 1;
+        }
+        if let Some(i) = task.completed_at.and_then(week_of) {
+            weekly[i].completed += 1;
+        }
+        if task.tags.is_empty() {
+            counts.untagged += 1;
+        }
+        for tag in &task.tags {
+            let entry = tags.entry(tag).or_insert_with(|| TagCounts {
+                tag: tag.clone(),
+                open: 0,
+                completed: 0,
+            });
+            if task.completed {
+                entry.completed += 1;
+            } else {
+                entry.open += 1;
+            }
+        }
+
+        let spent = time_spent(task).num_seconds();
+        time.tracked_secs += spent;
+        if let Some(estimate) = task.estimate_secs {
+            time.estimated_secs += estimate;
+            time.tracked_on_estimated_secs += spent;
+        }
+        if spent > 0 {
+            *time
+                .by_project
+                .entry(task.project.clone().unwrap_or_default())
+                .or_default() += spent;
+        }
+        for entry in &task.time_entries {
+            *time.by_user.entry(entry.user.clone()).or_default() += entry.duration_secs;
+            if let Some(i) = week_of(entry.at) {
+                weekly[i].tracked_secs += entry.duration_secs;
+            }
+        }
+    }
+
+    let mut tags: Vec<TagCounts> = tags.into_values().collect();
+    tags.sort_by(|a, b| {
+        (b.open + b.completed)
+            .cmp(&(a.open + a.completed))
+            .then_with(|| a.tag.cmp(&b.tag))
+    });
+    StatsExport {
+        version: SCHEMA_VERSION,
+        generated_at: now,
+        tasks: counts,
+        weekly,
+        tags,
+        time,
+    }
+}
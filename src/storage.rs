@@ -0,0 +1,859 @@
+use crate::formats::DataFormat;
+use crate::log;
+use crate::merge::{self, Resolver};
+use crate::metrics;
+use crate::shutdown;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+/// Directory holding the git repository of the git storage backend. It is kept
+/// separate from `.git` so the data files never end up in an enclosing repository.
+pub const GIT_DIR: &str = ".todo-git";
+
+/// Trailer naming, by UUID, a task changed by a commit of the git backend.
+pub const TASK_ID_TRAILER: &str = "Task-Id";
+
+/// Which storage backend persists the data files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    File,
+    Git,
+}
+
+/// Persistence of the app's named data files.
+pub trait Storage {
+    /// Reads a data file, returning `None` if it doesn't exist yet.
+    fn read(&self, name: &str) -> io::Result<Option<String>>;
+
+    /// Writes a data file. `message` describes the change that caused the write.
+    fn write(&self, name: &str, contents: &str, message: &str) -> io::Result<()>;
+
+    /// Opens a data file for reading as a stream, returning `None` if it
+    /// doesn't exist yet.
+    fn reader(&self, name: &str) -> io::Result<Option<Box<dyn BufRead>>> {
+        Ok(self.read(name)?.map(in_memory_reader))
+    }
+
+    /// Writes a data file by handing `write` a writer, so large files don't
+    /// have to be built in memory first where the backend allows it.
+    fn write_with(
+        &self,
+        name: &str,
+        message: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.write(name, &in_memory_contents(write)?, message)
+    }
+
+    /// Reads the previous valid version of a data file, if one was kept.
+    fn backup(&self, name: &str) -> io::Result<Option<String>> {
+        FileStorage::default().read(&backup_name(name))
+    }
+
+    /// Lists the recorded changes matching `pattern`, newest first.
+    fn history(&self, _pattern: &str) -> io::Result<Vec<String>> {
+        Err(unsupported("History requires the git storage backend"))
+    }
+
+    /// Rewrites the data files `names` in `format`, removing the old files.
+    fn convert(&self, _names: &[&str], _format: DataFormat) -> io::Result<()> {
+        Err(unsupported(
+            "Only the file storage backend supports other formats",
+        ))
+    }
+
+    /// Exchanges changes with the configured remote. `resolve` settles the
+    /// fields changed differently on both sides.
+    fn sync(&self, _resolve: &mut Resolver) -> io::Result<()> {
+        Err(unsupported("Sync requires the git storage backend"))
+    }
+}
+
+/// Whether data files are written as indented JSON.
+static PRETTY_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Turns indented JSON data files on or off.
+pub fn set_pretty_json(pretty: bool) {
+    PRETTY_JSON.store(pretty, Ordering::Relaxed);
+}
+
+/// Data files whose last write failed, with the contents and message of
+/// that write, so it can be retried by `flush_pending`.
+static PENDING: Mutex<BTreeMap<String, (String, String)>> = Mutex::new(BTreeMap::new());
+
+/// Serializes `value` as JSON straight into the data file `name`. If that
+/// fails, the write is kept to be retried by `flush_pending`.
+pub fn save_json<T: Serialize>(
+    storage: &dyn Storage,
+    name: &str,
+    value: &T,
+    message: &str,
+) -> io::Result<()> {
+    let _writing = shutdown::hold();
+    let started = Instant::now();
+    let result = storage.write_with(name, message, &mut |writer| {
+        if PRETTY_JSON.load(Ordering::Relaxed) {
+            Ok(serde_json::to_writer_pretty(writer, value)?)
+        } else {
+            Ok(serde_json::to_writer(writer, value)?)
+        }
+    });
+    metrics::record_save(started.elapsed(), result.is_ok());
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    match (&result, to_json(value)) {
+        (Ok(()), _) => {
+            pending.remove(name);
+        }
+        (Err(_), Ok(contents)) => {
+            pending.insert(name.to_string(), (contents, message.to_string()));
+        }
+        (Err(_), Err(_)) => {}
+    }
+    result
+}
+
+/// Retries the writes that failed, returning how many still fail. The
+/// caller must hold `shutdown::hold`.
+pub fn flush_pending(storage: &dyn Storage) -> usize {
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    pending.retain(
+        |name, (contents, message)| match storage.write(name, contents, message) {
+            Ok(()) => {
+                log::info(&format!("Saved {} on retry ({})", name, message));
+                false
+            }
+            Err(e) => {
+                log::error(&format!("Failed to save {} again: {}", name, e));
+                true
+            }
+        },
+    );
+    pending.len()
+}
+
+/// Serializes `value` the way data files are written.
+pub fn to_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    if PRETTY_JSON.load(Ordering::Relaxed) {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+/// Deserializes the data file `name` while reading it, or returns `None` if
+/// it doesn't exist yet.
+pub fn load_json<T: DeserializeOwned>(storage: &dyn Storage, name: &str) -> io::Result<Option<T>> {
+    match storage.reader(name)? {
+        Some(reader) => Ok(Some(serde_json::from_reader(reader)?)),
+        None => Ok(None),
+    }
+}
+
+fn in_memory_reader(contents: String) -> Box<dyn BufRead> {
+    Box::new(Cursor::new(contents.into_bytes()))
+}
+
+fn in_memory_contents(
+    write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<String> {
+    let mut contents = Vec::new();
+    write(&mut contents)?;
+    String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Plain data files, JSON unless configured otherwise, in the working
+/// directory or the one given with `in_dir`.
+pub struct FileStorage {
+    dir: PathBuf,
+    /// Format of newly created files. Existing files keep their format.
+    format: DataFormat,
+    /// Backups kept of each file: `tasks.json.bak` is the newest, then
+    /// `tasks.json.bak.1` and so on. 0 keeps none.
+    backups: u32,
+}
+
+impl Default for FileStorage {
+    fn default() -> Self {
+        Self::new(DataFormat::default())
+    }
+}
+
+impl FileStorage {
+    pub fn new(format: DataFormat) -> Self {
+        Self {
+            dir: PathBuf::new(),
+            format,
+            backups: 1,
+        }
+    }
+
+    /// Keeps the data files in `dir` instead of the working directory.
+    pub fn in_dir(mut self, dir: &Path) -> Self {
+        self.dir = dir.to_path_buf();
+        self
+    }
+
+    /// Keeps `count` backups of each data file.
+    pub fn keep_backups(mut self, count: u32) -> Self {
+        self.backups = count;
+        self
+    }
+
+    /// The format the data file `name` is stored in: that of the existing
+    /// file, or the configured one for a new file.
+    fn format_of(&self, name: &str) -> DataFormat {
+        let existing = [self.format]
+            .into_iter()
+            .chain(DataFormat::ALL)
+            .find(|format| self.path(*format, name).exists());
+        existing.unwrap_or(self.format)
+    }
+
+    /// Where the data file `name` is stored in `format`.
+    fn path(&self, format: DataFormat, name: &str) -> PathBuf {
+        self.dir.join(format.path(name))
+    }
+
+    /// Makes room for a new backup of `path` by shifting the older ones
+    /// along, dropping the oldest. Returns `false` when no backups are kept.
+    fn rotate_backups(&self, path: &Path) -> io::Result<bool> {
+        if self.backups == 0 {
+            return Ok(false);
+        }
+        for index in (1..self.backups).rev() {
+            let older = numbered_backup_path(path, index - 1);
+            if older.exists() {
+                fs::rename(older, numbered_backup_path(path, index))?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        let format = self.format_of(name);
+        let path = self.path(format, name);
+        log::debug(&format!("Reading {}", path.display()));
+        match fs::read_to_string(&path) {
+            Ok(contents) => format.decode(&contents).map(Some).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, name: &str, contents: &str, message: &str) -> io::Result<()> {
+        let format = self.format_of(name);
+        let path = self.path(format, name);
+        log::debug(&format!("Writing {} ({})", path.display(), message));
+        // Keep the previous version as a backup, unless it is itself damaged.
+        if let Ok(previous) = fs::read_to_string(&path) {
+            if format
+                .decode(&previous)
+                .is_ok_and(|json| serde_json::from_str::<serde_json::Value>(&json).is_ok())
+                && self.rotate_backups(&path)?
+            {
+                fs::write(numbered_backup_path(&path, 0), previous)?;
+            }
+        }
+        write_replacing(&path, |file| {
+            file.write_all(format.encode(contents)?.as_bytes())
+        })
+    }
+
+    fn reader(&self, name: &str) -> io::Result<Option<Box<dyn BufRead>>> {
+        if self.format_of(name) != DataFormat::Json {
+            return Ok(self.read(name)?.map(in_memory_reader));
+        }
+        let path = self.path(DataFormat::Json, name);
+        log::debug(&format!("Reading {}", path.display()));
+        match File::open(path) {
+            Ok(file) => Ok(Some(Box::new(BufReader::new(file)))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_with(
+        &self,
+        name: &str,
+        message: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.format_of(name) != DataFormat::Json {
+            return self.write(name, &in_memory_contents(write)?, message);
+        }
+        let path = self.path(DataFormat::Json, name);
+        log::debug(&format!("Writing {} ({})", path.display(), message));
+        // Keep the previous version as a backup, unless it is itself damaged.
+        if let Ok(previous) = File::open(&path) {
+            if serde_json::from_reader::<_, IgnoredAny>(BufReader::new(previous)).is_ok()
+                && self.rotate_backups(&path)?
+            {
+                fs::copy(&path, numbered_backup_path(&path, 0))?;
+            }
+        }
+        write_replacing(&path, |file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()
+        })
+    }
+
+    fn backup(&self, name: &str) -> io::Result<Option<String>> {
+        let format = self.format_of(name);
+        match fs::read_to_string(numbered_backup_path(&self.path(format, name), 0)) {
+            Ok(contents) => format.decode(&contents).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn convert(&self, names: &[&str], format: DataFormat) -> io::Result<()> {
+        for name in names {
+            let current = self.format_of(name);
+            let Some(json) = self.read(name)? else {
+                continue;
+            };
+            if current == format {
+                continue;
+            }
+            fs::write(self.path(format, name), format.encode(&json)?)?;
+            fs::remove_file(self.path(current, name))?;
+            log::info(&format!(
+                "Converted {} to {}",
+                current.path(name),
+                format.path(name)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// JSON files that are committed to a local git repository on every write.
+pub struct GitStorage {
+    /// The data files, at the top of the work tree.
+    files: FileStorage,
+    /// Name of the remote used by `sync`, e.g. `origin`.
+    remote: Option<String>,
+}
+
+impl GitStorage {
+    /// Opens the git repository of the data files in `files`, creating it
+    /// on first use.
+    pub fn open(files: FileStorage, remote: Option<String>) -> io::Result<Self> {
+        let storage = Self {
+            files: FileStorage {
+                format: DataFormat::Json,
+                ..files
+            },
+            remote,
+        };
+        if !storage.files.dir.join(GIT_DIR).exists() {
+            storage.git(&["init", "--quiet"])?;
+        }
+        Ok(storage)
+    }
+
+    fn git(&self, args: &[&str]) -> io::Result<Output> {
+        log::debug(&format!("Running git {}", args.join(" ")));
+        let mut command = Command::new("git");
+        if !self.files.dir.as_os_str().is_empty() {
+            command.current_dir(&self.files.dir);
+        }
+        let output = command
+            .arg(format!("--git-dir={}", GIT_DIR))
+            .arg("--work-tree=.")
+            .args(["-c", "user.name=todo", "-c", "user.email=todo@localhost"])
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(format!(
+                "git {}: {}",
+                args[0],
+                stderr.trim()
+            )));
+        }
+        Ok(output)
+    }
+}
+
+impl Storage for GitStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        self.files.read(name)
+    }
+
+    fn reader(&self, name: &str) -> io::Result<Option<Box<dyn BufRead>>> {
+        self.files.reader(name)
+    }
+
+    fn write(&self, name: &str, contents: &str, message: &str) -> io::Result<()> {
+        self.files.write(name, contents, message)?;
+        self.commit(name, message)
+    }
+
+    fn write_with(
+        &self,
+        name: &str,
+        message: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.files.write_with(name, message, write)?;
+        self.commit(name, message)
+    }
+
+    fn backup(&self, name: &str) -> io::Result<Option<String>> {
+        self.files.backup(name)
+    }
+
+    fn history(&self, pattern: &str) -> io::Result<Vec<String>> {
+        if self
+            .git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .is_err()
+        {
+            return Ok(Vec::new());
+        }
+        let output = self.git(&[
+            "log",
+            "--extended-regexp",
+            &format!("--grep={}", pattern),
+            "--date=format:%Y-%m-%d %H:%M",
+            "--pretty=format:%h %ad %s",
+        ])?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn sync(&self, resolve: &mut Resolver) -> io::Result<()> {
+        let remote = self
+            .remote
+            .as_deref()
+            .ok_or_else(|| unsupported("No git remote configured"))?;
+        let branch = self.git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let branch = String::from_utf8_lossy(&branch.stdout).trim().to_string();
+        self.git(&["fetch", "--quiet", remote])?;
+        let upstream = format!("{}/{}", remote, branch);
+        // The remote branch doesn't exist until the first push.
+        if self
+            .git(&["rev-parse", "--verify", "--quiet", &upstream])
+            .is_ok()
+        {
+            self.merge(&upstream, resolve)?;
+        }
+        self.git(&["push", "--quiet", remote, &branch])?;
+        Ok(())
+    }
+}
+
+impl GitStorage {
+    /// Merges `upstream` into the current branch. Data files changed on both
+    /// sides are merged field by field instead of line by line.
+    fn merge(&self, upstream: &str, resolve: &mut Resolver) -> io::Result<()> {
+        // Nothing to do when either side already contains the other.
+        if self
+            .git(&["merge-base", "--is-ancestor", upstream, "HEAD"])
+            .is_ok()
+        {
+            return Ok(());
+        }
+        if self
+            .git(&["merge-base", "--is-ancestor", "HEAD", upstream])
+            .is_ok()
+        {
+            self.git(&["merge", "--quiet", "--ff-only", upstream])?;
+            return Ok(());
+        }
+
+        let base = self.git(&["merge-base", "HEAD", upstream])?;
+        let base = String::from_utf8_lossy(&base.stdout).trim().to_string();
+        let changed = self.git(&["diff", "--name-only", &base, upstream])?;
+        let changed: Vec<String> = String::from_utf8_lossy(&changed.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect();
+
+        // Work out the merged files before touching the working tree.
+        let mut merged = Vec::new();
+        for name in changed {
+            let Some(theirs) = self.show(upstream, &name)? else {
+                continue;
+            };
+            let contents = match self.show("HEAD", &name)? {
+                Some(ours) if name.ends_with(".json") => {
+                    let base = self.show(&base, &name)?;
+                    merge::merge_file(&name, base.as_deref(), &ours, &theirs, resolve).map_err(
+                        |e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, e)),
+                    )?
+                }
+                Some(_) => continue,
+                None => theirs,
+            };
+            merged.push((name, contents));
+        }
+
+        // Record the merge with the local files, then replace them.
+        self.git(&[
+            "merge",
+            "--quiet",
+            "--no-ff",
+            "--no-commit",
+            "--strategy=ours",
+            upstream,
+        ])?;
+        for (name, contents) in &merged {
+            fs::write(self.files.dir.join(name), contents)?;
+            self.git(&["add", "--", name])?;
+        }
+        self.git(&["commit", "--quiet", "-m", &format!("Merge {}", upstream)])?;
+        Ok(())
+    }
+
+    /// Commits the data file `name` with `message`, adding a `Task-Id`
+    /// trailer for each task the change touched so `history` can find it.
+    fn commit(&self, name: &str, message: &str) -> io::Result<()> {
+        self.git(&["add", "--", name])?;
+        // Nothing to commit when the file didn't actually change.
+        if self.git(&["diff", "--cached", "--quiet"]).is_ok() {
+            return Ok(());
+        }
+        let trailers: Vec<String> = self
+            .changed_uuids(name)?
+            .iter()
+            .map(|uuid| format!("{}: {}", TASK_ID_TRAILER, uuid))
+            .collect();
+        let trailers = trailers.join("\n");
+        let mut args = vec!["commit", "--quiet", "-m", message];
+        if !trailers.is_empty() {
+            args.extend(["-m", &trailers]);
+        }
+        self.git(&args)?;
+        Ok(())
+    }
+
+    /// UUIDs of the entries of the data file `name` that were added,
+    /// changed or removed since the last commit.
+    fn changed_uuids(&self, name: &str) -> io::Result<BTreeSet<String>> {
+        let by_uuid = |contents: Option<&str>| {
+            let value: serde_json::Value = contents
+                .and_then(|contents| serde_json::from_str(contents).ok())
+                .unwrap_or_default();
+            let entries: Vec<serde_json::Value> = match value {
+                serde_json::Value::Array(entries) => entries,
+                serde_json::Value::Object(entries) => entries.into_iter().map(|(_, v)| v).collect(),
+                _ => Vec::new(),
+            };
+            entries
+                .into_iter()
+                .filter_map(|entry| Some((entry.get("uuid")?.as_str()?.to_string(), entry)))
+                .collect::<BTreeMap<String, serde_json::Value>>()
+        };
+        let before = by_uuid(self.show("HEAD", name)?.as_deref());
+        let after = by_uuid(
+            fs::read_to_string(self.files.dir.join(name))
+                .ok()
+                .as_deref(),
+        );
+        Ok(before
+            .keys()
+            .chain(after.keys())
+            .filter(|uuid| before.get(*uuid) != after.get(*uuid))
+            .cloned()
+            .collect())
+    }
+
+    /// Reads a file as of `revision`, returning `None` if it didn't exist.
+    fn show(&self, revision: &str, name: &str) -> io::Result<Option<String>> {
+        match self.git(&["show", &format!("{}:{}", revision, name)]) {
+            Ok(output) => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Writes the file at `path` through a temporary file that then replaces it,
+/// so the file is never left half-written if the app is stopped meanwhile.
+fn write_replacing(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    let temporary = with_suffix(path, ".tmp");
+    let mut file = File::create(&temporary)?;
+    write(&mut file)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Name of the backup kept of a data file.
+fn backup_name(name: &str) -> String {
+    format!("{}.bak", name)
+}
+
+/// The name of the `index`th newest backup of `name`, from 0.
+pub fn numbered_backup_name(name: &str, index: u32) -> String {
+    match index {
+        0 => backup_name(name),
+        _ => format!("{}.bak.{}", name, index),
+    }
+}
+
+/// The path of the `index`th newest backup of the data file at `path`.
+fn numbered_backup_path(path: &Path, index: u32) -> PathBuf {
+    match index {
+        0 => with_suffix(path, ".bak"),
+        _ => with_suffix(path, &format!(".bak.{}", index)),
+    }
+}
+
+/// `path` with `suffix` added to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn unsupported(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::{Conflict, Side};
+    use crate::testing;
+    use crate::{Task, TASKS_FILE};
+    use serde_json::{json, Value};
+
+    fn round_trip(format: DataFormat) {
+        let dir = testing::temp_dir(&format!("storage-{:?}", format));
+        let storage = FileStorage::new(format).in_dir(&dir);
+        let tasks = BTreeMap::from([(1, testing::awkward_task())]);
+        save_json(&storage, TASKS_FILE, &tasks, "Save").unwrap();
+
+        assert!(dir.join(format.path(TASKS_FILE)).exists());
+        let loaded: BTreeMap<u32, Task> = load_json(&storage, TASKS_FILE).unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&tasks).unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_files_read_back_what_was_saved() {
+        round_trip(DataFormat::Json);
+    }
+
+    #[test]
+    fn yaml_files_read_back_what_was_saved() {
+        round_trip(DataFormat::Yaml);
+    }
+
+    #[test]
+    fn toml_files_read_back_what_was_saved() {
+        round_trip(DataFormat::Toml);
+    }
+
+    #[test]
+    fn missing_files_load_as_none() {
+        let dir = testing::temp_dir("storage-missing");
+        for format in DataFormat::ALL {
+            let storage = FileStorage::new(format).in_dir(&dir);
+            let loaded: Option<BTreeMap<u32, Task>> = load_json(&storage, TASKS_FILE).unwrap();
+            assert!(loaded.is_none());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn converting_keeps_the_data_in_every_format() {
+        let dir = testing::temp_dir("storage-convert");
+        let storage = FileStorage::new(DataFormat::Json).in_dir(&dir);
+        let tasks = BTreeMap::from([(1, testing::awkward_task())]);
+        save_json(&storage, TASKS_FILE, &tasks, "Save").unwrap();
+
+        // TOML leaves out null fields, which read back as their defaults.
+        for format in [DataFormat::Yaml, DataFormat::Toml, DataFormat::Json] {
+            storage.convert(&[TASKS_FILE], format).unwrap();
+            assert!(dir.join(format.path(TASKS_FILE)).exists());
+            let loaded: BTreeMap<u32, Task> = load_json(&storage, TASKS_FILE).unwrap().unwrap();
+            assert_eq!(
+                serde_json::to_value(&loaded).unwrap(),
+                serde_json::to_value(&tasks).unwrap(),
+                "{:?}",
+                format
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saving_again_keeps_a_backup_of_the_previous_version() {
+        let dir = testing::temp_dir("storage-backup");
+        for format in DataFormat::ALL {
+            let storage = FileStorage::new(format).in_dir(&dir).keep_backups(2);
+            let name = format!("{:?}.json", format).to_lowercase();
+            for version in 1..=3 {
+                save_json(
+                    &storage,
+                    &name,
+                    &BTreeMap::from([("version", version)]),
+                    "Save",
+                )
+                .unwrap();
+            }
+
+            let backup: serde_json::Value =
+                serde_json::from_str(&storage.backup(&name).unwrap().unwrap()).unwrap();
+            assert_eq!(backup["version"], 2, "{:?}", format);
+            assert!(numbered_backup_path(&dir.join(format.path(&name)), 1).exists());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// A device with its own clone of the data repository in `root/name`,
+    /// syncing with the bare repository `root/remote.git`.
+    fn device(root: &Path, name: &str) -> GitStorage {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let storage = GitStorage::open(
+            FileStorage::new(DataFormat::Json).in_dir(&dir),
+            Some("origin".to_string()),
+        )
+        .unwrap();
+        let remote = root.join("remote.git");
+        storage
+            .git(&["remote", "add", "origin", remote.to_str().unwrap()])
+            .unwrap();
+        storage.git(&["fetch", "--quiet", "origin"]).unwrap();
+        if storage
+            .git(&["rev-parse", "--verify", "--quiet", "origin/main"])
+            .is_ok()
+        {
+            storage
+                .git(&["checkout", "--quiet", "-B", "main", "origin/main"])
+                .unwrap();
+        } else {
+            storage.git(&["checkout", "--quiet", "-b", "main"]).unwrap();
+        }
+        storage
+    }
+
+    fn edit(storage: &GitStorage, fields: Value) {
+        let mut tasks: Value =
+            serde_json::from_str(&storage.read(TASKS_FILE).unwrap().unwrap()).unwrap();
+        for (key, value) in fields.as_object().unwrap() {
+            tasks["1"][key] = value.clone();
+        }
+        storage
+            .write(TASKS_FILE, &tasks.to_string(), "Edit task")
+            .unwrap();
+    }
+
+    fn sync(storage: &GitStorage, prefer: Option<Side>) {
+        storage.sync(&mut |_: &Conflict| prefer).unwrap();
+    }
+
+    fn tasks(storage: &GitStorage) -> Value {
+        serde_json::from_str(&storage.read(TASKS_FILE).unwrap().unwrap()).unwrap()
+    }
+
+    /// Two devices that share a task through the remote.
+    fn two_devices(name: &str) -> (PathBuf, GitStorage, GitStorage) {
+        let root = testing::temp_dir(name);
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(root.join("remote.git"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let laptop = device(&root, "laptop");
+        let task = json!({"1": {"id": 1, "uuid": "u1", "title": "Buy milk", "completed": false}});
+        laptop
+            .write(TASKS_FILE, &task.to_string(), "Add task")
+            .unwrap();
+        sync(&laptop, None);
+        let phone = device(&root, "phone");
+        (root, laptop, phone)
+    }
+
+    #[test]
+    fn syncing_merges_edits_to_different_fields() {
+        let (root, laptop, phone) = two_devices("git-fields");
+        edit(&laptop, json!({"title": "Buy oat milk"}));
+        edit(&phone, json!({"completed": true}));
+        sync(&laptop, None);
+        sync(&phone, None);
+        sync(&laptop, None);
+
+        for device in [&laptop, &phone] {
+            let tasks = tasks(device);
+            assert_eq!(tasks["1"]["title"], "Buy oat milk");
+            assert_eq!(tasks["1"]["completed"], true);
+            assert_eq!(tasks.as_object().unwrap().len(), 1);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn syncing_settles_edits_to_the_same_field_by_the_preferred_side() {
+        let (root, laptop, phone) = two_devices("git-prefer");
+        edit(&laptop, json!({"title": "Buy oat milk"}));
+        edit(&phone, json!({"title": "Buy soy milk"}));
+        sync(&laptop, None);
+        sync(&phone, Some(Side::Remote));
+        assert_eq!(tasks(&phone)["1"]["title"], "Buy oat milk");
+
+        edit(&laptop, json!({"title": "Buy rice milk"}));
+        edit(&phone, json!({"title": "Buy goat milk"}));
+        sync(&phone, None);
+        sync(&laptop, Some(Side::Local));
+        sync(&phone, None);
+        for device in [&laptop, &phone] {
+            assert_eq!(tasks(device)["1"]["title"], "Buy rice milk");
+            assert_eq!(tasks(device).as_object().unwrap().len(), 1);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn syncing_without_an_answer_keeps_both_versions() {
+        let (root, laptop, phone) = two_devices("git-copy");
+        edit(&laptop, json!({"title": "Buy oat milk"}));
+        edit(&phone, json!({"title": "Buy soy milk"}));
+        sync(&laptop, None);
+        sync(&phone, None);
+
+        let tasks = tasks(&phone);
+        assert_eq!(tasks["1"]["title"], "Buy soy milk");
+        assert_eq!(tasks["2"]["title"], "Buy oat milk (conflict)");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn syncing_brings_back_a_task_deleted_here_but_edited_remotely() {
+        let (root, laptop, phone) = two_devices("git-delete");
+        edit(&laptop, json!({"completed": true}));
+        phone.write(TASKS_FILE, "{}", "Delete task").unwrap();
+        sync(&laptop, None);
+        sync(&phone, None);
+
+        assert_eq!(tasks(&phone)["1"]["completed"], true);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+//! Suggesting a project and tags for a new task from the user's tasks with
+//! similar titles, with the `suggestions` setting on. Titles are compared
+//! word by word: every word a past task shares with the new title counts
+//! towards its project and tags, and the ones with the most are offered.
+//!
+//! A suggestion is picked with a single key press: its number, `a` for all
+//! of them, or anything else for none.
+
+use crate::i18n::tr;
+use crate::terminal::{self, Key, RawMode};
+use crate::TodoApp;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+/// Words shorter than this, like "a" or "to", don't make titles similar.
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Most tags suggested at once.
+const MAX_TAGS: usize = 3;
+
+/// A project and tags for a new task.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Suggestion {
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none() && self.tags.is_empty()
+    }
+
+    /// The suggestions as `+project` and `@tag`, in the order they're
+    /// numbered.
+    fn labels(&self) -> Vec<String> {
+        self.project
+            .iter()
+            .map(|project| format!("+{}", project))
+            .chain(self.tags.iter().map(|tag| format!("@{}", tag)))
+            .collect()
+    }
+
+    /// Only the suggestion numbered `number`, counting from 1.
+    fn only(&self, number: usize) -> Self {
+        let project_count = usize::from(self.project.is_some());
+        if number <= project_count {
+            return Self {
+                project: self.project.clone(),
+                tags: Vec::new(),
+            };
+        }
+        Self {
+            project: None,
+            tags: self
+                .tags
+                .get(number - project_count - 1)
+                .cloned()
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// The distinct words of a title that count when comparing titles.
+fn words(title: &str) -> BTreeSet<String> {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The names with the highest scores, best first, ties by name.
+fn best(scores: HashMap<&str, usize>, count: usize) -> Vec<String> {
+    let mut scores: Vec<(&str, usize)> = scores.into_iter().collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    scores
+        .into_iter()
+        .take(count)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+impl TodoApp {
+    /// The project and tags most used by the current user's tasks with
+    /// titles like `title`.
+    pub fn suggest(&self, title: &str) -> Suggestion {
+        let Some(user_id) = &self.current_user else {
+            return Suggestion::default();
+        };
+        let wanted = words(title);
+        let mut projects = HashMap::new();
+        let mut tags = HashMap::new();
+        for task in self.own_tasks(user_id) {
+            let shared = words(&task.title).intersection(&wanted).count();
+            if shared == 0 {
+                continue;
+            }
+            if let Some(project) = &task.project {
+                *projects.entry(project.as_str()).or_default() += shared;
+            }
+            for tag in &task.tags {
+                *tags.entry(tag.as_str()).or_default() += shared;
+            }
+        }
+        Suggestion {
+            project: best(projects, 1).pop(),
+            tags: best(tags, MAX_TAGS),
+        }
+    }
+
+    /// Adds a task for the current user in the picked project and with the
+    /// picked tags, which bring their defaults along.
+    pub fn add_suggested_task(
+        &mut self,
+        title: &str,
+        description: &str,
+        due_date: Option<DateTime<Utc>>,
+        picked: Suggestion,
+    ) -> Result<u32, &'static str> {
+        let mut task = self.new_task(title, description, due_date)?;
+        task.project = picked.project;
+        task.tags.extend(picked.tags);
+        self.apply_defaults(&mut task);
+        self.create_task(task)
+    }
+}
+
+/// Offers the suggestions for a task titled `title` if the user turned them
+/// on, returning the ones picked.
+pub fn offer(app: &TodoApp, title: &str) -> io::Result<Suggestion> {
+    let enabled = app.settings().is_ok_and(|settings| settings.suggestions);
+    let suggestion = if enabled {
+        app.suggest(title)
+    } else {
+        Suggestion::default()
+    };
+    if suggestion.is_empty() {
+        return Ok(suggestion);
+    }
+    let labels = suggestion.labels();
+    let choices: Vec<String> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| format!("[{}] {}", i + 1, label))
+        .collect();
+    println!("{} {}", tr("Suggested:"), choices.join("  "));
+    let prompt = tr("Number to pick one, a for all, any other key for none: ");
+
+    let key = match terminal::is_interactive().then(RawMode::enable).flatten() {
+        Some(_raw) => {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+            let key = terminal::read_key(&mut io::stdin().lock())?;
+            println!();
+            match key {
+                Key::Char(c) => Some(c),
+                _ => None,
+            }
+        }
+        None => crate::prompt_input(prompt)?.chars().next(),
+    };
+    Ok(match key {
+        Some('a' | 'A') => suggestion,
+        Some(c) => match c.to_digit(10).map(|n| n as usize) {
+            Some(n @ 1..) if n <= labels.len() => suggestion.only(n),
+            _ => Suggestion::default(),
+        },
+        None => Suggestion::default(),
+    })
+}
@@ -0,0 +1,86 @@
+//! Renaming a tag, or merging one into another, on every task of the current
+//! list at once. The tag's defaults move along with it. Either every task is
+//! changed or none is.
+
+use crate::{Task, TodoApp};
+
+/// A tag as typed, with or without its `@`, in the lowercase tags are
+/// stored in.
+pub fn parse_tag(tag: &str) -> Result<String, &'static str> {
+    let tag = tag.strip_prefix('@').unwrap_or(tag).to_lowercase();
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err("Tags can't be empty or contain spaces");
+    }
+    Ok(tag)
+}
+
+impl TodoApp {
+    /// Renames a tag on the tasks of the current list. Returns how many
+    /// tasks changed. The new name mustn't be in use; merge the tags
+    /// instead.
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize, &'static str> {
+        let (old, new) = (parse_tag(old)?, parse_tag(new)?);
+        if self.tagged(&new)?.next().is_some() {
+            return Err("The new tag is already in use, merge the tags instead");
+        }
+        self.retag(&old, &new, "Rename")
+    }
+
+    /// Replaces tag `from` by `into` on the tasks of the current list, so
+    /// tasks that had either have `into`. Returns how many tasks changed.
+    pub fn merge_tags(&mut self, from: &str, into: &str) -> Result<usize, &'static str> {
+        let (from, into) = (parse_tag(from)?, parse_tag(into)?);
+        self.retag(&from, &into, "Merge")
+    }
+
+    /// The tasks of the current list with `tag`.
+    fn tagged<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> Result<impl Iterator<Item = &'a Task> + 'a, &'static str> {
+        Ok(self
+            .list_tasks()?
+            .into_iter()
+            .filter(move |task| task.tags.contains(tag)))
+    }
+
+    fn retag(&mut self, from: &str, into: &str, verb: &str) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if from == into {
+            return Err("The tags are the same");
+        }
+        let tasks: Vec<Task> = self.tagged(from)?.cloned().collect();
+        if tasks.is_empty() {
+            return Err("No tasks have this tag");
+        }
+        if !tasks
+            .iter()
+            .all(|task| self.workspaces.can_edit(task, &user_id))
+        {
+            return Err("Not authorized to modify this task");
+        }
+
+        let changed = tasks.len();
+        let mut transaction = self.begin();
+        for mut task in tasks {
+            task.tags.remove(from);
+            task.tags.insert(into.to_string());
+            task.touch();
+            transaction.insert_task(task);
+        }
+        let message = format!("{} tag @{} into @{} on {} tasks", verb, from, into, changed);
+        transaction.commit(&message)?;
+
+        // The defaults of `into` win when both tags have some.
+        if let Some(user_defaults) = self.defaults.get_mut(&user_id) {
+            if let Some(defaults) = user_defaults.tags.remove(from) {
+                user_defaults
+                    .tags
+                    .entry(into.to_string())
+                    .or_insert(defaults);
+                let _ = self.save_defaults(&message);
+            }
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,168 @@
+//! Reading and writing of plain (ustar) tar archives, enough for the
+//! account archives: regular files only, with names under 100 bytes.
+
+use std::io::{self, ErrorKind};
+
+const BLOCK: usize = 512;
+
+/// Builds a tar archive from `(name, contents)` pairs.
+pub fn write(files: &[(String, Vec<u8>)], mtime: i64) -> io::Result<Vec<u8>> {
+    let mut archive = Vec::new();
+    for (name, contents) in files {
+        if name.len() >= 100 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("File name too long: {}", name),
+            ));
+        }
+        let mut header = [0u8; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        put_octal(&mut header[100..108], 0o644);
+        put_octal(&mut header[108..116], 0);
+        put_octal(&mut header[116..124], 0);
+        put_octal(&mut header[124..136], contents.len() as u64);
+        put_octal(&mut header[136..148], mtime.max(0) as u64);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // The checksum is computed with its own field filled with spaces.
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        put_octal(&mut header[148..155], u64::from(checksum));
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(contents);
+        archive.resize(archive.len().next_multiple_of(BLOCK), 0);
+    }
+    // Two empty blocks mark the end of the archive.
+    archive.resize(archive.len() + 2 * BLOCK, 0);
+    Ok(archive)
+}
+
+/// Extracts the regular files of a tar archive as `(name, contents)` pairs.
+pub fn read(archive: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = archive.get(offset..offset + BLOCK) {
+        if header.iter().all(|&byte| byte == 0) {
+            return Ok(files);
+        }
+        let stored: u32 = parse_octal(&header[148..156])? as u32;
+        let checksum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| {
+                if (148..156).contains(&i) {
+                    32
+                } else {
+                    u32::from(byte)
+                }
+            })
+            .sum();
+        if stored != checksum {
+            return Err(invalid("Damaged archive header"));
+        }
+
+        let name_len = header[..100]
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(100);
+        let name = String::from_utf8_lossy(&header[..name_len]).into_owned();
+        if !is_safe_name(&name) {
+            return Err(invalid(&format!("Unsafe file name in archive: {}", name)));
+        }
+        let size = parse_octal(&header[124..136])? as usize;
+        let start = offset + BLOCK;
+        let contents = archive
+            .get(start..start + size)
+            .ok_or_else(|| invalid("Truncated archive"))?;
+        // Directories and other special entries are skipped.
+        if matches!(header[156], b'0' | 0) {
+            files.push((name, contents.to_vec()));
+        }
+        offset = start + size.next_multiple_of(BLOCK);
+    }
+    Err(invalid("Truncated archive"))
+}
+
+/// Whether `name` stays inside the directory it would be extracted to: a
+/// relative path without `..` components.
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['/', '\\'])
+        && name.get(1..2) != Some(":")
+        && !name.split(['/', '\\']).any(|part| part == "..")
+}
+
+/// Writes `value` as zero-padded octal followed by a NUL.
+fn put_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn parse_octal(field: &[u8]) -> io::Result<u64> {
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    u64::from_str_radix(text, 8).map_err(|_| invalid("Damaged archive header"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<(String, Vec<u8>)> {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    format!("contents of {}", name).into_bytes(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let mut files = files(&["manifest.json", "tasks.json", "dir/nested.json", "..json"]);
+        files.push(("empty.json".to_string(), Vec::new()));
+        files.push(("block.bin".to_string(), vec![7; BLOCK]));
+        let archive = write(&files, 1_700_000_000).unwrap();
+
+        assert_eq!(archive.len() % BLOCK, 0);
+        assert_eq!(read(&archive).unwrap(), files);
+    }
+
+    #[test]
+    fn refuses_names_leading_out_of_the_directory() {
+        for name in [
+            "../tasks.json",
+            "data/../../tasks.json",
+            "..",
+            "/etc/passwd",
+            "\\windows\\system.ini",
+            "..\\tasks.json",
+            "C:\\tasks.json",
+        ] {
+            let archive = write(&files(&["manifest.json", name]), 0).unwrap();
+            let error = read(&archive).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{}", name);
+            assert!(error.to_string().contains("Unsafe"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn refuses_damaged_and_truncated_archives() {
+        let archive = write(&files(&["tasks.json"]), 0).unwrap();
+        let mut damaged = archive.clone();
+        damaged[0] = b'x';
+        assert!(read(&damaged).is_err());
+        assert!(read(&archive[..BLOCK + 4]).is_err());
+        assert!(read(&archive[..BLOCK * 2]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Raw keyboard input for the interactive prompts.
+//!
+//! The terminal is switched to unbuffered, unechoed input with `stty`, so
+//! this only works when stdin and stdout are terminals and `stty` exists.
+//! Callers fall back to line input otherwise.
+
+use crate::lock;
+use std::io::{self, IsTerminal, Read};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+const ESC: u8 = 0x1b;
+
+/// Whether both stdin and stdout are terminals.
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Terminal settings to go back to while raw input is on.
+static SAVED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Unbuffered, unechoed input until dropped.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Switches the terminal to raw input, or returns `None` if it can't.
+    pub fn enable() -> Option<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        *SAVED.lock().unwrap_or_else(PoisonError::into_inner) = Some(saved.clone());
+        stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+        Some(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+        *SAVED.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// Turns raw input off if it's on, for when the app exits without
+/// returning to the code that turned it on.
+pub fn restore() {
+    if let Some(saved) = SAVED.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        let _ = stty(&[&saved]);
+    }
+}
+
+/// Runs `stty` on the terminal attached to stdin, returning its output.
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A control key, given as its lowercase letter: `Ctrl('c')` for Ctrl-C.
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Escape,
+}
+
+/// Reads one key press, decoding escape sequences and UTF-8. Keys that
+/// aren't recognised are skipped.
+pub fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    loop {
+        let byte = read_byte(input)?;
+        lock::record_input();
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            0x7f | 0x08 => Key::Backspace,
+            ESC => match read_escape(input)? {
+                Some(key) => key,
+                None => continue,
+            },
+            byte @ 0x01..=0x1a => Key::Ctrl(char::from(b'a' + byte - 1)),
+            byte if byte >= 0x80 => match read_utf8(input, byte)? {
+                Some(c) => Key::Char(c),
+                None => continue,
+            },
+            byte if byte >= b' ' => Key::Char(char::from(byte)),
+            _ => continue,
+        };
+        return Ok(key);
+    }
+}
+
+/// Decodes what follows an ESC. Only the sequences terminals commonly send
+/// for arrows, Home, End and Delete are recognised.
+fn read_escape(input: &mut impl Read) -> io::Result<Option<Key>> {
+    match read_byte(input)? {
+        b'[' | b'O' => {}
+        ESC => return Ok(Some(Key::Escape)),
+        _ => return Ok(None),
+    }
+    let mut code = read_byte(input)?;
+    let mut number = 0u32;
+    while code.is_ascii_digit() || code == b';' {
+        if code.is_ascii_digit() {
+            number = number * 10 + u32::from(code - b'0');
+        }
+        code = read_byte(input)?;
+    }
+    Ok(match (code, number) {
+        (b'A', _) => Some(Key::Up),
+        (b'B', _) => Some(Key::Down),
+        (b'C', _) => Some(Key::Right),
+        (b'D', _) => Some(Key::Left),
+        (b'H', _) | (b'~', 1 | 7) => Some(Key::Home),
+        (b'F', _) | (b'~', 4 | 8) => Some(Key::End),
+        (b'~', 3) => Some(Key::Delete),
+        _ => None,
+    })
+}
+
+/// Reads the rest of a multi-byte character starting with `first`.
+fn read_utf8(input: &mut impl Read, first: u8) -> io::Result<Option<char>> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(None),
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        bytes.push(read_byte(input)?);
+    }
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|text| text.chars().next()))
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
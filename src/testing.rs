@@ -0,0 +1,137 @@
+//! Helpers for the tests: an app whose data files live in memory.
+
+use crate::reminders::Reminder;
+use crate::storage::Storage;
+use crate::timesheet::TimeEntry;
+use crate::{Task, TodoApp};
+use chrono::{TimeZone, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Data files kept in memory. Clones share the files, so a test can look
+/// at what an app saved or open a second app on the same data.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    files: Arc<Mutex<HashMap<String, String>>>,
+    /// Files whose writes fail.
+    failing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl MemoryStorage {
+    pub fn put(&self, name: &str, contents: &str) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), contents.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.files.lock().unwrap().get(name).cloned()
+    }
+
+    /// Makes writes to `name` fail from now on.
+    pub fn fail_writes(&self, name: &str) {
+        self.failing.lock().unwrap().insert(name.to_string());
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> io::Result<Option<String>> {
+        Ok(self.get(name))
+    }
+
+    fn write(&self, name: &str, contents: &str, _message: &str) -> io::Result<()> {
+        if self.failing.lock().unwrap().contains(name) {
+            return Err(io::Error::other(format!("Writes to {} fail", name)));
+        }
+        self.put(name, contents);
+        Ok(())
+    }
+
+    fn backup(&self, _name: &str) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// An app on empty storage.
+pub fn app() -> TodoApp {
+    app_on(&MemoryStorage::default())
+}
+
+/// An app on `storage`, which the test keeps to look at or break.
+pub fn app_on(storage: &MemoryStorage) -> TodoApp {
+    TodoApp::new(Box::new(storage.clone()))
+}
+
+/// An app on empty storage in dry-run mode, so nothing is written outside
+/// of memory: no saves, activity or hooks.
+pub fn dry_run_app() -> TodoApp {
+    let mut app = app();
+    app.set_dry_run();
+    app
+}
+
+/// Registers the users, all with the same password.
+pub fn register(app: &mut TodoApp, usernames: &[&str]) {
+    for username in usernames {
+        app.register(username, PASSWORD).unwrap();
+    }
+}
+
+/// Logs out whoever is logged in and logs in as `username`.
+pub fn login(app: &mut TodoApp, username: &str) {
+    app.logout();
+    app.login(username, PASSWORD).unwrap();
+}
+
+pub const PASSWORD: &str = "correct horse";
+
+/// An empty directory of its own for the test `name`, under the system's
+/// temporary directory.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lab3-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A task with text that data formats must quote or escape, nested and
+/// empty collections, and optional fields both set and unset.
+pub fn awkward_task() -> Task {
+    let mut app = dry_run_app();
+    register(&mut app, &["alice"]);
+    login(&mut app, "alice");
+    let due = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let mut task = app
+        .new_task(
+            "Say \"hi\" to O'Brien: #1 — café 🦀",
+            "line one\nline two\t'tabbed' \\ \"quoted\"\n\n  indented = true\n[not a table]\n- not a list",
+            Some(due),
+        )
+        .unwrap();
+    task.custom
+        .insert("client".to_string(), "Acme, \"Inc.\"".to_string());
+    task.custom.insert("empty".to_string(), String::new());
+    task.custom
+        .insert("ключ з пробілом".to_string(), "значення".to_string());
+    task.tags.insert("日本".to_string());
+    task.tags.insert("yes".to_string());
+    task.reminders.push(Reminder {
+        offset_secs: 3600,
+        notified: false,
+    });
+    task.time_entries.push(TimeEntry {
+        user: "alice".to_string(),
+        at: due,
+        duration_secs: 1800,
+        note: "null".to_string(),
+    });
+    task.project = Some("true".to_string());
+    task.icon = Some("🏠".to_string());
+    task.progress = Some(0);
+    task.estimate_secs = Some(-1);
+    task
+}
@@ -0,0 +1,332 @@
+//! Colours and symbols for task statuses, priorities and tags.
+//!
+//! Each colour scheme setting has a built-in theme. The `theme` section of
+//! the config and the theme file named by `theme_file` change parts of it;
+//! both are JSON of this shape:
+//!
+//! ```json
+//! {
+//!   "statuses": { "overdue": { "color": "red", "bold": true, "symbol": "!" } },
+//!   "priorities": { "urgent": { "color": "bright-red" } },
+//!   "tags": { "work": { "color": "blue", "symbol": "#" } }
+//! }
+//! ```
+//!
+//! Unknown keys, colours and statuses are rejected rather than ignored, so
+//! typos don't go unnoticed. The accessibility modes (see `accessibility`)
+//! replace the theme and its changes altogether.
+
+use crate::accessibility::{self, Accessibility};
+use crate::priority::Priority;
+use crate::settings::{ColorScheme, Role};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::sync::{Mutex, PoisonError};
+
+const RESET: &str = "\x1b[0m";
+
+/// Most characters in a symbol.
+const MAX_SYMBOL_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl Color {
+    /// The ANSI foreground colour code.
+    fn code(self) -> u8 {
+        match self {
+            Self::Black => 30,
+            Self::Red => 31,
+            Self::Green => 32,
+            Self::Yellow => 33,
+            Self::Blue => 34,
+            Self::Magenta => 35,
+            Self::Cyan => 36,
+            Self::White => 37,
+            Self::BrightBlack => 90,
+            Self::BrightRed => 91,
+            Self::BrightGreen => 92,
+            Self::BrightYellow => 93,
+            Self::BrightBlue => 94,
+            Self::BrightMagenta => 95,
+            Self::BrightCyan => 96,
+            Self::BrightWhite => 97,
+        }
+    }
+}
+
+/// How a piece of text is shown.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: bool,
+    /// Swaps the foreground and background colours.
+    pub reverse: bool,
+    /// Shown before the text, e.g. `!` for overdue tasks.
+    pub symbol: Option<String>,
+}
+
+impl Style {
+    fn new(color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..Self::default()
+        }
+    }
+
+    fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    /// `text` in this style. Colours are left out when output isn't a
+    /// terminal; the symbol is kept.
+    fn apply(&self, text: &str) -> String {
+        let text = match &self.symbol {
+            Some(symbol) => format!("{} {}", symbol, text),
+            None => text.to_string(),
+        };
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push(1);
+        }
+        if self.reverse {
+            codes.push(7);
+        }
+        codes.extend(self.color.map(Color::code));
+        if codes.is_empty() || !io::stdout().is_terminal() {
+            return text;
+        }
+        let codes: Vec<String> = codes.iter().map(u8::to_string).collect();
+        format!("\x1b[{}m{}{}", codes.join(";"), text, RESET)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Task statuses and other highlighted text, such as calendar days.
+    pub statuses: BTreeMap<Role, Style>,
+    pub priorities: BTreeMap<Priority, Style>,
+    /// Keyed by tag name, without the `@`.
+    pub tags: BTreeMap<String, Style>,
+}
+
+impl Theme {
+    /// The built-in theme of a colour scheme.
+    pub fn builtin(scheme: ColorScheme) -> Self {
+        let statuses = match scheme {
+            ColorScheme::None => Vec::new(),
+            ColorScheme::Dark => vec![
+                (Role::Completed, Style::new(Color::BrightGreen)),
+                (Role::Pending, Style::new(Color::BrightYellow)),
+                (Role::Overdue, Style::new(Color::BrightRed)),
+                (Role::LightDay, Style::new(Color::BrightCyan)),
+                (Role::BusyDay, Style::new(Color::BrightYellow)),
+                (Role::PackedDay, Style::new(Color::BrightRed).bold()),
+                (
+                    Role::Notice,
+                    Style::new(Color::BrightYellow).bold().reverse(),
+                ),
+                (Role::Match, Style::new(Color::BrightRed).bold()),
+            ],
+            ColorScheme::Light => vec![
+                (Role::Completed, Style::new(Color::Green)),
+                (Role::Pending, Style::new(Color::Blue)),
+                (Role::Overdue, Style::new(Color::Red)),
+                (Role::LightDay, Style::new(Color::Cyan)),
+                (Role::BusyDay, Style::new(Color::Yellow)),
+                (Role::PackedDay, Style::new(Color::Red).bold()),
+                (Role::Notice, Style::new(Color::Blue).bold().reverse()),
+                (Role::Match, Style::new(Color::Red).bold()),
+            ],
+            ColorScheme::Monochrome => vec![
+                (Role::Completed, Style::default().symbol("[x]")),
+                (Role::Pending, Style::default().symbol("[ ]")),
+                (Role::Overdue, Style::default().bold().symbol("!")),
+                (Role::PackedDay, Style::default().bold()),
+                (Role::Notice, Style::default().reverse()),
+                (Role::Match, Style::default().bold()),
+            ],
+        };
+        let priorities = match scheme {
+            ColorScheme::None => Vec::new(),
+            ColorScheme::Dark => vec![
+                (Priority::Urgent, Style::new(Color::BrightRed).bold()),
+                (Priority::High, Style::new(Color::BrightMagenta)),
+                (Priority::Low, Style::new(Color::BrightBlack)),
+            ],
+            ColorScheme::Light => vec![
+                (Priority::Urgent, Style::new(Color::Red).bold()),
+                (Priority::High, Style::new(Color::Magenta)),
+                (Priority::Low, Style::new(Color::BrightBlack)),
+            ],
+            ColorScheme::Monochrome => vec![
+                (Priority::Urgent, Style::default().bold().symbol("!!")),
+                (Priority::High, Style::default().symbol("!")),
+            ],
+        };
+        Self {
+            statuses: statuses.into_iter().collect(),
+            priorities: priorities.into_iter().collect(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// The theme of the symbols accessibility mode: the monochrome one,
+    /// with low priority marked too.
+    fn symbols() -> Self {
+        let mut theme = Self::builtin(ColorScheme::Monochrome);
+        theme
+            .priorities
+            .insert(Priority::Low, Style::default().symbol("v"));
+        theme
+    }
+
+    /// Replaces the styles that `other` sets.
+    pub fn merge(&mut self, other: &Theme) {
+        self.statuses.extend(other.statuses.clone());
+        self.priorities.extend(other.priorities.clone());
+        self.tags.extend(other.tags.clone());
+    }
+
+    /// Checks what the JSON format can't: that symbols are short and
+    /// printable.
+    pub fn validate(&self) -> Result<(), String> {
+        let styles = self
+            .statuses
+            .iter()
+            .map(|(role, style)| (format!("statuses.{}", role.name()), style))
+            .chain(
+                self.priorities
+                    .iter()
+                    .map(|(priority, style)| (format!("priorities.{}", priority.name()), style)),
+            )
+            .chain(
+                self.tags
+                    .iter()
+                    .map(|(tag, style)| (format!("tags.{}", tag), style)),
+            );
+        for (key, style) in styles {
+            let Some(symbol) = &style.symbol else {
+                continue;
+            };
+            if symbol.chars().count() > MAX_SYMBOL_LENGTH {
+                return Err(format!(
+                    "{}: symbols can be at most {} characters long",
+                    key, MAX_SYMBOL_LENGTH
+                ));
+            }
+            if symbol.is_empty() || symbol.chars().any(|c| c.is_control() || c.is_whitespace()) {
+                return Err(format!(
+                    "{}: symbols must be printable and without spaces",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads and checks a theme file.
+pub fn load_file(path: &str) -> Result<Theme, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let theme: Theme = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+    theme.validate().map_err(|e| format!("{}: {}", path, e))?;
+    Ok(theme)
+}
+
+/// Changes to the built-in themes, from the config and theme file.
+static CUSTOM: Mutex<Option<Theme>> = Mutex::new(None);
+
+/// The theme in use: the active colour scheme's with the custom changes.
+static ACTIVE: Mutex<Option<Theme>> = Mutex::new(None);
+
+/// Sets the changes made to every built-in theme: those in `file`, if
+/// given, then those in `config`. Takes effect with the next `activate`.
+pub fn customize(config: &Theme, file: Option<&str>) -> Result<(), String> {
+    config.validate().map_err(|e| format!("theme: {}", e))?;
+    let mut custom = match file {
+        Some(path) => load_file(path)?,
+        None => Theme::default(),
+    };
+    custom.merge(config);
+    *CUSTOM.lock().unwrap_or_else(PoisonError::into_inner) = Some(custom);
+    Ok(())
+}
+
+/// Switches to the theme of `scheme`, unless an accessibility mode is on.
+pub fn activate(scheme: ColorScheme) {
+    let theme = match accessibility::mode() {
+        Accessibility::Off => {
+            let mut theme = Theme::builtin(scheme);
+            if let Some(custom) = CUSTOM
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_ref()
+            {
+                theme.merge(custom);
+            }
+            theme
+        }
+        Accessibility::Symbols => Theme::symbols(),
+        Accessibility::ScreenReader => Theme::default(),
+    };
+    *ACTIVE.lock().unwrap_or_else(PoisonError::into_inner) = Some(theme);
+}
+
+/// `text` in the active theme's style picked by `style`, or as it is if
+/// the theme has none.
+fn paint_with(text: &str, style: impl FnOnce(&Theme) -> Option<&Style>) -> String {
+    let active = ACTIVE.lock().unwrap_or_else(PoisonError::into_inner);
+    match active.as_ref().and_then(style) {
+        Some(style) => style.apply(text),
+        None => text.to_string(),
+    }
+}
+
+/// `text` in the style of a status or other role.
+pub fn paint(text: &str, role: Role) -> String {
+    paint_with(text, |theme| theme.statuses.get(&role))
+}
+
+/// `text` in the style of a priority.
+pub fn paint_priority(text: &str, priority: Priority) -> String {
+    paint_with(text, |theme| theme.priorities.get(&priority))
+}
+
+/// `text` in the style of a tag.
+pub fn paint_tag(text: &str, tag: &str) -> String {
+    paint_with(text, |theme| theme.tags.get(tag))
+}
@@ -0,0 +1,171 @@
+//! Time tracking: time spent on a task is logged with `track`, and
+//! `timesheet` exports a user's logged time over a range of days as CSV for
+//! invoicing. Durations in the CSV are decimal hours, e.g. `1.50`, so
+//! spreadsheets can add them up.
+
+use crate::i18n::tr;
+use crate::schedule::local_date;
+use crate::{Task, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Marks the CSV as UTF-8 for Excel, which otherwise garbles non-ASCII text.
+const BYTE_ORDER_MARK: &str = "\u{feff}";
+
+/// Time a user spent on a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub user: String,
+    /// When the time was logged.
+    #[serde(with = "ts_seconds")]
+    pub at: DateTime<Utc>,
+    pub duration_secs: i64,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl TimeEntry {
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.duration_secs)
+    }
+}
+
+/// A line of a timesheet.
+pub struct Row<'a> {
+    pub task: &'a Task,
+    pub date: NaiveDate,
+    pub duration: Duration,
+    pub note: &'a str,
+}
+
+impl TodoApp {
+    /// Logs `duration` spent on a task by the current user.
+    pub fn log_time(
+        &mut self,
+        task_id: u32,
+        duration: Duration,
+        note: &str,
+    ) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let task = self.tasks.get_mut(&task_id).ok_or("Task not found")?;
+        if !self.workspaces.can_edit(task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        if duration <= Duration::zero() {
+            return Err("Invalid duration, expected e.g. 30m, 2h or 3 days");
+        }
+
+        task.time_entries.push(TimeEntry {
+            user: user_id,
+            at: Utc::now(),
+            duration_secs: duration.num_seconds(),
+            note: note.trim().to_string(),
+        });
+        task.touch();
+        self.save_tasks(&format!("Log time on task #{}", task_id))
+            .map_err(|_| "Failed to save tasks")?;
+        Ok(())
+    }
+
+    /// The time the current user logged from `from` to `to`, both included,
+    /// by date and then task.
+    pub fn timesheet(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Row<'_>>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        if from > to {
+            return Err("The start date is after the end date");
+        }
+        let mut rows: Vec<Row> = self
+            .tasks
+            .values()
+            .filter(|task| self.workspaces.can_access(task, user_id))
+            .flat_map(|task| {
+                task.time_entries
+                    .iter()
+                    .filter(|entry| &entry.user == user_id)
+                    .map(move |entry| Row {
+                        task,
+                        date: local_date(entry.at),
+                        duration: entry.duration(),
+                        note: &entry.note,
+                    })
+            })
+            .filter(|row| (from..=to).contains(&row.date))
+            .collect();
+        rows.sort_by_key(|row| (row.date, row.task.id));
+        Ok(rows)
+    }
+}
+
+/// Total time spent on a task by everyone.
+pub fn time_spent(task: &Task) -> Duration {
+    task.time_entries
+        .iter()
+        .map(TimeEntry::duration)
+        .fold(Duration::zero(), |total, duration| total + duration)
+}
+
+/// The timesheet as CSV: the entries, then subtotals per day and per
+/// project, then the total.
+pub fn render_csv(rows: &[Row]) -> String {
+    let mut by_day: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+    let mut by_project: BTreeMap<&str, Duration> = BTreeMap::new();
+    let mut total = Duration::zero();
+
+    let mut out = String::from(BYTE_ORDER_MARK);
+    push_line(&mut out, &["task", "project", "date", "duration", "notes"]);
+    for row in rows {
+        let task = format!("#{} {}", row.task.id, row.task.title);
+        let project = row.task.project.as_deref().unwrap_or_default();
+        let date = row.date.format("%Y-%m-%d").to_string();
+        push_line(
+            &mut out,
+            &[&task, project, &date, &hours(row.duration), row.note],
+        );
+        *by_day.entry(row.date).or_insert_with(Duration::zero) += row.duration;
+        *by_project.entry(project).or_insert_with(Duration::zero) += row.duration;
+        total += row.duration;
+    }
+
+    out.push_str("\r\n");
+    push_line(&mut out, &["date", "duration"]);
+    for (date, duration) in &by_day {
+        push_line(
+            &mut out,
+            &[&date.format("%Y-%m-%d").to_string(), &hours(*duration)],
+        );
+    }
+    out.push_str("\r\n");
+    push_line(&mut out, &["project", "duration"]);
+    for (project, duration) in &by_project {
+        let project = if project.is_empty() {
+            tr("No project")
+        } else {
+            project
+        };
+        push_line(&mut out, &[project, &hours(*duration)]);
+    }
+    out.push_str("\r\n");
+    push_line(&mut out, &["total", &hours(total)]);
+    out
+}
+
+/// A duration as decimal hours with two places.
+fn hours(duration: Duration) -> String {
+    format!("{:.2}", duration.num_seconds() as f64 / 3600.0)
+}
+
+/// Appends a CSV record, quoting fields that need it.
+fn push_line(out: &mut String, fields: &[&str]) {
+    let fields: Vec<String> = fields.iter().map(|field| quote(field)).collect();
+    out.push_str(&fields.join(","));
+    out.push_str("\r\n");
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
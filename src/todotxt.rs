@@ -0,0 +1,234 @@
+//! Import and export of the [todo.txt](https://github.com/todotxt/todo.txt)
+//! format, one task per line:
+//!
+//! ```text
+//! x 2024-03-02 2024-03-01 Pay rent +home @errands due:2024-03-05
+//! (A) 2024-03-01 Call the bank +home tag:money
+//! ```
+//!
+//! A leading `x` and date mark a completed task, `(A)` to `(Z)` the
+//! priority, then comes the creation date. `+project` and `@context` map to
+//! the task's project and context; further contexts become tags, and tags
+//! are written as `tag:name`. `due:YYYY-MM-DD` is the due date. Completed
+//! tasks keep their priority as `pri:A`, as todo.txt drops the `(A)`.
+//! Descriptions have no place in the format and aren't exported.
+
+use crate::contexts::parse_context;
+use crate::importing::{Decision, Resolution};
+use crate::log;
+use crate::priority::Priority;
+use crate::schedule::{local_date, start_of_day};
+use crate::validation;
+use crate::{Task, TodoApp};
+use chrono::{NaiveDate, Utc};
+use std::collections::BTreeSet;
+use std::fs;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A task as read from a todo.txt line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub title: String,
+    pub completed_on: Option<NaiveDate>,
+    pub completed: bool,
+    pub priority: Priority,
+    pub created_on: Option<NaiveDate>,
+    pub project: Option<String>,
+    pub context: Option<String>,
+    pub tags: BTreeSet<String>,
+    pub due: Option<NaiveDate>,
+}
+
+/// The todo.txt letter for a priority; normal priority has none.
+fn priority_letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::Urgent => Some('A'),
+        Priority::High => Some('B'),
+        Priority::Normal => None,
+        Priority::Low => Some('D'),
+    }
+}
+
+/// The priority of a todo.txt letter: A is urgent, B high, C normal and
+/// anything later low.
+fn letter_priority(letter: char) -> Priority {
+    match letter {
+        'A' => Priority::Urgent,
+        'B' => Priority::High,
+        'C' => Priority::Normal,
+        _ => Priority::Low,
+    }
+}
+
+/// Parses `(A)` to `(Z)`.
+fn parse_priority(word: &str) -> Option<Priority> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_uppercase() => Some(letter_priority(letter)),
+        _ => None,
+    }
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, DATE_FORMAT).ok()
+}
+
+/// Parses a todo.txt line, or returns `None` for a blank one.
+pub fn parse_line(line: &str) -> Option<Entry> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+
+    let completed = words.next_if_eq(&"x").is_some();
+    let completed_on = if completed {
+        words
+            .next_if(|word| parse_date(word).is_some())
+            .and_then(parse_date)
+    } else {
+        None
+    };
+    let mut priority = words
+        .next_if(|word| parse_priority(word).is_some())
+        .and_then(parse_priority)
+        .unwrap_or_default();
+    let created_on = words
+        .next_if(|word| parse_date(word).is_some())
+        .and_then(parse_date);
+
+    let mut title = Vec::new();
+    let mut project = None;
+    let mut context = None;
+    let mut tags = BTreeSet::new();
+    let mut due = None;
+    for word in words {
+        if let Some(name) = word.strip_prefix('+').filter(|_| project.is_none()) {
+            if !name.is_empty() {
+                project = Some(name.to_string());
+                continue;
+            }
+        } else if let Some(name) = word.strip_prefix('@') {
+            if let Ok(name) = parse_context(name) {
+                if context.is_none() {
+                    context = Some(name);
+                } else {
+                    tags.insert(name);
+                }
+                continue;
+            }
+        } else if let Some(tag) = word.strip_prefix("tag:").filter(|tag| !tag.is_empty()) {
+            tags.insert(tag.to_lowercase());
+            continue;
+        } else if let Some(date) = word.strip_prefix("due:").and_then(parse_date) {
+            due = Some(date);
+            continue;
+        } else if let Some(letter) = word.strip_prefix("pri:") {
+            if let Some(found) = parse_priority(&format!("({})", letter)) {
+                priority = found;
+                continue;
+            }
+        }
+        title.push(word);
+    }
+
+    Some(Entry {
+        title: title.join(" "),
+        completed_on,
+        completed,
+        priority,
+        created_on,
+        project,
+        context,
+        tags,
+        due,
+    })
+}
+
+/// `task` as a todo.txt line.
+pub fn format_line(task: &Task) -> String {
+    let mut words = Vec::new();
+    if task.completed {
+        words.push("x".to_string());
+        if let Some(completed_at) = task.completed_at {
+            words.push(local_date(completed_at).format(DATE_FORMAT).to_string());
+        }
+    } else if let Some(letter) = priority_letter(task.priority) {
+        words.push(format!("({})", letter));
+    }
+    words.push(local_date(task.created_at).format(DATE_FORMAT).to_string());
+    words.push(task.title.clone());
+    if let Some(project) = &task.project {
+        words.push(format!("+{}", project));
+    }
+    if let Some(context) = &task.context {
+        words.push(format!("@{}", context));
+    }
+    for tag in &task.tags {
+        words.push(format!("tag:{}", tag));
+    }
+    if let Some(due) = task.due_date {
+        words.push(format!("due:{}", local_date(due).format(DATE_FORMAT)));
+    }
+    if task.completed {
+        if let Some(letter) = priority_letter(task.priority) {
+            words.push(format!("pri:{}", letter));
+        }
+    }
+    words.join(" ")
+}
+
+impl TodoApp {
+    /// Writes the tasks in the current list to a todo.txt file at `path`.
+    /// Returns how many were written.
+    pub fn export_todotxt(&self, path: &str) -> Result<usize, &'static str> {
+        let tasks = self.list_tasks()?;
+        let mut out = String::new();
+        for task in &tasks {
+            out.push_str(&format_line(task));
+            out.push('\n');
+        }
+        fs::write(path, out)
+            .inspect_err(|e| log::error(&format!("Failed to write {}: {}", path, e)))
+            .map_err(|_| "Failed to write the file")?;
+        Ok(tasks.len())
+    }
+
+    /// Adds a task for each line of the todo.txt file at `path`, asking
+    /// `resolve` about those that already exist.
+    pub fn import_todotxt(
+        &mut self,
+        path: &str,
+        resolve: &mut dyn FnMut(&Task, &Task) -> Resolution,
+    ) -> Result<Vec<Decision>, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let text = fs::read_to_string(path).map_err(|_| "Failed to read the file")?;
+        let entries: Vec<Entry> = text.lines().filter_map(parse_line).collect();
+        for entry in &entries {
+            validation::title(&entry.title)?;
+        }
+        let mut tasks = Vec::new();
+        // todo.txt has no IDs; number the lines so they can be told apart.
+        for (line, entry) in (1..).zip(entries) {
+            let mut task = self.new_task(&entry.title, "", entry.due.map(start_of_day))?;
+            task.id = line;
+            task.priority = entry.priority;
+            task.project = entry.project;
+            task.context = entry.context;
+            task.tags = entry.tags;
+            if let Some(created_on) = entry.created_on {
+                task.created_at = start_of_day(created_on);
+            }
+            if entry.completed {
+                task.completed = true;
+                task.completed_at = Some(entry.completed_on.map_or_else(Utc::now, start_of_day));
+            }
+            tasks.push(task);
+        }
+        let mut transaction = self.begin();
+        let decisions = transaction.merge_imported(tasks, resolve)?;
+        let message = format!("Import {} tasks from todo.txt", decisions.len());
+        transaction.commit(&message)?;
+        log::info(&format!("{}: {} from {}", user_id, message, path));
+        Ok(decisions)
+    }
+}
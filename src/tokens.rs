@@ -0,0 +1,87 @@
+//! Bearer tokens for API clients. Only a hash of each token is stored, so a
+//! leaked data file doesn't give access to accounts.
+
+use crate::crypto;
+use crate::log;
+use crate::metrics;
+use crate::passwords;
+use crate::storage;
+use crate::TodoApp;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const TOKENS_FILE: &str = "tokens.json";
+
+/// An issued token, stored under the hash of the token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub username: String,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl TodoApp {
+    /// Checks the credentials and issues a new token for the account.
+    pub fn issue_token(&mut self, username: &str, password: &str) -> Result<String, &'static str> {
+        let username = &self.find_user(username).unwrap_or(username).to_string();
+        match self.users.get(username) {
+            Some(user) if passwords::verify(&user.password, password) => {}
+            _ => {
+                log::warn(&format!("Failed token request for {}", username));
+                metrics::record_auth_failure();
+                return Err("Invalid username or password");
+            }
+        }
+
+        let token = crypto::random_token().map_err(|_| crypto::NO_RANDOM)?;
+        self.tokens.insert(
+            hash(&token),
+            Token {
+                username: username.to_string(),
+                created_at: Utc::now(),
+            },
+        );
+        self.save_tokens(&format!("Issue token for user {}", username))
+            .map_err(|_| "Failed to save tokens")?;
+        log::info(&format!("Issued token for {}", username));
+        Ok(token)
+    }
+
+    /// The user a token was issued to, if the token is valid.
+    pub fn token_user(&self, token: &str) -> Option<&str> {
+        self.tokens
+            .get(&hash(token))
+            .map(|token| token.username.as_str())
+    }
+
+    /// Invalidates a token.
+    pub fn revoke_token(&mut self, token: &str) -> Result<(), &'static str> {
+        let revoked = self.tokens.remove(&hash(token)).ok_or("Invalid token")?;
+        self.save_tokens(&format!("Revoke token of user {}", revoked.username))
+            .map_err(|_| "Failed to save tokens")?;
+        Ok(())
+    }
+
+    /// Saves all tokens. `message` describes the change being saved.
+    pub fn save_tokens(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), TOKENS_FILE, &self.tokens, message)
+            .inspect_err(|e| log::error(&format!("Failed to save tokens ({}): {}", message, e)))
+    }
+
+    /// Loads all tokens. If the file doesn't exist, it's ignored.
+    pub fn load_tokens(&mut self) -> io::Result<()> {
+        if let Some(tokens) = storage::load_json(self.storage.as_ref(), TOKENS_FILE)? {
+            self.tokens = tokens;
+        }
+        Ok(())
+    }
+}
+
+/// The form a token is stored in.
+pub fn hash(token: &str) -> String {
+    crypto::to_hex(&crypto::sha256(token.as_bytes()))
+}
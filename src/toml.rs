@@ -0,0 +1,510 @@
+//! TOML for human-editable data files. Covers tables, arrays of tables,
+//! inline arrays and tables, strings, numbers and booleans. TOML has no
+//! null, so null fields are left out; dates are read back as strings.
+
+use serde_json::{Map, Number, Value};
+use std::io;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Writes a JSON object as a TOML document.
+pub fn encode(value: &Value) -> io::Result<String> {
+    let Value::Object(map) = value else {
+        return Err(invalid("TOML documents must be tables"));
+    };
+    let mut out = String::new();
+    write_table(map, &[], &mut out)?;
+    Ok(out)
+}
+
+fn write_table(map: &Map<String, Value>, path: &[String], out: &mut String) -> io::Result<()> {
+    // Plain values must come before any sub-table header.
+    for (key, value) in map {
+        if !is_table(value) && !is_table_array(value) && !value.is_null() {
+            out.push_str(&format!("{} = {}\n", key_text(key), inline(value)?));
+        }
+    }
+    for (key, value) in map {
+        let mut path = path.to_vec();
+        path.push(key_text(key));
+        match value {
+            Value::Object(table) if is_table(value) => {
+                out.push_str(&format!("\n[{}]\n", path.join(".")));
+                write_table(table, &path, out)?;
+            }
+            Value::Array(tables) if is_table_array(value) => {
+                for table in tables.iter().filter_map(Value::as_object) {
+                    out.push_str(&format!("\n[[{}]]\n", path.join(".")));
+                    write_table(table, &path, out)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_table(value: &Value) -> bool {
+    value.is_object()
+}
+
+fn is_table_array(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+fn inline(value: &Value) -> io::Result<String> {
+    Ok(match value {
+        Value::Null => return Err(invalid("TOML can't store null inside arrays")),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(number) => number.to_string(),
+        // JSON string escapes are valid in TOML basic strings.
+        Value::String(_) => value.to_string(),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(inline).collect::<io::Result<_>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| Ok(format!("{} = {}", key_text(key), inline(value)?)))
+                .collect::<io::Result<_>>()?;
+            format!("{{ {} }}", entries.join(", "))
+        }
+    })
+}
+
+fn key_text(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// Reads a TOML document as a JSON object.
+pub fn decode(text: &str) -> io::Result<Value> {
+    let mut root = Map::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let number = i + 1;
+        let mut statement = line.to_string();
+        // Arrays may span several lines.
+        while !balanced(&statement) {
+            let Some((_, next)) = lines.next() else {
+                return Err(error(number, "unterminated array"));
+            };
+            statement.push('\n');
+            statement.push_str(next);
+        }
+        let mut chars = statement.chars().peekable();
+        skip_space(&mut chars);
+        match chars.peek() {
+            None | Some('#') => continue,
+            Some('[') => {
+                chars.next();
+                let array = chars.next_if_eq(&'[').is_some();
+                let path = parse_key(&mut chars).map_err(|e| error(number, &e))?;
+                let closing = if array { "]]" } else { "]" };
+                for expected in closing.chars() {
+                    skip_space(&mut chars);
+                    if chars.next() != Some(expected) {
+                        return Err(error(number, "expected ]"));
+                    }
+                }
+                let parent =
+                    table_at(&mut root, &path[..path.len() - 1]).map_err(|e| error(number, &e))?;
+                let key = path[path.len() - 1].clone();
+                if array {
+                    let entry = parent
+                        .entry(key)
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    let Value::Array(tables) = entry else {
+                        return Err(error(number, "not an array of tables"));
+                    };
+                    tables.push(Value::Object(Map::new()));
+                } else {
+                    parent
+                        .entry(key)
+                        .or_insert_with(|| Value::Object(Map::new()));
+                }
+                current = path;
+            }
+            Some(_) => {
+                let path = parse_key(&mut chars).map_err(|e| error(number, &e))?;
+                skip_space(&mut chars);
+                if chars.next() != Some('=') {
+                    return Err(error(number, "expected ="));
+                }
+                let value = parse_value(&mut chars).map_err(|e| error(number, &e))?;
+                let mut full = current.clone();
+                full.extend_from_slice(&path[..path.len() - 1]);
+                let table = table_at(&mut root, &full).map_err(|e| error(number, &e))?;
+                if table.insert(path[path.len() - 1].clone(), value).is_some() {
+                    return Err(error(number, "duplicate key"));
+                }
+            }
+        }
+        skip_space(&mut chars);
+        if chars.next().is_some_and(|c| c != '#') {
+            return Err(error(number, "unexpected text after value"));
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+/// The table at `path`, created if missing. An array of tables stands for
+/// its last table.
+fn table_at<'a>(
+    root: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>, String> {
+    let mut table = root;
+    for key in path {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        table = match entry {
+            Value::Object(next) => next,
+            Value::Array(items) => match items.last_mut() {
+                Some(Value::Object(next)) => next,
+                _ => return Err(format!("{} is not a table", key)),
+            },
+            _ => return Err(format!("{} is not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+/// Whether all brackets and braces opened outside strings are closed.
+fn balanced(text: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut comment = false;
+    for c in text.chars() {
+        if comment {
+            // A comment runs to the end of its line, not of the statement.
+            comment = c != '\n';
+            continue;
+        }
+        match (quote, c) {
+            (None, '#') => comment = true,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            _ => {}
+        }
+        escaped = false;
+    }
+    // Table headers like `[a]` are balanced on their own line.
+    depth <= 0
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+}
+
+/// Skips whitespace, newlines and comments inside arrays.
+fn skip_blank(chars: &mut Peekable<Chars>) {
+    loop {
+        skip_space(chars);
+        match chars.peek() {
+            Some('\n' | '\r') => {
+                chars.next();
+            }
+            Some('#') => while chars.next_if(|c| *c != '\n').is_some() {},
+            _ => return,
+        }
+    }
+}
+
+/// Parses a possibly dotted key such as `a."b c".d`.
+fn parse_key(chars: &mut Peekable<Chars>) -> Result<Vec<String>, String> {
+    let mut path = Vec::new();
+    loop {
+        skip_space(chars);
+        let part = match chars.peek() {
+            Some('"') => parse_basic_string(chars)?,
+            Some('\'') => parse_literal_string(chars)?,
+            _ => {
+                let mut key = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                {
+                    key.push(c);
+                }
+                if key.is_empty() {
+                    return Err("expected a key".to_string());
+                }
+                key
+            }
+        };
+        path.push(part);
+        skip_space(chars);
+        if chars.next_if_eq(&'.').is_none() {
+            return Ok(path);
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_space(chars);
+    match chars.peek() {
+        Some('"') => parse_basic_string(chars).map(Value::String),
+        Some('\'') => parse_literal_string(chars).map(Value::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_blank(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(items));
+                }
+                items.push(parse_value(chars)?);
+                skip_blank(chars);
+                if chars.next_if_eq(&',').is_none() {
+                    skip_blank(chars);
+                    return match chars.next() {
+                        Some(']') => Ok(Value::Array(items)),
+                        _ => Err("expected , or ]".to_string()),
+                    };
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut table = Map::new();
+            skip_space(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Value::Object(table));
+            }
+            loop {
+                let path = parse_key(chars)?;
+                skip_space(chars);
+                if chars.next() != Some('=') {
+                    return Err("expected =".to_string());
+                }
+                let value = parse_value(chars)?;
+                let mut target = &mut table;
+                for key in &path[..path.len() - 1] {
+                    target = table_at(target, std::slice::from_ref(key))?;
+                }
+                target.insert(path[path.len() - 1].clone(), value);
+                skip_space(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Value::Object(table)),
+                    _ => return Err("expected , or }".to_string()),
+                }
+            }
+        }
+        _ => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, ',' | ']' | '}' | '#' | '\n')) {
+                word.push(c);
+            }
+            parse_bare_value(word.trim())
+        }
+    }
+}
+
+/// Booleans, numbers and dates. Dates are kept as strings.
+fn parse_bare_value(word: &str) -> Result<Value, String> {
+    match word {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        "" => return Err("expected a value".to_string()),
+        _ => {}
+    }
+    let digits = word.replace('_', "");
+    if let Ok(number) = digits.parse::<i64>() {
+        return Ok(Value::from(number));
+    }
+    if let Some(number) = digits.parse::<f64>().ok().and_then(Number::from_f64) {
+        return Ok(Value::Number(number));
+    }
+    if word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('-') {
+        return Ok(Value::String(word.to_string()));
+    }
+    Err(format!("invalid value {}", word))
+}
+
+fn parse_basic_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut raw = String::new();
+    raw.push(chars.next().unwrap_or('"'));
+    let mut escaped = false;
+    for c in chars.by_ref() {
+        raw.push(c);
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => {
+                // TOML's escapes match JSON's, apart from the rarely used \U.
+                return serde_json::from_str(&raw).map_err(|e| e.to_string());
+            }
+            '\n' => return Err("unterminated string".to_string()),
+            _ => escaped = false,
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn parse_literal_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next();
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        match c {
+            '\'' => return Ok(text),
+            '\n' => break,
+            c => text.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn error(line: usize, message: &str) -> io::Error {
+    invalid(&format!("TOML line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::Task;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn round_trip(value: &Value) -> Value {
+        decode(&encode(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn tasks_read_back_identical() {
+        let tasks = BTreeMap::from([(1, testing::awkward_task())]);
+        let value = serde_json::to_value(&tasks).unwrap();
+        // Null fields are left out and read back as their defaults.
+        let read: BTreeMap<u32, Task> = serde_json::from_value(round_trip(&value)).unwrap();
+        assert_eq!(serde_json::to_value(read).unwrap(), value);
+    }
+
+    #[test]
+    fn strings_read_back_identical_as_values_and_keys() {
+        let strings = [
+            "",
+            " ",
+            "  padded  ",
+            "true",
+            "12",
+            "1.5",
+            "2024-01-01",
+            "a = b",
+            "[table]",
+            "[[array]]",
+            "# hash",
+            "'single'",
+            "\"double\"",
+            "it's",
+            "back\\slash",
+            "tab\there",
+            "line\nbreak",
+            "\r\n",
+            "a.b",
+            "café",
+            "日本語",
+            "🦀",
+            "—",
+        ];
+        for text in strings {
+            let value = json!({ text: text, "list": [text], "inline": [{ text: text }] });
+            assert_eq!(round_trip(&value), value, "{:?}", text);
+            let table = json!({ text: { text: text } });
+            assert_eq!(round_trip(&table), table, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn empty_and_nested_collections_read_back_identical() {
+        let value = json!({
+            "empty list": [],
+            "empty table": {},
+            "lists": [[], [1, [2, 3]], [{}], {"a": []}],
+            "tables": [{"a": 1, "b": {"c": [true]}, "d": [{"e": "f"}]}, {"g": {}}],
+            "deep": {"a": {"b": {"c": {"d": "e"}}}},
+            "numbers": [0, -1, 1.5, 9007199254740993_u64],
+        });
+        assert_eq!(round_trip(&value), value);
+        assert_eq!(round_trip(&json!({})), json!({}));
+    }
+
+    #[test]
+    fn null_fields_are_left_out() {
+        let value = json!({"a": null, "b": {"c": null, "d": 1}, "e": [{"f": null}]});
+        assert_eq!(round_trip(&value), json!({"b": {"d": 1}, "e": [{}]}));
+        assert!(encode(&json!({"a": [null]})).is_err());
+        assert!(encode(&json!([1])).is_err());
+    }
+
+    #[test]
+    fn reads_hand_written_documents() {
+        let text = r#"
+# A comment
+title = "Buy milk" # trailing comment
+'literal key' = 'C:\path'
+tags = [
+    "home",  # it's [first]
+    "errands",
+]
+point = { x = 1, y.z = 2 }
+due = 2024-01-01T09:00:00Z
+big = 1_000
+
+[custom]
+client = "Acme"
+
+[[entries]]
+note = "first"
+
+[[entries]]
+note = "second"
+"#;
+        assert_eq!(
+            decode(text).unwrap(),
+            json!({
+                "title": "Buy milk",
+                "literal key": "C:\\path",
+                "tags": ["home", "errands"],
+                "point": {"x": 1, "y": {"z": 2}},
+                "due": "2024-01-01T09:00:00Z",
+                "big": 1000,
+                "custom": {"client": "Acme"},
+                "entries": [{"note": "first"}, {"note": "second"}],
+            })
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_malformed_documents() {
+        let error = decode("a = 1\na = 2\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+        assert!(decode("a = \"unterminated\n").is_err());
+        assert!(decode("a = [1, 2\n").is_err());
+        assert!(decode("a = 1 b = 2\n").is_err());
+        assert!(decode("a = nope\n").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Several changes to the tasks saved as one. `TodoApp::begin` starts a
+//! transaction, which is used like the app itself; `commit` saves every
+//! change at once. If saving fails, or the transaction is dropped without
+//! being committed, the tasks are put back the way they were.
+
+use crate::{Task, TodoApp};
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+/// Changes to the tasks not saved yet.
+pub(crate) struct Transaction<'a> {
+    app: &'a mut TodoApp,
+    /// The tasks as they were when the transaction began.
+    tasks: BTreeMap<u32, Task>,
+    next_task_id: u32,
+    committed: bool,
+}
+
+impl TodoApp {
+    /// Starts a transaction over the tasks.
+    pub fn begin(&mut self) -> Transaction<'_> {
+        Transaction {
+            tasks: self.tasks.clone(),
+            next_task_id: self.next_task_id,
+            app: self,
+            committed: false,
+        }
+    }
+}
+
+impl Transaction<'_> {
+    /// Saves the changes. `message` describes them. If saving fails, they're
+    /// undone.
+    pub fn commit(mut self, message: &str) -> Result<(), &'static str> {
+        self.app
+            .save_tasks(message)
+            .map_err(|_| "Failed to save tasks")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        let ids: Vec<u32> = self.app.tasks.keys().copied().collect();
+        for task_id in ids {
+            self.app.remove_task(task_id);
+        }
+        for task in std::mem::take(&mut self.tasks).into_values() {
+            self.app.insert_task(task);
+        }
+        self.app.next_task_id = self.next_task_id;
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = TodoApp;
+
+    fn deref(&self) -> &TodoApp {
+        self.app
+    }
+}
+
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut TodoApp {
+        self.app
+    }
+}
@@ -0,0 +1,132 @@
+//! Deleted tasks are kept in the trash, where `trash` lists them and
+//! `restore` brings one back, until the retention policy purges them (see
+//! `retention`) or `trash empty` removes them.
+
+use crate::log;
+use crate::storage;
+use crate::{Task, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const TRASH_FILE: &str = "trash.json";
+
+/// A deleted task, stored under its UUID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trashed {
+    pub task: Task,
+    #[serde(with = "ts_seconds")]
+    pub deleted_at: DateTime<Utc>,
+    /// User who deleted the task.
+    pub deleted_by: String,
+}
+
+impl TodoApp {
+    /// Puts deleted tasks in the trash. Like the audit log, the trash never
+    /// fails the deletion itself.
+    pub fn move_to_trash(&mut self, tasks: &[Task], deleted_by: &str) {
+        let deleted_at = Utc::now();
+        for task in tasks {
+            self.trash.insert(
+                task.uuid.clone(),
+                Trashed {
+                    task: task.clone(),
+                    deleted_at,
+                    deleted_by: deleted_by.to_string(),
+                },
+            );
+        }
+        let _ = self.save_trash(&format!("Trash {} tasks", tasks.len()));
+    }
+
+    /// The deleted tasks the current user can see, newest first.
+    pub fn trashed_tasks(&self) -> Result<Vec<&Trashed>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let mut trashed: Vec<&Trashed> = self
+            .trash
+            .values()
+            .filter(|trashed| self.workspaces.can_access(&trashed.task, user_id))
+            .collect();
+        trashed.sort_by_key(|trashed| std::cmp::Reverse(trashed.deleted_at));
+        Ok(trashed)
+    }
+
+    /// Takes the most recently deleted task with `task_id` out of the trash.
+    /// It gets a new ID if another task has taken its old one. Returns the
+    /// task's ID.
+    pub fn restore_task(&mut self, task_id: u32) -> Result<u32, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let (uuid, trashed) = self
+            .trash
+            .iter()
+            .filter(|(_, trashed)| {
+                trashed.task.id == task_id && self.workspaces.can_access(&trashed.task, &user_id)
+            })
+            .max_by_key(|(_, trashed)| trashed.deleted_at)
+            .ok_or("Task not found in the trash")?;
+        if !self.workspaces.can_edit(&trashed.task, &user_id) {
+            return Err("Not authorized to modify this task");
+        }
+        let uuid = uuid.clone();
+        let mut task = trashed.task.clone();
+        self.check_quota(1)?;
+
+        if self.tasks.contains_key(&task.id) {
+            task.id = self.next_task_id;
+        }
+        self.next_task_id = self.next_task_id.max(task.id + 1);
+        task.touch();
+        let restored = task.id;
+        self.insert_task(task);
+        self.save_tasks(&format!("Restore task #{} from the trash", restored))
+            .map_err(|_| "Failed to save tasks")?;
+        self.trash.remove(&uuid);
+        let _ = self.save_trash(&format!("Restore task #{} from the trash", restored));
+        Ok(restored)
+    }
+
+    /// Removes the deleted tasks the current user may edit from the trash
+    /// for good. Returns how many were removed.
+    pub fn empty_trash(&mut self) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        let uuids: Vec<String> = self
+            .trash
+            .iter()
+            .filter(|(_, trashed)| self.workspaces.can_edit(&trashed.task, &user_id))
+            .map(|(uuid, _)| uuid.clone())
+            .collect();
+        if uuids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut removed = Vec::new();
+        for uuid in &uuids {
+            removed.extend(self.trash.remove_entry(uuid));
+        }
+        if self
+            .save_trash(&format!("Empty the trash of {}", user_id))
+            .is_err()
+        {
+            self.trash.extend(removed);
+            return Err("Failed to save the trash");
+        }
+        Ok(removed.len())
+    }
+
+    /// Saves the trash. `message` describes the change being saved.
+    pub fn save_trash(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(self.storage.as_ref(), TRASH_FILE, &self.trash, message)
+            .inspect_err(|e| log::error(&format!("Failed to save the trash ({}): {}", message, e)))
+    }
+
+    /// Loads the trash. If the file doesn't exist, it's ignored.
+    pub fn load_trash(&mut self) -> io::Result<()> {
+        if let Some(trash) = storage::load_json(self.storage.as_ref(), TRASH_FILE)? {
+            self.trash = trash;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Local usage statistics, so users can see their own patterns: how often
+//! they run each command and how many tasks they create each week, shown by
+//! `usage`. They're kept per user in `usage.json` in the data directory,
+//! outside the storage so they're never synced, and aren't sent anywhere.
+//! `usage_stats: false` in `config.json` stops the counting.
+
+use crate::log;
+use crate::schedule::local_date;
+use crate::TodoApp;
+use chrono::{serde::ts_seconds_option, DateTime, Datelike, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+const USAGE_FILE: &str = "usage.json";
+
+/// Weeks of created tasks shown in the report.
+pub const REPORT_WEEKS: u64 = 8;
+
+/// One user's counts.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserUsage {
+    /// When counting started.
+    #[serde(with = "ts_seconds_option")]
+    pub since: Option<DateTime<Utc>>,
+    /// Times each command was run, by name.
+    pub commands: BTreeMap<String, u64>,
+    /// Tasks created, by ISO week such as `2026-W42`.
+    pub tasks_created: BTreeMap<String, u64>,
+}
+
+impl UserUsage {
+    /// The commands by how often they were run, most first.
+    pub fn top_commands(&self) -> Vec<(&str, u64)> {
+        let mut commands: Vec<(&str, u64)> = self
+            .commands
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        commands.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        commands
+    }
+
+    /// Tasks created in each of the last `weeks` weeks up to the one with
+    /// `today`, oldest first.
+    pub fn weekly_tasks(&self, today: NaiveDate, weeks: u64) -> Vec<(String, u64)> {
+        (0..weeks)
+            .rev()
+            .filter_map(|ago| today.checked_sub_days(Days::new(ago * 7)))
+            .map(|day| {
+                let week = week_key(day);
+                let count = self.tasks_created.get(&week).copied().unwrap_or(0);
+                (week, count)
+            })
+            .collect()
+    }
+}
+
+/// The ISO week of `date`, e.g. `2026-W42`.
+pub fn week_key(date: NaiveDate) -> String {
+    let week = date.iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+fn load() -> io::Result<BTreeMap<String, UserUsage>> {
+    match fs::read_to_string(USAGE_FILE) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(usage: &BTreeMap<String, UserUsage>) -> io::Result<()> {
+    let json = serde_json::to_string(usage)?;
+    fs::write(USAGE_FILE, json)?;
+    Ok(())
+}
+
+impl TodoApp {
+    /// Counts a command run by the current user.
+    pub fn count_command(&self, command: &str) {
+        if let Some(user_id) = &self.current_user {
+            self.count_usage(user_id, |usage| {
+                *usage.commands.entry(command.to_string()).or_default() += 1;
+            });
+        }
+    }
+
+    /// Counts a task created by `user`.
+    pub fn count_task_created(&self, user: &str) {
+        self.count_usage(user, |usage| {
+            let week = week_key(local_date(Utc::now()));
+            *usage.tasks_created.entry(week).or_default() += 1;
+        });
+    }
+
+    /// Updates the counts of `user`, unless counting is off or this is a
+    /// dry run. Failures are only logged, as the counts aren't worth
+    /// failing a command over.
+    fn count_usage(&self, user: &str, update: impl FnOnce(&mut UserUsage)) {
+        if !self.track_usage || self.dry_run {
+            return;
+        }
+        let result = load().and_then(|mut all| {
+            let usage = all.entry(user.to_string()).or_default();
+            usage.since.get_or_insert_with(Utc::now);
+            update(usage);
+            save(&all)
+        });
+        if let Err(e) = result {
+            log::warn(&format!("Failed to count usage: {}", e));
+        }
+    }
+
+    /// The current user's counts.
+    pub fn usage_stats(&self) -> Result<UserUsage, String> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let mut all = load().map_err(|e| e.to_string())?;
+        Ok(all.remove(user_id).unwrap_or_default())
+    }
+}
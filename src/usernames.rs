@@ -0,0 +1,121 @@
+//! Usernames are compared case-insensitively and regardless of how accented
+//! letters are encoded, so "Alice", "alice" and "ALICE" are one account.
+//! New accounts are stored under the normalized name; accounts registered
+//! before keep theirs and are found by it.
+
+use crate::TodoApp;
+use std::collections::BTreeMap;
+
+/// Combining marks and the letters they compose with, as
+/// (base, mark, composed). Covers the Latin and Cyrillic letters used by
+/// the app's languages; marks on other letters are kept as they are.
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('a', '\u{300}', 'à'),
+    ('e', '\u{300}', 'è'),
+    ('i', '\u{300}', 'ì'),
+    ('o', '\u{300}', 'ò'),
+    ('u', '\u{300}', 'ù'),
+    ('a', '\u{301}', 'á'),
+    ('c', '\u{301}', 'ć'),
+    ('e', '\u{301}', 'é'),
+    ('i', '\u{301}', 'í'),
+    ('n', '\u{301}', 'ń'),
+    ('o', '\u{301}', 'ó'),
+    ('s', '\u{301}', 'ś'),
+    ('u', '\u{301}', 'ú'),
+    ('y', '\u{301}', 'ý'),
+    ('z', '\u{301}', 'ź'),
+    ('a', '\u{302}', 'â'),
+    ('e', '\u{302}', 'ê'),
+    ('i', '\u{302}', 'î'),
+    ('o', '\u{302}', 'ô'),
+    ('u', '\u{302}', 'û'),
+    ('a', '\u{303}', 'ã'),
+    ('n', '\u{303}', 'ñ'),
+    ('o', '\u{303}', 'õ'),
+    ('a', '\u{306}', 'ă'),
+    ('и', '\u{306}', 'й'),
+    ('у', '\u{306}', 'ў'),
+    ('z', '\u{307}', 'ż'),
+    ('a', '\u{308}', 'ä'),
+    ('e', '\u{308}', 'ë'),
+    ('i', '\u{308}', 'ï'),
+    ('o', '\u{308}', 'ö'),
+    ('u', '\u{308}', 'ü'),
+    ('y', '\u{308}', 'ÿ'),
+    ('е', '\u{308}', 'ё'),
+    ('і', '\u{308}', 'ї'),
+    ('a', '\u{30a}', 'å'),
+    ('u', '\u{30a}', 'ů'),
+    ('c', '\u{30c}', 'č'),
+    ('e', '\u{30c}', 'ě'),
+    ('n', '\u{30c}', 'ň'),
+    ('r', '\u{30c}', 'ř'),
+    ('s', '\u{30c}', 'š'),
+    ('z', '\u{30c}', 'ž'),
+    ('c', '\u{327}', 'ç'),
+    ('s', '\u{327}', 'ş'),
+    ('a', '\u{328}', 'ą'),
+    ('e', '\u{328}', 'ę'),
+];
+
+/// The form usernames are compared in: trimmed, lowercased with the few
+/// letters whose case folding differs from lowercasing folded too, and with
+/// letters followed by a combining mark composed into one character.
+pub fn normalize(username: &str) -> String {
+    let mut normalized = String::new();
+    for c in username.trim().to_lowercase().chars() {
+        let composed = normalized.chars().next_back().and_then(|base| {
+            COMPOSITIONS
+                .iter()
+                .find(|(b, mark, _)| *b == base && *mark == c)
+                .map(|(_, _, composed)| *composed)
+        });
+        match (composed, c) {
+            (Some(composed), _) => {
+                normalized.pop();
+                normalized.push(composed);
+            }
+            (None, 'ß') => normalized.push_str("ss"),
+            (None, 'ς') => normalized.push('σ'),
+            (None, c) => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Groups of usernames that normalize to the same name, such as accounts
+/// registered as "Alice" and "alice" before names were normalized.
+pub fn duplicates<'a>(usernames: impl IntoIterator<Item = &'a str>) -> Vec<Vec<&'a str>> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for username in usernames {
+        groups
+            .entry(normalize(username))
+            .or_default()
+            .push(username);
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+impl TodoApp {
+    /// The name `username` is registered under, if it matches an account.
+    /// An exact match wins; otherwise the normalized names must match
+    /// exactly one account.
+    pub fn find_user(&self, username: &str) -> Option<&str> {
+        if let Some((name, _)) = self.users.get_key_value(username) {
+            return Some(name);
+        }
+        let normalized = normalize(username);
+        let mut matches = self
+            .users
+            .keys()
+            .filter(|name| normalize(name) == normalized);
+        match (matches.next(), matches.next()) {
+            (Some(name), None) => Some(name),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Checks on text typed by users before it's stored: task titles and
+//! usernames are trimmed and must be non-empty, within a length limit and
+//! free of control characters, which would garble lists and the terminal.
+
+/// Most characters in a task title.
+pub const MAX_TITLE_LENGTH: usize = 200;
+
+/// Most characters in a username.
+pub const MAX_USERNAME_LENGTH: usize = 32;
+
+/// The trimmed title, if it's valid.
+pub fn title(title: &str) -> Result<String, &'static str> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("The title can't be empty");
+    }
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err("Titles can be at most 200 characters long");
+    }
+    if title.chars().any(char::is_control) {
+        return Err("Titles can't contain control characters");
+    }
+    Ok(title.to_string())
+}
+
+/// The trimmed username, if it's valid. Usernames are single words, as
+/// they're typed after commands such as `su` and `assign`.
+pub fn username(username: &str) -> Result<String, &'static str> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err("The username can't be empty");
+    }
+    if username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err("Usernames can be at most 32 characters long");
+    }
+    if username.chars().any(char::is_control) {
+        return Err("Usernames can't contain control characters");
+    }
+    if username.chars().any(char::is_whitespace) {
+        return Err("Usernames can't contain spaces");
+    }
+    Ok(username.to_string())
+}
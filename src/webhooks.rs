@@ -0,0 +1,191 @@
+//! Delivery of task events to user-configured webhook URLs.
+//!
+//! Webhooks are posted over plain HTTP. HTTPS services such as Slack and
+//! Discord are reached through a local TLS proxy, e.g. stunnel, by
+//! configuring the proxy's `http://localhost` URL; `https://` URLs are
+//! refused when the config is loaded.
+
+use crate::crypto;
+use crate::http;
+use crate::i18n::trf;
+use crate::log;
+use crate::{Task, TaskEvent};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const MAX_ATTEMPTS: u32 = 3;
+
+/// A URL that receives a JSON POST for each matching task event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// An `http://` URL, possibly of a local proxy to an HTTPS service.
+    pub url: String,
+    /// Shared secret used to sign the payload in the `X-Todo-Signature` header.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events delivered to this hook. Empty means all events.
+    #[serde(default)]
+    pub events: Vec<TaskEvent>,
+}
+
+impl WebhookConfig {
+    /// Fails unless `url` is a plain `http://` URL, so a hook that can
+    /// never be delivered is reported up front.
+    pub fn validate(&self) -> io::Result<()> {
+        http::check_url(&self.url).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                trf(
+                    "Webhook URL {} must be an http:// URL; reach https:// services such as Slack or Discord through a local TLS proxy",
+                    &[&self.url],
+                ),
+            )
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: TaskEvent,
+    task: &'a Task,
+    sent_at: i64,
+}
+
+/// Sends webhook requests in the background, retrying failed deliveries.
+#[derive(Default)]
+pub struct Webhooks {
+    hooks: Vec<WebhookConfig>,
+    /// Behind a lock so events can be dispatched through a shared `TodoApp`.
+    deliveries: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            hooks,
+            deliveries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues delivery of `event` for `task` to every hook subscribed to it.
+    pub fn dispatch(&self, event: TaskEvent, task: &Task) {
+        let mut deliveries = self
+            .deliveries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        deliveries.retain(|delivery| !delivery.is_finished());
+
+        let payload = Payload {
+            event,
+            task,
+            sent_at: Utc::now().timestamp(),
+        };
+        let Ok(body) = serde_json::to_string(&payload) else {
+            return;
+        };
+
+        for hook in &self.hooks {
+            if !hook.events.is_empty() && !hook.events.contains(&event) {
+                continue;
+            }
+            let url = hook.url.clone();
+            let signature = hook.secret.as_ref().map(|secret| {
+                format!(
+                    "sha256={}",
+                    crypto::to_hex(&crypto::hmac_sha256(secret.as_bytes(), body.as_bytes()))
+                )
+            });
+            let body = body.clone();
+            deliveries.push(thread::spawn(move || {
+                deliver(&url, signature.as_deref(), &body)
+            }));
+        }
+    }
+}
+
+impl Drop for Webhooks {
+    /// Waits for in-flight deliveries so events aren't lost on exit.
+    fn drop(&mut self) {
+        let deliveries = self
+            .deliveries
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        for delivery in deliveries.drain(..) {
+            let _ = delivery.join();
+        }
+    }
+}
+
+/// Posts the payload, backing off between attempts until it succeeds.
+fn deliver(url: &str, signature: Option<&str>, body: &str) {
+    let mut headers = vec![("Content-Type", "application/json")];
+    if let Some(signature) = signature {
+        headers.push(("X-Todo-Signature", signature));
+    }
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        match http::send("POST", url, &headers, body) {
+            Ok(response) if response.is_success() => return,
+            _ if attempt < MAX_ATTEMPTS => thread::sleep(Duration::from_secs(1 << attempt)),
+            _ => {
+                let message = format!(
+                    "Webhook delivery to {} failed after {} attempts",
+                    url, MAX_ATTEMPTS
+                );
+                log::warn(&message);
+                eprintln!("{}", message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing;
+
+    fn hook(url: &str) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            secret: None,
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn https_urls_are_refused_with_the_proxy_advice() {
+        let error = hook("https://hooks.slack.com/services/T0/B0/x")
+            .validate()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("TLS proxy"));
+        assert!(hook("http://localhost:8443/services/T0/B0/x")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn configs_with_https_webhooks_fail_to_load() {
+        let dir = testing::temp_dir("webhooks");
+        let path = dir.join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"webhooks": [{"url": "https://discord.com/api/webhooks/1/x"}]}"#,
+        )
+        .unwrap();
+        assert!(Config::load_from(&path).is_err());
+
+        std::fs::write(
+            &path,
+            r#"{"webhooks": [{"url": "http://localhost:8443/"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(Config::load_from(&path).unwrap().webhooks.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+//! Server side of the WebSocket protocol (RFC 6455), as far as needed to
+//! push text messages to clients and read their short replies.
+
+use crate::crypto;
+use crate::http;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// Appended to the client's key to prove the server speaks WebSocket.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// An open WebSocket connection.
+pub struct WebSocket {
+    stream: TcpStream,
+}
+
+impl WebSocket {
+    /// Completes the opening handshake for a request carrying
+    /// `Sec-WebSocket-Key: key`.
+    pub fn accept(mut stream: TcpStream, key: &str) -> io::Result<Self> {
+        let accept = http::base64_encode(&crypto::sha1(
+            format!("{}{}", key, HANDSHAKE_GUID).as_bytes(),
+        ));
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        )?;
+        stream.set_nonblocking(true)?;
+        Ok(Self { stream })
+    }
+
+    /// Sends a text message.
+    pub fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.send_frame(OPCODE_TEXT, text.as_bytes())
+    }
+
+    /// Handles frames sent by the client: answers pings, adds text messages
+    /// to `received` and returns `false` once the client has closed the
+    /// connection. Only messages shorter than 126 bytes are read.
+    pub fn poll(&mut self, received: &mut Vec<String>) -> bool {
+        let mut buffer = [0u8; 1024];
+        match self.stream.read(&mut buffer) {
+            Ok(0) => false,
+            Ok(len) => match buffer[0] & 0x0f {
+                OPCODE_CLOSE => {
+                    let _ = self.send_frame(OPCODE_CLOSE, &[]);
+                    false
+                }
+                OPCODE_TEXT => {
+                    if let Ok(text) = String::from_utf8(unmask(&buffer[..len])) {
+                        received.push(text);
+                    }
+                    true
+                }
+                OPCODE_PING => self
+                    .send_frame(OPCODE_PONG, &unmask(&buffer[..len]))
+                    .is_ok(),
+                _ => true,
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => true,
+            Err(_) => false,
+        }
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len if len < 126 => frame.push(len as u8),
+            len if len <= usize::from(u16::MAX) => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        // The socket is non-blocking for `poll`; block while writing.
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(&frame);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+}
+
+/// Extracts the payload of a short masked client frame.
+fn unmask(frame: &[u8]) -> Vec<u8> {
+    let Some(&len) = frame.get(1) else {
+        return Vec::new();
+    };
+    let len = usize::from(len & 0x7f);
+    // 126 and 127 mean the length follows in 2 or 8 more bytes.
+    if len >= 126 {
+        return Vec::new();
+    }
+    let Some(mask) = frame.get(2..6) else {
+        return Vec::new();
+    };
+    frame
+        .iter()
+        .skip(6)
+        .take(len)
+        .enumerate()
+        .map(|(i, byte)| byte ^ mask[i % 4])
+        .collect()
+}
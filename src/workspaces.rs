@@ -0,0 +1,544 @@
+//! Workspaces shared by a small team. Tasks created while a workspace is
+//! active belong to it. What each member may do with them depends on their
+//! permission level, from viewing them up to managing the members. A group
+//! of users (see `groups`) can be given a level too; each of its members
+//! then has at least that level.
+
+use crate::groups::Group;
+use crate::log;
+use crate::storage;
+use crate::{Task, TaskEvent, TodoApp};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+pub const WORKSPACES_FILE: &str = "workspaces.json";
+
+/// What a member may do in a workspace. Each level may do everything the
+/// ones before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May see the workspace's tasks.
+    View,
+    /// May also comment on them.
+    Comment,
+    /// May also add, change and delete them. Members invited before there
+    /// were levels have this one.
+    #[serde(alias = "member")]
+    Edit,
+    /// May also invite and remove members and change their permissions.
+    Admin,
+    /// Created the workspace; can't be removed or given another level.
+    Owner,
+}
+
+impl Role {
+    /// Parses a level that can be given to a member; there's only one owner.
+    pub fn parse(name: &str) -> Result<Self, &'static str> {
+        match name {
+            "view" => Ok(Self::View),
+            "comment" => Ok(Self::Comment),
+            "edit" => Ok(Self::Edit),
+            "admin" => Ok(Self::Admin),
+            _ => Err("Unknown permission, expected view, comment, edit or admin"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::View => "view",
+            Self::Comment => "comment",
+            Self::Edit => "edit",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    /// Role of each member, keyed by username.
+    pub members: BTreeMap<String, Role>,
+    /// Role of the members of each group, keyed by group name.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Role>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// All workspaces, keyed by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Workspaces {
+    workspaces: BTreeMap<String, Workspace>,
+    /// All groups, keyed by name, stored in their own file.
+    #[serde(skip)]
+    pub groups: BTreeMap<String, Group>,
+}
+
+impl Workspaces {
+    /// The role of `username` in a workspace, if they're a member or in a
+    /// group with a role there: the highest of those.
+    pub fn role(&self, workspace: &str, username: &str) -> Option<Role> {
+        let workspace = self.workspaces.get(workspace)?;
+        let by_groups = workspace
+            .groups
+            .iter()
+            .filter(|(group, _)| {
+                self.groups
+                    .get(*group)
+                    .is_some_and(|group| group.members.contains(username))
+            })
+            .map(|(_, role)| *role)
+            .max();
+        workspace.members.get(username).copied().max(by_groups)
+    }
+
+    /// Takes away the roles of a group in every workspace. Returns whether
+    /// it had any.
+    pub fn revoke_everywhere(&mut self, group: &str) -> bool {
+        let mut revoked = false;
+        for workspace in self.workspaces.values_mut() {
+            revoked |= workspace.groups.remove(group).is_some();
+        }
+        revoked
+    }
+
+    /// Whether `username` owns a workspace or a group.
+    pub fn owns_any(&self, username: &str) -> bool {
+        self.workspaces
+            .values()
+            .any(|workspace| workspace.members.get(username) == Some(&Role::Owner))
+            || self.groups.values().any(|group| group.owner == username)
+    }
+
+    /// Takes `username` out of every workspace and group. Returns whether
+    /// the workspaces and the groups changed.
+    pub fn remove_everywhere(&mut self, username: &str) -> (bool, bool) {
+        let mut workspaces = false;
+        for workspace in self.workspaces.values_mut() {
+            workspaces |= workspace.members.remove(username).is_some();
+        }
+        let mut groups = false;
+        for group in self.groups.values_mut() {
+            groups |= group.members.remove(username);
+        }
+        (workspaces, groups)
+    }
+
+    /// Whether `username` may view `task`: their own tasks, and the tasks
+    /// of workspaces they're a member of.
+    pub fn can_access(&self, task: &Task, username: &str) -> bool {
+        self.allows(task, username, Role::View)
+    }
+
+    /// Whether `username` may comment on `task`.
+    pub fn can_comment(&self, task: &Task, username: &str) -> bool {
+        self.allows(task, username, Role::Comment)
+    }
+
+    /// Whether `username` may change or delete `task`.
+    pub fn can_edit(&self, task: &Task, username: &str) -> bool {
+        self.allows(task, username, Role::Edit)
+    }
+
+    /// Whether `username` may add tasks to `workspace`, or to their own
+    /// tasks with `None`.
+    pub fn can_add(&self, workspace: Option<&str>, username: &str) -> bool {
+        workspace.is_none_or(|workspace| {
+            self.role(workspace, username)
+                .is_some_and(|role| role >= Role::Edit)
+        })
+    }
+
+    /// Whether `username` has at least `level` in the workspace of `task`,
+    /// or, for a task outside any workspace, created it. Creating a
+    /// workspace task gives no say over it beyond the creator's role.
+    fn allows(&self, task: &Task, username: &str, level: Role) -> bool {
+        match task.workspace.as_deref() {
+            Some(workspace) => self
+                .role(workspace, username)
+                .is_some_and(|role| role >= level),
+            None => task.user_id == username,
+        }
+    }
+}
+
+impl TodoApp {
+    /// Creates a workspace owned by the current user.
+    pub fn create_workspace(&mut self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if name.is_empty() {
+            return Err("Workspace name cannot be empty");
+        }
+        if self.workspaces.workspaces.contains_key(name) {
+            return Err("Workspace already exists");
+        }
+
+        self.workspaces.workspaces.insert(
+            name.to_string(),
+            Workspace {
+                name: name.to_string(),
+                members: BTreeMap::from([(user_id.clone(), Role::Owner)]),
+                groups: BTreeMap::new(),
+                created_at: Utc::now(),
+            },
+        );
+        self.save_workspaces(&format!("Create workspace {}", name))
+            .map_err(|_| "Failed to save workspaces")?;
+        log::info(&format!("{} created workspace {}", user_id, name));
+        Ok(())
+    }
+
+    /// Adds a user to a workspace with permission to edit its tasks. Only
+    /// admins may invite.
+    pub fn invite_to_workspace(&mut self, name: &str, username: &str) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        let username = &self
+            .find_user(username)
+            .ok_or("User not found")?
+            .to_string();
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get_mut(name)
+            .ok_or("Workspace not found")?;
+        if workspace.members.contains_key(username) {
+            return Err("User is already a member");
+        }
+
+        workspace.members.insert(username.to_string(), Role::Edit);
+        self.save_workspaces(&format!("Invite {} to workspace {}", username, name))
+            .map_err(|_| "Failed to save workspaces")?;
+        Ok(())
+    }
+
+    /// Removes a member from a workspace. Only admins may remove members,
+    /// and the owner can't be removed.
+    pub fn remove_from_workspace(
+        &mut self,
+        name: &str,
+        username: &str,
+    ) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        let username = &self.find_user(username).unwrap_or(username).to_string();
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get_mut(name)
+            .ok_or("Workspace not found")?;
+        match workspace.members.get(username) {
+            None => return Err("User is not a member"),
+            Some(Role::Owner) => return Err("The owner can't be removed"),
+            Some(_) => {}
+        }
+
+        workspace.members.remove(username);
+        self.save_workspaces(&format!("Remove {} from workspace {}", username, name))
+            .map_err(|_| "Failed to save workspaces")?;
+        Ok(())
+    }
+
+    /// Deletes a workspace, moving its tasks to the trash. Only its owner
+    /// may. Returns how many tasks were trashed.
+    pub fn delete_workspace(&mut self, name: &str) -> Result<usize, &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        self.check_workspace_owner(name)?;
+        let task_ids: Vec<u32> = self
+            .tasks
+            .values()
+            .filter(|task| task.workspace.as_deref() == Some(name))
+            .map(|task| task.id)
+            .collect();
+
+        let message = format!("Delete workspace {}", name);
+        let mut transaction = self.begin();
+        let mut deleted = Vec::new();
+        for task_id in &task_ids {
+            deleted.extend(transaction.remove_task(*task_id));
+            transaction.remove_links_to(*task_id);
+        }
+        transaction.commit(&message)?;
+        let workspace = self
+            .workspaces
+            .workspaces
+            .remove(name)
+            .ok_or("Workspace not found")?;
+        if self.save_workspaces(&message).is_err() {
+            self.workspaces
+                .workspaces
+                .insert(name.to_string(), workspace);
+            return Err("Failed to save workspaces");
+        }
+        if self.active_workspace.as_deref() == Some(name) {
+            self.active_workspace = None;
+        }
+        if !deleted.is_empty() {
+            self.move_to_trash(&deleted, &user_id);
+        }
+        for task in &deleted {
+            self.emit(TaskEvent::Deleted, task);
+        }
+        log::info(&format!("{} deleted workspace {}", user_id, name));
+        Ok(deleted.len())
+    }
+
+    /// Makes a workspace active, or the personal task list with `None`.
+    /// The choice is remembered for the user's next session.
+    pub fn switch_workspace(&mut self, name: Option<&str>) -> Result<(), &'static str> {
+        let user_id = self.current_user.clone().ok_or("Not logged in")?;
+        if let Some(name) = name {
+            if !self.workspaces.workspaces.contains_key(name) {
+                return Err("Workspace not found");
+            }
+            if self.workspaces.role(name, &user_id).is_none() {
+                return Err("Not a member of this workspace");
+            }
+        }
+
+        self.active_workspace = name.map(str::to_string);
+        self.settings.entry(user_id.clone()).or_default().workspace = self.active_workspace.clone();
+        self.save_settings(&format!("Change settings of user {}", user_id))
+            .map_err(|_| "Failed to save settings")?;
+        Ok(())
+    }
+
+    /// The workspaces the current user is a member of, with their role, by name.
+    pub fn my_workspaces(&self) -> Result<Vec<(&Workspace, Role)>, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let mut workspaces: Vec<(&Workspace, Role)> = self
+            .workspaces
+            .workspaces
+            .values()
+            .filter_map(|workspace| {
+                self.workspaces
+                    .role(&workspace.name, user_id)
+                    .map(|role| (workspace, role))
+            })
+            .collect();
+        workspaces.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        Ok(workspaces)
+    }
+
+    /// The members of a workspace with their permissions, by name. Any
+    /// member may see them.
+    pub fn permissions(&self, name: &str) -> Result<Vec<(&str, Role)>, &'static str> {
+        let workspace = self.visible_workspace(name)?;
+        Ok(workspace
+            .members
+            .iter()
+            .map(|(username, role)| (username.as_str(), *role))
+            .collect())
+    }
+
+    /// The groups with a permission in a workspace, by name. Any member may
+    /// see them.
+    pub fn group_permissions(&self, name: &str) -> Result<Vec<(&str, Role)>, &'static str> {
+        let workspace = self.visible_workspace(name)?;
+        Ok(workspace
+            .groups
+            .iter()
+            .map(|(group, role)| (group.as_str(), *role))
+            .collect())
+    }
+
+    /// A workspace the current user is a member of.
+    fn visible_workspace(&self, name: &str) -> Result<&Workspace, &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get(name)
+            .ok_or("Workspace not found")?;
+        if self.workspaces.role(name, user_id).is_none() {
+            return Err("Not a member of this workspace");
+        }
+        Ok(workspace)
+    }
+
+    /// Gives the members of a group a permission in a workspace. Only
+    /// admins may.
+    pub fn grant_group(&mut self, name: &str, group: &str, role: Role) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        if !self.workspaces.groups.contains_key(group) {
+            return Err("Group not found");
+        }
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get_mut(name)
+            .ok_or("Workspace not found")?;
+        workspace.groups.insert(group.to_string(), role);
+        self.save_workspaces(&format!(
+            "Give group {} {} permission in workspace {}",
+            group,
+            role.name(),
+            name
+        ))
+        .map_err(|_| "Failed to save workspaces")?;
+        Ok(())
+    }
+
+    /// Takes away a group's permission in a workspace. Only admins may.
+    pub fn revoke_group(&mut self, name: &str, group: &str) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get_mut(name)
+            .ok_or("Workspace not found")?;
+        if workspace.groups.remove(group).is_none() {
+            return Err("Group has no permission in this workspace");
+        }
+        self.save_workspaces(&format!("Revoke group {} in workspace {}", group, name))
+            .map_err(|_| "Failed to save workspaces")?;
+        Ok(())
+    }
+
+    /// Changes what a member may do in a workspace. Only admins may, and
+    /// the owner's permissions can't be changed.
+    pub fn set_permission(
+        &mut self,
+        name: &str,
+        username: &str,
+        role: Role,
+    ) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        let username = &self.find_user(username).unwrap_or(username).to_string();
+        let workspace = self
+            .workspaces
+            .workspaces
+            .get_mut(name)
+            .ok_or("Workspace not found")?;
+        match workspace.members.get_mut(username) {
+            None => return Err("User is not a member"),
+            Some(Role::Owner) => return Err("The owner's permissions can't be changed"),
+            Some(current) => *current = role,
+        }
+
+        self.save_workspaces(&format!(
+            "Give {} {} permission in workspace {}",
+            username,
+            role.name(),
+            name
+        ))
+        .map_err(|_| "Failed to save workspaces")?;
+        Ok(())
+    }
+
+    /// Fails unless the current user is the owner of the workspace.
+    pub fn check_workspace_owner(&self, name: &str) -> Result<(), &'static str> {
+        self.check_admin(name)?;
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        match self.workspaces.role(name, user_id) {
+            Some(Role::Owner) => Ok(()),
+            _ => Err("Only the workspace's owner can delete it"),
+        }
+    }
+
+    /// Fails unless the current user is an admin or the owner of the workspace.
+    fn check_admin(&self, name: &str) -> Result<(), &'static str> {
+        let user_id = self.current_user.as_ref().ok_or("Not logged in")?;
+        match self.workspaces.role(name, user_id) {
+            Some(role) if role >= Role::Admin => Ok(()),
+            Some(_) => Err("Only workspace admins can do this"),
+            None if self.workspaces.workspaces.contains_key(name) => {
+                Err("Not a member of this workspace")
+            }
+            None => Err("Workspace not found"),
+        }
+    }
+
+    /// Saves all workspaces. `message` describes the change being saved.
+    pub fn save_workspaces(&self, message: &str) -> io::Result<()> {
+        if self.skip_save(message) {
+            return Ok(());
+        }
+        storage::save_json(
+            self.storage.as_ref(),
+            WORKSPACES_FILE,
+            &self.workspaces,
+            message,
+        )
+        .inspect_err(|e| log::error(&format!("Failed to save workspaces ({}): {}", message, e)))
+    }
+
+    /// Loads all workspaces, keeping the groups. If the file doesn't exist,
+    /// it's ignored.
+    pub fn load_workspaces(&mut self) -> io::Result<()> {
+        if let Some(workspaces) = storage::load_json(self.storage.as_ref(), WORKSPACES_FILE)? {
+            let groups = std::mem::take(&mut self.workspaces.groups);
+            self.workspaces = workspaces;
+            self.workspaces.groups = groups;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// An app where bob added task 1 to alice's workspace `team`, with
+    /// alice logged in.
+    fn team_task() -> TodoApp {
+        let mut app = testing::dry_run_app();
+        testing::register(&mut app, &["alice", "bob"]);
+        testing::login(&mut app, "alice");
+        app.create_workspace("team").unwrap();
+        app.invite_to_workspace("team", "bob").unwrap();
+        testing::login(&mut app, "bob");
+        app.switch_workspace(Some("team")).unwrap();
+        let task = app.new_task("Book the venue", "", None).unwrap();
+        app.create_task(task).unwrap();
+        testing::login(&mut app, "alice");
+        app
+    }
+
+    #[test]
+    fn a_creator_downgraded_to_view_cannot_change_their_task() {
+        let mut app = team_task();
+        app.set_permission("team", "bob", Role::View).unwrap();
+        testing::login(&mut app, "bob");
+
+        assert!(app.workspaces.can_access(&app.tasks[&1], "bob"));
+        assert!(app.edit_task(1, 1, "Book a bigger venue", "").is_err());
+        assert!(app.complete_task(1).is_err());
+        assert!(app.delete_task(1).is_err());
+        assert!(app.tasks.contains_key(&1));
+    }
+
+    #[test]
+    fn a_removed_creator_loses_access_to_their_task() {
+        let mut app = team_task();
+        app.remove_from_workspace("team", "bob").unwrap();
+
+        assert!(!app.workspaces.can_access(&app.tasks[&1], "bob"));
+        testing::login(&mut app, "bob");
+        assert!(app.delete_task(1).is_err());
+    }
+
+    #[test]
+    fn editors_may_change_any_task_of_the_workspace() {
+        let mut app = team_task();
+        assert!(app.workspaces.can_edit(&app.tasks[&1], "alice"));
+        app.edit_task(1, 1, "Book a bigger venue", "").unwrap();
+        assert_eq!(app.tasks[&1].title, "Book a bigger venue");
+    }
+
+    #[test]
+    fn personal_tasks_belong_to_their_creator() {
+        let mut app = testing::dry_run_app();
+        testing::register(&mut app, &["alice", "bob"]);
+        testing::login(&mut app, "alice");
+        let task = app.new_task("Water the plants", "", None).unwrap();
+        app.create_task(task).unwrap();
+
+        assert!(app.workspaces.can_edit(&app.tasks[&1], "alice"));
+        assert!(!app.workspaces.can_access(&app.tasks[&1], "bob"));
+    }
+}
@@ -0,0 +1,429 @@
+//! The subset of YAML used for human-editable data files: block mappings
+//! and sequences of scalars, with `[]` and `{}` for empty collections.
+//! Strings that could be read as something else are double-quoted.
+
+use serde_json::{Map, Number, Value};
+use std::io;
+
+/// Writes a value as a YAML document.
+pub fn encode(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_map(map, 0, &mut out),
+        Value::Array(items) if !items.is_empty() => write_seq(items, 0, &mut out),
+        scalar => {
+            out.push_str(&scalar_text(scalar));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_map(map: &Map<String, Value>, indent: usize, out: &mut String) {
+    for (key, value) in map {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&string_text(key));
+        out.push(':');
+        write_nested(value, indent, out);
+    }
+}
+
+fn write_seq(items: &[Value], indent: usize, out: &mut String) {
+    for item in items {
+        match item {
+            // The first entry of a mapping goes on the dash line.
+            Value::Object(map) if !map.is_empty() => {
+                let mut nested = String::new();
+                write_map(map, indent + 2, &mut nested);
+                out.push_str(&" ".repeat(indent));
+                out.push_str("- ");
+                out.push_str(&nested[indent + 2..]);
+            }
+            _ => {
+                out.push_str(&" ".repeat(indent));
+                out.push('-');
+                write_nested(item, indent, out);
+            }
+        }
+    }
+}
+
+/// Writes the value after a `key:` or `-`, on the same line for scalars.
+fn write_nested(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_map(map, indent + 2, out);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_seq(items, indent + 2, out);
+        }
+        scalar => {
+            out.push(' ');
+            out.push_str(&scalar_text(scalar));
+            out.push('\n');
+        }
+    }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => string_text(text),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+/// A string as a plain scalar when that reads back as the same string,
+/// otherwise double-quoted with JSON escapes, which YAML shares.
+fn string_text(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text.trim() == text
+        && !text.starts_with(['-', '?', '.'])
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || " _./()+-".contains(c))
+        && matches!(parse_scalar(text), Ok(Value::String(_)));
+    if plain {
+        text.to_string()
+    } else {
+        Value::String(text.to_string()).to_string()
+    }
+}
+
+/// Reads a YAML document written by `encode`, or edited by hand within the
+/// same subset.
+pub fn decode(text: &str) -> io::Result<Value> {
+    let lines: Vec<Line> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let content = strip_comment(line).trim_end();
+            let trimmed = content.trim_start();
+            if trimmed.is_empty() || trimmed == "---" {
+                return None;
+            }
+            Some(Line {
+                number: i + 1,
+                indent: content.len() - trimmed.len(),
+                text: trimmed.to_string(),
+            })
+        })
+        .collect();
+    let mut parser = Parser { lines, pos: 0 };
+    let Some(first) = parser.lines.first() else {
+        return Ok(Value::Null);
+    };
+    let value = parser.block(first.indent)?;
+    match parser.lines.get(parser.pos) {
+        Some(line) => Err(error(line.number, "unexpected indentation")),
+        None => Ok(value),
+    }
+}
+
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Parses the collection or scalar starting at the current line, whose
+    /// indentation is `indent`.
+    fn block(&mut self, indent: usize) -> io::Result<Value> {
+        let line = &self.lines[self.pos];
+        if is_seq_item(&line.text) {
+            self.seq(indent)
+        } else if split_key(&line.text).is_some() {
+            self.map(indent)
+        } else {
+            let line = &self.lines[self.pos];
+            self.pos += 1;
+            parse_scalar(&line.text).map_err(|e| error(line.number, &e))
+        }
+    }
+
+    fn seq(&mut self, indent: usize) -> io::Result<Value> {
+        let mut items = Vec::new();
+        while let Some(line) = self.lines.get_mut(self.pos) {
+            if line.indent != indent || !is_seq_item(&line.text) {
+                break;
+            }
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(self.nested(indent)?);
+            } else {
+                // Whatever follows the dash is parsed as if on its own line.
+                line.indent += line.text.len() - rest.len();
+                line.text = rest;
+                let item_indent = line.indent;
+                items.push(self.block(item_indent)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, indent: usize) -> io::Result<Value> {
+        let mut map = Map::new();
+        while let Some(line) = self.lines.get(self.pos) {
+            if line.indent != indent || is_seq_item(&line.text) {
+                break;
+            }
+            let number = line.number;
+            let (key, rest) =
+                split_key(&line.text).ok_or_else(|| error(number, "expected key: value"))?;
+            let key = match parse_scalar(&key).map_err(|e| error(number, &e))? {
+                Value::String(key) => key,
+                other => other.to_string(),
+            };
+            self.pos += 1;
+            let value = if rest.is_empty() {
+                // A sequence may sit at the same indentation as its key.
+                match self.lines.get(self.pos) {
+                    Some(next) if next.indent == indent && is_seq_item(&next.text) => {
+                        self.seq(indent)?
+                    }
+                    _ => self.nested(indent)?,
+                }
+            } else {
+                parse_scalar(&rest).map_err(|e| error(number, &e))?
+            };
+            if map.insert(key, value).is_some() {
+                return Err(error(number, "duplicate key"));
+            }
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// The block indented under the previous line, or null if there is none.
+    fn nested(&mut self, indent: usize) -> io::Result<Value> {
+        match self.lines.get(self.pos) {
+            Some(next) if next.indent > indent => {
+                let indent = next.indent;
+                self.block(indent)
+            }
+            _ => Ok(Value::Null),
+        }
+    }
+}
+
+fn is_seq_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Splits `key: value` into its parts, unless the colon is inside quotes.
+fn split_key(text: &str) -> Option<(String, String)> {
+    let end = quoted_end(text).unwrap_or(0);
+    let colon = text[end..]
+        .match_indices(':')
+        .map(|(i, _)| end + i)
+        .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))?;
+    if text.starts_with(['[', '{']) {
+        return None;
+    }
+    Some((
+        text[..colon].trim().to_string(),
+        text[colon + 1..].trim().to_string(),
+    ))
+}
+
+/// The index just past a leading quoted string, if the text starts with one.
+fn quoted_end(text: &str) -> Option<usize> {
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(i + 1),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Removes a `#` comment, unless the `#` is inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') if previous == ' ' => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                previous = c;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            _ => {}
+        }
+        escaped = false;
+        previous = c;
+    }
+    line
+}
+
+fn parse_scalar(text: &str) -> Result<Value, String> {
+    match text {
+        "null" | "~" | "" => return Ok(Value::Null),
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if text.starts_with(['"', '[', '{']) {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    }
+    if let Some(inner) = text.strip_prefix('\'') {
+        let inner = inner.strip_suffix('\'').ok_or("unterminated string")?;
+        return Ok(Value::String(inner.replace("''", "'")));
+    }
+    if let Ok(number) = text.parse::<i64>() {
+        return Ok(Value::from(number));
+    }
+    if let Some(number) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+        return Ok(Value::Number(number));
+    }
+    Ok(Value::String(text.to_string()))
+}
+
+fn error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("YAML line {}: {}", line, message),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::Task;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn round_trip(value: &Value) -> Value {
+        decode(&encode(value)).unwrap()
+    }
+
+    #[test]
+    fn tasks_read_back_identical() {
+        let tasks = BTreeMap::from([(1, testing::awkward_task())]);
+        let value = serde_json::to_value(&tasks).unwrap();
+        assert_eq!(round_trip(&value), value);
+        let read: BTreeMap<u32, Task> = serde_json::from_value(round_trip(&value)).unwrap();
+        assert_eq!(serde_json::to_value(read).unwrap(), value);
+    }
+
+    #[test]
+    fn strings_that_look_like_other_values_stay_strings() {
+        let strings = [
+            "",
+            " ",
+            "  padded  ",
+            "true",
+            "False",
+            "null",
+            "~",
+            "12",
+            "-3",
+            "1.5",
+            "1e3",
+            "0x1F",
+            "- item",
+            "-",
+            "? key",
+            "key: value",
+            "a:b",
+            "# hash",
+            "a # not a comment",
+            "'single'",
+            "\"double\"",
+            "it's",
+            "back\\slash",
+            "tab\there",
+            "line\nbreak",
+            "\n",
+            "trailing\n",
+            "[1, 2]",
+            "{}",
+            "@at",
+            "&anchor",
+            "*alias",
+            "!tag",
+            "|",
+            ">",
+            "%",
+            "café",
+            "日本語",
+            "🦀",
+            "—",
+        ];
+        for text in strings {
+            let value = json!({ text: text, "list": [text] });
+            assert_eq!(round_trip(&value), value, "{:?}", text);
+            assert_eq!(round_trip(&json!(text)), json!(text), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn empty_and_nested_collections_read_back_identical() {
+        let value = json!({
+            "empty list": [],
+            "empty map": {},
+            "lists": [[], [1, [2, 3]], {}, {"a": []}],
+            "maps": [{"a": 1, "b": {"c": [true, null]}}, {"d": {}}],
+            "deep": {"a": {"b": {"c": {"d": "e"}}}},
+            "numbers": [0, -1, 1.5, 9007199254740993_u64],
+            "optional": null,
+        });
+        assert_eq!(round_trip(&value), value);
+        for value in [json!([]), json!({}), json!(null), json!([{}]), json!([[]])] {
+            assert_eq!(round_trip(&value), value);
+        }
+    }
+
+    #[test]
+    fn reads_hand_written_documents() {
+        let text = "\
+---
+# A comment
+title: Buy milk  # trailing comment
+'quoted key': \"# not a comment\"
+tags:
+- home
+- 'it''s'
+custom:
+    client: Acme
+empty:
+";
+        assert_eq!(
+            decode(text).unwrap(),
+            json!({
+                "title": "Buy milk",
+                "quoted key": "# not a comment",
+                "tags": ["home", "it's"],
+                "custom": {"client": "Acme"},
+                "empty": null,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_malformed_documents() {
+        let error = decode("a: 1\na: 2\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+        assert!(decode("a:\n    b: 1\n  c: 2\n").is_err());
+        assert!(decode("a: 'unterminated\n").is_err());
+    }
+}